@@ -0,0 +1,79 @@
+//! Throughput of `OrderbookEngine::apply_delta`
+//! 
+//! Replays a high-volume delta stream into a book seeded from a 1000-level
+//! snapshot. The stream is generated deterministically to match a busy Kraken
+//! book: mostly volume changes near the touch, with a quarter of updates
+//! removing a level.
+//! 
+//! Run with `cargo bench --bench apply_delta`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use orderbook_arena::kraken::types::{BookDelta, BookSnapshot};
+use orderbook_arena::orderbook::engine::OrderbookEngine;
+
+const BOOK_LEVELS: usize = 1000;
+const STREAM_DELTAS: usize = 10_000;
+
+/// Minimal LCG so the stream is identical between runs
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self, bound: u64) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) % bound
+    }
+}
+
+fn level(price: f64, volume: f64, timestamp: f64) -> serde_json::Value {
+    serde_json::json!([format!("{:.1}", price), format!("{:.8}", volume), format!("{:.6}", timestamp)])
+}
+
+fn snapshot() -> BookSnapshot {
+    BookSnapshot {
+        bids: (0..BOOK_LEVELS).map(|i| level(49_999.5 - i as f64 * 0.5, 1.0, 1_700_000_000.0)).collect(),
+        asks: (0..BOOK_LEVELS).map(|i| level(50_000.5 + i as f64 * 0.5, 1.0, 1_700_000_000.0)).collect(),
+    }
+}
+
+fn delta_stream() -> Vec<BookDelta> {
+    let mut rng = Lcg(0x5eed);
+    (0..STREAM_DELTAS)
+        .map(|i| {
+            let timestamp = 1_700_000_000.0 + i as f64 * 0.001;
+            let mut update = |touch: f64, sign: f64| {
+                let price = touch + sign * rng.next(100) as f64 * 0.5;
+                let volume = if rng.next(4) == 0 { 0.0 } else { rng.next(500) as f64 / 100.0 };
+                level(price, volume, timestamp)
+            };
+            BookDelta {
+                bids: vec![update(49_999.5, -1.0)],
+                asks: vec![update(50_000.5, 1.0)],
+                checksum: None,
+            }
+        })
+        .collect()
+}
+
+fn bench_apply_delta(c: &mut Criterion) {
+    let snapshot = snapshot();
+    let deltas = delta_stream();
+    let seeded = || {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&snapshot).unwrap();
+        engine
+    };
+
+    let mut group = c.benchmark_group("apply_delta");
+    group.throughput(Throughput::Elements(deltas.len() as u64));
+    group.bench_function("stream", |b| {
+        b.iter_batched_ref(seeded, |engine| {
+            for delta in &deltas {
+                black_box(engine.apply_delta(black_box(delta)).unwrap());
+            }
+        }, BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_apply_delta);
+criterion_main!(benches);
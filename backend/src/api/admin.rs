@@ -0,0 +1,328 @@
+//! Admin endpoint handlers
+//! 
+//! This module contains operator-only endpoints under /admin. Every admin route
+//! requires `Authorization: Bearer <ADMIN_TOKEN>`; when no token is configured
+//! the admin endpoints are disabled.
+//! 
+//! PATCH /admin/config changes the tunable config fields of the running server,
+//! and POST /admin/tickers/{ticker}/freeze and /unfreeze hold a book still.
+
+use axum::{
+    extract::{Path, Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::{Json, Response},
+    Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use crate::api::error::ApiError;
+use crate::api::routes::{get_ticker_data, AppState};
+use std::sync::atomic::Ordering;
+use crate::config::Config;
+
+/// Build the /admin router, with every route behind admin authentication
+pub fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/selfcheck", axum::routing::get(get_selfcheck))
+        .route("/config", axum::routing::patch(patch_config))
+        .route("/tickers/:ticker/freeze", axum::routing::post(freeze_ticker))
+        .route("/tickers/:ticker/unfreeze", axum::routing::post(unfreeze_ticker))
+        .route_layer(axum::middleware::from_fn_with_state(state, require_admin))
+}
+
+/// Reject requests that don't carry the configured admin bearer token
+/// 
+/// Returns 403 if no admin token is configured, 401 if the token is missing or wrong
+async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let expected = state
+        .config
+        .borrow()
+        .admin_token
+        .clone()
+        .ok_or_else(|| ApiError::forbidden("Admin endpoints are disabled (ADMIN_TOKEN is not set)"))?;
+
+    let provided = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if tokens_match(token, &expected) => Ok(next.run(request).await),
+        _ => Err(ApiError::unauthorized("Missing or invalid admin token")),
+    }
+}
+
+/// Compare tokens without short-circuiting on the first differing byte
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// GET /admin/selfcheck - Run engine invariants on every ticker
+/// 
+/// Returns `healthy` plus a per-ticker report with the first failing invariant
+async fn get_selfcheck(State(state): State<AppState>) -> Json<Value> {
+    // Clone the engine handles so the registry lock isn't held while checking
+    let engines: Vec<_> = {
+        let tickers = state.tickers.lock().await;
+        tickers
+            .iter()
+            .map(|(ticker, data)| (ticker.clone(), data.engine.clone()))
+            .collect()
+    };
+
+    let mut report = Map::new();
+    let mut healthy = true;
+    for (ticker, engine) in engines {
+        let result = engine.read().await.self_check();
+        let entry = match result {
+            Ok(()) => json!({ "ok": true }),
+            Err(failure) => {
+                healthy = false;
+                json!({ "ok": false, "failure": failure })
+            }
+        };
+        report.insert(ticker, entry);
+    }
+
+    Json(json!({
+        "healthy": healthy,
+        "tickers": report,
+    }))
+}
+
+/// POST /admin/tickers/{ticker}/freeze - Hold the ticker's book at its current state
+/// 
+/// Incoming book updates are dropped until the ticker is unfrozen, e.g. for
+/// demos and screenshots. Returns 404 if the ticker is not registered
+async fn freeze_ticker(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    set_frozen(&state, ticker, true).await
+}
+
+/// POST /admin/tickers/{ticker}/unfreeze - Resume live updates of a frozen book
+/// 
+/// The feed task resubscribes on the next book message so the book catches up
+/// with a fresh snapshot. Returns 404 if the ticker is not registered
+async fn unfreeze_ticker(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    set_frozen(&state, ticker, false).await
+}
+
+async fn set_frozen(state: &AppState, ticker: String, frozen: bool) -> Result<Json<Value>, ApiError> {
+    let ticker_data = get_ticker_data(state, &ticker).await?;
+    if ticker_data.frozen.swap(frozen, Ordering::Relaxed) != frozen {
+        tracing::info!(ticker = %ticker, frozen, "Book freeze toggled");
+    }
+    Ok(Json(json!({
+        "ticker": ticker,
+        "frozen": frozen,
+    })))
+}
+
+/// Config fields that are safe to change while the server is running
+/// 
+/// Every field is optional; omitted fields keep their current value.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ConfigPatch {
+    pub snapshot_interval_secs: Option<u64>,
+    pub snapshot_retention_secs: Option<i64>,
+    pub trade_retention_secs: Option<i64>,
+    pub sse_throttle_ms: Option<u64>,
+    pub stale_feed_threshold_secs: Option<u64>,
+    pub stuck_price_threshold_secs: Option<u64>,
+    pub resubscribe_on_gap: Option<bool>,
+}
+
+impl ConfigPatch {
+    /// Copy the patched fields onto `config`
+    fn apply(&self, config: &mut Config) {
+        if let Some(interval) = self.snapshot_interval_secs {
+            config.snapshot_interval_secs = interval;
+        }
+        if let Some(retention) = self.snapshot_retention_secs {
+            config.snapshot_retention_secs = retention;
+        }
+        if let Some(retention) = self.trade_retention_secs {
+            config.trade_retention_secs = retention;
+        }
+        if let Some(throttle) = self.sse_throttle_ms {
+            config.sse_throttle_ms = throttle;
+        }
+        if let Some(threshold) = self.stale_feed_threshold_secs {
+            config.stale_feed_threshold_secs = threshold;
+        }
+        if let Some(threshold) = self.stuck_price_threshold_secs {
+            config.stuck_price_threshold_secs = threshold;
+        }
+        if let Some(enabled) = self.resubscribe_on_gap {
+            config.resubscribe_on_gap = enabled;
+        }
+    }
+}
+
+/// Tunable fields of a config, in the shape accepted by PATCH /admin/config
+fn tunable_fields(config: &Config) -> Value {
+    json!({
+        "snapshotIntervalSecs": config.snapshot_interval_secs,
+        "snapshotRetentionSecs": config.snapshot_retention_secs,
+        "tradeRetentionSecs": config.trade_retention_secs,
+        "sseThrottleMs": config.sse_throttle_ms,
+        "staleFeedThresholdSecs": config.stale_feed_threshold_secs,
+        "stuckPriceThresholdSecs": config.stuck_price_threshold_secs,
+        "resubscribeOnGap": config.resubscribe_on_gap,
+    })
+}
+
+/// PATCH /admin/config - Change tunable config fields without a restart
+/// 
+/// The patched config is validated as a whole before being applied; returns
+/// 400 listing every problem if it is invalid (or the body has unknown fields),
+/// otherwise the resulting tunable fields. Subscribed storage tasks are woken and
+/// pick up a new snapshot interval at once; SSE throttling applies to streams
+/// opened afterwards.
+async fn patch_config(
+    State(state): State<AppState>,
+    body: axum::body::Bytes,
+) -> Result<Json<Value>, ApiError> {
+    let patch: ConfigPatch = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::bad_request(format!("Invalid config patch: {}", e)))?;
+
+    // Validate and apply under the channel's lock, so concurrent patches don't race
+    let mut outcome = Ok(());
+    state.config.send_if_modified(|config| {
+        let mut patched = config.clone();
+        patch.apply(&mut patched);
+        outcome = patched.validate();
+        if outcome.is_ok() {
+            *config = patched;
+        }
+        outcome.is_ok()
+    });
+    if let Err(errors) = outcome {
+        let problems: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        return Err(ApiError::bad_request(format!("Invalid config: {}", problems.join("; "))));
+    }
+
+    tracing::info!(patch = ?patch, "Config updated via /admin/config");
+    Ok(Json(tunable_fields(&state.config.borrow())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::routes::tests::{request_json, test_state};
+    use crate::config::Config;
+    use crate::kraken::types::BookSnapshot;
+    use axum::http::{Method, StatusCode};
+
+    #[tokio::test]
+    async fn test_selfcheck_reports_mixed_results() {
+        let state = test_state(&["BTC", "ETH"], Config::new().with_admin_token("secret"));
+        {
+            let tickers = state.tickers.lock().await;
+            let mut engine = tickers["ETH"].engine.write().await;
+            engine.apply_snapshot(&BookSnapshot {
+                bids: vec![serde_json::json!(["100.0", "1.0", "1.0"])],
+                asks: vec![serde_json::json!(["101.0", "1.0", "1.0"])],
+            }).unwrap();
+            // Corrupt the book: zero out the best ask's volume behind the engine's back
+            let ask_price = engine.asks_mut().keys().next().copied().unwrap();
+            engine.asks_mut().insert(ask_price, 0.0);
+        }
+
+        let (status, body) = request_json(state, Method::GET, "/admin/selfcheck", Some("secret"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["healthy"], false);
+        assert_eq!(body["tickers"]["BTC"]["ok"], true);
+        assert_eq!(body["tickers"]["ETH"]["ok"], false);
+        assert!(body["tickers"]["ETH"]["failure"].as_str().unwrap().contains("invalid volume"));
+    }
+
+    #[tokio::test]
+    async fn test_selfcheck_requires_admin_token() {
+        let state = test_state(&["BTC"], Config::new().with_admin_token("secret"));
+        let (status, _) = request_json(state.clone(), Method::GET, "/admin/selfcheck", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = request_json(state.clone(), Method::GET, "/admin/selfcheck", Some("wrong"), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, body) = request_json(state, Method::GET, "/admin/selfcheck", Some("secret"), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["healthy"], true);
+
+        // Without a configured token the admin endpoints are disabled
+        let (status, _) = request_json(test_state(&["BTC"], Config::new()), Method::GET, "/admin/selfcheck", Some("secret"), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_patch_config_applies_tunable_fields() {
+        let state = test_state(&["BTC"], Config::new().with_admin_token("secret"));
+
+        let (status, body) = request_json(state.clone(), Method::PATCH, "/admin/config", Some("secret"), Some(r#"{"snapshotIntervalSecs": 1, "sseThrottleMs": 50}"#)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["snapshotIntervalSecs"], 1);
+        assert_eq!(body["sseThrottleMs"], 50);
+        // Omitted fields are unchanged
+        assert_eq!(body["snapshotRetentionSecs"], 3600);
+
+        let config = state.config.borrow();
+        assert_eq!(config.snapshot_interval_secs, 1);
+        assert_eq!(config.sse_throttle_ms, 50);
+    }
+
+    #[tokio::test]
+    async fn test_patch_config_rejects_invalid_changes() {
+        let state = test_state(&["BTC"], Config::new().with_admin_token("secret"));
+
+        // Validation runs on the whole patched config, and nothing is applied on failure
+        let (status, body) = request_json(state.clone(), Method::PATCH, "/admin/config", Some("secret"), Some(r#"{"snapshotIntervalSecs": 0, "sseThrottleMs": 50}"#)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["error"].as_str().unwrap().contains("snapshot_interval_secs"));
+        assert_eq!(state.config.borrow().sse_throttle_ms, 250);
+
+        // Fields that aren't tunable (or don't exist) are rejected
+        let (status, _) = request_json(state.clone(), Method::PATCH, "/admin/config", Some("secret"), Some(r#"{"port": 9000}"#)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = request_json(state, Method::PATCH, "/admin/config", Some("wrong"), Some(r#"{"sseThrottleMs": 50}"#)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_freeze_and_unfreeze_ticker() {
+        let state = test_state(&["BTC"], Config::new().with_admin_token("secret"));
+        let post = |uri: &'static str, token: &'static str| request_json(state.clone(), Method::POST, uri, Some(token), None);
+
+        let (status, _) = post("/admin/tickers/BTC/freeze", "wrong").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(!state.tickers.lock().await["BTC"].is_frozen());
+
+        let (status, _) = post("/admin/tickers/BTC/freeze", "secret").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(state.tickers.lock().await["BTC"].is_frozen());
+
+        let (status, body) = post("/admin/tickers/BTC/unfreeze", "secret").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "ticker": "BTC", "frozen": false }));
+        assert!(!state.tickers.lock().await["BTC"].is_frozen());
+
+        let (status, _) = post("/admin/tickers/DOGE/freeze", "secret").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
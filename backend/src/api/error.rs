@@ -1,6 +1,8 @@
 //! Error handling for API routes
 //! 
 //! This module provides custom error types and error responses for the API.
+//! Error bodies have the form `{"error": <message>, "code": <CODE>, "status": <status>}`,
+//! where `code` is a stable identifier clients can match on instead of the message.
 
 use axum::{
     http::StatusCode,
@@ -12,12 +14,20 @@ use serde_json::json;
 /// API error type that can be converted to HTTP responses
 #[derive(Debug)]
 pub enum ApiError {
-    /// Bad request (400) - invalid input
+    /// Bad request (400, `BAD_REQUEST`) - invalid input
     BadRequest(String),
-    /// Not found (404) - resource not found
+    /// Bad request (400, `INVALID_TIMESTAMP`) - a timestamp couldn't be parsed
+    InvalidTimestamp(String),
+    /// Unauthorized (401, `UNAUTHORIZED`) - missing or invalid credentials
+    Unauthorized(String),
+    /// Forbidden (403, `FORBIDDEN`) - the operation is not permitted
+    Forbidden(String),
+    /// Not found (404, `NOT_FOUND`) - resource not found
     NotFound(String),
-    /// Internal server error (500) - unexpected error
-    Internal(String),
+    /// Not found (404, `TICKER_NOT_FOUND`) - the ticker isn't registered
+    TickerNotFound(String),
+    /// Not found (404, `SNAPSHOT_NOT_FOUND`) - no stored snapshot matches
+    SnapshotNotFound(String),
 }
 
 impl ApiError {
@@ -26,27 +36,76 @@ impl ApiError {
         Self::BadRequest(msg.into())
     }
 
+    /// Create an invalid timestamp error
+    pub fn invalid_timestamp(msg: impl Into<String>) -> Self {
+        Self::InvalidTimestamp(msg.into())
+    }
+
+    /// Create an unauthorized error
+    pub fn unauthorized(msg: impl Into<String>) -> Self {
+        Self::Unauthorized(msg.into())
+    }
+
+    /// Create a forbidden error
+    pub fn forbidden(msg: impl Into<String>) -> Self {
+        Self::Forbidden(msg.into())
+    }
+
     /// Create a not found error
     pub fn not_found(msg: impl Into<String>) -> Self {
         Self::NotFound(msg.into())
     }
 
-    /// Create an internal server error
-    pub fn internal(msg: impl Into<String>) -> Self {
-        Self::Internal(msg.into())
+    /// Create an unknown ticker error
+    pub fn ticker_not_found(msg: impl Into<String>) -> Self {
+        Self::TickerNotFound(msg.into())
+    }
+
+    /// Create a missing snapshot error
+    pub fn snapshot_not_found(msg: impl Into<String>) -> Self {
+        Self::SnapshotNotFound(msg.into())
+    }
+
+    /// HTTP status of the response
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) | ApiError::InvalidTimestamp(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) | ApiError::TickerNotFound(_) | ApiError::SnapshotNotFound(_) => StatusCode::NOT_FOUND,
+        }
+    }
+
+    /// Stable machine-readable code sent as the `code` field
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "BAD_REQUEST",
+            ApiError::InvalidTimestamp(_) => "INVALID_TIMESTAMP",
+            ApiError::Unauthorized(_) => "UNAUTHORIZED",
+            ApiError::Forbidden(_) => "FORBIDDEN",
+            ApiError::NotFound(_) => "NOT_FOUND",
+            ApiError::TickerNotFound(_) => "TICKER_NOT_FOUND",
+            ApiError::SnapshotNotFound(_) => "SNAPSHOT_NOT_FOUND",
+        }
     }
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
-            ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+        let (status, code) = (self.status(), self.code());
+        let error_message = match self {
+            ApiError::BadRequest(msg)
+            | ApiError::InvalidTimestamp(msg)
+            | ApiError::Unauthorized(msg)
+            | ApiError::Forbidden(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::TickerNotFound(msg)
+            | ApiError::SnapshotNotFound(msg) => msg,
         };
 
         let body = Json(json!({
             "error": error_message,
+            "code": code,
             "status": status.as_u16(),
         }));
 
@@ -54,3 +113,31 @@ impl IntoResponse for ApiError {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+
+    #[tokio::test]
+    async fn test_each_variant_emits_its_code() {
+        let cases = [
+            (ApiError::bad_request("bad"), StatusCode::BAD_REQUEST, "BAD_REQUEST"),
+            (ApiError::invalid_timestamp("bad ts"), StatusCode::BAD_REQUEST, "INVALID_TIMESTAMP"),
+            (ApiError::unauthorized("who"), StatusCode::UNAUTHORIZED, "UNAUTHORIZED"),
+            (ApiError::forbidden("no"), StatusCode::FORBIDDEN, "FORBIDDEN"),
+            (ApiError::not_found("gone"), StatusCode::NOT_FOUND, "NOT_FOUND"),
+            (ApiError::ticker_not_found("Unknown ticker: DOGE"), StatusCode::NOT_FOUND, "TICKER_NOT_FOUND"),
+            (ApiError::snapshot_not_found("none"), StatusCode::NOT_FOUND, "SNAPSHOT_NOT_FOUND"),
+        ];
+        for (error, status, code) in cases {
+            let message = format!("{:?}", error);
+            let response = error.into_response();
+            assert_eq!(response.status(), status, "{}", message);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["code"], code, "{}", message);
+            assert_eq!(body["status"], status.as_u16());
+            assert!(body["error"].as_str().is_some_and(|error| message.contains(error)));
+        }
+    }
+}
@@ -1,11 +1,17 @@
-/// API module for REST and WebSocket endpoints
-/// 
-/// This module organizes all API-related functionality including:
-/// - REST route handlers (routes.rs)
-/// - WebSocket handlers (websocket.rs)
-/// - Error handling (error.rs)
+//! API module for REST and WebSocket endpoints
+//! 
+//! This module organizes all API-related functionality including:
+//! - REST route handlers (routes.rs)
+//! - WebSocket handlers (websocket.rs)
+//! - Server-Sent Events handlers (sse.rs)
+//! - Error handling (error.rs)
+//! - Request correlation ids (request_id.rs)
+//! - Admin-only endpoints (admin.rs)
 
 pub mod routes;
 pub mod websocket;
+pub mod sse;
 pub mod error;
+pub mod request_id;
+pub mod admin;
 
@@ -0,0 +1,119 @@
+//! Request correlation ids
+//! 
+//! This module contains middleware that assigns every request a correlation id,
+//! taken from an incoming `X-Request-Id` header or generated, runs the handler
+//! inside a tracing span carrying it, and echoes it back in the response.
+
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Header used to receive and echo the correlation id
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-provided id we accept before generating our own
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Correlation id for the current request, available as a request extension
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+/// Middleware that assigns a correlation id and wraps the handler in a `request` span
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        uri = %request.uri(),
+    );
+
+    let mut response = next.run(request).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get, Extension, Router};
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    /// Tracing layer that records the fields of every new span as `name=value`
+    struct CaptureSpans(Arc<Mutex<Vec<String>>>);
+
+    struct FieldRecorder<'a>(&'a mut Vec<String>);
+
+    impl Visit for FieldRecorder<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for CaptureSpans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            let mut fields = self.0.lock().unwrap();
+            attrs.record(&mut FieldRecorder(&mut fields));
+        }
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route("/", get(|Extension(id): Extension<RequestId>| async move { id.0 }))
+            .layer(axum::middleware::from_fn(request_id_middleware))
+    }
+
+    #[tokio::test]
+    async fn test_provided_request_id_is_echoed_and_spanned() {
+        let fields = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(CaptureSpans(fields.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri("/")
+                    .header(REQUEST_ID_HEADER, "abc-123")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "abc-123");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"abc-123");
+        assert!(fields.lock().unwrap().contains(&"request_id=abc-123".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_request_id_generated_when_absent() {
+        let response = app()
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let id = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+        assert!(Uuid::parse_str(id).is_ok());
+    }
+}
@@ -2,54 +2,193 @@
 //! 
 //! This module contains handlers for REST endpoints:
 //! - GET /snapshot/{timestamp} - Retrieve snapshot by timestamp
-//! - GET /history - Get history range (min/max timestamps)
+//! - GET /snapshots/{ticker}?from=&to= - Snapshots in a timestamp window, for bulk export
+//! - GET /export/{ticker} - Entire retained history as newline-delimited JSON
+//! - GET /diff/{ticker}?from=&to= - Levels added, removed and resized between two snapshots
+//! - GET /history - History range (min/max timestamps) of every ticker with snapshots
+//! - GET /history/{ticker} - Get history range (min/max timestamps)
+//! - GET /stats/{ticker} - Engine statistics and self-diagnostics
+//! - GET /health - 503 unless some feed received a message recently; per-ticker diagnostics
+//! - GET /ready - Readiness: 503 with Retry-After until every fed book has data and a live feed
+//! - GET /metrics - Counters and gauges in Prometheus text format
+//! - GET /sse/{ticker} - Server-Sent Events stream of top of book
+//! - GET /orderbook/{ticker} - Full current book, e.g. to bootstrap before opening /live
+//! - GET /spread/{ticker} - Current bid-ask spread
+//! - GET /spread_history/{ticker} - Top of book and spread of every stored snapshot
+//! - GET /depth/{ticker} - Top N bid and ask levels, or aggregated cumulative ladders
+//! - GET /depth_curve/{ticker} - Cumulative volume per level, best price outward
+//! - GET /imbalance/{ticker} - Bid/ask volume imbalance over the top N levels
+//! - GET /liquidity/{ticker}?bps=N - Bid and ask volume within N basis points of the mid
+//! - GET /trades/{ticker}/csv - Inferred trade tape as CSV
+//! - GET /subscriptions/{ticker} - Kraken subscription parameters and ack state
+//! - GET /ticker_info/{ticker} - Trading pair split into base and quote currency
+//! - GET /arena/{asset}/imbalance - Volume-weighted imbalance across venues
+//! - GET /arena/{asset}/mid - Volume-weighted mid price across venues
+//! - GET /arena/{asset}/leadlag - Which venue's mid moves first, and by how much
+//! - GET /arena/health - Average arena spread and healthy/stale feed counts
+//! - GET /admin/selfcheck - Engine invariant report (admin only, see admin.rs)
+//! - POST /admin/tickers/{ticker}/freeze, /unfreeze - Hold a book still (admin only)
+//! - WS /arbitrage - Stream of cross-exchange arbitrage opportunities
+//! 
+//! Per-ticker prices are multiplied by the ticker's display scale (see
+//! /instruments/{ticker}) as they are serialized; engines and the snapshot store
+//! hold exchange prices. Cross-venue /arena output is left unscaled.
 
 use axum::{
-    extract::{Path, State},
-    response::Json,
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
     Router,
 };
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::collections::HashMap;
+use std::time::Duration;
 use tokio::sync::{broadcast, RwLock, Mutex};
 use crate::orderbook::store::SnapshotStore;
-use crate::orderbook::snapshot::Snapshot;
-use crate::orderbook::engine::{OrderbookState, OrderbookEngine};
+use crate::orderbook::trades::{trades_to_csv, TradeStore};
+use crate::orderbook::snapshot::{diff_snapshots, BookDiff, Snapshot};
+use crate::orderbook::engine::{cumulative_ladder, BookUpdate, OrderbookEngine, OrderbookState, PriceLevelEntry, Side};
+use crate::kraken::diagnostics::FEED_DIAGNOSTICS;
+use crate::metrics::METRICS;
+use crate::kraken::subscription::SubscriptionState;
 use crate::kraken::types::OhlcData;
+use crate::api::admin;
 use crate::api::error::ApiError;
-use crate::api::websocket::handle_websocket;
-use serde_json::{json, Value};
+use crate::api::websocket::{handle_websocket, handle_arbitrage_websocket, ConnectionLimiter};
+use crate::api::sse::handle_sse;
+use crate::api::request_id::request_id_middleware;
+use crate::config::SharedConfig;
+use crate::arena::analytics::ArenaAnalytics;
+use crate::arena::arbitrage::ArbitrageDetector;
+use crate::exchange::health::ConnectionHealth;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Default number of levels per side returned by /depth
+const DEFAULT_DEPTH_LEVELS: usize = 10;
+
+/// Maximum number of levels per side returned by /depth
+const MAX_DEPTH_LEVELS: usize = 500;
+
+/// Seconds clients are asked to wait before retrying /ready while warming up
+const READY_RETRY_AFTER_SECS: u64 = 5;
+
+/// Default window of the rolling mid range reported by /stats, in seconds
+const DEFAULT_MID_WINDOW_SECS: u64 = 300;
+
+/// Default band around the mid, in basis points, of aggregated /depth ladders
+const DEFAULT_DEPTH_BAND_BPS: f64 = 100.0;
+
+/// Default number of mid samples /arena/{asset}/leadlag correlates over
+const DEFAULT_LEADLAG_WINDOW: usize = 500;
+
+/// Default largest lag, in ticks, tried by /arena/{asset}/leadlag
+const DEFAULT_LEADLAG_MAX_LAG: usize = 20;
 
 /// Per-ticker orderbook data
 #[derive(Clone)]
 pub struct TickerData {
-    /// Broadcast channel for streaming orderbook updates to WebSocket clients
-    pub orderbook_updates: broadcast::Sender<OrderbookState>,
+    /// Broadcast channel for streaming orderbook updates (full states and diffs) to clients
+    pub orderbook_updates: broadcast::Sender<BookUpdate>,
     /// Broadcast channel for streaming OHLC (candlestick) updates to WebSocket clients
     pub ohlc_updates: broadcast::Sender<OhlcData>,
     /// Orderbook engine for getting current state
     pub engine: Arc<RwLock<OrderbookEngine>>,
+    /// Kraken subscription of this ticker's feed; `None` for tickers created on
+    /// demand by /live, which have no Kraken task
+    pub subscription: Option<Arc<RwLock<SubscriptionState>>>,
+    /// Set while the book is frozen; the Kraken task drops book messages meanwhile
+    pub frozen: Arc<AtomicBool>,
+}
+
+impl TickerData {
+    /// Whether the ticker's book is frozen
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Relaxed)
+    }
+
+    /// Symbol and trading pair of the ticker
+    /// 
+    /// Tickers without a Kraken subscription use the symbol itself as the pair.
+    pub async fn info(&self, ticker: &str) -> TickerInfo {
+        let pair = match &self.subscription {
+            Some(subscription) => subscription.read().await.pair.clone(),
+            None => ticker.to_string(),
+        };
+        TickerInfo::new(ticker, &pair)
+    }
+}
+
+/// Quote currency reported for pairs that aren't of the form BASE/QUOTE
+pub const UNKNOWN_QUOTE: &str = "unknown";
+
+/// A ticker's trading pair split into base and quote currency, for display
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TickerInfo {
+    /// Ticker symbol, e.g. "ZEC"
+    pub symbol: String,
+    /// Trading pair, e.g. "ZEC/USD"
+    pub pair: String,
+    /// Currency being priced, e.g. "ZEC"
+    pub base: String,
+    /// Currency prices are quoted in, e.g. "USD", or `UNKNOWN_QUOTE`
+    pub quote: String,
+}
+
+impl TickerInfo {
+    /// Split `pair` on its `/`; a pair without one is all base and an unknown quote
+    pub fn new(symbol: &str, pair: &str) -> Self {
+        let (base, quote) = pair.split_once('/').unwrap_or((pair, UNKNOWN_QUOTE));
+        Self {
+            symbol: symbol.to_string(),
+            pair: pair.to_string(),
+            base: base.to_string(),
+            quote: quote.to_string(),
+        }
+    }
 }
 
 /// Application state shared across all handlers
 #[derive(Clone)]
 pub struct AppState {
     pub snapshot_store: Arc<SnapshotStore>,
+    /// Trades inferred from book deltas
+    pub trade_store: Arc<TradeStore>,
     /// Map of ticker symbol to ticker data
     pub tickers: Arc<Mutex<HashMap<String, TickerData>>>,
+    /// Application configuration; tunable fields can be changed via PATCH /admin/config
+    pub config: SharedConfig,
+    /// Cross-exchange analytics over all venues
+    pub arena: Arc<ArenaAnalytics>,
+    /// Cross-exchange arbitrage detection, streamed on /arbitrage
+    pub arbitrage: Arc<ArbitrageDetector>,
+    /// Last message time of each ticker's feed, for /health and /ready
+    pub connection_health: Arc<ConnectionHealth>,
+    /// Open /live and /arbitrage clients, capped by `max_connections`
+    pub websocket_connections: Arc<ConnectionLimiter>,
 }
 
 /// Create the REST API router with all routes
-pub fn create_router(state: AppState) -> Router {
-    use tower_http::cors::{CorsLayer, Any};
+/// 
+/// `allowed_origins` are the CORS origins fixed at startup; the router never
+/// reads them from the shared config, which PATCH /admin/config may replace.
+pub fn create_router(state: AppState, allowed_origins: &[String]) -> Router {
+    use tower_http::cors::{AllowOrigin, CorsLayer, Any};
     use tower::ServiceBuilder;
     use tower_http::trace::TraceLayer;
     
-    // Configure CORS for development
-    // Allows all origins, methods, and headers for local development
+    // Configure CORS from the allowed origins, allowing any origin when none are
+    // configured (local development).
     // Note: CORS doesn't apply to WebSocket connections, but we apply it to REST routes
+    let allow_origin = if allowed_origins.is_empty() {
+        AllowOrigin::from(Any)
+    } else {
+        // Config::validate rejects origins that aren't valid header values
+        AllowOrigin::list(allowed_origins.iter().filter_map(|origin| HeaderValue::from_str(origin).ok()))
+    };
     let cors = CorsLayer::new()
-        .allow_origin(Any)
+        .allow_origin(allow_origin)
         .allow_methods(Any)
         .allow_headers(Any);
     
@@ -57,34 +196,216 @@ pub fn create_router(state: AppState) -> Router {
     // WebSocket upgrades happen at the route level, not affected by CORS
     Router::new()
         .route("/live", axum::routing::get(handle_websocket))
+        .route("/arbitrage", axum::routing::get(handle_arbitrage_websocket))
+        .route("/sse/:ticker", axum::routing::get(handle_sse))
         .route("/snapshot/:ticker/:timestamp", axum::routing::get(get_snapshot))
+        .route("/snapshots/:ticker", axum::routing::get(get_snapshot_range))
+        .route("/export/:ticker", axum::routing::get(export_history))
+        .route("/diff/:ticker", axum::routing::get(get_diff))
+        .route("/history", axum::routing::get(get_history_overview))
         .route("/history/:ticker", axum::routing::get(get_history))
+        .route("/stats/:ticker", axum::routing::get(get_stats))
+        .route("/health", axum::routing::get(get_health))
+        .route("/ready", axum::routing::get(get_ready))
+        .route("/metrics", axum::routing::get(get_metrics))
+        .route("/tickers", axum::routing::get(get_tickers))
+        .route("/orderbook/:ticker", axum::routing::get(get_orderbook))
+        .route("/spread/:ticker", axum::routing::get(get_spread))
+        .route("/spread_history/:ticker", axum::routing::get(get_spread_history))
+        .route("/depth/:ticker", axum::routing::get(get_depth))
+        .route("/depth_curve/:ticker", axum::routing::get(get_depth_curve))
+        .route("/imbalance/:ticker", axum::routing::get(get_imbalance))
+        .route("/liquidity/:ticker", axum::routing::get(get_liquidity))
+        .route("/trades/:ticker/csv", axum::routing::get(get_trades_csv))
+        .route("/subscriptions/:ticker", axum::routing::get(get_subscription))
+        .route("/ticker_info/:ticker", axum::routing::get(get_ticker_info))
+        .route("/instruments/:ticker", axum::routing::get(get_instrument))
+        .route("/arena/:asset/imbalance", axum::routing::get(get_arena_imbalance))
+        .route("/arena/:asset/mid", axum::routing::get(get_arena_mid))
+        .route("/arena/:asset/leadlag", axum::routing::get(get_arena_leadlag))
+        .route("/arena/health", axum::routing::get(get_arena_health))
+        .nest("/admin", admin::router(state.clone()))
         .layer(
             ServiceBuilder::new()
+                .layer(axum::middleware::from_fn(request_id_middleware))
                 .layer(TraceLayer::new_for_http())
                 .layer(cors)
         )
         .with_state(state)
 }
 
-/// GET /snapshot/{ticker}/{timestamp} - Retrieve snapshot by ticker and timestamp
+#[derive(Debug, Deserialize)]
+pub struct SnapshotQuery {
+    /// `exact` (default) or `nearest`
+    mode: Option<String>,
+}
+
+/// GET /snapshot/{ticker}/{timestamp}?mode= - Retrieve snapshot by ticker and timestamp
 /// 
-/// Returns 404 if snapshot not found, 400 if timestamp format is invalid
+/// With `mode=nearest`, the snapshot closest to the timestamp is returned instead
+/// of requiring an exact match (ties go to the earlier snapshot).
+/// Returns 404 if snapshot not found, 400 if timestamp format or mode is invalid
 async fn get_snapshot(
     Path((ticker, timestamp_str)): Path<(String, String)>,
+    Query(query): Query<SnapshotQuery>,
     State(state): State<AppState>,
 ) -> Result<Json<Snapshot>, ApiError> {
     // Parse and validate timestamp format
     let timestamp = timestamp_str
         .parse::<i64>()
-        .map_err(|_| ApiError::bad_request("Invalid timestamp format. Expected a Unix timestamp (integer)"))?;
+        .map_err(|_| ApiError::invalid_timestamp("Invalid timestamp format. Expected a Unix timestamp (integer)"))?;
     
     // Retrieve snapshot from store
-    state.snapshot_store
-        .get_snapshot(&ticker, timestamp)
-        .await
-        .map(Json)
-        .ok_or_else(|| ApiError::not_found(format!("No snapshot found for ticker {} at timestamp: {}", ticker, timestamp)))
+    let snapshot = match query.mode.as_deref() {
+        None | Some("exact") => state.snapshot_store.get_snapshot(&ticker, timestamp).await,
+        Some("nearest") => state.snapshot_store.get_nearest_snapshot(&ticker, timestamp).await,
+        Some(mode) => {
+            return Err(ApiError::bad_request(format!("Invalid mode: {}. Expected exact or nearest", mode)));
+        }
+    };
+    let scale = display_scale(&state, &ticker).await;
+    snapshot
+        .map(|snapshot| Json(snapshot.scaled(scale)))
+        .ok_or_else(|| ApiError::snapshot_not_found(format!("No snapshot found for ticker {} at timestamp: {}", ticker, timestamp)))
+}
+
+/// Most snapshots returned by one /snapshots request
+const MAX_RANGE_SNAPSHOTS: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotRangeQuery {
+    /// Start of the window as a Unix timestamp (inclusive, default: unbounded)
+    from: Option<String>,
+    /// End of the window as a Unix timestamp (inclusive, default: unbounded)
+    to: Option<String>,
+}
+
+/// GET /snapshots/{ticker}?from=&to= - Every snapshot in a timestamp window
+/// 
+/// Returns `{snapshots, truncated}`: the snapshots with `from <= timestamp <= to`,
+/// oldest first, capped at the first `MAX_RANGE_SNAPSHOTS`, and whether the cap
+/// left any out (request again from the last timestamp + 1 for the rest). An
+/// empty window returns no snapshots.
+/// Returns 400 if `from`/`to` are not integers or `from` is after `to`
+async fn get_snapshot_range(
+    Path(ticker): Path<String>,
+    Query(query): Query<SnapshotRangeQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let parse = |name: &str, raw: Option<String>| {
+        raw.map(|raw| {
+            raw.parse::<i64>()
+                .map_err(|_| ApiError::invalid_timestamp(format!("{} must be a Unix timestamp (integer)", name)))
+        })
+        .transpose()
+    };
+    let from = parse("from", query.from)?.unwrap_or(i64::MIN);
+    let to = parse("to", query.to)?.unwrap_or(i64::MAX);
+    if from > to {
+        return Err(ApiError::bad_request("from must not be after to"));
+    }
+
+    let (snapshots, truncated) = state.snapshot_store.get_range(&ticker, from, to, MAX_RANGE_SNAPSHOTS).await;
+    let scale = display_scale(&state, &ticker).await;
+    let snapshots: Vec<Snapshot> = snapshots.into_iter().map(|snapshot| snapshot.scaled(scale)).collect();
+    Ok(Json(json!({
+        "snapshots": snapshots,
+        "truncated": truncated,
+    })))
+}
+
+/// Snapshots rebuilt and serialized per chunk of an /export body
+const EXPORT_BATCH: usize = 64;
+
+/// GET /export/{ticker} - Every stored snapshot as `application/x-ndjson`
+/// 
+/// One snapshot per line, oldest first. Snapshots are rebuilt from the store
+/// `EXPORT_BATCH` at a time as the body streams out, so the history is never
+/// held in memory all at once.
+/// Returns 404 if the ticker has no snapshots
+async fn export_history(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Some((from, to)) = state.snapshot_store.get_history_range(&ticker).await else {
+        return Err(ApiError::snapshot_not_found(format!("No history available for ticker {}. No snapshots have been stored yet.", ticker)));
+    };
+
+    let scale = display_scale(&state, &ticker).await;
+    let history = state.snapshot_store.history(&ticker, from, to);
+    let chunks = futures_util::stream::unfold(history, move |mut history| async move {
+        let batch = history.next_batch(EXPORT_BATCH).await;
+        if batch.is_empty() {
+            return None;
+        }
+        let mut chunk = Vec::new();
+        for snapshot in batch {
+            if let Err(e) = serde_json::to_writer(&mut chunk, &snapshot.scaled(scale)) {
+                return Some((Err(e), history));
+            }
+            chunk.push(b'\n');
+        }
+        Some((Ok(chunk), history))
+    });
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        axum::body::Body::from_stream(chunks),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    /// Unix timestamp of the earlier snapshot
+    from: Option<String>,
+    /// Unix timestamp of the later snapshot
+    to: Option<String>,
+}
+
+/// GET /diff/{ticker}?from=&to= - How the book changed between two snapshots
+/// 
+/// Each timestamp resolves to the nearest stored snapshot, as with
+/// `/snapshot?mode=nearest`. Returns per-side added, removed and changed levels.
+/// Returns 400 if `from`/`to` are missing or not integers, 404 if no snapshot exists
+async fn get_diff(
+    Path(ticker): Path<String>,
+    Query(query): Query<DiffQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<BookDiff>, ApiError> {
+    let parse = |name: &str, raw: Option<String>| {
+        raw.ok_or_else(|| ApiError::bad_request(format!("{} is required", name)))?
+            .parse::<i64>()
+            .map_err(|_| ApiError::invalid_timestamp(format!("{} must be a Unix timestamp (integer)", name)))
+    };
+    let from = parse("from", query.from)?;
+    let to = parse("to", query.to)?;
+
+    let scale = display_scale(&state, &ticker).await;
+    let mut snapshots = Vec::with_capacity(2);
+    for timestamp in [from, to] {
+        let snapshot = state.snapshot_store.get_nearest_snapshot(&ticker, timestamp).await.ok_or_else(|| {
+            ApiError::snapshot_not_found(format!("No snapshot found for ticker {} near timestamp: {}", ticker, timestamp))
+        })?;
+        snapshots.push(snapshot.scaled(scale));
+    }
+    Ok(Json(diff_snapshots(&snapshots[0], &snapshots[1])))
+}
+
+/// GET /history - History range of every ticker that has stored snapshots
+/// 
+/// Returns an object keyed by ticker, each value in the shape of /history/{ticker};
+/// empty if nothing has been stored yet
+async fn get_history_overview(State(state): State<AppState>) -> Json<Value> {
+    let mut overview = Map::new();
+    for ticker in state.snapshot_store.list_tickers().await {
+        // A ticker's snapshots may be pruned between listing and lookup
+        if let Some((min, max)) = state.snapshot_store.get_history_range(&ticker).await {
+            overview.insert(ticker, json!({
+                "minTimestamp": min,
+                "maxTimestamp": max,
+            }));
+        }
+    }
+    Json(Value::Object(overview))
 }
 
 /// GET /history/{ticker} - Get history range (min/max timestamps) for a specific ticker
@@ -102,6 +423,1562 @@ async fn get_history(
             "minTimestamp": min,
             "maxTimestamp": max,
         })))
-        .ok_or_else(|| ApiError::not_found(format!("No history available for ticker {}. No snapshots have been stored yet.", ticker)))
+        .ok_or_else(|| ApiError::snapshot_not_found(format!("No history available for ticker {}. No snapshots have been stored yet.", ticker)))
+}
+
+
+/// Display scale of a ticker's prices in API output
+/// 
+/// 1 for tickers that aren't registered, e.g. history from a ticker no longer fed
+pub(crate) async fn display_scale(state: &AppState, ticker: &str) -> f64 {
+    let engine = state.tickers.lock().await.get(ticker).map(|data| data.engine.clone());
+    match engine {
+        Some(engine) => engine.read().await.display_scale(),
+        None => 1.0,
+    }
+}
+
+/// Look up the data for a ticker, releasing the tickers lock before returning
+/// 
+/// Returns 404 if the ticker is not registered
+pub(crate) async fn get_ticker_data(state: &AppState, ticker: &str) -> Result<TickerData, ApiError> {
+    let tickers = state.tickers.lock().await;
+    tickers
+        .get(ticker)
+        .cloned()
+        .ok_or_else(|| ApiError::ticker_not_found(format!("Unknown ticker: {}", ticker)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    /// Window of `midRange` in seconds; parsed by the handler so bad values get a JSON error
+    mid_window_secs: Option<String>,
+}
+
+/// GET /stats/{ticker}?mid_window_secs=N - Engine statistics and self-diagnostics for a ticker
+/// 
+/// `midRange` holds the low and high mid price over the last N seconds
+/// (default 300), or nulls if the book had no two-sided update in that time.
+/// 
+/// Includes `lastPriceStuck`, which is set when `lastPrice` has not changed for the
+/// configured threshold while the book keeps updating (a possible trade-detection failure),
+/// and the out-of-order levels rejected or counted under the ticker's timestamp policy.
+/// Returns 404 if the ticker is not registered
+async fn get_stats(
+    Path(ticker): Path<String>,
+    Query(query): Query<StatsQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let window_secs = match query.mid_window_secs {
+        None => DEFAULT_MID_WINDOW_SECS,
+        Some(raw) => raw
+            .parse::<u64>()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .ok_or_else(|| ApiError::bad_request("mid_window_secs must be a positive integer"))?,
+    };
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+    let threshold = Duration::from_secs(state.config.borrow().stuck_price_threshold_secs);
+
+    let engine = ticker_data.engine.read().await;
+    let scale = engine.display_scale();
+    let mid_range = engine.rolling_mid_range(Duration::from_secs(window_secs));
+    Ok(Json(json!({
+        "ticker": ticker,
+        "bidLevels": engine.iter_bids().count(),
+        "askLevels": engine.iter_asks().count(),
+        "lastPrice": engine.last_price().map(|price| price * scale),
+        "secondsSinceLastPriceChange": engine.time_since_last_price_change().map(|d| d.as_secs_f64()),
+        "lastPriceStuck": engine.last_price_stuck(threshold),
+        "resyncing": engine.is_resyncing(),
+        "rejectedUpdates": engine.rejected_updates(),
+        "outOfOrderUpdates": engine.out_of_order_updates(),
+        "midRange": {
+            "windowSecs": window_secs,
+            "low": mid_range.map(|(low, _)| low * scale),
+            "high": mid_range.map(|(_, high)| high * scale),
+        },
+    })))
+}
+
+/// Tickers that have an exchange feed task, i.e. weren't created on demand by /live
+async fn fed_tickers(state: &AppState) -> Vec<(String, TickerData)> {
+    let tickers = state.tickers.lock().await;
+    tickers
+        .iter()
+        .filter(|(_, data)| data.subscription.is_some())
+        .map(|(t, d)| (t.clone(), d.clone()))
+        .collect()
+}
+
+/// GET /health - Liveness check for load balancers, with per-ticker diagnostics
+/// 
+/// Returns 200 if at least one ticker's feed delivered a message within the
+/// configured `stale_feed_threshold_secs`, else 503. `lastSeenAgeMs` gives each fed ticker's time since
+/// its last message (null if none yet). Tickers whose last price appears stuck
+/// are listed under `possibleDetectionFailures`, and `malformedBookMessages`
+/// counts Kraken book messages that carried no book data.
+async fn get_health(State(state): State<AppState>) -> Response {
+    let (threshold, stale_after) = {
+        let config = state.config.borrow();
+        (Duration::from_secs(config.stuck_price_threshold_secs), Duration::from_secs(config.stale_feed_threshold_secs))
+    };
+    let tickers = fed_tickers(&state).await;
+
+    let mut possible_detection_failures = Vec::new();
+    let mut last_seen_age_ms = Map::new();
+    let mut any_healthy = false;
+    for (ticker, ticker_data) in tickers {
+        if ticker_data.engine.read().await.last_price_stuck(threshold) {
+            possible_detection_failures.push(ticker.clone());
+        }
+        let age = state.connection_health.last_seen_age(&ticker).await;
+        any_healthy |= age.is_some_and(|age| age <= stale_after);
+        last_seen_age_ms.insert(ticker, json!(age.map(|age| age.as_millis() as u64)));
+    }
+    possible_detection_failures.sort();
+
+    let status = if any_healthy { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(json!({
+        "status": if any_healthy { "ok" } else { "unavailable" },
+        "lastSeenAgeMs": last_seen_age_ms,
+        "possibleDetectionFailures": possible_detection_failures,
+        "malformedBookMessages": FEED_DIAGNOSTICS.malformed_book_messages(),
+    }))).into_response()
+}
+
+/// GET /ready - Readiness gate for load balancers and orchestrators
+/// 
+/// Returns 503 with a `Retry-After` header unless every ticker with an exchange
+/// feed is ready. Tickers listed under `warming` have fewer than the configured
+/// `min_ready_levels` on either side because no snapshot has landed yet (or they
+/// are resyncing); tickers under `stale` haven't received a message within the
+/// configured `stale_feed_threshold_secs`. Returns 200 once both lists are empty.
+async fn get_ready(State(state): State<AppState>) -> Response {
+    let (min_levels, stale_after) = {
+        let config = state.config.borrow();
+        (config.min_ready_levels, Duration::from_secs(config.stale_feed_threshold_secs))
+    };
+    let tickers = fed_tickers(&state).await;
+
+    let mut warming = Vec::new();
+    let mut stale = Vec::new();
+    for (ticker, ticker_data) in tickers {
+        if !state.connection_health.is_healthy(&ticker, stale_after).await {
+            stale.push(ticker.clone());
+        }
+        if !ticker_data.engine.read().await.has_min_levels(min_levels) {
+            warming.push(ticker);
+        }
+    }
+    warming.sort();
+    stale.sort();
+
+    let ready = warming.is_empty() && stale.is_empty();
+    let body = Json(json!({
+        "ready": ready,
+        "warming": warming,
+        "stale": stale,
+    }));
+    if ready {
+        body.into_response()
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, READY_RETRY_AFTER_SECS.to_string())],
+            body,
+        ).into_response()
+    }
+}
+
+/// GET /metrics - Counters and gauges in Prometheus text format
+/// 
+/// Book depth per ticker is read from the engines at scrape time.
+async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let tickers: Vec<(String, TickerData)> = {
+        let tickers = state.tickers.lock().await;
+        tickers.iter().map(|(t, d)| (t.clone(), d.clone())).collect()
+    };
+    let mut depths = Vec::with_capacity(tickers.len());
+    for (ticker, ticker_data) in tickers {
+        let engine = ticker_data.engine.read().await;
+        depths.push((ticker, engine.iter_bids().count(), engine.iter_asks().count()));
+    }
+    depths.sort();
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        METRICS.render(&depths),
+    )
+}
+
+/// GET /tickers - Every registered ticker and whether its book has data
+/// 
+/// Returns a JSON array sorted by ticker, with level counts and last price
+async fn get_tickers(State(state): State<AppState>) -> Json<Vec<Value>> {
+    let mut tickers: Vec<(String, TickerData)> = {
+        let tickers = state.tickers.lock().await;
+        tickers.iter().map(|(t, d)| (t.clone(), d.clone())).collect()
+    };
+    tickers.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut statuses = Vec::with_capacity(tickers.len());
+    for (ticker, ticker_data) in tickers {
+        let info = ticker_data.info(&ticker).await;
+        let engine = ticker_data.engine.read().await;
+        statuses.push(json!({
+            "ticker": ticker,
+            "info": info,
+            "hasData": !engine.is_empty(),
+            "bidLevels": engine.iter_bids().count(),
+            "askLevels": engine.iter_asks().count(),
+            "lastPrice": engine.last_price().map(|price| price * engine.display_scale()),
+        }));
+    }
+    Json(statuses)
+}
+
+/// GET /orderbook/{ticker} - The ticker's current book
+/// 
+/// Returns the same state the /live socket sends on connect, with every level.
+/// Returns 404 if the ticker is not registered
+async fn get_orderbook(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<OrderbookState>, ApiError> {
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+    let engine = ticker_data.engine.read().await;
+    Ok(Json(engine.get_current_state().scaled(engine.display_scale())))
+}
+
+/// GET /spread/{ticker} - Current bid-ask spread for a ticker
+/// 
+/// Returns best bid/ask, the absolute spread, and the spread in ticks when the
+/// ticker has a configured tick size (`spreadTickAligned` is false if rounded).
+/// Prices and the tick size are in display-scaled units.
+/// Returns 404 if the ticker is not registered
+async fn get_spread(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+
+    let engine = ticker_data.engine.read().await;
+    let scale = engine.display_scale();
+    let best_bid = engine.top_bids().first().map(|(price, _)| *price * scale);
+    let best_ask = engine.top_asks().first().map(|(price, _)| *price * scale);
+    let spread = match (best_bid, best_ask) {
+        (Some(bid), Some(ask)) => Some(ask - bid),
+        _ => None,
+    };
+
+    Ok(Json(json!({
+        "ticker": ticker,
+        "bestBid": best_bid,
+        "bestAsk": best_ask,
+        "spread": spread,
+        "tickSize": engine.tick_size().map(|tick| tick * scale),
+        "spreadTicks": engine.spread_ticks(),
+        "spreadTickAligned": engine.spread_tick_aligned(),
+    })))
+}
+
+/// GET /spread_history/{ticker} - Spread over the retained snapshot history
+/// 
+/// Returns `[{timestamp, bestBid, bestAsk, spread, midPrice}]` for every stored
+/// snapshot of the ticker, oldest first. Only each snapshot's stored top of
+/// book is read, not its full book; fields that need an empty side are null.
+/// Returns 404 if the ticker has no snapshots
+async fn get_spread_history(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Value>>, ApiError> {
+    let tops = state.snapshot_store.get_top_of_book_history(&ticker).await;
+    if tops.is_empty() {
+        return Err(ApiError::snapshot_not_found(format!("No history available for ticker {}. No snapshots have been stored yet.", ticker)));
+    }
+
+    let scale = display_scale(&state, &ticker).await;
+    let history = tops
+        .into_iter()
+        .map(|(timestamp, best_bid, best_ask)| {
+            let best_bid = best_bid.map(|price| price * scale);
+            let best_ask = best_ask.map(|price| price * scale);
+            let (spread, mid_price) = match (best_bid, best_ask) {
+                (Some(bid), Some(ask)) => (Some(ask - bid), Some((bid + ask) / 2.0)),
+                _ => (None, None),
+            };
+            json!({
+                "timestamp": timestamp,
+                "bestBid": best_bid,
+                "bestAsk": best_ask,
+                "spread": spread,
+                "midPrice": mid_price,
+            })
+        })
+        .collect();
+    Ok(Json(history))
+}
+
+/// Parse a per-side level count, defaulting to 10 and capping at 500
+fn parse_levels_param(name: &str, raw: Option<String>) -> Result<usize, ApiError> {
+    match raw {
+        None => Ok(DEFAULT_DEPTH_LEVELS),
+        Some(raw) => match raw.parse::<usize>() {
+            Ok(levels) if levels > 0 => Ok(levels.min(MAX_DEPTH_LEVELS)),
+            _ => Err(ApiError::bad_request(format!("{} must be a positive integer", name))),
+        },
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DepthQuery {
+    /// Levels per side; parsed by the handler so bad values get a JSON error
+    levels: Option<String>,
+    /// Buckets per side of an aggregated ladder; parsed like `levels`
+    buckets: Option<String>,
+    /// Band around the mid of an aggregated ladder, in basis points
+    within_bps: Option<String>,
+}
+
+/// GET /depth/{ticker}?levels=N - Top N bids (descending) and asks (ascending)
+/// 
+/// N defaults to 10 and is capped at 500.
+/// 
+/// With `buckets` and/or `within_bps`, each side is instead aggregated into
+/// `buckets` (default 10) equal price bands within `within_bps` (default 100) of
+/// the mid, as `{price, volume, cumulative}` rungs nearest the mid first.
+/// Returns 404 if the ticker is not registered, 400 if a parameter is not positive
+async fn get_depth(
+    Path(ticker): Path<String>,
+    Query(query): Query<DepthQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    if query.buckets.is_some() || query.within_bps.is_some() {
+        return get_depth_ladder(&state, &ticker, query).await;
+    }
+    let levels = parse_levels_param("levels", query.levels)?;
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+
+    // Copy the levels out so the read guard is released before serializing
+    let (bids, asks) = {
+        let engine = ticker_data.engine.read().await;
+        let scale = engine.display_scale();
+        let to_entry = |(price, volume)| PriceLevelEntry { price: price * scale, volume };
+        (
+            engine.best_levels(Side::Bid, levels).map(to_entry).collect::<Vec<_>>(),
+            engine.best_levels(Side::Ask, levels).map(to_entry).collect::<Vec<_>>(),
+        )
+    };
+
+    Ok(Json(json!({
+        "ticker": ticker,
+        "levels": levels,
+        "bids": bids,
+        "asks": asks,
+    })))
+}
+
+/// Aggregated, cumulative /depth ladders for depth charts
+async fn get_depth_ladder(state: &AppState, ticker: &str, query: DepthQuery) -> Result<Json<Value>, ApiError> {
+    let buckets = parse_levels_param("buckets", query.buckets)?;
+    let within_bps = match query.within_bps {
+        None => DEFAULT_DEPTH_BAND_BPS,
+        Some(raw) => raw
+            .parse::<f64>()
+            .ok()
+            .filter(|bps| bps.is_finite() && *bps > 0.0)
+            .ok_or_else(|| ApiError::bad_request("within_bps must be a positive number"))?,
+    };
+    let ticker_data = get_ticker_data(state, ticker).await?;
+
+    let (mid_price, bids, asks) = {
+        let engine = ticker_data.engine.read().await;
+        let scale = engine.display_scale();
+        let scaled = |levels: Vec<(f64, f64)>| levels.into_iter().map(|(price, volume)| (price * scale, volume)).collect::<Vec<_>>();
+        (
+            engine.mid_price().map(|price| price * scale),
+            scaled(engine.aggregate_levels(Side::Bid, within_bps, buckets)),
+            scaled(engine.aggregate_levels(Side::Ask, within_bps, buckets)),
+        )
+    };
+
+    Ok(Json(json!({
+        "ticker": ticker,
+        "midPrice": mid_price,
+        "buckets": buckets,
+        "withinBps": within_bps,
+        "bids": cumulative_ladder(bids),
+        "asks": cumulative_ladder(asks),
+    })))
+}
+
+/// GET /depth_curve/{ticker} - Cumulative depth of each side for depth charts
+/// 
+/// Each side is an array of `{price, cumulative}` from the best price outward,
+/// so `cumulative` increases along the array.
+/// Returns 404 if the ticker is not registered
+async fn get_depth_curve(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+    let (scale, bids, asks) = {
+        let engine = ticker_data.engine.read().await;
+        (engine.display_scale(), engine.cumulative_depth(Side::Bid), engine.cumulative_depth(Side::Ask))
+    };
+    let to_points = |curve: Vec<(f64, f64)>| curve
+        .into_iter()
+        .map(|(price, cumulative)| json!({ "price": price * scale, "cumulative": cumulative }))
+        .collect::<Vec<_>>();
+
+    Ok(Json(json!({
+        "ticker": ticker,
+        "bids": to_points(bids),
+        "asks": to_points(asks),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImbalanceQuery {
+    /// Levels per side to sum; parsed like `DepthQuery::levels`
+    depth: Option<String>,
+}
+
+/// GET /imbalance/{ticker}?depth=N - Volume imbalance over the top N levels per side
+/// 
+/// `imbalance` is `(bid_vol - ask_vol) / (bid_vol + ask_vol)` in [-1, 1], or null
+/// when the book is empty. N defaults to 10 and is capped at 500.
+/// Returns 404 if the ticker is not registered, 400 if `depth` is not a positive integer
+async fn get_imbalance(
+    Path(ticker): Path<String>,
+    Query(query): Query<ImbalanceQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let depth = parse_levels_param("depth", query.depth)?;
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+    let imbalance = ticker_data.engine.read().await.imbalance(depth);
+
+    Ok(Json(json!({
+        "ticker": ticker,
+        "depth": depth,
+        "imbalance": imbalance,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LiquidityQuery {
+    /// Distance from the mid price in basis points (required)
+    bps: Option<String>,
+}
+
+/// GET /liquidity/{ticker}?bps=N - Volume resting within N basis points of the mid price
+/// 
+/// Returns `{ticker, bps, midPrice, bidVolume, askVolume}`; levels exactly N bps
+/// away count. The volumes and mid are null when either side of the book is empty.
+/// Returns 404 if the ticker is not registered, 400 if `bps` is missing or negative
+async fn get_liquidity(
+    Path(ticker): Path<String>,
+    Query(query): Query<LiquidityQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let bps = query
+        .bps
+        .ok_or_else(|| ApiError::bad_request("bps is required"))?
+        .parse::<f64>()
+        .ok()
+        .filter(|bps| bps.is_finite() && *bps >= 0.0)
+        .ok_or_else(|| ApiError::bad_request("bps must be a non-negative number"))?;
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+    let engine = ticker_data.engine.read().await;
+    let liquidity = engine.liquidity_within_bps(bps);
+
+    Ok(Json(json!({
+        "ticker": ticker,
+        "bps": bps,
+        "midPrice": engine.mid_price().map(|price| price * engine.display_scale()),
+        "bidVolume": liquidity.map(|(bid_volume, _)| bid_volume),
+        "askVolume": liquidity.map(|(_, ask_volume)| ask_volume),
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TradeRangeQuery {
+    /// Start of the range in Unix milliseconds (inclusive, default: unbounded)
+    start: Option<String>,
+    /// End of the range in Unix milliseconds (inclusive, default: unbounded)
+    end: Option<String>,
+}
+
+/// Parse an optional millisecond timestamp query parameter
+fn parse_timestamp_param(name: &str, raw: Option<String>) -> Result<Option<i64>, ApiError> {
+    raw.map(|raw| {
+        raw.parse::<i64>()
+            .map_err(|_| ApiError::invalid_timestamp(format!("{} must be a Unix timestamp in milliseconds", name)))
+    })
+    .transpose()
+}
+
+/// GET /trades/{ticker}/csv?start=&end= - Inferred trades as `text/csv`
+/// 
+/// Columns are `timestamp,price,volume,side` with timestamps in Unix milliseconds.
+/// Returns 404 if the ticker is not registered, 400 if `start`/`end` are invalid
+async fn get_trades_csv(
+    Path(ticker): Path<String>,
+    Query(query): Query<TradeRangeQuery>,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, ApiError> {
+    let start = parse_timestamp_param("start", query.start)?.unwrap_or(i64::MIN);
+    let end = parse_timestamp_param("end", query.end)?.unwrap_or(i64::MAX);
+    if start > end {
+        return Err(ApiError::bad_request("start must not be after end"));
+    }
+    get_ticker_data(&state, &ticker).await?;
+
+    let trades = state.trade_store.get_range(&ticker, start, end).await;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}_trades.csv\"", ticker)),
+        ],
+        trades_to_csv(&trades),
+    ))
+}
+
+/// GET /subscriptions/{ticker} - What the ticker is subscribed to and what Kraken confirmed
+/// 
+/// Returns the pair, book depth, OHLC interval and per-channel ack state, plus
+/// `confirmed` when every channel is acknowledged.
+/// Returns 404 if the ticker is not registered or has no Kraken feed
+async fn get_subscription(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+    let subscription = ticker_data
+        .subscription
+        .ok_or_else(|| ApiError::not_found(format!("No Kraken subscription for ticker: {}", ticker)))?;
+    let subscription = subscription.read().await;
+
+    Ok(Json(json!({
+        "ticker": ticker,
+        "pair": subscription.pair,
+        "depth": subscription.depth,
+        "ohlcInterval": subscription.ohlc_interval,
+        "channels": subscription.channels,
+        "confirmed": subscription.is_confirmed(),
+    })))
+}
+
+/// GET /ticker_info/{ticker} - Symbol, trading pair, base and quote currency
+/// 
+/// Returns 404 if ticker not found
+async fn get_ticker_info(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<TickerInfo>, ApiError> {
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+    Ok(Json(ticker_data.info(&ticker).await))
+}
+
+/// GET /instruments/{ticker} - Static instrument details for a ticker
+/// 
+/// `displayScale` is the factor the ticker's prices are multiplied by in REST,
+/// SSE and WebSocket output; divide by it to recover exchange prices. `tickSize`
+/// is scaled the same way, so it is in the units of the prices it applies to.
+/// Returns 404 if the ticker is not registered
+async fn get_instrument(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let ticker_data = get_ticker_data(&state, &ticker).await?;
+    let pair = match &ticker_data.subscription {
+        Some(subscription) => Some(subscription.read().await.pair.clone()),
+        None => None,
+    };
+    let engine = ticker_data.engine.read().await;
+
+    Ok(Json(json!({
+        "ticker": ticker,
+        "pair": pair,
+        "tickSize": engine.tick_size().map(|tick| tick * engine.display_scale()),
+        "displayScale": engine.display_scale(),
+    })))
+}
+
+/// GET /arena/health - Aggregate market health across every asset and venue
+/// 
+/// `averageSpreadBps` is null when no venue has a usable two-sided book. Feeds
+/// without a book update within the configured threshold count as stale.
+async fn get_arena_health(State(state): State<AppState>) -> Json<Value> {
+    let stale_after = Duration::from_secs(state.config.borrow().stale_feed_threshold_secs);
+    let (healthy, stale) = state.arena.feed_health(stale_after).await;
+
+    Json(json!({
+        "averageSpreadBps": state.arena.average_spread_bps().await,
+        "healthyFeeds": healthy,
+        "staleFeeds": stale,
+    }))
+}
+
+/// GET /arena/{asset}/imbalance - Consolidated order flow imbalance across venues
+/// 
+/// Returns 404 if no venue is tracked for the asset or every tracked book is empty
+async fn get_arena_imbalance(
+    Path(asset): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let venues = state.arena.venues_for(&asset).await.len();
+    if venues == 0 {
+        return Err(ApiError::not_found(format!("No venues tracked for asset {}", asset)));
+    }
+
+    state.arena
+        .consolidated_imbalance(&asset)
+        .await
+        .map(|imbalance| Json(json!({
+            "asset": asset,
+            "imbalance": imbalance,
+            "venues": venues,
+        })))
+        .ok_or_else(|| ApiError::not_found(format!("No orderbook data for asset {} on any venue", asset)))
+}
+
+/// GET /arena/{asset}/mid - Consolidated "arena fair price" across venues
+/// 
+/// Returns 404 if no venue is tracked for the asset or no venue has a two-sided book
+async fn get_arena_mid(
+    Path(asset): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let venues = state.arena.venues_for(&asset).await.len();
+    if venues == 0 {
+        return Err(ApiError::not_found(format!("No venues tracked for asset {}", asset)));
+    }
+
+    state.arena
+        .consolidated_mid(&asset)
+        .await
+        .map(|mid| Json(json!({
+            "asset": asset,
+            "mid": mid,
+            "venues": venues,
+        })))
+        .ok_or_else(|| ApiError::not_found(format!("No two-sided orderbook for asset {} on any venue", asset)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeadLagQuery {
+    /// Newest aligned mid samples to correlate over (default 500)
+    window: Option<String>,
+    /// Largest lag in ticks to try each way (default 20)
+    max_lag: Option<String>,
+}
+
+/// GET /arena/{asset}/leadlag?window=N&max_lag=M - Lead-lag between each pair of venues
+/// 
+/// Each pair's mid changes are cross-correlated at lags up to `max_lag` ticks each
+/// way; `lagTicks` is positive when `venueA` moves first, and `lagMs` converts it
+/// using the average tick spacing. An asset has a second venue only when
+/// `ARENA_SOURCE` names one for it. Returns 404 if no venue is tracked for the asset
+/// or no pair has enough moving mid history, 400 if a parameter isn't a positive integer
+async fn get_arena_leadlag(
+    Path(asset): Path<String>,
+    Query(query): Query<LeadLagQuery>,
+    State(state): State<AppState>,
+) -> Result<Json<Value>, ApiError> {
+    let parse = |name: &str, raw: Option<String>, default: usize| match raw {
+        None => Ok(default),
+        Some(raw) => match raw.parse::<usize>() {
+            Ok(value) if value > 0 => Ok(value),
+            _ => Err(ApiError::bad_request(format!("{} must be a positive integer", name))),
+        },
+    };
+    let window = parse("window", query.window, DEFAULT_LEADLAG_WINDOW)?;
+    let max_lag = parse("max_lag", query.max_lag, DEFAULT_LEADLAG_MAX_LAG)?;
+
+    if state.arena.venues_for(&asset).await.is_empty() {
+        return Err(ApiError::not_found(format!("No venues tracked for asset {}", asset)));
+    }
+    let pairs = state.arena.lead_lag(&asset, window, max_lag).await;
+    if pairs.is_empty() {
+        return Err(ApiError::not_found(format!("Not enough mid history for asset {} to compare venues", asset)));
+    }
+
+    Ok(Json(json!({
+        "asset": asset,
+        "window": window,
+        "maxLag": max_lag,
+        "pairs": pairs,
+    })))
 }
 
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Method, Request};
+    use futures_util::StreamExt;
+    use tower::ServiceExt;
+    use crate::config::Config;
+    use crate::orderbook::engine::BookStatus;
+
+    /// Build an AppState with the given tickers registered and empty engines
+    pub(crate) fn test_state(tickers: &[&str], config: Config) -> AppState {
+        let mut map = HashMap::new();
+        for ticker in tickers {
+            let (orderbook_updates, _) = broadcast::channel::<BookUpdate>(100);
+            let (ohlc_updates, _) = broadcast::channel::<OhlcData>(100);
+            map.insert(ticker.to_string(), TickerData {
+                orderbook_updates,
+                ohlc_updates,
+                engine: Arc::new(RwLock::new(OrderbookEngine::default())),
+                subscription: Some(Arc::new(RwLock::new(SubscriptionState::new(&format!("{}/USD", ticker), 100, 1, crate::exchange::ExchangeSource::Kraken.channels())))),
+                frozen: Arc::new(AtomicBool::new(false)),
+            });
+        }
+        let arena = Arc::new(ArenaAnalytics::new());
+        AppState {
+            snapshot_store: Arc::new(SnapshotStore::new()),
+            trade_store: Arc::new(TradeStore::new()),
+            tickers: Arc::new(Mutex::new(map)),
+            arbitrage: Arc::new(ArbitrageDetector::new(arena.clone(), config.arbitrage_threshold_bps)),
+            config: SharedConfig::new(config),
+            arena,
+            connection_health: Arc::new(ConnectionHealth::new()),
+            websocket_connections: Arc::new(ConnectionLimiter::new()),
+        }
+    }
+
+    /// Send a GET request through the router and parse the JSON response
+    async fn get_json(state: AppState, uri: &str) -> (StatusCode, Value) {
+        request_json(state, Method::GET, uri, None, None).await
+    }
+
+    /// Send a request through the full router and decode the JSON response.
+    /// `token` goes in a bearer Authorization header and `body` is sent as JSON
+    pub(crate) async fn request_json(
+        state: AppState,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<&str>,
+    ) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(header::CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = create_router(state, &[]).oneshot(request.body(body).unwrap()).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn sample_state(bid: f64, ask: f64) -> OrderbookState {
+        OrderbookState {
+            timestamp: 1000,
+            last_price: Some(bid),
+            bids: vec![PriceLevelEntry { price: bid, volume: 1.0 }],
+            asks: vec![PriceLevelEntry { price: ask, volume: 1.0 }],
+            resyncing: false,
+            mid_price: Some((bid + ask) / 2.0),
+            spread: Some(ask - bid),
+            microprice: Some((bid + ask) / 2.0),
+            age_ms: Some(0),
+            smoothed_price: Some(bid),
+            bid_volume: 1.0,
+            ask_volume: 1.0,
+            book_status: BookStatus::Full,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sse_streams_top_of_book() {
+        let state = test_state(&["BTC"], Config::new().with_sse_throttle(0));
+        let app = create_router(state.clone(), &[]);
+
+        let response = app
+            .oneshot(Request::builder().uri("/sse/BTC").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+
+        let sender = state.tickers.lock().await["BTC"].orderbook_updates.clone();
+        sender.send(BookUpdate::Full(sample_state(100.0, 102.0))).unwrap();
+
+        let mut body = response.into_body().into_data_stream();
+        let chunk = tokio::time::timeout(Duration::from_secs(1), body.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let text = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(text.contains("event: top"));
+        assert!(text.contains(r#""bestBid":100.0"#));
+        assert!(text.contains(r#""bestAsk":102.0"#));
+        assert!(text.contains(r#""mid":101.0"#));
+    }
+
+    #[tokio::test]
+    async fn test_spread_includes_ticks() {
+        use crate::kraken::types::BookSnapshot;
+
+        let state = test_state(&["BTC"], Config::new());
+        {
+            let tickers = state.tickers.lock().await;
+            let mut engine = tickers["BTC"].engine.write().await;
+            *engine = OrderbookEngine::new(Some(0.5));
+            engine.apply_snapshot(&BookSnapshot {
+                bids: vec![json!(["100.0", "1.0", "1.0"])],
+                asks: vec![json!(["101.5", "1.0", "1.0"])],
+            }).unwrap();
+        }
+
+        let response = create_router(state, &[])
+            .oneshot(Request::builder().uri("/spread/BTC").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["spread"], 1.5);
+        assert_eq!(body["spreadTicks"], 3);
+        assert_eq!(body["spreadTickAligned"], true);
+    }
+
+    /// Build a state whose BTC book has `levels` bids below 1000 and asks above it
+    async fn deep_book_state(levels: usize) -> AppState {
+        use crate::kraken::types::BookSnapshot;
+
+        let state = test_state(&["BTC"], Config::new());
+        {
+            let tickers = state.tickers.lock().await;
+            let mut engine = tickers["BTC"].engine.write().await;
+            engine.apply_snapshot(&BookSnapshot {
+                bids: (1..=levels).map(|i| json!([format!("{}.0", 1000 - i), "1.0", "1.0"])).collect(),
+                asks: (1..=levels).map(|i| json!([format!("{}.0", 1000 + i), "1.0", "1.0"])).collect(),
+            }).unwrap();
+        }
+        state
+    }
+
+    #[tokio::test]
+    async fn test_depth_defaults_to_ten_levels() {
+        let (status, body) = get_json(deep_book_state(20).await, "/depth/BTC").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["levels"], 10);
+        let bids = body["bids"].as_array().unwrap();
+        let asks = body["asks"].as_array().unwrap();
+        assert_eq!(bids.len(), 10);
+        assert_eq!(asks.len(), 10);
+        assert_eq!(bids[0]["price"], 999.0);
+        assert_eq!(bids[9]["price"], 990.0);
+        assert_eq!(asks[0]["price"], 1001.0);
+        assert_eq!(asks[9]["price"], 1010.0);
+    }
+
+    #[tokio::test]
+    async fn test_depth_levels_param_and_cap() {
+        let (status, body) = get_json(deep_book_state(600).await, "/depth/BTC?levels=3").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["bids"].as_array().unwrap().len(), 3);
+
+        let (status, body) = get_json(deep_book_state(600).await, "/depth/BTC?levels=10000").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["levels"], 500);
+        assert_eq!(body["asks"].as_array().unwrap().len(), 500);
+    }
+
+    #[tokio::test]
+    async fn test_depth_aggregated_ladder() {
+        // Mid 1000, band 100 bps = 10, so five buckets 2 wide holding two levels each
+        let (status, body) = get_json(deep_book_state(20).await, "/depth/BTC?buckets=5&within_bps=100").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["midPrice"], 1000.0);
+        let bids = body["bids"].as_array().unwrap();
+        let asks = body["asks"].as_array().unwrap();
+        assert_eq!(bids.len(), 5);
+        assert_eq!(asks.len(), 5);
+        let column = |rungs: &[Value], field: &str| rungs.iter().map(|rung| rung[field].as_f64().unwrap()).collect::<Vec<_>>();
+        assert_eq!(column(bids, "price"), vec![998.0, 996.0, 994.0, 992.0, 990.0]);
+        assert_eq!(column(bids, "volume"), vec![2.0; 5]);
+        assert_eq!(column(bids, "cumulative"), vec![2.0, 4.0, 6.0, 8.0, 10.0]);
+        assert_eq!(column(asks, "price"), vec![1002.0, 1004.0, 1006.0, 1008.0, 1010.0]);
+        assert_eq!(column(asks, "cumulative"), vec![2.0, 4.0, 6.0, 8.0, 10.0]);
+
+        // Defaults fill in the missing parameter
+        let (_, body) = get_json(deep_book_state(20).await, "/depth/BTC?within_bps=50").await;
+        assert_eq!(body["buckets"], 10);
+        assert_eq!(body["bids"].as_array().unwrap().len(), 10);
+
+        for query in ["buckets=0", "within_bps=0", "within_bps=abc"] {
+            let (status, _) = get_json(deep_book_state(1).await, &format!("/depth/BTC?{}", query)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+        let (status, _) = get_json(deep_book_state(1).await, "/depth/DOGE?buckets=5").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_depth_curve() {
+        let (status, body) = get_json(deep_book_state(3).await, "/depth_curve/BTC").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["bids"], json!([
+            { "price": 999.0, "cumulative": 1.0 },
+            { "price": 998.0, "cumulative": 2.0 },
+            { "price": 997.0, "cumulative": 3.0 },
+        ]));
+        assert_eq!(body["asks"][2], json!({ "price": 1003.0, "cumulative": 3.0 }));
+
+        let (status, _) = get_json(deep_book_state(3).await, "/depth_curve/DOGE").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_stats_reports_rolling_mid_range() {
+        use crate::kraken::types::BookDelta;
+
+        let state = deep_book_state(3).await;
+        state.tickers.lock().await["BTC"].engine.write().await.apply_delta(&BookDelta {
+            bids: vec![json!(["999.0", "0.0", "2.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+
+        // Mid moved from 1000 to 999.5 when the best bid was removed
+        let (status, body) = get_json(state.clone(), "/stats/BTC").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["midRange"], json!({ "windowSecs": 300, "low": 999.5, "high": 1000.0 }));
+
+        let (status, _) = get_json(state, "/stats/BTC?mid_window_secs=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_scrape() {
+        use crate::metrics::TickerCounter;
+
+        // The registry is process-wide, so this test uses its own ticker name
+        let state = deep_book_state(3).await;
+        let metrics = METRICS.ticker("METRICS_TEST");
+        for _ in 0..3 {
+            metrics.increment(TickerCounter::MessagesReceived);
+        }
+        metrics.increment(TickerCounter::DeltasApplied);
+
+        let response = create_router(state, &[])
+            .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers()[header::CONTENT_TYPE].to_str().unwrap().starts_with("text/plain"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("orderbook_messages_received_total{ticker=\"METRICS_TEST\"} 3\n"));
+        assert!(text.contains("orderbook_deltas_applied_total{ticker=\"METRICS_TEST\"} 1\n"));
+        assert!(text.contains("orderbook_snapshots_stored_total{ticker=\"METRICS_TEST\"} 0\n"));
+        assert!(text.contains("orderbook_depth_levels{ticker=\"BTC\",side=\"bid\"} 3\n"));
+        assert!(text.contains("# TYPE websocket_clients gauge\n"));
+    }
+
+    #[tokio::test]
+    async fn test_ready_gates_on_first_snapshots() {
+        use crate::kraken::types::BookSnapshot;
+
+        let state = test_state(&["BTC", "ETH"], Config::new());
+        state.connection_health.record_message("BTC").await;
+        state.connection_health.record_message("ETH").await;
+        let ready = |state: AppState| async move {
+            create_router(state, &[])
+                .oneshot(Request::builder().uri("/ready").body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+        };
+        let snapshot = || BookSnapshot {
+            bids: vec![json!(["99.0", "1.0", "1.0"])],
+            asks: vec![json!(["101.0", "1.0", "1.0"])],
+        };
+
+        let response = ready(state.clone()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "5");
+
+        // Still warming until every Kraken-fed ticker has its first snapshot
+        state.tickers.lock().await["BTC"].engine.write().await.apply_snapshot(&snapshot()).unwrap();
+        let response = ready(state.clone()).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), json!({ "ready": false, "warming": ["ETH"], "stale": [] }));
+
+        state.tickers.lock().await["ETH"].engine.write().await.apply_snapshot(&snapshot()).unwrap();
+        let response = ready(state).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::RETRY_AFTER).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cors_only_allows_configured_origins() {
+        let allow_origin_for = |config: Config, origin: &'static str| async move {
+            let origins = config.allowed_origins.clone();
+            let response = create_router(test_state(&["BTC"], config), &origins)
+                .oneshot(Request::builder().uri("/tickers").header(header::ORIGIN, origin).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            response.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).cloned()
+        };
+
+        // Any origin when none are configured
+        assert_eq!(allow_origin_for(Config::new(), "https://evil.example").await.unwrap(), "*");
+
+        let config = || Config::new().with_allowed_origins(&["https://app.example.com"]);
+        assert_eq!(allow_origin_for(config(), "https://app.example.com").await.unwrap(), "https://app.example.com");
+        assert!(allow_origin_for(config(), "https://evil.example").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_health_requires_a_live_feed() {
+        let state = test_state(&["BTC", "ETH"], Config::new().with_stale_feed_threshold(30));
+        let stale = std::time::Instant::now().checked_sub(Duration::from_secs(60)).unwrap();
+
+        // No messages yet, then only stale ones
+        let (status, body) = get_json(state.clone(), "/health").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["lastSeenAgeMs"], json!({ "BTC": null, "ETH": null }));
+        state.connection_health.record_message_at("BTC", stale).await;
+        assert_eq!(get_json(state.clone(), "/health").await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        // One live feed is enough
+        state.connection_health.record_message("ETH").await;
+        let (status, body) = get_json(state, "/health").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+        assert!(body["lastSeenAgeMs"]["BTC"].as_u64().unwrap() >= 60_000);
+        assert!(body["lastSeenAgeMs"]["ETH"].as_u64().unwrap() < 1000);
+    }
+
+    #[tokio::test]
+    async fn test_ready_requires_every_feed_live() {
+        let state = test_state(&["BTC", "ETH"], Config::new());
+        for ticker in ["BTC", "ETH"] {
+            state.tickers.lock().await[ticker].engine.write().await.apply_snapshot(&crate::kraken::types::BookSnapshot {
+                bids: vec![json!(["99.0", "1.0", "1.0"])],
+                asks: vec![json!(["101.0", "1.0", "1.0"])],
+            }).unwrap();
+        }
+
+        state.connection_health.record_message("BTC").await;
+        let stale = std::time::Instant::now().checked_sub(Duration::from_secs(60)).unwrap();
+        state.connection_health.record_message_at("ETH", stale).await;
+        let (status, body) = get_json(state.clone(), "/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body, json!({ "ready": false, "warming": [], "stale": ["ETH"] }));
+
+        // A threshold raised at runtime applies straight away, as on /arena/health
+        state.config.send_modify(|config| config.stale_feed_threshold_secs = 120);
+        assert_eq!(get_json(state.clone(), "/ready").await.0, StatusCode::OK);
+
+        state.connection_health.record_message("ETH").await;
+        assert_eq!(get_json(state, "/ready").await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_ready_requires_min_levels_per_side() {
+        let state = test_state(&["BTC"], Config::new().with_min_ready_levels(3));
+        state.connection_health.record_message("BTC").await;
+        let status = |state: AppState| async move { get_json(state, "/ready").await.0 };
+        let level = |price: f64| json!([format!("{}", price), "1.0", "1.0"]);
+        let snapshot = |bids: usize, asks: usize| crate::kraken::types::BookSnapshot {
+            bids: (0..bids).map(|i| level(99.0 - i as f64)).collect(),
+            asks: (0..asks).map(|i| level(101.0 + i as f64)).collect(),
+        };
+
+        // One lonely bid, then a deep bid side with too few asks
+        let engine = state.tickers.lock().await["BTC"].engine.clone();
+        engine.write().await.apply_snapshot(&snapshot(1, 1)).unwrap();
+        assert_eq!(status(state.clone()).await, StatusCode::SERVICE_UNAVAILABLE);
+        engine.write().await.apply_snapshot(&snapshot(5, 2)).unwrap();
+        assert_eq!(status(state.clone()).await, StatusCode::SERVICE_UNAVAILABLE);
+
+        engine.write().await.apply_snapshot(&snapshot(3, 3)).unwrap();
+        assert_eq!(status(state).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_depth_errors() {
+        for levels in ["0", "-1", "abc"] {
+            let (status, body) = get_json(deep_book_state(1).await, &format!("/depth/BTC?levels={}", levels)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body["status"], 400);
+            assert_eq!(body["code"], "BAD_REQUEST");
+        }
+
+        let (status, body) = get_json(deep_book_state(1).await, "/depth/DOGE").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "TICKER_NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_imbalance_endpoint() {
+        // 2 bids and 2 asks of volume 1 each: balanced
+        let (status, body) = get_json(deep_book_state(2).await, "/imbalance/BTC?depth=5").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["depth"], 5);
+        assert_eq!(body["imbalance"], 0.0);
+
+        let (status, body) = get_json(test_state(&["BTC"], Config::new()), "/imbalance/BTC").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["depth"], 10);
+        assert!(body["imbalance"].is_null());
+
+        let (status, _) = get_json(deep_book_state(1).await, "/imbalance/BTC?depth=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(deep_book_state(1).await, "/imbalance/DOGE").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_orderbook_returns_current_book() {
+        use crate::kraken::types::BookSnapshot;
+
+        let state = test_state(&["BTC"], Config::new());
+        state.tickers.lock().await["BTC"].engine.write().await.apply_snapshot(&BookSnapshot {
+            bids: vec![json!(["100.0", "1.5", "1.0"]), json!(["99.0", "2.0", "1.0"])],
+            asks: vec![json!(["101.0", "0.5", "1.0"])],
+        }).unwrap();
+
+        let (status, body) = get_json(state.clone(), "/orderbook/BTC").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["bids"], json!([{"price": 100.0, "volume": 1.5}, {"price": 99.0, "volume": 2.0}]));
+        assert_eq!(body["asks"], json!([{"price": 101.0, "volume": 0.5}]));
+        assert_eq!(body["midPrice"], 100.5);
+        assert_eq!(body["bookStatus"], "full");
+
+        let (status, _) = get_json(state, "/orderbook/DOGE").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_liquidity_endpoint() {
+        // Bids 999, 998, ... and asks 1001, 1002, ... around a mid of 1000; 20 bps is 2.0
+        let (status, body) = get_json(deep_book_state(5).await, "/liquidity/BTC?bps=20").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"ticker": "BTC", "bps": 20.0, "midPrice": 1000.0, "bidVolume": 2.0, "askVolume": 2.0}));
+
+        let (status, body) = get_json(test_state(&["BTC"], Config::new()), "/liquidity/BTC?bps=20").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["bidVolume"].is_null());
+
+        for uri in ["/liquidity/BTC", "/liquidity/BTC?bps=-1", "/liquidity/BTC?bps=wide"] {
+            let (status, _) = get_json(deep_book_state(1).await, uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        }
+        let (status, _) = get_json(deep_book_state(1).await, "/liquidity/DOGE?bps=20").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_details() {
+        use crate::kraken::types::SubscriptionStatus;
+
+        let state = test_state(&["BTC"], Config::new());
+        {
+            let tickers = state.tickers.lock().await;
+            let ack: SubscriptionStatus = serde_json::from_value(json!({
+                "event": "subscriptionStatus",
+                "status": "subscribed",
+                "channelID": 42,
+                "pair": "BTC/USD",
+                "subscription": {"name": "book", "depth": 100},
+            })).unwrap();
+            tickers["BTC"].subscription.as_ref().unwrap().write().await.apply_status(&ack.channel_status().unwrap());
+        }
+
+        let (status, body) = get_json(state.clone(), "/subscriptions/BTC").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["pair"], "BTC/USD");
+        assert_eq!(body["depth"], 100);
+        assert_eq!(body["ohlcInterval"], 1);
+        assert_eq!(body["confirmed"], false);
+        assert_eq!(body["channels"], json!([
+            {"name": "book", "confirmed": true, "channelId": 42},
+            {"name": "ohlc", "confirmed": false, "channelId": null},
+            {"name": "trade", "confirmed": false, "channelId": null},
+        ]));
+
+        let (status, _) = get_json(state, "/subscriptions/DOGE").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_nearest_mode() {
+        let state = test_state(&["BTC"], Config::new());
+        for timestamp in [1000, 2000, 3000] {
+            state.snapshot_store
+                .store_snapshot(Snapshot::new("BTC".to_string(), timestamp, None, vec![], vec![]))
+                .await;
+        }
+
+        let (status, _) = get_json(state.clone(), "/snapshot/BTC/2400").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, body) = get_json(state.clone(), "/snapshot/BTC/2400?mode=nearest").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["timestamp"], 2000);
+        let (_, body) = get_json(state.clone(), "/snapshot/BTC/2600?mode=nearest").await;
+        assert_eq!(body["timestamp"], 3000);
+
+        let (status, _) = get_json(state.clone(), "/snapshot/BTC/2000?mode=closest").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(state, "/snapshot/ETH/2000?mode=nearest").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_range() {
+        let state = test_state(&["BTC"], Config::new());
+        for timestamp in [1000, 2000, 3000] {
+            state.snapshot_store
+                .store_snapshot(Snapshot::new("BTC".to_string(), timestamp, None, vec![], vec![]))
+                .await;
+        }
+        let timestamps = |body: Value| -> Vec<i64> {
+            assert_eq!(body["truncated"], false);
+            body["snapshots"].as_array().unwrap().iter().map(|snapshot| snapshot["timestamp"].as_i64().unwrap()).collect()
+        };
+
+        let (status, body) = get_json(state.clone(), "/snapshots/BTC?from=1000&to=2000").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(timestamps(body), vec![1000, 2000]);
+        let (_, body) = get_json(state.clone(), "/snapshots/BTC?from=1500").await;
+        assert_eq!(timestamps(body), vec![2000, 3000]);
+        let (status, body) = get_json(state.clone(), "/snapshots/BTC?from=3001&to=4000").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "snapshots": [], "truncated": false }));
+
+        // Windows over the cap say so
+        for timestamp in 0..MAX_RANGE_SNAPSHOTS as i64 {
+            state.snapshot_store
+                .store_snapshot(Snapshot::new("ETH".to_string(), timestamp, Some(timestamp as f64), vec![], vec![]))
+                .await;
+        }
+        let (_, body) = get_json(state.clone(), "/snapshots/ETH").await;
+        assert_eq!(body["truncated"], false);
+        state.snapshot_store.store_snapshot(Snapshot::new("ETH".to_string(), 5000, None, vec![], vec![])).await;
+        let (_, body) = get_json(state.clone(), "/snapshots/ETH").await;
+        assert_eq!(body["snapshots"].as_array().unwrap().len(), MAX_RANGE_SNAPSHOTS);
+        assert_eq!(body["truncated"], true);
+
+        let (status, _) = get_json(state.clone(), "/snapshots/BTC?from=2000&to=1000").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(state, "/snapshots/BTC?from=soon").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_diff_between_nearest_snapshots() {
+        let state = test_state(&["BTC"], Config::new());
+        let level = |price: f64, volume: f64| PriceLevelEntry { price, volume };
+        state.snapshot_store
+            .store_snapshot(Snapshot::new("BTC".to_string(), 1000, None, vec![level(99.0, 1.0)], vec![level(101.0, 1.0)]))
+            .await;
+        state.snapshot_store
+            .store_snapshot(Snapshot::new("BTC".to_string(), 2000, None, vec![level(99.0, 3.0)], vec![level(102.0, 1.0)]))
+            .await;
+
+        let (status, body) = get_json(state.clone(), "/diff/BTC?from=1100&to=1900").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((body["from"].as_i64(), body["to"].as_i64()), (Some(1000), Some(2000)));
+        assert_eq!(body["bids"]["changed"], json!([{ "price": 99.0, "fromVolume": 1.0, "toVolume": 3.0 }]));
+        assert_eq!(body["asks"]["added"], json!([{ "price": 102.0, "volume": 1.0 }]));
+        assert_eq!(body["asks"]["removed"], json!([{ "price": 101.0, "volume": 1.0 }]));
+
+        let (status, _) = get_json(state.clone(), "/diff/ETH?from=1000&to=2000").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get_json(state, "/diff/BTC?from=1000").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_export_streams_one_snapshot_per_line() {
+        let state = test_state(&["BTC"], Config::new());
+        // More than one batch
+        let stored: Vec<i64> = (1..=EXPORT_BATCH as i64 + 5).map(|i| i * 1000).collect();
+        for &timestamp in &stored {
+            let bids = vec![PriceLevelEntry { price: 100.0, volume: timestamp as f64 }];
+            state.snapshot_store
+                .store_snapshot(Snapshot::new("BTC".to_string(), timestamp, Some(100.0), bids, vec![]))
+                .await;
+        }
+
+        let response = create_router(state.clone(), &[])
+            .oneshot(Request::builder().uri("/export/BTC").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let snapshots: Vec<Snapshot> = std::str::from_utf8(&body)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(snapshots.len(), state.snapshot_store.len().await);
+        let timestamps: Vec<i64> = snapshots.iter().map(|snapshot| snapshot.timestamp).collect();
+        assert_eq!(timestamps, stored);
+
+        let (status, _) = get_json(state, "/export/ETH").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_spread_history_follows_snapshots() {
+        let state = test_state(&["BTC"], Config::new());
+        let level = |price: f64| PriceLevelEntry { price, volume: 1.0 };
+        for (timestamp, bids, asks) in [
+            (1000, vec![level(100.0), level(99.0)], vec![level(101.0)]),
+            (2000, vec![], vec![level(103.0)]),
+            (3000, vec![level(101.0)], vec![level(101.5), level(102.0)]),
+        ] {
+            state.snapshot_store
+                .store_snapshot(Snapshot::new("BTC".to_string(), timestamp, None, bids, asks))
+                .await;
+        }
+
+        let (status, body) = get_json(state.clone(), "/spread_history/BTC").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([
+            {"timestamp": 1000, "bestBid": 100.0, "bestAsk": 101.0, "spread": 1.0, "midPrice": 100.5},
+            {"timestamp": 2000, "bestBid": null, "bestAsk": 103.0, "spread": null, "midPrice": null},
+            {"timestamp": 3000, "bestBid": 101.0, "bestAsk": 101.5, "spread": 0.5, "midPrice": 101.25},
+        ]));
+
+        let (status, _) = get_json(state, "/spread_history/ETH").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_history_overview() {
+        let state = test_state(&["BTC", "ETH"], Config::new());
+        let (status, body) = get_json(state.clone(), "/history").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({}));
+
+        for (ticker, timestamp) in [("BTC", 1000), ("BTC", 3000), ("ETH", 2000)] {
+            state.snapshot_store
+                .store_snapshot(Snapshot::new(ticker.to_string(), timestamp, None, vec![], vec![]))
+                .await;
+        }
+        let (_, body) = get_json(state, "/history").await;
+        assert_eq!(body, json!({
+            "BTC": { "minTimestamp": 1000, "maxTimestamp": 3000 },
+            "ETH": { "minTimestamp": 2000, "maxTimestamp": 2000 },
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_tickers_lists_data_status() {
+        use crate::kraken::types::BookSnapshot;
+
+        let state = test_state(&["BTC", "ETH"], Config::new());
+        state.tickers.lock().await["BTC"].engine.write().await.apply_snapshot(&BookSnapshot {
+            bids: vec![json!(["99.0", "1.0", "1.0"]), json!(["98.0", "1.0", "1.0"])],
+            asks: vec![json!(["101.0", "1.0", "1.0"])],
+        }).unwrap();
+
+        let (status, body) = get_json(state, "/tickers").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([
+            {
+                "ticker": "BTC",
+                "info": { "symbol": "BTC", "pair": "BTC/USD", "base": "BTC", "quote": "USD" },
+                "hasData": true, "bidLevels": 2, "askLevels": 1, "lastPrice": null,
+            },
+            {
+                "ticker": "ETH",
+                "info": { "symbol": "ETH", "pair": "ETH/USD", "base": "ETH", "quote": "USD" },
+                "hasData": false, "bidLevels": 0, "askLevels": 0, "lastPrice": null,
+            },
+        ]));
+    }
+
+    #[test]
+    fn test_ticker_info_splits_pair() {
+        let info = TickerInfo::new("BTC", "BTC/USD");
+        assert_eq!((info.base.as_str(), info.quote.as_str()), ("BTC", "USD"));
+        let info = TickerInfo::new("BTC", "XBT/EUR");
+        assert_eq!(info.symbol, "BTC");
+        assert_eq!((info.base.as_str(), info.quote.as_str()), ("XBT", "EUR"));
+        let info = TickerInfo::new("BTC", "BTCUSD");
+        assert_eq!(info.pair, "BTCUSD");
+        assert_eq!((info.base.as_str(), info.quote.as_str()), ("BTCUSD", UNKNOWN_QUOTE));
+    }
+
+    #[tokio::test]
+    async fn test_ticker_info_endpoint() {
+        let state = test_state(&["ZEC"], Config::new());
+        let (status, body) = get_json(state.clone(), "/ticker_info/ZEC").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "symbol": "ZEC", "pair": "ZEC/USD", "base": "ZEC", "quote": "USD" }));
+        let (status, _) = get_json(state, "/ticker_info/XMR").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_instrument_reports_display_scale() {
+        let state = test_state(&["SHIB", "BTC"], Config::new());
+        {
+            let tickers = state.tickers.lock().await;
+            let mut engine = OrderbookEngine::new(Some(1e-8)).with_display_scale(1e8);
+            engine.apply_snapshot(&crate::kraken::types::BookSnapshot {
+                bids: vec![json!(["0.00001200", "1.0", "1.0"])],
+                asks: vec![json!(["0.00001203", "1.0", "1.0"])],
+            }).unwrap();
+            *tickers["SHIB"].engine.write().await = engine;
+        }
+
+        // The tick size is in the same scaled units as the prices it applies to
+        let (status, body) = get_json(state.clone(), "/instruments/SHIB").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({ "ticker": "SHIB", "pair": "SHIB/USD", "tickSize": 1.0, "displayScale": 1e8 }));
+        let (_, body) = get_json(state.clone(), "/spread/SHIB").await;
+        assert_eq!(body["tickSize"], 1.0);
+        assert!((body["spread"].as_f64().unwrap() - 3.0).abs() < 1e-9);
+        assert_eq!(body["spreadTicks"], 3.0);
+
+        let (_, body) = get_json(state.clone(), "/instruments/BTC").await;
+        assert_eq!(body["displayScale"], 1.0);
+        let (status, _) = get_json(state, "/instruments/XMR").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_display_scale_applies_to_output_only() {
+        let state = deep_book_state(3).await;
+        {
+            let tickers = state.tickers.lock().await;
+            let mut engine = tickers["BTC"].engine.write().await;
+            let book = std::mem::take(&mut *engine);
+            *engine = book.with_display_scale(10.0);
+            let snapshot = Snapshot::from_orderbook_state("BTC".to_string(), engine.get_current_state());
+            state.snapshot_store.store_snapshot(Snapshot { timestamp: 1000, ..snapshot }).await;
+        }
+
+        // Live and stored books alike are scaled as they are served
+        let (_, body) = get_json(state.clone(), "/spread/BTC").await;
+        assert_eq!((body["bestBid"].clone(), body["bestAsk"].clone(), body["spread"].clone()), (json!(9990.0), json!(10010.0), json!(20.0)));
+        let (_, body) = get_json(state.clone(), "/depth/BTC?levels=1").await;
+        assert_eq!(body["bids"][0]["price"], 9990.0);
+        let (_, body) = get_json(state.clone(), "/orderbook/BTC").await;
+        assert_eq!((body["bids"][0]["price"].clone(), body["midPrice"].clone()), (json!(9990.0), json!(10000.0)));
+        let (_, body) = get_json(state.clone(), "/snapshot/BTC/1000").await;
+        assert_eq!(body["asks"][0]["price"], 10010.0);
+        let (_, body) = get_json(state.clone(), "/spread_history/BTC").await;
+        assert_eq!(body[0]["midPrice"], 10000.0);
+
+        // The engine and the store keep exchange prices
+        assert_eq!(state.tickers.lock().await["BTC"].engine.read().await.best_bid(), Some(999.0));
+        assert_eq!(state.snapshot_store.get_snapshot("BTC", 1000).await.unwrap().bids[0].price, 999.0);
+    }
+
+    #[tokio::test]
+    async fn test_arena_health() {
+        let state = deep_book_state(1).await;
+        let engine = state.tickers.lock().await["BTC"].engine.clone();
+        state.arena.register_venue("BTC", "kraken", engine).await;
+        state.arena.register_venue("ETH", "kraken", Arc::new(RwLock::new(OrderbookEngine::default()))).await;
+
+        let (status, body) = get_json(state, "/arena/health").await;
+        assert_eq!(status, StatusCode::OK);
+        // 999/1001: 2 / 1000 = 20 bps
+        assert!((body["averageSpreadBps"].as_f64().unwrap() - 20.0).abs() < 1e-9);
+        assert_eq!(body["healthyFeeds"], 1);
+        assert_eq!(body["staleFeeds"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_arena_leadlag() {
+        use crate::kraken::types::BookSnapshot;
+
+        let state = test_state(&["BTC"], Config::new());
+        let (status, _) = get_json(state.clone(), "/arena/BTC/leadlag").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let leader = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let follower = Arc::new(RwLock::new(OrderbookEngine::default()));
+        state.arena.register_venue("BTC", "binance", leader.clone()).await;
+        state.arena.register_venue("BTC", "kraken", follower.clone()).await;
+
+        // The follower's mid repeats the leader's one tick later
+        let book = |mid: f64| BookSnapshot {
+            bids: vec![json!([format!("{}", mid - 0.5), "1.0", "1.0"])],
+            asks: vec![json!([format!("{}", mid + 0.5), "1.0", "1.0"])],
+        };
+        let mids = [100.0, 102.0, 101.0, 104.0, 103.0, 107.0, 105.0, 106.0, 110.0, 108.0];
+        let mut previous = mids[0];
+        for mid in mids {
+            leader.write().await.apply_snapshot(&book(mid)).unwrap();
+            follower.write().await.apply_snapshot(&book(previous)).unwrap();
+            state.arena.record_mids("BTC").await;
+            previous = mid;
+        }
+
+        let (status, body) = get_json(state.clone(), "/arena/BTC/leadlag?max_lag=3").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["maxLag"], 3);
+        assert_eq!(body["pairs"][0]["venueA"], "binance");
+        assert_eq!(body["pairs"][0]["venueB"], "kraken");
+        assert_eq!(body["pairs"][0]["lagTicks"], 1);
+
+        let (status, _) = get_json(state, "/arena/BTC/leadlag?window=0").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_trades_csv_export_within_range() {
+        use crate::orderbook::trades::{DetectedTrade, TradeSide};
+
+        let state = test_state(&["BTC"], Config::new());
+        for (timestamp_ms, price, side) in [
+            (1_000, 100.0, TradeSide::Sell),
+            (2_000, 100.5, TradeSide::Buy),
+            (3_000, 101.0, TradeSide::Sell),
+        ] {
+            state.trade_store.store_trade("BTC", DetectedTrade { timestamp_ms, price, volume: 0.25, side }).await;
+        }
+
+        let response = create_router(state.clone(), &[])
+            .oneshot(Request::builder().uri("/trades/BTC/csv?start=1500&end=3000").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/csv");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            "timestamp,price,volume,side\n2000,100.5,0.25,buy\n3000,101,0.25,sell\n"
+        );
+
+        let (status, _) = get_json(state.clone(), "/trades/BTC/csv?start=abc").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(state, "/trades/DOGE/csv").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_sse_unknown_ticker() {
+        let app = create_router(test_state(&["BTC"], Config::new()), &[]);
+        let response = app
+            .oneshot(Request::builder().uri("/sse/DOGE").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
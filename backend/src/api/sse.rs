@@ -0,0 +1,142 @@
+//! Server-Sent Events endpoint handler
+//! 
+//! This module contains the handler for the /sse/{ticker} endpoint that streams
+//! a minimal top-of-book view for simple dashboards that don't need WebSockets.
+
+use axum::{
+    extract::{Path, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::stream::{self, Stream};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::{Duration, Instant};
+use crate::api::error::ApiError;
+use crate::api::routes::AppState;
+use crate::orderbook::engine::{BookUpdate, OrderbookEngine, OrderbookState};
+
+/// Top-of-book summary sent as the data of each SSE event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TopOfBook {
+    #[serde(rename = "bestBid")]
+    pub best_bid: Option<f64>,
+    #[serde(rename = "bestAsk")]
+    pub best_ask: Option<f64>,
+    pub mid: Option<f64>,
+    #[serde(rename = "lastPrice")]
+    pub last_price: Option<f64>,
+}
+
+impl TopOfBook {
+    /// Extract the top of book from a full orderbook state
+    /// 
+    /// Bids and asks in the state are already sorted best-first.
+    pub fn from_state(state: &OrderbookState) -> Self {
+        let best_bid = state.bids.first().map(|level| level.price);
+        let best_ask = state.asks.first().map(|level| level.price);
+        let mid = match (best_bid, best_ask) {
+            (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+            _ => None,
+        };
+
+        Self {
+            best_bid,
+            best_ask,
+            mid,
+            last_price: state.last_price,
+        }
+    }
+
+    /// Extract the top of book from an engine's cached best levels
+    pub fn from_engine(engine: &OrderbookEngine) -> Self {
+        Self {
+            best_bid: engine.top_bids().first().map(|(price, _)| *price),
+            best_ask: engine.top_asks().first().map(|(price, _)| *price),
+            mid: engine.mid_price(),
+            last_price: engine.last_price(),
+        }
+    }
+
+    /// This top of book with every price multiplied by a ticker's display scale
+    pub fn scaled(self, scale: f64) -> Self {
+        let scale_price = |price: Option<f64>| price.map(|price| price * scale);
+        Self {
+            best_bid: scale_price(self.best_bid),
+            best_ask: scale_price(self.best_ask),
+            mid: scale_price(self.mid),
+            last_price: scale_price(self.last_price),
+        }
+    }
+}
+
+/// SSE handler for /sse/{ticker}
+/// 
+/// Streams a `top` event carrying `TopOfBook` JSON on orderbook updates, emitting
+/// at most one event per `sse_throttle_ms` (always the latest state). Prices are
+/// multiplied by the ticker's display scale.
+/// Returns 404 if the ticker is not registered
+pub async fn handle_sse(
+    Path(ticker): Path<String>,
+    State(state): State<AppState>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let (receiver, engine) = {
+        let tickers = state.tickers.lock().await;
+        tickers
+            .get(&ticker)
+            .map(|ticker_data| (ticker_data.orderbook_updates.subscribe(), ticker_data.engine.clone()))
+            .ok_or_else(|| ApiError::ticker_not_found(format!("Unknown ticker: {}", ticker)))?
+    };
+    let throttle = Duration::from_millis(state.config.borrow().sse_throttle_ms);
+    let scale = engine.read().await.display_scale();
+
+    tracing::info!(ticker = %ticker, "SSE client connected");
+    Ok(Sse::new(top_of_book_stream(receiver, engine, throttle, scale)).keep_alive(KeepAlive::default()))
+}
+
+/// Turn a broadcast receiver into a throttled stream of top-of-book events
+/// 
+/// Full states carry their own top of book; diffs only carry changed levels, so
+/// the top of book is read from the engine instead.
+fn top_of_book_stream(
+    receiver: broadcast::Receiver<BookUpdate>,
+    engine: Arc<RwLock<OrderbookEngine>>,
+    throttle: Duration,
+    scale: f64,
+) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream::unfold((receiver, engine, None::<Instant>), move |(mut receiver, engine, last_sent)| async move {
+        loop {
+            let mut latest = match receiver.recv().await {
+                Ok(update) => update,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+
+            // Wait out the throttle window, then coalesce to the newest update
+            if let Some(last_sent) = last_sent {
+                tokio::time::sleep_until(last_sent + throttle).await;
+            }
+            loop {
+                match receiver.try_recv() {
+                    Ok(update) => latest = update,
+                    Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                }
+            }
+
+            let top = match &latest {
+                BookUpdate::Full(state) => TopOfBook::from_state(state),
+                BookUpdate::Diff(_) => TopOfBook::from_engine(&*engine.read().await),
+            };
+            let event = match Event::default().event("top").json_data(top.scaled(scale)) {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::error!(error = %e, "Error serializing top of book");
+                    continue;
+                }
+            };
+            return Some((Ok(event), (receiver, engine, Some(Instant::now()))));
+        }
+    })
+}
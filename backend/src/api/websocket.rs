@@ -1,163 +1,872 @@
-//! WebSocket server endpoint handler
+//! WebSocket server endpoint handlers
 //! 
-//! This module contains the WebSocket handler for the /live endpoint
-//! that streams real-time orderbook updates.
+//! This module contains the WebSocket handlers for the /live endpoint
+//! that streams real-time orderbook updates (a full book on connect, then
+//! diffs of changed levels), and the /arbitrage endpoint
+//! that streams cross-exchange arbitrage opportunities.
+//! 
+//! `/live?tickers=BTC,ETH` follows several books over one socket; every /live
+//! message carries a `ticker` field to demultiplex on. A ticker whose feed goes
+//! away is dropped with an `info` message; the socket closes once none are left.
+//! `/live?throttle_ms=N` coalesces orderbook updates: at most one full book per
+//! ticker is sent per N milliseconds, always the latest. OHLC messages are never
+//! throttled.
+//! `/live?depth=N` truncates every book sent to this client to its top N levels
+//! per side; such clients get full (truncated) books in place of diffs.
+//! `/live?depths=10,100` sends one truncated book per listed depth for every
+//! update, each tagged with a `depth` field, so one socket serves several views.
+//! `/live?ticker=BTC&replay=true&speed=N` streams the ticker's stored snapshots
+//! instead of the live feed, N times faster than recorded, then closes.
+//! 
+//! Once connected, a /live client can send `{"action":"subscribe","ticker":"BTC"}`
+//! to switch to another ticker (its current book is sent straight away) or
+//! `{"action":"unsubscribe"}` to pause updates until its next subscribe.
+//! 
+//! At most `max_connections` /live and /arbitrage sockets are open at once;
+//! further upgrades are closed straight away with 1013 (try again later).
 
 use axum::{
     extract::{ws::{Message, WebSocketUpgrade}, State, Query},
     response::Response,
+    Extension,
 };
-use futures_util::{SinkExt, StreamExt};
-use tokio::sync::broadcast;
-use crate::api::routes::AppState;
-use crate::orderbook::engine::OrderbookState;
+use axum::extract::ws::{close_code, CloseFrame, WebSocket};
+use futures_util::{stream::SplitSink, SinkExt, Stream, StreamExt};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{Interval, MissedTickBehavior};
+use crate::api::routes::{display_scale, AppState, TickerData};
+use crate::api::request_id::RequestId;
+use crate::metrics::METRICS;
+use crate::orderbook::engine::{BookUpdate, OrderbookDelta, OrderbookEngine, OrderbookState};
 use crate::kraken::types::OhlcData;
+use crate::arena::arbitrage::ArbitrageOpportunity;
 use serde::{Deserialize, Serialize};
+use tracing::Instrument;
 
 /// WebSocket message wrapper to distinguish between different data types
 #[derive(Debug, Serialize)]
 #[serde(tag = "type")]
 enum WebSocketMessage {
+    /// A full book; `depth` is set when the client asked for several depth views
     #[serde(rename = "orderbook")]
-    Orderbook { data: OrderbookState },
+    Orderbook {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        depth: Option<usize>,
+        data: OrderbookState,
+    },
+    /// Levels changed since the previous message; volume 0 removes a level
+    #[serde(rename = "orderbookDiff")]
+    OrderbookDiff { data: OrderbookDelta },
     #[serde(rename = "ohlc")]
     Ohlc { data: OhlcData },
+    /// The book is being rebuilt; clients should show a loading state
+    #[serde(rename = "resyncing")]
+    Resyncing,
+    /// A fresh snapshot has landed after a resync
+    #[serde(rename = "resynced")]
+    Resynced,
+    /// A cross-exchange opportunity above the configured threshold
+    #[serde(rename = "arbitrage")]
+    Arbitrage { data: ArbitrageOpportunity },
+    /// Informational notice, e.g. that a requested ticker was skipped
+    #[serde(rename = "info")]
+    Info { message: String },
+}
+
+/// A command sent by a /live client as a text message
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum ClientCommand {
+    /// Follow this ticker in place of the current ones
+    Subscribe { ticker: String },
+    /// Stop sending updates until the next subscribe
+    Unsubscribe,
+}
+
+/// Parse a client text message into a command
+fn parse_client_command(text: &str) -> Result<ClientCommand, String> {
+    serde_json::from_str(text).map_err(|e| format!("invalid command: {}", e))
+}
+
+/// Largest per-side book depth a /live client may request
+const MAX_LIVE_DEPTH: usize = 1000;
+
+/// Slowest replay speed a /live client may request
+const MIN_REPLAY_SPEED: f64 = 0.01;
+
+/// Stored snapshots rebuilt per store read while replaying
+const REPLAY_BATCH: usize = 32;
+
+/// A /live message tagged with the ticker it concerns
+#[derive(Debug, Serialize)]
+struct TickerMessage<'a> {
+    ticker: &'a str,
+    #[serde(flatten)]
+    message: WebSocketMessage,
+}
+
+/// Book depths a /live client receives
+#[derive(Debug, Clone, PartialEq)]
+enum DepthViews {
+    /// Every level, as diffs after the first full book
+    Full,
+    /// Full books truncated to this many levels per side (`?depth=N`)
+    Single(usize),
+    /// One full book per depth, each tagged with its depth (`?depths=10,100`)
+    Tagged(Vec<usize>),
+}
+
+/// Copy of a state truncated to `depth` levels per side
+fn truncated(state: &OrderbookState, depth: usize) -> OrderbookState {
+    OrderbookState {
+        bids: state.bids[..depth.min(state.bids.len())].to_vec(),
+        asks: state.asks[..depth.min(state.asks.len())].to_vec(),
+        ..*state
+    }
+}
+
+/// Build the messages to send for an orderbook state
+/// 
+/// Emits a `resyncing`/`resynced` control message ahead of the state whenever the
+/// state's resync flag differs from the last one this client saw. The state is
+/// then sent once per depth view, truncated to that view's depth.
+fn orderbook_messages(mut state: OrderbookState, client_resyncing: &mut bool, views: &DepthViews) -> Vec<WebSocketMessage> {
+    let mut messages = Vec::with_capacity(2);
+    if state.resyncing != *client_resyncing {
+        *client_resyncing = state.resyncing;
+        messages.push(if state.resyncing {
+            WebSocketMessage::Resyncing
+        } else {
+            WebSocketMessage::Resynced
+        });
+    }
+    match views {
+        DepthViews::Full => messages.push(WebSocketMessage::Orderbook { depth: None, data: state }),
+        DepthViews::Single(depth) => {
+            state.bids.truncate(*depth);
+            state.asks.truncate(*depth);
+            messages.push(WebSocketMessage::Orderbook { depth: None, data: state });
+        }
+        DepthViews::Tagged(depths) => messages.extend(depths.iter().map(|&depth| WebSocketMessage::Orderbook {
+            depth: Some(depth),
+            data: truncated(&state, depth),
+        })),
+    }
+    messages
 }
 
 #[derive(Debug, Deserialize)]
 pub struct WebSocketQuery {
     #[serde(default = "default_ticker")]
     ticker: String,
+    /// Comma-separated tickers to follow; takes precedence over `ticker`
+    tickers: Option<String>,
+    /// Minimum milliseconds between orderbook messages (0 or absent = unthrottled)
+    throttle_ms: Option<u64>,
+    /// Levels per side of each book sent, 1 to 1000 (absent = full depth)
+    depth: Option<usize>,
+    /// Comma-separated depths, each sent as its own tagged view; takes precedence over `depth`
+    depths: Option<String>,
+    /// Stream the ticker's stored snapshots instead of the live feed
+    #[serde(default)]
+    replay: bool,
+    /// Replay speed as a multiple of recorded time (default 1)
+    speed: Option<f64>,
 }
 
 fn default_ticker() -> String {
     "ZEC".to_string()
 }
 
+impl WebSocketQuery {
+    /// Tickers to follow, and whether missing ones should be created on demand
+    /// 
+    /// Only a lone legacy `ticker` is created on demand; an explicit list skips
+    /// unknown tickers instead.
+    fn requested_tickers(&self) -> (Vec<String>, bool) {
+        match &self.tickers {
+            Some(list) => (
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|ticker| !ticker.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                false,
+            ),
+            None => (vec![self.ticker.clone()], true),
+        }
+    }
+
+    /// Validated replay speed, `None` unless `replay` was requested
+    fn replay_speed(&self) -> Result<Option<f64>, String> {
+        if !self.replay {
+            return Ok(None);
+        }
+        match self.speed.unwrap_or(1.0) {
+            speed if speed.is_finite() && speed >= MIN_REPLAY_SPEED => Ok(Some(speed)),
+            _ => Err(format!("speed must be a number of at least {}", MIN_REPLAY_SPEED)),
+        }
+    }
+
+    /// Validated depth views; every depth must be between 1 and 1000
+    fn depth_views(&self) -> Result<DepthViews, String> {
+        let check = |depth: usize| {
+            if (1..=MAX_LIVE_DEPTH).contains(&depth) {
+                Ok(depth)
+            } else {
+                Err(format!("depth must be between 1 and {}", MAX_LIVE_DEPTH))
+            }
+        };
+        match (&self.depths, self.depth) {
+            (Some(list), _) => {
+                let mut depths = Vec::new();
+                for raw in list.split(',').map(str::trim).filter(|raw| !raw.is_empty()) {
+                    let depth = raw.parse::<usize>().map_err(|_| format!("invalid depth {:?}", raw))?;
+                    let depth = check(depth)?;
+                    if !depths.contains(&depth) {
+                        depths.push(depth);
+                    }
+                }
+                if depths.is_empty() {
+                    return Err("depths must list at least one depth".to_string());
+                }
+                Ok(DepthViews::Tagged(depths))
+            }
+            (None, Some(depth)) => check(depth).map(DepthViews::Single),
+            (None, None) => Ok(DepthViews::Full),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArbitrageQuery {
+    /// Only stream opportunities for this asset (all assets if omitted)
+    asset: Option<String>,
+}
+
+/// Build the message for an arbitrage opportunity, or `None` if the client's
+/// asset filter excludes it
+fn arbitrage_message(opportunity: ArbitrageOpportunity, asset: Option<&str>) -> Option<WebSocketMessage> {
+    match asset {
+        Some(asset) if !opportunity.asset.eq_ignore_ascii_case(asset) => None,
+        _ => Some(WebSocketMessage::Arbitrage { data: opportunity }),
+    }
+}
+
+/// Count of open WebSocket clients, shared by /live and /arbitrage
+#[derive(Debug, Default)]
+pub struct ConnectionLimiter {
+    open: AtomicUsize,
+}
+
+impl ConnectionLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of connections currently holding a slot
+    pub fn open(&self) -> usize {
+        self.open.load(Ordering::SeqCst)
+    }
+
+    /// Take a slot for a new connection, or `None` if `max` are already open
+    /// 
+    /// The slot is released when the returned guard is dropped.
+    pub fn try_acquire(self: &Arc<Self>, max: usize) -> Option<ConnectionGuard> {
+        self.open
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |open| (open < max).then_some(open + 1))
+            .ok()?;
+        Some(ConnectionGuard { limiter: Arc::clone(self) })
+    }
+}
+
+/// A connection's slot in the [`ConnectionLimiter`], released on drop
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    limiter: Arc<ConnectionLimiter>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.limiter.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Take a connection slot, logging when the limit turns a client away
+async fn acquire_connection(state: &AppState, conn_id: &str) -> Option<ConnectionGuard> {
+    let max_connections = state.config.borrow().max_connections;
+    let guard = state.websocket_connections.try_acquire(max_connections);
+    if guard.is_none() {
+        tracing::warn!(conn_id = %conn_id, max_connections, "Rejecting WebSocket connection, limit reached");
+    }
+    guard
+}
+
+/// Complete the upgrade only to close the socket with 1013 (try again later)
+fn reject_busy(ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(|socket| close_with_reason(socket, close_code::AGAIN, "too many connections, try again later".to_string()))
+}
+
+/// Delays between replayed snapshots, fed their timestamps in order
+struct ReplaySchedule {
+    speed: f64,
+    previous: Option<i64>,
+}
+
+impl ReplaySchedule {
+    fn new(speed: f64) -> Self {
+        Self { speed, previous: None }
+    }
+
+    /// Delay to wait before sending the snapshot recorded at `timestamp`
+    /// 
+    /// The first snapshot is due immediately; each later one after the time between
+    /// its timestamp and its predecessor's, divided by `speed`. A gap too long
+    /// to represent waits forever rather than overflowing.
+    fn delay(&mut self, timestamp: i64) -> Duration {
+        let gap_secs = self.previous.map_or(0, |previous| timestamp.saturating_sub(previous).max(0));
+        self.previous = Some(timestamp);
+        Duration::try_from_secs_f64(gap_secs as f64 / self.speed).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Gate that coalesces orderbook updates into at most one send per interval
+/// 
+/// The first update after a quiet period is released immediately; updates
+/// arriving within the interval are folded into the next release.
+struct UpdateThrottle {
+    interval: Interval,
+    pending: bool,
+}
+
+impl UpdateThrottle {
+    /// Create a throttle, or `None` when `throttle_ms` is 0 (unthrottled)
+    fn new(throttle_ms: u64) -> Option<Self> {
+        if throttle_ms == 0 {
+            return None;
+        }
+        let mut interval = tokio::time::interval(Duration::from_millis(throttle_ms));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Some(Self { interval, pending: false })
+    }
+
+    /// Record that an update arrived and is waiting to be sent
+    fn mark_pending(&mut self) {
+        self.pending = true;
+    }
+
+    /// Wait until an update is pending and the interval allows a send
+    /// 
+    /// Never completes while nothing is pending, or when unthrottled.
+    async fn ready(throttle: &mut Option<Self>) {
+        match throttle {
+            Some(throttle) if throttle.pending => {
+                throttle.interval.tick().await;
+                throttle.pending = false;
+            }
+            _ => std::future::pending().await,
+        }
+    }
+}
+
+/// Serialize and send messages in order, tagged with their ticker
+/// 
+/// Returns false if the client disconnected.
+async fn send_messages(sender: &mut SplitSink<WebSocket, Message>, ticker: &str, messages: Vec<WebSocketMessage>) -> bool {
+    for message in messages {
+        let json = match serde_json::to_string(&TickerMessage { ticker, message }) {
+            Ok(json) => json,
+            Err(e) => {
+                tracing::error!(error = %e, "Error serializing orderbook update");
+                continue;
+            }
+        };
+        if sender.send(Message::Text(json)).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
 /// WebSocket handler for /live endpoint
 /// 
 /// Accepts WebSocket connections and streams real-time orderbook updates
-/// Query parameter: ticker (optional, defaults to "ZEC")
+/// Query parameters: ticker (optional, defaults to "ZEC"), tickers (optional,
+/// comma-separated), throttle_ms (optional), depth (optional, 1 to 1000),
+/// depths (optional, comma-separated, each 1 to 1000), replay (optional) and
+/// speed (optional, at least 0.01, defaults to 1)
+/// 
+/// An out-of-range or malformed depth or speed is rejected by closing the socket
+/// with a policy violation (1008) right after the upgrade, and a connection over
+/// `max_connections` with 1013 (try again later).
+/// 
+/// The upgrade request's correlation id becomes the connection id used in all of
+/// the connection's logs and its tracing span.
 pub async fn handle_websocket(
     ws: WebSocketUpgrade,
     Query(query): Query<WebSocketQuery>,
     State(state): State<AppState>,
+    Extension(RequestId(conn_id)): Extension<RequestId>,
 ) -> Response {
-    eprintln!("WebSocket upgrade request received for /live endpoint with ticker: {}", query.ticker);
+    let (requested, create_missing) = query.requested_tickers();
+    let ticker_label = requested.join(",");
+    tracing::info!(conn_id = %conn_id, tickers = %ticker_label, "WebSocket upgrade request received for /live");
     
-    ws.on_upgrade(|socket| {
-        eprintln!("WebSocket connection upgraded for ticker {}, starting handler", query.ticker);
-        handle_socket(socket, state, query.ticker)
-    })
-}
-
-/// Handle an individual WebSocket connection
-async fn handle_socket(socket: axum::extract::ws::WebSocket, state: AppState, ticker: String) {
-    eprintln!("WebSocket handler started for ticker: {}", ticker);
-    let (mut sender, mut receiver) = socket.split();
-    
-    // Get or create ticker data
-    let ticker_data = {
-        let mut tickers = state.tickers.lock().await;
-        tickers.entry(ticker.clone()).or_insert_with(|| {
-            eprintln!("Creating new ticker data for: {}", ticker);
-            let (orderbook_tx, _) = broadcast::channel::<OrderbookState>(100);
-            let (ohlc_tx, _) = broadcast::channel::<OhlcData>(100);
-            crate::api::routes::TickerData {
-                orderbook_updates: orderbook_tx,
-                ohlc_updates: ohlc_tx,
-                engine: std::sync::Arc::new(tokio::sync::RwLock::new(
-                    crate::orderbook::engine::OrderbookEngine::new()
-                )),
-            }
-        }).clone()
+    let (views, replay_speed) = match query.depth_views().and_then(|views| Ok((views, query.replay_speed()?))) {
+        Ok(options) => options,
+        Err(reason) => {
+            tracing::info!(conn_id = %conn_id, reason = %reason, "Rejecting /live connection");
+            return ws.on_upgrade(move |socket| close_with_reason(socket, close_code::POLICY, reason));
+        }
     };
     
-    // Send current state immediately when client connects
-    let current_state = {
-        let engine_guard = ticker_data.engine.read().await;
-        engine_guard.get_current_state()
+    let Some(connection) = acquire_connection(&state, &conn_id).await else {
+        return reject_busy(ws);
     };
     
-    eprintln!("Current orderbook state for {}: {} bids, {} asks", ticker, current_state.bids.len(), current_state.asks.len());
+    if let Some(speed) = replay_speed {
+        return ws.on_upgrade(move |socket| {
+            let span = tracing::info_span!("ws_replay", conn_id = %conn_id, ticker = %ticker_label, speed);
+            handle_replay_socket(socket, state, connection, requested, speed, views).instrument(span)
+        });
+    }
     
-    // Send initial state if orderbook has data
-    if !current_state.bids.is_empty() || !current_state.asks.is_empty() {
-        let message = WebSocketMessage::Orderbook { data: current_state };
-        if let Ok(json) = serde_json::to_string(&message) {
-            eprintln!("Sending initial state to client for ticker {}", ticker);
-            if let Err(e) = sender.send(Message::Text(json)).await {
-                eprintln!("Error sending initial state: {}", e);
+    ws.on_upgrade(move |socket| {
+        let span = tracing::info_span!("ws_connection", conn_id = %conn_id, ticker = %ticker_label);
+        let throttle = UpdateThrottle::new(query.throttle_ms.unwrap_or(0));
+        handle_socket(socket, state, connection, requested, create_missing, throttle, views).instrument(span)
+    })
+}
+
+/// Close a freshly upgraded socket, telling the client why
+async fn close_with_reason(mut socket: WebSocket, code: u16, reason: String) {
+    let frame = CloseFrame { code, reason: reason.into() };
+    let _ = socket.send(Message::Close(Some(frame))).await;
+}
+
+/// Stream a ticker's stored snapshots as orderbook messages, then close
+/// 
+/// Snapshots are sent oldest first, spaced by their recorded intervals divided
+/// by `speed`. Replay follows exactly one ticker; the socket is closed with a
+/// reason if more were requested or the ticker has no stored history.
+async fn handle_replay_socket(
+    socket: WebSocket,
+    state: AppState,
+    _connection: ConnectionGuard,
+    requested: Vec<String>,
+    speed: f64,
+    views: DepthViews,
+) {
+    let [ticker] = requested.as_slice() else {
+        return close_with_reason(socket, close_code::POLICY, "replay follows a single ticker".to_string()).await;
+    };
+    let Some((from, to)) = state.snapshot_store.get_history_range(ticker).await else {
+        return close_with_reason(socket, close_code::NORMAL, format!("no stored history for {}", ticker)).await;
+    };
+    tracing::info!(from, to, "Replay started");
+    let _client = METRICS.websocket_connected();
+    let (mut sender, mut receiver) = socket.split();
+
+    // Snapshots are rebuilt a batch at a time as the replay reaches them
+    let scale = display_scale(&state, ticker).await;
+    let mut history = state.snapshot_store.history(ticker, from, to);
+    let mut schedule = ReplaySchedule::new(speed);
+    let mut engine = OrderbookEngine::default();
+    let mut client_resyncing = false;
+    loop {
+        let batch = history.next_batch(REPLAY_BATCH).await;
+        if batch.is_empty() {
+            break;
+        }
+        for snapshot in batch {
+            // Stop early if the client goes away while we wait
+            let due = tokio::time::sleep(schedule.delay(snapshot.timestamp));
+            tokio::pin!(due);
+            loop {
+                tokio::select! {
+                    _ = &mut due => break,
+                    message = receiver.next() => {
+                        if matches!(message, None | Some(Ok(Message::Close(_))) | Some(Err(_))) {
+                            tracing::info!("Client left during replay");
+                            return;
+                        }
+                    }
+                }
+            }
+            if let Err(e) = engine.load_snapshot(&snapshot) {
+                tracing::warn!(error = %e, timestamp = snapshot.timestamp, "Skipping unloadable snapshot in replay");
+                continue;
+            }
+            let mut orderbook_state = engine.get_current_state().scaled(scale);
+            orderbook_state.timestamp = snapshot.timestamp;
+            let messages = orderbook_messages(orderbook_state, &mut client_resyncing, &views);
+            if !send_messages(&mut sender, ticker, messages).await {
                 return;
             }
         }
-    } else {
-        eprintln!("Orderbook is empty for {}, not sending initial state", ticker);
     }
-    
-    // Subscribe to orderbook updates for this ticker
-    let mut orderbook_rx = ticker_data.orderbook_updates.subscribe();
-    // Subscribe to OHLC updates for this ticker
-    let mut ohlc_rx = ticker_data.ohlc_updates.subscribe();
-    
+
+    tracing::info!("Replay finished");
+    let frame = CloseFrame { code: close_code::NORMAL, reason: "replay finished".into() };
+    let _ = sender.send(Message::Close(Some(frame))).await;
+}
+
+/// WebSocket handler for /arbitrage endpoint
+/// 
+/// Streams every arbitrage opportunity the detector finds
+/// Query parameter: asset (optional, defaults to all assets)
+/// 
+/// A connection over `max_connections` is closed with 1013 (try again later).
+pub async fn handle_arbitrage_websocket(
+    ws: WebSocketUpgrade,
+    Query(query): Query<ArbitrageQuery>,
+    State(state): State<AppState>,
+    Extension(RequestId(conn_id)): Extension<RequestId>,
+) -> Response {
+    let asset_label = query.asset.clone().unwrap_or_else(|| "*".to_string());
+    tracing::info!(conn_id = %conn_id, asset = %asset_label, "WebSocket upgrade request received for /arbitrage");
+
+    let Some(connection) = acquire_connection(&state, &conn_id).await else {
+        return reject_busy(ws);
+    };
+
+    ws.on_upgrade(move |socket| {
+        let span = tracing::info_span!("ws_connection", conn_id = %conn_id, asset = %asset_label);
+        handle_arbitrage_socket(socket, state, connection, query.asset).instrument(span)
+    })
+}
+
+/// Handle an individual /arbitrage WebSocket connection
+async fn handle_arbitrage_socket(
+    socket: axum::extract::ws::WebSocket,
+    state: AppState,
+    _connection: ConnectionGuard,
+    asset: Option<String>,
+) {
+    let _client = METRICS.websocket_connected();
+    let (mut sender, mut receiver) = socket.split();
+    let mut opportunities_rx = state.arbitrage.subscribe();
+
     loop {
         tokio::select! {
-            // Handle incoming orderbook updates
-            result = orderbook_rx.recv() => {
+            result = opportunities_rx.recv() => {
                 match result {
-                    Ok(orderbook_state) => {
-                        let message = WebSocketMessage::Orderbook { data: orderbook_state };
+                    Ok(opportunity) => {
+                        let Some(message) = arbitrage_message(opportunity, asset.as_deref()) else {
+                            continue;
+                        };
                         let json = match serde_json::to_string(&message) {
                             Ok(json) => json,
                             Err(e) => {
-                                eprintln!("Error serializing orderbook state: {}", e);
+                                tracing::error!(error = %e, "Error serializing arbitrage opportunity");
                                 continue;
                             }
                         };
-                        
+
                         if sender.send(Message::Text(json)).await.is_err() {
                             // Client disconnected
                             break;
                         }
                     }
                     Err(broadcast::error::RecvError::Lagged(_)) => {
-                        // We lagged behind, skip this update
+                        // We lagged behind, skip the missed opportunities
                         continue;
                     }
                     Err(broadcast::error::RecvError::Closed) => {
-                        // Broadcast channel closed
                         break;
                     }
                 }
             }
-            
-            // Handle incoming OHLC updates
-            result = ohlc_rx.recv() => {
-                match result {
-                    Ok(ohlc_data) => {
-                        let message = WebSocketMessage::Ohlc { data: ohlc_data };
-                        let json = match serde_json::to_string(&message) {
-                            Ok(json) => json,
-                            Err(e) => {
-                                eprintln!("Error serializing OHLC data: {}", e);
-                                continue;
-                            }
-                        };
-                        
-                        if sender.send(Message::Text(json)).await.is_err() {
-                            // Client disconnected
+
+            msg = receiver.next() => {
+                match msg {
+                    Some(Ok(Message::Ping(payload))) => {
+                        let pong = sender.send(Message::Pong(payload)).await;
+                        if pong.is_err() {
                             break;
                         }
                     }
-                    Err(broadcast::error::RecvError::Lagged(_)) => {
-                        // We lagged behind, skip this update
-                        continue;
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+    tracing::info!("/arbitrage connection closed");
+}
+
+/// A ticker followed by a /live connection, with what its client has seen
+/// 
+/// Only the engine is held, not the broadcast senders, so the ticker's
+/// channels close when its feed goes away.
+struct FollowedTicker {
+    /// Tags this ticker's feed events, unique within the connection's followed set
+    id: usize,
+    ticker: String,
+    engine: Arc<tokio::sync::RwLock<OrderbookEngine>>,
+    /// Resync state as last seen by the client
+    client_resyncing: bool,
+    /// Whether the client holds a full book that diffs can be applied to
+    client_has_book: bool,
+    /// Factor the ticker's prices are multiplied by before sending
+    display_scale: f64,
+}
+
+impl FollowedTicker {
+    /// The ticker's current book, scaled for the client
+    async fn current_state(&self) -> OrderbookState {
+        self.engine.read().await.get_current_state().scaled(self.display_scale)
+    }
+}
+
+/// An update from one of a connection's followed tickers, by `FollowedTicker::id`
+enum FeedEvent {
+    Book(usize, Result<BookUpdate, RecvError>),
+    Ohlc(usize, Result<OhlcData, RecvError>),
+}
+
+impl FeedEvent {
+    fn id(&self) -> usize {
+        match self {
+            FeedEvent::Book(id, _) | FeedEvent::Ohlc(id, _) => *id,
+        }
+    }
+
+    /// Whether the ticker's channel closed, i.e. its feed is gone
+    fn is_closed(&self) -> bool {
+        matches!(self, FeedEvent::Book(_, Err(RecvError::Closed)) | FeedEvent::Ohlc(_, Err(RecvError::Closed)))
+    }
+}
+
+/// Book and OHLC updates of every followed ticker, merged into one stream
+type FeedEvents = futures_util::stream::SelectAll<futures_util::stream::BoxStream<'static, FeedEvent>>;
+
+/// Subscribe to a ticker's broadcast channels, tagging its events with `id`
+fn subscribe_feed(events: &mut FeedEvents, id: usize, data: &TickerData) {
+    let book = receiver_stream(data.orderbook_updates.subscribe())
+        .map(move |result| FeedEvent::Book(id, result));
+    let ohlc = receiver_stream(data.ohlc_updates.subscribe())
+        .map(move |result| FeedEvent::Ohlc(id, result));
+    events.push(book.boxed());
+    events.push(ohlc.boxed());
+}
+
+/// Send each followed book's current state, skipping books with no data yet
+/// 
+/// Returns false if the client disconnected.
+async fn send_current_books(sender: &mut SplitSink<WebSocket, Message>, followed: &mut [FollowedTicker], views: &DepthViews) -> bool {
+    for followed in followed {
+        let current_state = followed.current_state().await;
+        tracing::debug!(ticker = %followed.ticker, bids = current_state.bids.len(), asks = current_state.asks.len(), "Current orderbook state");
+        
+        // Send initial state if orderbook has data (or is resyncing)
+        if !current_state.bids.is_empty() || !current_state.asks.is_empty() || current_state.resyncing {
+            tracing::debug!(ticker = %followed.ticker, "Sending initial state to client");
+            followed.client_has_book = true;
+            let messages = orderbook_messages(current_state, &mut followed.client_resyncing, views);
+            if !send_messages(sender, &followed.ticker, messages).await {
+                tracing::warn!(ticker = %followed.ticker, "Error sending initial state");
+                return false;
+            }
+        } else {
+            tracing::debug!(ticker = %followed.ticker, "Orderbook is empty, not sending initial state");
+        }
+    }
+    true
+}
+
+/// Stream a broadcast receiver's results, ending after the channel closes
+fn receiver_stream<T: Clone + Send + 'static>(receiver: broadcast::Receiver<T>) -> impl Stream<Item = Result<T, RecvError>> {
+    futures_util::stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+        let result = receiver.recv().await;
+        let next = (!matches!(result, Err(RecvError::Closed))).then_some(receiver);
+        Some((result, next))
+    })
+}
+
+/// Look up and subscribe to the tickers a /live connection asked for
+/// 
+/// A lone legacy `ticker` is created on demand if it isn't registered. Unknown
+/// tickers from a `tickers` list are returned separately so the client can be told.
+/// Subscribing here, before any book is read, means no diff falls between a
+/// client's initial state and its first update; diffs carry absolute volumes,
+/// so re-applying one already reflected in the initial state is harmless.
+async fn resolve_tickers(state: &AppState, requested: &[String], create_missing: bool) -> (Vec<FollowedTicker>, FeedEvents, Vec<String>) {
+    let mut tickers = state.tickers.lock().await;
+    let mut followed = Vec::new();
+    let mut events = FeedEvents::new();
+    let mut unknown = Vec::new();
+    for ticker in requested {
+        if followed.iter().any(|f: &FollowedTicker| &f.ticker == ticker) {
+            continue;
+        }
+        let data = if create_missing {
+            tickers.entry(ticker.clone()).or_insert_with(|| {
+                tracing::info!(ticker = %ticker, "Creating new ticker data");
+                let (orderbook_tx, _) = broadcast::channel::<BookUpdate>(100);
+                let (ohlc_tx, _) = broadcast::channel::<OhlcData>(100);
+                TickerData {
+                    orderbook_updates: orderbook_tx,
+                    ohlc_updates: ohlc_tx,
+                    engine: std::sync::Arc::new(tokio::sync::RwLock::new(
+                        crate::orderbook::engine::OrderbookEngine::default()
+                    )),
+                    subscription: None,
+                    frozen: std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false)),
+                }
+            }).clone()
+        } else {
+            match tickers.get(ticker) {
+                Some(data) => data.clone(),
+                None => {
+                    unknown.push(ticker.clone());
+                    continue;
+                }
+            }
+        };
+        subscribe_feed(&mut events, followed.len(), &data);
+        followed.push(FollowedTicker {
+            id: followed.len(),
+            ticker: ticker.clone(),
+            engine: data.engine,
+            client_resyncing: false,
+            client_has_book: false,
+            display_scale: 1.0,
+        });
+    }
+    drop(tickers);
+
+    for followed in &mut followed {
+        followed.display_scale = followed.engine.read().await.display_scale();
+    }
+    (followed, events, unknown)
+}
+
+/// Handle an individual WebSocket connection
+async fn handle_socket(
+    socket: WebSocket,
+    state: AppState,
+    _connection: ConnectionGuard,
+    requested: Vec<String>,
+    create_missing: bool,
+    mut throttle: Option<UpdateThrottle>,
+    views: DepthViews,
+) {
+    tracing::info!("WebSocket handler started");
+    let _client = METRICS.websocket_connected();
+    let (mut sender, mut receiver) = socket.split();
+    
+    let (mut followed, mut events, unknown) = resolve_tickers(&state, &requested, create_missing).await;
+    for ticker in unknown {
+        tracing::info!(ticker = %ticker, "Skipping unknown ticker");
+        let info = WebSocketMessage::Info { message: format!("Unknown ticker {}, skipped", ticker) };
+        if !send_messages(&mut sender, &ticker, vec![info]).await {
+            return;
+        }
+    }
+    if followed.is_empty() {
+        tracing::info!("No known tickers requested, closing");
+        return;
+    }
+    
+    // Send each book's current state immediately when the client connects
+    if !send_current_books(&mut sender, &mut followed, &views).await {
+        return;
+    }
+    
+    // Ids of tickers with coalesced updates awaiting the throttle's next release
+    let mut throttled = BTreeSet::new();
+    
+    loop {
+        tokio::select! {
+            // Nothing is followed while the client is unsubscribed
+            event = events.next(), if !followed.is_empty() => {
+                let Some(event) = event else {
+                    break;
+                };
+                let Some(index) = followed.iter().position(|followed| followed.id == event.id()) else {
+                    // The other channel of a ticker already dropped below
+                    continue;
+                };
+                if event.is_closed() {
+                    // Only this ticker's feed is gone; the socket stays open while others remain
+                    let gone = followed.remove(index);
+                    throttled.remove(&gone.id);
+                    tracing::info!(ticker = %gone.ticker, "Followed ticker's feed closed");
+                    let info = WebSocketMessage::Info { message: format!("Ticker {} closed, no longer followed", gone.ticker) };
+                    if followed.is_empty() || !send_messages(&mut sender, &gone.ticker, vec![info]).await {
+                        break;
                     }
-                    Err(broadcast::error::RecvError::Closed) => {
-                        // Broadcast channel closed
+                    continue;
+                }
+                let messages = match event {
+                    // Handle incoming orderbook updates
+                    FeedEvent::Book(id, result) => {
+                        if let Some(throttle) = throttle.as_mut() {
+                            // Coalesced: the latest full book goes out on the next release
+                            throttle.mark_pending();
+                            throttled.insert(id);
+                            continue;
+                        }
+                        let followed = &mut followed[index];
+                        match result {
+                            Ok(BookUpdate::Full(orderbook_state)) => {
+                                followed.client_has_book = true;
+                                orderbook_messages(orderbook_state.scaled(followed.display_scale), &mut followed.client_resyncing, &views)
+                            }
+                            // Depth-limited clients get truncated full books, since a diff
+                            // can't bring levels from beyond their depth into view
+                            Ok(BookUpdate::Diff(diff)) if followed.client_has_book && views == DepthViews::Full => {
+                                vec![WebSocketMessage::OrderbookDiff { data: diff.scaled(followed.display_scale) }]
+                            }
+                            Ok(BookUpdate::Diff(_)) | Err(_) => {
+                                // The client has no book to apply this diff to, is depth-limited,
+                                // or we lagged and missed diffs: send the full current book instead
+                                followed.client_has_book = true;
+                                let orderbook_state = followed.current_state().await;
+                                orderbook_messages(orderbook_state, &mut followed.client_resyncing, &views)
+                            }
+                        }
+                    }
+                    // Handle incoming OHLC updates
+                    FeedEvent::Ohlc(_, result) => match result {
+                        Ok(ohlc_data) => vec![WebSocketMessage::Ohlc { data: ohlc_data }],
+                        Err(_) => {
+                            // We lagged behind, skip this update
+                            continue;
+                        }
+                    },
+                };
+                
+                if !send_messages(&mut sender, &followed[index].ticker, messages).await {
+                    // Client disconnected
+                    break;
+                }
+            }
+            
+            // Release the latest books of a throttled stream
+            _ = UpdateThrottle::ready(&mut throttle) => {
+                let mut disconnected = false;
+                for id in std::mem::take(&mut throttled) {
+                    let Some(followed) = followed.iter_mut().find(|followed| followed.id == id) else {
+                        continue;
+                    };
+                    followed.client_has_book = true;
+                    let orderbook_state = followed.current_state().await;
+                    let messages = orderbook_messages(orderbook_state, &mut followed.client_resyncing, &views);
+                    if !send_messages(&mut sender, &followed.ticker, messages).await {
+                        disconnected = true;
                         break;
                     }
                 }
+                if disconnected {
+                    break;
+                }
             }
             
             // Handle incoming WebSocket messages
@@ -169,10 +878,38 @@ async fn handle_socket(socket: axum::extract::ws::WebSocket, state: AppState, ti
                     }
                     Some(Ok(Message::Ping(payload))) => {
                         // Respond to ping with pong
-                        if sender.send(Message::Pong(payload)).await.is_err() {
+                        let pong = sender.send(Message::Pong(payload)).await;
+                        if pong.is_err() {
                             break;
                         }
                     }
+                    Some(Ok(Message::Text(text))) => match parse_client_command(&text) {
+                        Ok(ClientCommand::Subscribe { ticker }) => {
+                            let (resolved, resolved_events, _) = resolve_tickers(&state, std::slice::from_ref(&ticker), false).await;
+                            if resolved.is_empty() {
+                                let info = WebSocketMessage::Info { message: format!("Unknown ticker {}, subscription unchanged", ticker) };
+                                if !send_messages(&mut sender, &ticker, vec![info]).await {
+                                    break;
+                                }
+                                continue;
+                            }
+                            tracing::info!(ticker = %ticker, "Client switched ticker");
+                            // Subscribed before sending the book, as on connect
+                            followed = resolved;
+                            events = resolved_events;
+                            throttled.clear();
+                            if !send_current_books(&mut sender, &mut followed, &views).await {
+                                break;
+                            }
+                        }
+                        Ok(ClientCommand::Unsubscribe) => {
+                            tracing::info!("Client paused updates");
+                            followed.clear();
+                            events = FeedEvents::new();
+                            throttled.clear();
+                        }
+                        Err(reason) => tracing::debug!(reason = %reason, "Ignoring client message"),
+                    },
                     Some(Err(_)) => {
                         // Error receiving message, close connection
                         break;
@@ -190,3 +927,500 @@ async fn handle_socket(socket: axum::extract::ws::WebSocket, state: AppState, ti
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serialize messages and return their `type` tags in order
+    fn message_types(messages: &[WebSocketMessage]) -> Vec<String> {
+        messages
+            .iter()
+            .map(|m| serde_json::to_value(m).unwrap()["type"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_resync_lifecycle_messages() {
+        let mut engine = OrderbookEngine::default();
+        let mut client_resyncing = false;
+        let mut types = Vec::new();
+
+        types.extend(message_types(&orderbook_messages(engine.get_current_state(), &mut client_resyncing, &DepthViews::Full)));
+
+        engine.begin_resync();
+        types.extend(message_types(&orderbook_messages(engine.get_current_state(), &mut client_resyncing, &DepthViews::Full)));
+        types.extend(message_types(&orderbook_messages(engine.get_current_state(), &mut client_resyncing, &DepthViews::Full)));
+
+        engine.apply_snapshot(&crate::kraken::types::BookSnapshot { bids: vec![], asks: vec![] }).unwrap();
+        types.extend(message_types(&orderbook_messages(engine.get_current_state(), &mut client_resyncing, &DepthViews::Full)));
+
+        assert_eq!(
+            types,
+            vec!["orderbook", "resyncing", "orderbook", "orderbook", "resynced", "orderbook"]
+        );
+    }
+
+    #[test]
+    fn test_orderbook_diff_message() {
+        use crate::kraken::types::{BookDelta, BookSnapshot};
+
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "1.0"])],
+            asks: vec![serde_json::json!(["101.0", "1.0", "1.0"])],
+        }).unwrap();
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["100.0", "0.0", "2.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+
+        let message = serde_json::to_value(WebSocketMessage::OrderbookDiff { data: engine.take_changes() }).unwrap();
+        assert_eq!(message["type"], "orderbookDiff");
+        assert_eq!(message["data"]["bids"], serde_json::json!([{ "price": 100.0, "volume": 0.0 }]));
+        assert_eq!(message["data"]["asks"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_crossed_venues_stream_arbitrage_message() {
+        use crate::arena::analytics::ArenaAnalytics;
+        use crate::arena::arbitrage::ArbitrageDetector;
+        use std::sync::Arc;
+        use tokio::sync::RwLock;
+
+        let venue = |bid: &str, ask: &str| Arc::new(RwLock::new(book(bid, ask)));
+        let arena = Arc::new(ArenaAnalytics::new());
+        arena.register_venue("BTC", "kraken", venue("99.0", "100.0")).await;
+        arena.register_venue("BTC", "binance", venue("101.0", "102.0")).await;
+
+        let detector = ArbitrageDetector::new(arena, 10.0);
+        let mut rx = detector.subscribe();
+        detector.check("BTC").await;
+        let opportunity = rx.recv().await.unwrap();
+
+        // Filtered out for clients watching another asset
+        assert!(arbitrage_message(opportunity.clone(), Some("ETH")).is_none());
+
+        let message = serde_json::to_value(arbitrage_message(opportunity, Some("btc")).unwrap()).unwrap();
+        assert_eq!(message["type"], "arbitrage");
+        assert_eq!(message["data"]["buyExchange"], "kraken");
+        assert_eq!(message["data"]["sellExchange"], "binance");
+        assert_eq!(message["data"]["spread"], 1.0);
+        assert_eq!(message["data"]["size"], 1.0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle_coalesces_rapid_updates() {
+        assert!(UpdateThrottle::new(0).is_none());
+
+        // The clock is paused: the 2ms gaps and 50ms releases advance it deterministically
+
+        let (tx, mut rx) = broadcast::channel::<u32>(1000);
+        tokio::spawn(async move {
+            for i in 0..100 {
+                tx.send(i).unwrap();
+                tokio::time::sleep(Duration::from_millis(2)).await;
+            }
+        });
+
+        let mut throttle = UpdateThrottle::new(50);
+        let start = tokio::time::Instant::now();
+        let (mut received, mut sends, mut latest, mut sent) = (0, 0, None, None);
+        loop {
+            tokio::select! {
+                result = rx.recv() => match result {
+                    Ok(value) => {
+                        received += 1;
+                        latest = Some(value);
+                        throttle.as_mut().unwrap().mark_pending();
+                    }
+                    Err(_) => break,
+                },
+                _ = UpdateThrottle::ready(&mut throttle) => {
+                    sends += 1;
+                    sent = latest;
+                },
+            }
+        }
+        // Flush what arrived after the last release, as the stream would
+        if throttle.as_ref().unwrap().pending {
+            UpdateThrottle::ready(&mut throttle).await;
+            sends += 1;
+            sent = latest;
+        }
+
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        assert_eq!(received, 100);
+        // One leading release plus at most one per elapsed interval
+        assert!(sends >= 2 && sends <= elapsed_ms / 50 + 2, "{} sends in {}ms", sends, elapsed_ms);
+        assert_eq!(sent, Some(99));
+    }
+
+    /// An engine with one level at each of the given bid and ask prices
+    fn book(bid: &str, ask: &str) -> OrderbookEngine {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&crate::kraken::types::BookSnapshot {
+            bids: vec![serde_json::json!([bid, "1.0", "1.0"])],
+            asks: vec![serde_json::json!([ask, "1.0", "1.0"])],
+        }).unwrap();
+        engine
+    }
+
+    /// Serve the full router for `state` on an ephemeral local port
+    async fn spawn_server(state: AppState) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let router = crate::api::routes::create_router(state, &[]);
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        addr
+    }
+
+    /// Read the next text frame from a client socket as JSON
+    async fn next_json<S>(socket: &mut S) -> serde_json::Value
+    where
+        S: Stream<Item = Result<tokio_tungstenite::tungstenite::Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        loop {
+            let frame = tokio::time::timeout(Duration::from_secs(2), socket.next())
+                .await
+                .expect("timed out waiting for a message")
+                .expect("socket closed")
+                .unwrap();
+            if let tokio_tungstenite::tungstenite::Message::Text(text) = frame {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_live_follows_multiple_tickers() {
+        use crate::api::routes::tests::test_state;
+        use crate::config::Config;
+
+        let state = test_state(&["BTC", "ETH"], Config::new());
+        *state.tickers.lock().await["BTC"].engine.write().await = book("100.0", "101.0");
+        let addr = spawn_server(state.clone()).await;
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/live?tickers=BTC,DOGE,ETH", addr))
+            .await
+            .unwrap();
+
+        // Unknown tickers are skipped with a notice; only BTC has a book to send
+        let info = next_json(&mut socket).await;
+        assert_eq!(info["type"], "info");
+        assert_eq!(info["ticker"], "DOGE");
+        let initial = next_json(&mut socket).await;
+        assert_eq!(initial["type"], "orderbook");
+        assert_eq!(initial["ticker"], "BTC");
+        assert_eq!(initial["data"]["bids"][0]["price"], 100.0);
+
+        // Updates on any followed ticker are fanned in and tagged
+        let eth = state.tickers.lock().await["ETH"].clone();
+        eth.orderbook_updates.send(BookUpdate::Full(book("10.0", "11.0").get_current_state())).unwrap();
+        let update = next_json(&mut socket).await;
+        assert_eq!(update["type"], "orderbook");
+        assert_eq!(update["ticker"], "ETH");
+        assert_eq!(update["data"]["asks"][0]["price"], 11.0);
+    }
+
+    #[tokio::test]
+    async fn test_live_keeps_other_tickers_when_one_closes() {
+        use crate::api::routes::tests::test_state;
+        use crate::config::Config;
+
+        let state = test_state(&["BTC", "ETH"], Config::new());
+        let addr = spawn_server(state.clone()).await;
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/live?tickers=BTC,ETH", addr))
+            .await
+            .unwrap();
+        let btc = state.tickers.lock().await["BTC"].clone();
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while btc.orderbook_updates.receiver_count() == 0 {
+                tokio::task::yield_now().await;
+            }
+        }).await.expect("client never subscribed");
+
+        // Dropping ETH's only senders closes its channels; the client is told
+        let eth = state.tickers.lock().await.remove("ETH");
+        drop(eth);
+        let info = next_json(&mut socket).await;
+        assert_eq!((info["type"].as_str(), info["ticker"].as_str()), (Some("info"), Some("ETH")));
+
+        // BTC is still followed on the same socket
+        btc.orderbook_updates.send(BookUpdate::Full(book("100.0", "101.0").get_current_state())).unwrap();
+        let update = next_json(&mut socket).await;
+        assert_eq!((update["type"].as_str(), update["ticker"].as_str()), (Some("orderbook"), Some("BTC")));
+
+        // Once the last followed ticker closes, so does the socket
+        state.tickers.lock().await.remove("BTC");
+        drop(btc);
+        let closed = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                match socket.next().await {
+                    Some(Ok(tokio_tungstenite::tungstenite::Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                }
+            }
+        }).await;
+        assert!(closed.is_ok(), "socket stayed open with nothing followed");
+    }
+
+    #[test]
+    fn test_parse_client_command() {
+        assert_eq!(
+            parse_client_command(r#"{"action":"subscribe","ticker":"BTC"}"#),
+            Ok(ClientCommand::Subscribe { ticker: "BTC".to_string() })
+        );
+        assert_eq!(parse_client_command(r#"{"action":"unsubscribe"}"#), Ok(ClientCommand::Unsubscribe));
+        assert!(parse_client_command(r#"{"action":"subscribe"}"#).is_err());
+        assert!(parse_client_command(r#"{"action":"dance"}"#).is_err());
+        assert!(parse_client_command("subscribe BTC").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_live_switches_ticker_on_subscribe() {
+        use crate::api::routes::tests::test_state;
+        use crate::config::Config;
+        use tokio_tungstenite::tungstenite::Message as ClientMessage;
+
+        let state = test_state(&["BTC", "ETH"], Config::new());
+        *state.tickers.lock().await["BTC"].engine.write().await = book("100.0", "101.0");
+        *state.tickers.lock().await["ETH"].engine.write().await = book("10.0", "11.0");
+        let addr = spawn_server(state.clone()).await;
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/live?tickers=BTC", addr))
+            .await
+            .unwrap();
+        assert_eq!(next_json(&mut socket).await["ticker"], "BTC");
+
+        // Switching sends the new ticker's book, and its updates follow
+        socket.send(ClientMessage::Text(r#"{"action":"subscribe","ticker":"ETH"}"#.to_string())).await.unwrap();
+        let switched = next_json(&mut socket).await;
+        assert_eq!((switched["type"].as_str(), switched["ticker"].as_str()), (Some("orderbook"), Some("ETH")));
+        assert_eq!(switched["data"]["bids"][0]["price"], 10.0);
+        let eth = state.tickers.lock().await["ETH"].clone();
+        eth.orderbook_updates.send(BookUpdate::Full(book("12.0", "13.0").get_current_state())).unwrap();
+        assert_eq!(next_json(&mut socket).await["data"]["bids"][0]["price"], 12.0);
+
+        // Unknown tickers leave the subscription as it was
+        socket.send(ClientMessage::Text(r#"{"action":"subscribe","ticker":"DOGE"}"#.to_string())).await.unwrap();
+        let info = next_json(&mut socket).await;
+        assert_eq!((info["type"].as_str(), info["ticker"].as_str()), (Some("info"), Some("DOGE")));
+
+        // Paused: the ETH update is dropped, so the next message is BTC's book
+        socket.send(ClientMessage::Text(r#"{"action":"unsubscribe"}"#.to_string())).await.unwrap();
+        // Wait until the server has processed the unsubscribe and dropped its ETH receiver
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while eth.orderbook_updates.receiver_count() > 0 {
+                tokio::task::yield_now().await;
+            }
+        }).await.expect("unsubscribe was not processed");
+        // No one is subscribed to ETH any more, so the send itself reports no receivers
+        assert!(eth.orderbook_updates.send(BookUpdate::Full(book("14.0", "15.0").get_current_state())).is_err());
+        socket.send(ClientMessage::Text(r#"{"action":"subscribe","ticker":"BTC"}"#.to_string())).await.unwrap();
+        let resumed = next_json(&mut socket).await;
+        assert_eq!(resumed["ticker"], "BTC");
+        assert_eq!(resumed["data"]["bids"][0]["price"], 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_live_depth_truncates_each_client() {
+        use crate::api::routes::tests::test_state;
+        use crate::config::Config;
+        use crate::kraken::types::BookSnapshot;
+
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: (1..=20).map(|i| serde_json::json!([format!("{}.0", 100 - i), "1.0", "1.0"])).collect(),
+            asks: (1..=20).map(|i| serde_json::json!([format!("{}.0", 100 + i), "1.0", "1.0"])).collect(),
+        }).unwrap();
+        let book = engine.get_current_state();
+        let state = test_state(&["BTC"], Config::new());
+        *state.tickers.lock().await["BTC"].engine.write().await = engine;
+        let addr = spawn_server(state.clone()).await;
+
+        let (mut shallow, _) = tokio_tungstenite::connect_async(format!("ws://{}/live?tickers=BTC&depth=5", addr))
+            .await
+            .unwrap();
+        let (mut full, _) = tokio_tungstenite::connect_async(format!("ws://{}/live?tickers=BTC", addr))
+            .await
+            .unwrap();
+        // Each initial book is sent after the client subscribed, so once both have
+        // arrived the broadcast below reaches both clients
+        assert_eq!(next_json(&mut shallow).await["data"]["bids"].as_array().unwrap().len(), 5);
+        assert_eq!(next_json(&mut full).await["data"]["bids"].as_array().unwrap().len(), 20);
+
+        let btc = state.tickers.lock().await["BTC"].clone();
+        btc.orderbook_updates.send(BookUpdate::Full(book)).unwrap();
+
+        let update = next_json(&mut shallow).await;
+        assert_eq!(update["data"]["bids"].as_array().unwrap().len(), 5);
+        assert_eq!(update["data"]["asks"].as_array().unwrap().len(), 5);
+        assert_eq!(update["data"]["bids"][0]["price"], 99.0);
+        assert_eq!(update["data"]["asks"][4]["price"], 105.0);
+
+        let update = next_json(&mut full).await;
+        assert_eq!(update["data"]["bids"].as_array().unwrap().len(), 20);
+
+        // Out-of-range depths are closed with a policy violation
+        let (mut rejected, _) = tokio_tungstenite::connect_async(format!("ws://{}/live?tickers=BTC&depth=1001", addr))
+            .await
+            .unwrap();
+        match tokio::time::timeout(Duration::from_secs(2), rejected.next()).await.unwrap() {
+            Some(Ok(tokio_tungstenite::tungstenite::Message::Close(Some(frame)))) => {
+                assert_eq!(u16::from(frame.code), 1008);
+            }
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_connection_guard_releases_slot_on_drop() {
+        let limiter = Arc::new(ConnectionLimiter::new());
+        let first = limiter.try_acquire(2).unwrap();
+        let second = limiter.try_acquire(2).unwrap();
+        assert_eq!(limiter.open(), 2);
+        assert!(limiter.try_acquire(2).is_none());
+        assert_eq!(limiter.open(), 2);
+
+        drop(first);
+        assert_eq!(limiter.open(), 1);
+        let third = limiter.try_acquire(2).unwrap();
+        drop((second, third));
+        assert_eq!(limiter.open(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_live_rejects_connections_over_limit() {
+        use crate::api::routes::tests::test_state;
+        use crate::config::Config;
+
+        let state = test_state(&["BTC"], Config::new().with_max_connections(1));
+        *state.tickers.lock().await["BTC"].engine.write().await = book("100.0", "101.0");
+        let addr = spawn_server(state.clone()).await;
+
+        let (mut first, _) = tokio_tungstenite::connect_async(format!("ws://{}/live?tickers=BTC", addr))
+            .await
+            .unwrap();
+        assert_eq!(next_json(&mut first).await["type"], "orderbook");
+
+        let (mut rejected, _) = tokio_tungstenite::connect_async(format!("ws://{}/live?tickers=BTC", addr))
+            .await
+            .unwrap();
+        match tokio::time::timeout(Duration::from_secs(2), rejected.next()).await.unwrap() {
+            Some(Ok(tokio_tungstenite::tungstenite::Message::Close(Some(frame)))) => {
+                assert_eq!(u16::from(frame.code), 1013);
+            }
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+        assert_eq!(state.websocket_connections.open(), 1);
+    }
+
+    #[test]
+    fn test_depth_views_tag_one_message_per_depth() {
+        use crate::kraken::types::BookSnapshot;
+
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: (1..=150).map(|i| serde_json::json!([format!("{}.0", 1000 - i), "1.0", "1.0"])).collect(),
+            asks: (1..=150).map(|i| serde_json::json!([format!("{}.0", 1000 + i), "1.0", "1.0"])).collect(),
+        }).unwrap();
+
+        let views = DepthViews::Tagged(vec![10, 100]);
+        let messages = orderbook_messages(engine.get_current_state(), &mut false, &views);
+        let messages: Vec<serde_json::Value> = messages.iter().map(|m| serde_json::to_value(m).unwrap()).collect();
+        assert_eq!(messages.len(), 2);
+        for (message, depth) in messages.iter().zip([10, 100]) {
+            assert_eq!(message["type"], "orderbook");
+            assert_eq!(message["depth"], depth);
+            assert_eq!(message["data"]["bids"].as_array().unwrap().len(), depth);
+            assert_eq!(message["data"]["asks"].as_array().unwrap().len(), depth);
+        }
+
+        // Untagged views don't carry a depth field
+        let messages = orderbook_messages(engine.get_current_state(), &mut false, &DepthViews::Single(5));
+        assert!(serde_json::to_value(&messages[0]).unwrap().get("depth").is_none());
+    }
+
+    #[test]
+    fn test_depth_views_from_query() {
+        let query = |depth: Option<usize>, depths: Option<&str>| WebSocketQuery {
+            ticker: default_ticker(),
+            tickers: None,
+            throttle_ms: None,
+            depth,
+            depths: depths.map(str::to_string),
+            replay: false,
+            speed: None,
+        };
+        assert_eq!(query(None, None).depth_views(), Ok(DepthViews::Full));
+        assert_eq!(query(Some(5), None).depth_views(), Ok(DepthViews::Single(5)));
+        assert_eq!(query(Some(5), Some("10, 100,10")).depth_views(), Ok(DepthViews::Tagged(vec![10, 100])));
+        assert!(query(None, Some("10,abc")).depth_views().is_err());
+        assert!(query(None, Some("10,2000")).depth_views().is_err());
+        assert!(query(None, Some(",")).depth_views().is_err());
+        assert!(query(Some(0), None).depth_views().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_schedule_orders_and_scales_delays() {
+        use crate::orderbook::snapshot::Snapshot;
+        use crate::orderbook::store::SnapshotStore;
+
+        let store = SnapshotStore::new().with_keyframe_interval(2);
+        for timestamp in [1000, 1010, 1030, 1031, 1040] {
+            store.store_snapshot(Snapshot::new("BTC".to_string(), timestamp, Some(timestamp as f64), vec![], vec![])).await;
+        }
+        let schedule = |speed| {
+            let store = &store;
+            async move {
+                // Small batches so the schedule spans several store reads
+                let mut history = store.history("BTC", 1000, 1031);
+                let mut schedule = ReplaySchedule::new(speed);
+                let mut delays = Vec::new();
+                loop {
+                    let batch = history.next_batch(3).await;
+                    if batch.is_empty() {
+                        return delays;
+                    }
+                    delays.extend(batch.iter().map(|snapshot| (schedule.delay(snapshot.timestamp), snapshot.timestamp)));
+                }
+            }
+        };
+        assert_eq!(schedule(10.0).await, vec![
+            (Duration::ZERO, 1000),
+            (Duration::from_secs(1), 1010),
+            (Duration::from_secs(2), 1030),
+            (Duration::from_millis(100), 1031),
+        ]);
+
+        // Real time at speed 1
+        assert_eq!(schedule(1.0).await[2].0, Duration::from_secs(20));
+
+        // Gaps too long for a Duration wait forever instead of panicking
+        let mut schedule = ReplaySchedule::new(MIN_REPLAY_SPEED);
+        schedule.delay(i64::MIN);
+        assert_eq!(schedule.delay(i64::MAX), Duration::MAX);
+    }
+
+    #[test]
+    fn test_replay_speed_from_query() {
+        let query = |replay: bool, speed: Option<f64>| WebSocketQuery {
+            ticker: default_ticker(),
+            tickers: None,
+            throttle_ms: None,
+            depth: None,
+            depths: None,
+            replay,
+            speed,
+        };
+        assert_eq!(query(false, Some(10.0)).replay_speed(), Ok(None));
+        assert_eq!(query(true, None).replay_speed(), Ok(Some(1.0)));
+        assert_eq!(query(true, Some(10.0)).replay_speed(), Ok(Some(10.0)));
+        assert!(query(true, Some(0.0)).replay_speed().is_err());
+        assert!(query(true, Some(1e-300)).replay_speed().is_err());
+        assert!(query(true, Some(f64::NAN)).replay_speed().is_err());
+    }
+}
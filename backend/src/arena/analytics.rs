@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use crate::arena::leadlag::{lead_lag, LeadLag, MidHistory, MidTick};
+use crate::orderbook::engine::OrderbookEngine;
+
+/// Shared handle to a single venue's orderbook engine
+pub type EngineHandle = Arc<RwLock<OrderbookEngine>>;
+
+/// Cross-exchange analytics over every venue's orderbook for an asset
+/// 
+/// Engines are registered per (asset, exchange) and read on demand, so
+/// consolidated signals always reflect the live books.
+pub struct ArenaAnalytics {
+    /// Map from asset to (exchange name to engine)
+    venues: RwLock<HashMap<String, HashMap<String, EngineHandle>>>,
+    /// Map from asset to its sampled per-venue mids, for lead-lag analysis
+    mid_history: RwLock<HashMap<String, MidHistory>>,
+}
+
+impl ArenaAnalytics {
+    /// Create an empty arena with no venues registered
+    pub fn new() -> Self {
+        Self {
+            venues: RwLock::new(HashMap::new()),
+            mid_history: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Register an exchange's engine for an asset, replacing any previous one
+    pub async fn register_venue(&self, asset: &str, exchange: &str, engine: EngineHandle) {
+        let mut venues = self.venues.write().await;
+        venues
+            .entry(asset.to_string())
+            .or_default()
+            .insert(exchange.to_string(), engine);
+    }
+
+    /// Get the engines tracked for an asset as (exchange, engine) pairs sorted by exchange
+    pub async fn venues_for(&self, asset: &str) -> Vec<(String, EngineHandle)> {
+        let venues = self.venues.read().await;
+        let mut engines: Vec<(String, EngineHandle)> = venues
+            .get(asset)
+            .map(|by_exchange| {
+                by_exchange
+                    .iter()
+                    .map(|(exchange, engine)| (exchange.clone(), engine.clone()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        engines.sort_by(|a, b| a.0.cmp(&b.0));
+        engines
+    }
+
+    /// Volume-weighted order flow imbalance for an asset across all venues
+    /// 
+    /// Each venue's imbalance `(bid_vol - ask_vol) / (bid_vol + ask_vol)` is weighted
+    /// by that venue's total resting volume, yielding a value in `[-1, 1]`.
+    /// Returns `None` if no venue is tracked or every tracked book is empty.
+    pub async fn consolidated_imbalance(&self, asset: &str) -> Option<f64> {
+        let mut weighted_sum = 0.0;
+        let mut total_weight = 0.0;
+
+        for (_, engine) in self.venues_for(asset).await {
+            let engine = engine.read().await;
+            let bid_volume: f64 = engine.iter_bids().map(|(_, volume)| volume).sum();
+            let ask_volume: f64 = engine.iter_asks().map(|(_, volume)| volume).sum();
+            let venue_volume = bid_volume + ask_volume;
+            if venue_volume <= 0.0 {
+                continue;
+            }
+
+            let imbalance = (bid_volume - ask_volume) / venue_volume;
+            weighted_sum += imbalance * venue_volume;
+            total_weight += venue_volume;
+        }
+
+        if total_weight > 0.0 {
+            Some(weighted_sum / total_weight)
+        } else {
+            None
+        }
+    }
+
+    /// Top-of-book volume-weighted mid price for an asset across all venues
+    /// 
+    /// Each venue's mid is weighted by the volume resting at its best bid and best
+    /// ask. Venues with a one-sided or empty book are excluded from the weighting.
+    /// Returns `None` if no venue has a two-sided book.
+    pub async fn consolidated_mid(&self, asset: &str) -> Option<f64> {
+        let mut weighted_sum = 0.0;
+        let mut total_weight = 0.0;
+
+        for (_, engine) in self.venues_for(asset).await {
+            let engine = engine.read().await;
+            let (Some(&(_, bid_volume)), Some(&(_, ask_volume)), Some(mid)) =
+                (engine.top_bids().first(), engine.top_asks().first(), engine.mid_price())
+            else {
+                continue;
+            };
+            let venue_weight = bid_volume + ask_volume;
+            if venue_weight <= 0.0 {
+                continue;
+            }
+
+            weighted_sum += mid * venue_weight;
+            total_weight += venue_weight;
+        }
+
+        if total_weight > 0.0 {
+            Some(weighted_sum / total_weight)
+        } else {
+            None
+        }
+    }
+
+    /// Notional-weighted average of each asset's tightest venue spread, in basis points
+    /// 
+    /// For every asset, the venue with the smallest spread (relative to its mid) is
+    /// taken as the asset's best spread, weighted by that venue's top-of-book notional
+    /// `(bid volume + ask volume) * mid` so assets of different prices are comparable.
+    /// Venues with a one-sided, empty or crossed book are skipped.
+    /// Returns `None` if no asset has a usable venue.
+    pub async fn average_spread_bps(&self) -> Option<f64> {
+        let assets: Vec<String> = self.venues.read().await.keys().cloned().collect();
+        let mut weighted_sum = 0.0;
+        let mut total_weight = 0.0;
+
+        for asset in assets {
+            // (spread in bps, weight) of the asset's tightest venue
+            let mut best: Option<(f64, f64)> = None;
+            for (_, engine) in self.venues_for(&asset).await {
+                let engine = engine.read().await;
+                let (Some(&(_, bid_volume)), Some(&(_, ask_volume)), Some(mid), Some(spread)) =
+                    (engine.top_bids().first(), engine.top_asks().first(), engine.mid_price(), engine.spread())
+                else {
+                    continue;
+                };
+                if spread < 0.0 || mid <= 0.0 {
+                    continue;
+                }
+                let spread_bps = spread / mid * 10_000.0;
+                if best.is_none_or(|(best_bps, _)| spread_bps < best_bps) {
+                    best = Some((spread_bps, (bid_volume + ask_volume) * mid));
+                }
+            }
+
+            if let Some((spread_bps, weight)) = best.filter(|(_, weight)| *weight > 0.0) {
+                weighted_sum += spread_bps * weight;
+                total_weight += weight;
+            }
+        }
+
+        if total_weight > 0.0 {
+            Some(weighted_sum / total_weight)
+        } else {
+            None
+        }
+    }
+
+    /// Sample the mid of every venue of an asset into its mid history
+    /// 
+    /// Intended to be run after every book update. Assets with fewer than two
+    /// venues have nothing to compare and are skipped.
+    pub async fn record_mids(&self, asset: &str) {
+        let venues = self.venues_for(asset).await;
+        if venues.len() < 2 {
+            return;
+        }
+
+        let mut mids = Vec::with_capacity(venues.len());
+        for (exchange, engine) in venues {
+            if let Some(mid) = engine.read().await.mid_price() {
+                mids.push((exchange, mid));
+            }
+        }
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+
+        self.mid_history
+            .write()
+            .await
+            .entry(asset.to_string())
+            .or_default()
+            .push(MidTick { timestamp_ms, mids });
+    }
+
+    /// Lead-lag of every pair of venues for an asset over the newest `window` samples
+    /// 
+    /// Pairs are ordered by exchange name; pairs without enough aligned, moving
+    /// samples are omitted.
+    pub async fn lead_lag(&self, asset: &str, window: usize, max_lag: usize) -> Vec<LeadLag> {
+        let exchanges: Vec<String> = self.venues_for(asset).await.into_iter().map(|(exchange, _)| exchange).collect();
+        let history = self.mid_history.read().await;
+        let Some(history) = history.get(asset) else {
+            return Vec::new();
+        };
+
+        let mut results = Vec::new();
+        for (i, venue_a) in exchanges.iter().enumerate() {
+            for venue_b in &exchanges[i + 1..] {
+                results.extend(lead_lag(history, venue_a, venue_b, window, max_lag));
+            }
+        }
+        results
+    }
+
+    /// Count venues as (healthy, stale) across every asset
+    /// 
+    /// A venue is stale if its book has never been updated or was last updated
+    /// `stale_after` or longer ago.
+    pub async fn feed_health(&self, stale_after: Duration) -> (usize, usize) {
+        let engines: Vec<EngineHandle> = self
+            .venues
+            .read()
+            .await
+            .values()
+            .flat_map(|by_exchange| by_exchange.values().cloned())
+            .collect();
+
+        let mut healthy = 0;
+        for engine in &engines {
+            let since_update = engine.read().await.time_since_last_book_update();
+            if since_update.is_some_and(|elapsed| elapsed < stale_after) {
+                healthy += 1;
+            }
+        }
+        (healthy, engines.len() - healthy)
+    }
+}
+
+impl Default for ArenaAnalytics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::kraken::types::BookSnapshot;
+
+    /// Build an engine handle with a single bid and ask level
+    pub(crate) fn venue_engine(bid: &str, bid_volume: &str, ask: &str, ask_volume: &str) -> EngineHandle {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!([bid, bid_volume, "1.0"])],
+            asks: vec![serde_json::json!([ask, ask_volume, "1.0"])],
+        }).unwrap();
+        Arc::new(RwLock::new(engine))
+    }
+
+    #[tokio::test]
+    async fn test_consolidated_imbalance_no_venues() {
+        let arena = ArenaAnalytics::new();
+        assert_eq!(arena.consolidated_imbalance("BTC").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_consolidated_imbalance_weights_by_volume() {
+        let arena = ArenaAnalytics::new();
+        // Venue A: 3 bid / 1 ask -> imbalance 0.5, volume 4
+        arena.register_venue("BTC", "a", venue_engine("100.0", "3.0", "101.0", "1.0")).await;
+        // Venue B: 2 bid / 10 ask -> imbalance -2/3, volume 12
+        arena.register_venue("BTC", "b", venue_engine("100.0", "2.0", "101.0", "10.0")).await;
+
+        // (0.5 * 4 + (-2/3) * 12) / 16 = (2 - 8) / 16 = -0.375
+        let imbalance = arena.consolidated_imbalance("BTC").await.unwrap();
+        assert!((imbalance - (-0.375)).abs() < 1e-12);
+
+        // Other assets are unaffected
+        assert_eq!(arena.consolidated_imbalance("ETH").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_consolidated_imbalance_skips_empty_venues() {
+        let arena = ArenaAnalytics::new();
+        arena.register_venue("BTC", "a", venue_engine("100.0", "3.0", "101.0", "1.0")).await;
+        arena.register_venue("BTC", "empty", Arc::new(RwLock::new(OrderbookEngine::default()))).await;
+
+        assert_eq!(arena.consolidated_imbalance("BTC").await, Some(0.5));
+    }
+
+    #[tokio::test]
+    async fn test_consolidated_mid_weights_by_top_of_book_volume() {
+        let arena = ArenaAnalytics::new();
+        // Venue A: mid 100.5, top-of-book volume 1 + 3 = 4
+        arena.register_venue("BTC", "a", venue_engine("100.0", "1.0", "101.0", "3.0")).await;
+        // Venue B: mid 110.0, top-of-book volume 6 + 6 = 12
+        arena.register_venue("BTC", "b", venue_engine("109.0", "6.0", "111.0", "6.0")).await;
+
+        // (100.5 * 4 + 110 * 12) / 16 = 107.625
+        let mid = arena.consolidated_mid("BTC").await.unwrap();
+        assert!((mid - 107.625).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_consolidated_mid_skips_one_sided_and_empty_venues() {
+        let arena = ArenaAnalytics::new();
+        assert_eq!(arena.consolidated_mid("BTC").await, None);
+
+        let mut one_sided = OrderbookEngine::default();
+        one_sided.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["500.0", "100.0", "1.0"])],
+            asks: vec![],
+        }).unwrap();
+        arena.register_venue("BTC", "one_sided", Arc::new(RwLock::new(one_sided))).await;
+        arena.register_venue("BTC", "empty", Arc::new(RwLock::new(OrderbookEngine::default()))).await;
+        assert_eq!(arena.consolidated_mid("BTC").await, None);
+
+        arena.register_venue("BTC", "a", venue_engine("100.0", "1.0", "101.0", "3.0")).await;
+        assert_eq!(arena.consolidated_mid("BTC").await, Some(100.5));
+    }
+
+    #[tokio::test]
+    async fn test_average_spread_bps_weights_assets_by_notional() {
+        let arena = ArenaAnalytics::new();
+        assert_eq!(arena.average_spread_bps().await, None);
+
+        // BTC: venue a (100/101) is tighter than venue b (109/111), so a counts:
+        // 1 / 100.5 * 10000 bps, weight (1 + 3) * 100.5 = 402
+        arena.register_venue("BTC", "a", venue_engine("100.0", "1.0", "101.0", "3.0")).await;
+        arena.register_venue("BTC", "b", venue_engine("109.0", "6.0", "111.0", "6.0")).await;
+
+        // ETH: 9.9/10.1 -> 200 bps, weight (5 + 5) * 10 = 100
+        arena.register_venue("ETH", "a", venue_engine("9.9", "5.0", "10.1", "5.0")).await;
+        arena.register_venue("ETH", "empty", Arc::new(RwLock::new(OrderbookEngine::default()))).await;
+
+        // (10000 / 100.5 * 402 + 200 * 100) / 502 = 60000 / 502
+        let average = arena.average_spread_bps().await.unwrap();
+        assert!((average - 60000.0 / 502.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_feed_health_counts_stale_venues() {
+        let arena = ArenaAnalytics::new();
+        arena.register_venue("BTC", "a", venue_engine("100.0", "1.0", "101.0", "1.0")).await;
+        arena.register_venue("BTC", "never_updated", Arc::new(RwLock::new(OrderbookEngine::default()))).await;
+        arena.register_venue("ETH", "a", venue_engine("100.0", "1.0", "101.0", "1.0")).await;
+
+        assert_eq!(arena.feed_health(Duration::from_secs(60)).await, (2, 1));
+        assert_eq!(arena.feed_health(Duration::ZERO).await, (0, 3));
+    }
+
+    #[tokio::test]
+    async fn test_record_mids_skips_single_venue_assets() {
+        let arena = ArenaAnalytics::new();
+        arena.register_venue("BTC", "kraken", venue_engine("100.0", "1.0", "101.0", "1.0")).await;
+        arena.record_mids("BTC").await;
+        assert!(arena.mid_history.read().await.get("BTC").is_none());
+
+        arena.register_venue("BTC", "binance", venue_engine("100.0", "1.0", "101.0", "1.0")).await;
+        for _ in 0..3 {
+            arena.record_mids("BTC").await;
+        }
+        // Both venues sampled, but their mids never moved
+        assert!(arena.mid_history.read().await.contains_key("BTC"));
+        assert!(arena.lead_lag("BTC", 100, 5).await.is_empty());
+    }
+
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tokio::sync::{broadcast, Mutex};
+use crate::arena::analytics::ArenaAnalytics;
+
+/// A cross-exchange opportunity: buy at one venue's best ask, sell at another's best bid
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArbitrageOpportunity {
+    pub asset: String,
+    /// Venue to buy on (lowest ask)
+    pub buy_exchange: String,
+    /// Venue to sell on (highest bid)
+    pub sell_exchange: String,
+    pub buy_price: f64,
+    pub sell_price: f64,
+    /// Sell price minus buy price
+    pub spread: f64,
+    /// Spread relative to the buy price, in basis points
+    pub spread_bps: f64,
+    /// Size executable at both touch prices (smaller of the two top-level volumes)
+    pub size: f64,
+    pub timestamp: i64,
+    /// True when the venues cross; false when a previously reported crossing has
+    /// closed, in which case the prices are the last ones seen while it was open
+    pub open: bool,
+}
+
+/// Open opportunities of an asset, keyed by (buy venue, sell venue)
+type OpenOpportunities = HashMap<(String, String), ArbitrageOpportunity>;
+
+/// Current time in seconds since the Unix epoch
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// Detects crossed books between venues of the same asset and broadcasts them
+/// 
+/// Intended to be run after every book update. Subscribers only hear about
+/// transitions: once when a venue pair's spread first exceeds the configured
+/// threshold, and once more when it stops doing so.
+pub struct ArbitrageDetector {
+    arena: Arc<ArenaAnalytics>,
+    /// Minimum spread in basis points for an opportunity to be reported
+    threshold_bps: f64,
+    opportunities: broadcast::Sender<ArbitrageOpportunity>,
+    /// Opportunities already broadcast as open, per asset
+    open: Mutex<HashMap<String, OpenOpportunities>>,
+}
+
+impl ArbitrageDetector {
+    /// Create a detector over the arena's venues with the given threshold in basis points
+    pub fn new(arena: Arc<ArenaAnalytics>, threshold_bps: f64) -> Self {
+        let (opportunities, _) = broadcast::channel(100);
+        Self {
+            arena,
+            threshold_bps,
+            opportunities,
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Subscribe to detected opportunities for every asset
+    pub fn subscribe(&self) -> broadcast::Receiver<ArbitrageOpportunity> {
+        self.opportunities.subscribe()
+    }
+
+    /// Find every (buy venue, sell venue) pair for an asset whose spread exceeds the threshold
+    pub async fn detect(&self, asset: &str) -> Vec<ArbitrageOpportunity> {
+        let venues = self.arena.venues_for(asset).await;
+        if venues.len() < 2 {
+            return Vec::new();
+        }
+
+        // Touch of each venue: (exchange, best bid, best ask)
+        let mut touches = Vec::with_capacity(venues.len());
+        for (exchange, engine) in venues {
+            let engine = engine.read().await;
+            touches.push((
+                exchange,
+                engine.top_bids().first().copied(),
+                engine.top_asks().first().copied(),
+            ));
+        }
+
+        let timestamp = now_secs();
+
+        let mut opportunities = Vec::new();
+        for (buy_exchange, _, ask) in &touches {
+            let Some((buy_price, ask_volume)) = *ask else { continue };
+            for (sell_exchange, bid, _) in &touches {
+                if sell_exchange == buy_exchange {
+                    continue;
+                }
+                let Some((sell_price, bid_volume)) = *bid else { continue };
+
+                let spread = sell_price - buy_price;
+                if spread <= 0.0 || buy_price <= 0.0 {
+                    continue;
+                }
+                let spread_bps = spread / buy_price * 10_000.0;
+                if spread_bps <= self.threshold_bps {
+                    continue;
+                }
+
+                opportunities.push(ArbitrageOpportunity {
+                    asset: asset.to_string(),
+                    buy_exchange: buy_exchange.clone(),
+                    sell_exchange: sell_exchange.clone(),
+                    buy_price,
+                    sell_price,
+                    spread,
+                    spread_bps,
+                    size: ask_volume.min(bid_volume),
+                    timestamp,
+                    open: true,
+                });
+            }
+        }
+        opportunities
+    }
+
+    /// Run detection for an asset and broadcast the opportunities that opened or closed
+    /// 
+    /// A pair that stays crossed across updates is not rebroadcast. Returns the
+    /// number of events broadcast.
+    pub async fn check(&self, asset: &str) -> usize {
+        let detected = self.detect(asset).await;
+        let mut open = self.open.lock().await;
+        let previous = open.remove(asset).unwrap_or_default();
+
+        let mut current = OpenOpportunities::new();
+        let mut events = Vec::new();
+        for opportunity in detected {
+            let key = (opportunity.buy_exchange.clone(), opportunity.sell_exchange.clone());
+            if !previous.contains_key(&key) {
+                events.push(opportunity.clone());
+            }
+            current.insert(key, opportunity);
+        }
+        for (key, mut closed) in previous {
+            if !current.contains_key(&key) {
+                closed.open = false;
+                closed.timestamp = now_secs();
+                events.push(closed);
+            }
+        }
+        if !current.is_empty() {
+            open.insert(asset.to_string(), current);
+        }
+
+        let count = events.len();
+        for event in events {
+            // No subscribers is fine; the event is simply not streamed
+            let _ = self.opportunities.send(event);
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::analytics::tests::venue_engine;
+    use crate::kraken::types::BookSnapshot;
+
+    #[tokio::test]
+    async fn test_crossed_venues_produce_event() {
+        let arena = Arc::new(ArenaAnalytics::new());
+        // Kraken's ask (100) is below binance's bid (101)
+        arena.register_venue("BTC", "kraken", venue_engine("99.0", "1.0", "100.0", "2.0")).await;
+        arena.register_venue("BTC", "binance", venue_engine("101.0", "0.5", "102.0", "1.0")).await;
+
+        let detector = ArbitrageDetector::new(arena, 10.0);
+        let mut rx = detector.subscribe();
+        assert_eq!(detector.check("BTC").await, 1);
+
+        let event = rx.try_recv().unwrap();
+        assert_eq!(event.asset, "BTC");
+        assert_eq!(event.buy_exchange, "kraken");
+        assert_eq!(event.sell_exchange, "binance");
+        assert_eq!(event.spread, 1.0);
+        assert_eq!(event.spread_bps, 100.0);
+        assert_eq!(event.size, 0.5);
+        assert!(event.open);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_only_transitions_are_broadcast() {
+        let arena = Arc::new(ArenaAnalytics::new());
+        let binance = venue_engine("101.0", "0.5", "102.0", "1.0");
+        arena.register_venue("BTC", "kraken", venue_engine("99.0", "1.0", "100.0", "2.0")).await;
+        arena.register_venue("BTC", "binance", binance.clone()).await;
+
+        let detector = ArbitrageDetector::new(arena, 10.0);
+        let mut rx = detector.subscribe();
+        assert_eq!(detector.check("BTC").await, 1);
+        assert!(rx.try_recv().unwrap().open);
+
+        // Still crossed: nothing new to report
+        assert_eq!(detector.check("BTC").await, 0);
+        assert!(rx.try_recv().is_err());
+
+        // Binance's bid drops below kraken's ask: the opportunity closes
+        binance.write().await.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["99.5", "0.5", "2.0"])],
+            asks: vec![serde_json::json!(["102.0", "1.0", "2.0"])],
+        }).unwrap();
+        assert_eq!(detector.check("BTC").await, 1);
+        let closed = rx.try_recv().unwrap();
+        assert!(!closed.open);
+        assert_eq!(closed.buy_exchange, "kraken");
+        assert_eq!(closed.sell_exchange, "binance");
+
+        // Staying uncrossed reports nothing further
+        assert_eq!(detector.check("BTC").await, 0);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_threshold_and_uncrossed_books() {
+        let arena = Arc::new(ArenaAnalytics::new());
+        arena.register_venue("BTC", "kraken", venue_engine("99.0", "1.0", "100.0", "2.0")).await;
+        arena.register_venue("BTC", "binance", venue_engine("100.05", "1.0", "101.0", "1.0")).await;
+        arena.register_venue("ETH", "kraken", venue_engine("99.0", "1.0", "100.0", "2.0")).await;
+        arena.register_venue("ETH", "binance", venue_engine("99.5", "1.0", "101.0", "1.0")).await;
+
+        // 5 bps crossing is below a 10 bps threshold
+        let detector = ArbitrageDetector::new(arena.clone(), 10.0);
+        assert!(detector.detect("BTC").await.is_empty());
+        assert_eq!(ArbitrageDetector::new(arena, 1.0).detect("BTC").await.len(), 1);
+
+        // Uncrossed books and single-venue assets never produce events
+        assert!(detector.detect("ETH").await.is_empty());
+        assert!(detector.detect("XMR").await.is_empty());
+    }
+}
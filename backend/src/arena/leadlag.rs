@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+use serde::Serialize;
+
+/// Mid samples kept per asset
+pub const MAX_MID_SAMPLES: usize = 2000;
+
+/// Mid price of every two-sided venue of an asset at one update tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct MidTick {
+    pub timestamp_ms: i64,
+    /// (exchange, mid) for each venue that had a two-sided book
+    pub mids: Vec<(String, f64)>,
+}
+
+/// Bounded history of an asset's per-venue mids, oldest first
+///
+/// Every venue is sampled together whenever any of them updates, so the
+/// series of two venues line up tick for tick.
+#[derive(Debug, Default)]
+pub struct MidHistory {
+    ticks: VecDeque<MidTick>,
+}
+
+impl MidHistory {
+    /// Append a tick, dropping the oldest once `MAX_MID_SAMPLES` are held
+    pub fn push(&mut self, tick: MidTick) {
+        if self.ticks.len() == MAX_MID_SAMPLES {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back(tick);
+    }
+
+    /// Aligned mids of two venues over the newest `window` ticks where both had one
+    ///
+    /// Returns (timestamps, mids of `a`, mids of `b`).
+    pub fn aligned(&self, a: &str, b: &str, window: usize) -> (Vec<i64>, Vec<f64>, Vec<f64>) {
+        let mid_of = |tick: &MidTick, exchange: &str| {
+            tick.mids.iter().find(|(name, _)| name == exchange).map(|(_, mid)| *mid)
+        };
+        let mut samples: Vec<(i64, f64, f64)> = self.ticks
+            .iter()
+            .rev()
+            .filter_map(|tick| Some((tick.timestamp_ms, mid_of(tick, a)?, mid_of(tick, b)?)))
+            .take(window)
+            .collect();
+        samples.reverse();
+
+        let mut timestamps = Vec::with_capacity(samples.len());
+        let mut mids_a = Vec::with_capacity(samples.len());
+        let mut mids_b = Vec::with_capacity(samples.len());
+        for (timestamp, mid_a, mid_b) in samples {
+            timestamps.push(timestamp);
+            mids_a.push(mid_a);
+            mids_b.push(mid_b);
+        }
+        (timestamps, mids_a, mids_b)
+    }
+}
+
+/// Lead-lag between two venues of an asset at peak cross-correlation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeadLag {
+    pub venue_a: String,
+    pub venue_b: String,
+    /// Ticks by which `venue_b` trails `venue_a`; negative when `venue_b` moves first
+    pub lag_ticks: i64,
+    /// `lag_ticks` converted using the window's average tick spacing
+    pub lag_ms: f64,
+    /// Correlation of the two venues' mid changes at `lag_ticks`
+    pub correlation: f64,
+    /// Aligned samples the correlation was computed over
+    pub samples: usize,
+}
+
+/// Pearson correlation of two equally long series, `None` if either is constant
+fn pearson(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len() as f64;
+    if xs.len() < 2 {
+        return None;
+    }
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x <= 0.0 || var_y <= 0.0 {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+/// Lag (in samples) at which `b`'s changes correlate most with `a`'s, and that correlation
+///
+/// The series are differenced first: mid levels of two trending venues correlate
+/// at every lag, their tick-to-tick moves don't. A positive lag means `b` trails
+/// `a`. Lags up to `max_lag` each way are tried, nearest to zero first so ties go
+/// to the smaller lag. Returns `None` if the series are too short or never move.
+pub fn peak_cross_correlation(a: &[f64], b: &[f64], max_lag: usize) -> Option<(i64, f64)> {
+    let n = a.len().min(b.len());
+    if n < 3 {
+        return None;
+    }
+    let diff = |series: &[f64]| series[..n].windows(2).map(|w| w[1] - w[0]).collect::<Vec<f64>>();
+    let (da, db) = (diff(a), diff(b));
+    let len = da.len();
+    // Keep at least two overlapping changes at every lag
+    let max_lag = max_lag.min(len - 2) as i64;
+
+    let mut best: Option<(i64, f64)> = None;
+    for lag in (0..=max_lag).flat_map(|k| if k == 0 { vec![0] } else { vec![k, -k] }) {
+        let shift = lag.unsigned_abs() as usize;
+        let (xs, ys) = if lag >= 0 {
+            (&da[..len - shift], &db[shift..])
+        } else {
+            (&da[shift..], &db[..len - shift])
+        };
+        let Some(correlation) = pearson(xs, ys) else { continue };
+        if best.is_none_or(|(_, peak)| correlation > peak) {
+            best = Some((lag, correlation));
+        }
+    }
+    best
+}
+
+/// Lead-lag of two venues over the newest `window` aligned samples of `history`
+pub fn lead_lag(history: &MidHistory, venue_a: &str, venue_b: &str, window: usize, max_lag: usize) -> Option<LeadLag> {
+    let (timestamps, mids_a, mids_b) = history.aligned(venue_a, venue_b, window);
+    let (lag_ticks, correlation) = peak_cross_correlation(&mids_a, &mids_b, max_lag)?;
+    let tick_ms = match (timestamps.first(), timestamps.last()) {
+        (Some(first), Some(last)) if timestamps.len() > 1 => (last - first) as f64 / (timestamps.len() - 1) as f64,
+        _ => 0.0,
+    };
+    Some(LeadLag {
+        venue_a: venue_a.to_string(),
+        venue_b: venue_b.to_string(),
+        lag_ticks,
+        lag_ms: lag_ticks as f64 * tick_ms,
+        correlation,
+        samples: timestamps.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic random walk of `n` steps
+    fn random_walk(n: usize) -> Vec<f64> {
+        let mut state: u64 = 42;
+        let mut price = 100.0;
+        (0..n)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                price += ((state >> 33) % 21) as f64 / 10.0 - 1.0;
+                price
+            })
+            .collect()
+    }
+
+    /// `series` delayed by `lag` samples, holding the first value at the start
+    fn delayed(series: &[f64], lag: usize) -> Vec<f64> {
+        (0..series.len()).map(|t| series[t.saturating_sub(lag)]).collect()
+    }
+
+    #[test]
+    fn test_detects_lag_of_shifted_copy() {
+        let leader = random_walk(300);
+        let follower = delayed(&leader, 3);
+
+        let (lag, correlation) = peak_cross_correlation(&leader, &follower, 10).unwrap();
+        assert_eq!(lag, 3);
+        assert!(correlation > 0.99);
+
+        // Swapping the venues flips the sign
+        assert_eq!(peak_cross_correlation(&follower, &leader, 10).unwrap().0, -3);
+        assert_eq!(peak_cross_correlation(&leader, &leader, 10).unwrap().0, 0);
+    }
+
+    #[test]
+    fn test_flat_or_short_series_have_no_lag() {
+        assert_eq!(peak_cross_correlation(&[1.0, 1.0, 1.0, 1.0], &[1.0, 2.0, 1.0, 2.0], 2), None);
+        assert_eq!(peak_cross_correlation(&[1.0, 2.0], &[1.0, 2.0], 2), None);
+    }
+
+    #[test]
+    fn test_lead_lag_over_history() {
+        let leader = random_walk(200);
+        let follower = delayed(&leader, 2);
+        let mut history = MidHistory::default();
+        for (t, (a, b)) in leader.iter().zip(&follower).enumerate() {
+            history.push(MidTick {
+                timestamp_ms: t as i64 * 50,
+                mids: vec![("binance".to_string(), *a), ("kraken".to_string(), *b)],
+            });
+        }
+
+        let result = lead_lag(&history, "binance", "kraken", 150, 10).unwrap();
+        assert_eq!(result.lag_ticks, 2);
+        assert_eq!(result.lag_ms, 100.0);
+        assert_eq!(result.samples, 150);
+        assert!(lead_lag(&history, "binance", "coinbase", 150, 10).is_none());
+    }
+}
@@ -0,0 +1,10 @@
+//! Arena module for cross-exchange analytics
+//! 
+//! This module combines orderbooks for the same asset across venues:
+//! - Registry of per-venue engines and consolidated signals (analytics.rs)
+//! - Cross-exchange arbitrage detection (arbitrage.rs)
+//! - Lead-lag between venues from their sampled mid prices (leadlag.rs)
+
+pub mod analytics;
+pub mod arbitrage;
+pub mod leadlag;
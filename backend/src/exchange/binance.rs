@@ -0,0 +1,439 @@
+//! Binance spot depth stream adapter
+//!
+//! Binance streams `{symbol}@depth` diffs over WebSocket but serves the full book
+//! separately over REST, so the book is synced per Binance's documented procedure:
+//! the stream is opened first, then the REST snapshot is fetched, diffs already
+//! covered by the snapshot's `lastUpdateId` are dropped, and every later diff must
+//! start right after the previous one ended. A gap fails the connection so the
+//! feed task reconnects and syncs from a fresh snapshot.
+
+use crate::exchange::{BookEvent, ChannelStatus, Exchange, ExchangeConnection};
+use crate::kraken::subscription::BOOK_CHANNEL;
+use crate::kraken::types::{BookDelta, BookSnapshot};
+use anyhow::{Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const BINANCE_WS_URL: &str = "wss://stream.binance.com:9443/ws";
+
+const BINANCE_REST_URL: &str = "https://api.binance.com";
+
+/// Deepest book the REST depth endpoint returns
+const MAX_SNAPSHOT_LIMIT: u32 = 5000;
+
+/// A `[price, quantity]` level as Binance sends it, both decimal strings
+type BinanceLevel = [String; 2];
+
+/// Diff event from the `{symbol}@depth` stream
+/// Format: {"e": "depthUpdate", "E": 1700000000123, "s": "BTCUSDT", "U": 157, "u": 160, "b": [...], "a": [...]}
+/// Quantities are absolute; "0.00000000" removes the level.
+#[derive(Debug, Deserialize)]
+pub struct DepthUpdate {
+    #[serde(rename = "e")]
+    pub event_type: String,
+    /// Event time in milliseconds
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// First update ID in this event
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    /// Final update ID in this event
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "b", default)]
+    pub bids: Vec<BinanceLevel>,
+    #[serde(rename = "a", default)]
+    pub asks: Vec<BinanceLevel>,
+}
+
+impl DepthUpdate {
+    /// Convert to a delta in the `[price, volume, timestamp]` level format the engine consumes
+    ///
+    /// Every level is stamped with the event time, in seconds like Kraken's.
+    pub fn to_book_delta(&self) -> BookDelta {
+        let timestamp = format!("{:.3}", self.event_time as f64 / 1000.0);
+        BookDelta {
+            bids: self.bids.iter().map(|level| to_level(level, &timestamp)).collect(),
+            asks: self.asks.iter().map(|level| to_level(level, &timestamp)).collect(),
+            checksum: None,
+        }
+    }
+}
+
+/// Response of `GET /api/v3/depth`
+/// Format: {"lastUpdateId": 160, "bids": [["0.0024", "10"]], "asks": [["0.0026", "100"]]}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DepthSnapshot {
+    pub last_update_id: u64,
+    #[serde(default)]
+    pub bids: Vec<BinanceLevel>,
+    #[serde(default)]
+    pub asks: Vec<BinanceLevel>,
+}
+
+impl DepthSnapshot {
+    /// Convert to a snapshot in the level format the engine consumes; REST levels carry no time
+    pub fn to_book_snapshot(&self) -> BookSnapshot {
+        BookSnapshot {
+            bids: self.bids.iter().map(|level| to_level(level, "")).collect(),
+            asks: self.asks.iter().map(|level| to_level(level, "")).collect(),
+        }
+    }
+}
+
+fn to_level(level: &BinanceLevel, timestamp: &str) -> serde_json::Value {
+    serde_json::json!([level[0], level[1], timestamp])
+}
+
+/// Binance stream symbol for a `BASE/QUOTE` pair, e.g. "BTC/USD" -> "btcusdt"
+///
+/// Binance has no USD-quoted spot markets, so a USD quote maps to USDT.
+pub fn binance_symbol(pair: &str) -> String {
+    let (base, quote) = pair.split_once('/').unwrap_or((pair, ""));
+    let quote = if quote.eq_ignore_ascii_case("USD") { "USDT" } else { quote };
+    format!("{}{}", base, quote).to_lowercase()
+}
+
+/// Tracks update IDs to decide which diffs follow on from the REST snapshot
+#[derive(Debug)]
+pub struct DepthSync {
+    /// Final update ID applied so far, starting at the snapshot's `lastUpdateId`
+    last_update_id: u64,
+    /// True once the first diff overlapping the snapshot was accepted
+    synced: bool,
+}
+
+impl DepthSync {
+    /// Start syncing from a snapshot with the given `lastUpdateId`
+    pub fn new(snapshot_update_id: u64) -> Self {
+        Self {
+            last_update_id: snapshot_update_id,
+            synced: false,
+        }
+    }
+
+    /// Decide whether a diff should be applied
+    ///
+    /// Returns `Ok(false)` for diffs the snapshot already covers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if updates were missed: either the snapshot is older than
+    /// the first buffered diff, or a diff doesn't start right after the previous one.
+    pub fn accept(&mut self, update: &DepthUpdate) -> Result<bool> {
+        let next_id = self.last_update_id + 1;
+        if !self.synced {
+            if update.final_update_id < next_id {
+                return Ok(false);
+            }
+            if update.first_update_id > next_id {
+                return Err(anyhow::anyhow!(
+                    "Binance snapshot is stale: first diff starts at update {} but snapshot ends at {}",
+                    update.first_update_id,
+                    self.last_update_id
+                ));
+            }
+            self.synced = true;
+        } else if update.first_update_id != next_id {
+            return Err(anyhow::anyhow!(
+                "Binance depth stream gap: expected update {}, got {}",
+                next_id,
+                update.first_update_id
+            ));
+        }
+        self.last_update_id = update.final_update_id;
+        Ok(true)
+    }
+}
+
+/// Client for Binance's spot depth stream of a single pair
+///
+/// Binance streams are chosen by URL, so the client is bound to its pair up front.
+pub struct BinanceClient {
+    symbol: String,
+    ws_url: String,
+    rest_url: String,
+    http: reqwest::Client,
+}
+
+impl BinanceClient {
+    /// Create a client streaming `pair` (e.g. "BTC/USD") from Binance
+    pub fn new(pair: &str) -> Self {
+        Self {
+            symbol: binance_symbol(pair),
+            ws_url: BINANCE_WS_URL.to_string(),
+            rest_url: BINANCE_REST_URL.to_string(),
+            http: reqwest::Client::new(),
+        }
+    }
+}
+
+/// Active depth stream connection to Binance
+pub struct BinanceConnection {
+    write: futures_util::stream::SplitSink<
+        tokio_tungstenite::WebSocketStream<
+            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+        >,
+        Message,
+    >,
+    read: futures_util::stream::SplitStream<
+        tokio_tungstenite::WebSocketStream<
+            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+        >,
+    >,
+    symbol: String,
+    rest_url: String,
+    http: reqwest::Client,
+    /// Set once the REST snapshot was fetched; diffs before then are ignored
+    sync: Option<DepthSync>,
+    /// Events produced by subscribing but not yet returned
+    pending: VecDeque<BookEvent>,
+}
+
+impl BinanceConnection {
+    /// Fetch up to `limit` levels per side of the book over REST
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the request fails, Binance answers with an error
+    /// status, or the body isn't a depth snapshot.
+    async fn fetch_snapshot(&self, limit: u32) -> Result<DepthSnapshot> {
+        let url = format!(
+            "{}/api/v3/depth?symbol={}&limit={}",
+            self.rest_url,
+            self.symbol.to_uppercase(),
+            limit
+        );
+        self.http
+            .get(&url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to request Binance depth snapshot from {}", url))?
+            .error_for_status()
+            .context("Binance rejected the depth snapshot request")?
+            .json::<DepthSnapshot>()
+            .await
+            .context("Failed to parse Binance depth snapshot")
+    }
+
+    /// Map a text frame to the event it carries, if it should be applied
+    fn map_text(&mut self, text: &str) -> Result<Option<BookEvent>> {
+        let update = match serde_json::from_str::<DepthUpdate>(text) {
+            Ok(update) => update,
+            Err(e) => {
+                tracing::warn!(error = %e, "Error parsing Binance depth update");
+                return Ok(Some(BookEvent::Malformed));
+            }
+        };
+        let Some(sync) = &mut self.sync else {
+            return Ok(None);
+        };
+        if sync.accept(&update)? {
+            Ok(Some(BookEvent::Delta(update.to_book_delta())))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Exchange for BinanceClient {
+    type Connection = BinanceConnection;
+
+    fn name(&self) -> &'static str {
+        "binance"
+    }
+
+    /// Open the pair's depth stream; diffs wait in the socket until the book is subscribed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebSocket connection cannot be established
+    async fn connect(&self) -> Result<BinanceConnection> {
+        let url = format!("{}/{}@depth", self.ws_url, self.symbol);
+        let (ws_stream, _) = connect_async(&url)
+            .await
+            .with_context(|| format!(
+                "Failed to connect to Binance WebSocket at {}: check network connection and URL",
+                url
+            ))?;
+
+        let (write, read) = ws_stream.split();
+
+        Ok(BinanceConnection {
+            write,
+            read,
+            symbol: self.symbol.clone(),
+            rest_url: self.rest_url.clone(),
+            http: self.http.clone(),
+            sync: None,
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl ExchangeConnection for BinanceConnection {
+    /// Fetch the REST snapshot the stream's diffs are applied on top of
+    ///
+    /// The stream is already open, so the snapshot is queued as the first event
+    /// along with an acknowledgement of the book channel.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pair` isn't the pair this connection streams, or the
+    /// snapshot cannot be fetched.
+    async fn subscribe_book(&mut self, pair: &str, depth: Option<u32>) -> Result<()> {
+        if binance_symbol(pair) != self.symbol {
+            return Err(anyhow::anyhow!(
+                "Binance connection streams {} but {} was requested",
+                self.symbol,
+                pair
+            ));
+        }
+
+        let limit = depth.unwrap_or(MAX_SNAPSHOT_LIMIT).min(MAX_SNAPSHOT_LIMIT);
+        let snapshot = self.fetch_snapshot(limit).await?;
+        self.sync = Some(DepthSync::new(snapshot.last_update_id));
+        self.pending.push_back(BookEvent::Snapshot(snapshot.to_book_snapshot()));
+        self.pending.push_back(BookEvent::Status(ChannelStatus {
+            channel: BOOK_CHANNEL.to_string(),
+            subscribed: true,
+            channel_id: None,
+        }));
+        Ok(())
+    }
+
+    /// Wait for the next normalized event
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the WebSocket fails or a diff shows updates were missed.
+    async fn next_book_event(&mut self) -> Result<BookEvent> {
+        if let Some(event) = self.pending.pop_front() {
+            return Ok(event);
+        }
+        match self.read.next().await {
+            Some(Ok(Message::Text(text))) => Ok(self.map_text(&text)?.unwrap_or(BookEvent::Heartbeat)),
+            Some(Ok(Message::Ping(data))) => {
+                // Binance drops connections that don't answer its pings
+                self.write
+                    .send(Message::Pong(data))
+                    .await
+                    .context("Failed to send pong response: connection may be closed")?;
+                Ok(BookEvent::Heartbeat)
+            }
+            Some(Ok(Message::Close(close_frame))) => {
+                if let Some(frame) = close_frame {
+                    tracing::info!(code = ?frame.code, reason = %frame.reason, "WebSocket closed by server");
+                } else {
+                    tracing::info!("WebSocket closed by server (no close frame)");
+                }
+                Ok(BookEvent::Close)
+            }
+            Some(Ok(_)) => Ok(BookEvent::Heartbeat),
+            Some(Err(e)) => Err(anyhow::anyhow!(
+                "WebSocket connection error: {}. Connection may be lost or network issue occurred",
+                e
+            )),
+            None => {
+                tracing::info!("WebSocket stream ended (connection closed)");
+                Ok(BookEvent::Close)
+            }
+        }
+    }
+
+    async fn close(&mut self) -> Result<()> {
+        self.write
+            .close()
+            .await
+            .context("Failed to send close frame: connection may already be closed")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kraken::types::parse_price_level;
+
+    // Captured from wss://stream.binance.com:9443/ws/btcusdt@depth
+    const DEPTH_UPDATE: &str = r#"{"e":"depthUpdate","E":1700000000123,"s":"BTCUSDT","U":4113,"u":4115,
+        "b":[["37000.01000000","0.51200000"],["36999.50000000","0.00000000"]],
+        "a":[["37000.02000000","1.20000000"]]}"#;
+
+    // Captured from https://api.binance.com/api/v3/depth?symbol=BTCUSDT&limit=5
+    const DEPTH_SNAPSHOT: &str = r#"{"lastUpdateId":4114,
+        "bids":[["37000.01000000","0.25000000"],["36999.50000000","3.00000000"]],
+        "asks":[["37000.02000000","0.80000000"],["37000.50000000","2.10000000"]]}"#;
+
+    fn update(first: u64, last: u64) -> DepthUpdate {
+        DepthUpdate {
+            event_type: "depthUpdate".to_string(),
+            event_time: 0,
+            symbol: "BTCUSDT".to_string(),
+            first_update_id: first,
+            final_update_id: last,
+            bids: Vec::new(),
+            asks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_depth_update_normalizes_to_delta() {
+        let update: DepthUpdate = serde_json::from_str(DEPTH_UPDATE).unwrap();
+        assert_eq!((update.first_update_id, update.final_update_id), (4113, 4115));
+
+        let delta = update.to_book_delta();
+        assert_eq!(delta.checksum, None);
+        assert_eq!(delta.bids.len(), 2);
+        assert_eq!(delta.asks.len(), 1);
+
+        let bid = parse_price_level(&delta.bids[0]).unwrap();
+        assert_eq!((bid.price, bid.volume, bid.timestamp), (37000.01, 0.512, Some(1700000000.123)));
+        // Zero quantity carries through as a removal
+        assert_eq!(parse_price_level(&delta.bids[1]).unwrap().volume, 0.0);
+        assert_eq!(parse_price_level(&delta.asks[0]).unwrap().price, 37000.02);
+    }
+
+    #[test]
+    fn test_depth_snapshot_normalizes_to_snapshot() {
+        let snapshot: DepthSnapshot = serde_json::from_str(DEPTH_SNAPSHOT).unwrap();
+        assert_eq!(snapshot.last_update_id, 4114);
+
+        let book = snapshot.to_book_snapshot();
+        assert_eq!((book.bids.len(), book.asks.len()), (2, 2));
+        let ask = parse_price_level(&book.asks[1]).unwrap();
+        assert_eq!((ask.price, ask.volume, ask.timestamp), (37000.5, 2.1, None));
+    }
+
+    #[test]
+    fn test_depth_sync_drops_covered_diffs_then_requires_continuity() {
+        let mut sync = DepthSync::new(100);
+
+        // Entirely covered by the snapshot
+        assert!(!sync.accept(&update(90, 100)).unwrap());
+        // Overlaps the snapshot: the first diff applied
+        assert!(sync.accept(&update(95, 105)).unwrap());
+        assert!(sync.accept(&update(106, 110)).unwrap());
+
+        // Update 111 was missed
+        assert!(sync.accept(&update(112, 115)).is_err());
+    }
+
+    #[test]
+    fn test_depth_sync_rejects_stale_snapshot() {
+        let mut sync = DepthSync::new(100);
+        assert!(sync.accept(&update(102, 110)).is_err());
+    }
+
+    #[test]
+    fn test_binance_symbol() {
+        assert_eq!(binance_symbol("BTC/USD"), "btcusdt");
+        assert_eq!(binance_symbol("ETH/BTC"), "ethbtc");
+        assert_eq!(binance_symbol("XMR/USDT"), "xmrusdt");
+    }
+}
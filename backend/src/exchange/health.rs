@@ -0,0 +1,71 @@
+//! Connection health of the exchange feeds
+//! 
+//! A feed task can be connected and subscribed yet receive nothing, e.g. after a
+//! silent network drop. Each task stamps its ticker on every message it
+//! receives, and /health and /ready judge the feeds by how long ago that was,
+//! against the configured `stale_feed_threshold_secs`.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Time each ticker's feed last delivered a message
+#[derive(Debug, Default)]
+pub struct ConnectionHealth {
+    last_message: RwLock<HashMap<String, Instant>>,
+}
+
+impl ConnectionHealth {
+    /// Create health with no messages seen for any ticker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `ticker`'s feed just delivered a message
+    pub async fn record_message(&self, ticker: &str) {
+        self.record_message_at(ticker, Instant::now()).await;
+    }
+
+    /// Record that `ticker`'s feed delivered a message at `at`
+    pub async fn record_message_at(&self, ticker: &str, at: Instant) {
+        let mut last_message = self.last_message.write().await;
+        match last_message.get_mut(ticker) {
+            Some(seen) => *seen = at,
+            None => {
+                last_message.insert(ticker.to_string(), at);
+            }
+        }
+    }
+
+    /// Time since `ticker`'s feed last delivered a message, `None` if it never has
+    pub async fn last_seen_age(&self, ticker: &str) -> Option<Duration> {
+        self.last_message.read().await.get(ticker).map(Instant::elapsed)
+    }
+
+    /// Whether `ticker`'s feed delivered a message within `within`
+    pub async fn is_healthy(&self, ticker: &str, within: Duration) -> bool {
+        self.last_seen_age(ticker).await.is_some_and(|age| age <= within)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health_follows_last_message() {
+        let within = Duration::from_secs(30);
+        let health = ConnectionHealth::new();
+        assert_eq!(health.last_seen_age("BTC").await, None);
+        assert!(!health.is_healthy("BTC", within).await);
+
+        health.record_message("BTC").await;
+        assert!(health.is_healthy("BTC", within).await);
+
+        let stale = Instant::now().checked_sub(within * 2).unwrap();
+        health.record_message_at("BTC", stale).await;
+        assert!(health.last_seen_age("BTC").await.unwrap() >= within * 2);
+        assert!(!health.is_healthy("BTC", within).await);
+        assert!(health.is_healthy("BTC", within * 3).await);
+    }
+}
@@ -0,0 +1,315 @@
+//! Exchange-agnostic interface to a market data feed
+//! 
+//! Each exchange adapter keeps its own wire format and parsing, and maps what it
+//! receives into normalized `BookEvent`s. The feed task in main.rs only deals in
+//! these events, so it applies books the same way whatever the data source.
+
+pub mod binance;
+pub mod health;
+
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+use anyhow::Result;
+use tokio::time::sleep;
+use crate::exchange::binance::BinanceClient;
+use crate::kraken::client::KrakenClient;
+use crate::kraken::subscription::BOOK_CHANNEL;
+use crate::kraken::types::{BookDelta, BookSnapshot, OhlcData};
+use crate::orderbook::trades::TradeSide;
+
+/// Exchange a ticker's book feed is taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExchangeSource {
+    #[default]
+    Kraken,
+    Binance,
+}
+
+impl ExchangeSource {
+    /// Short lowercase name, matching `Exchange::name`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Kraken => "kraken",
+            Self::Binance => "binance",
+        }
+    }
+
+    /// Channels the source streams, matching `Exchange::CHANNELS`
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Self::Kraken => KrakenClient::CHANNELS,
+            Self::Binance => BinanceClient::CHANNELS,
+        }
+    }
+}
+
+impl FromStr for ExchangeSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "kraken" => Ok(Self::Kraken),
+            "binance" => Ok(Self::Binance),
+            _ => Err(anyhow::anyhow!("unknown exchange source: {}", s)),
+        }
+    }
+}
+
+/// Acknowledgement (or rejection) of a channel subscription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelStatus {
+    /// Channel name, e.g. "book" or "ohlc"
+    pub channel: String,
+    /// True if the exchange confirmed the subscription
+    pub subscribed: bool,
+    /// Channel ID assigned by the exchange, if it uses them
+    pub channel_id: Option<u64>,
+}
+
+/// A trade executed on the exchange, with the side normalized
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutedTrade {
+    /// Execution price
+    pub price: f64,
+    /// Executed volume
+    pub volume: f64,
+    /// Execution time (Unix seconds, with fractional part)
+    pub time: f64,
+    /// Side of the taker
+    pub side: TradeSide,
+}
+
+/// A normalized event from an exchange's book feed
+#[derive(Debug)]
+pub enum BookEvent {
+    /// Full book, replacing whatever was held before
+    Snapshot(BookSnapshot),
+    /// Changed levels to apply on top of the current book
+    Delta(BookDelta),
+    /// A candle from the OHLC channel, if the exchange provides one
+    Candle(OhlcData),
+    /// An executed trade from the trade channel, if the exchange provides one
+    Trade(ExecutedTrade),
+    /// A subscription was acknowledged or rejected
+    Status(ChannelStatus),
+    /// A message couldn't be used; the adapter has already logged why
+    Malformed,
+    /// A frame carrying no event arrived, e.g. a heartbeat or pong, so the
+    /// connection is still alive
+    Heartbeat,
+    /// The exchange closed the connection
+    Close,
+}
+
+/// A source of market data that can be connected to
+pub trait Exchange {
+    type Connection: ExchangeConnection + Send;
+
+    /// Channels the exchange streams and acknowledges, in subscription order
+    /// 
+    /// Subscribing to any other channel is a no-op, so a feed's subscription
+    /// record only waits for these to be confirmed.
+    const CHANNELS: &'static [&'static str] = &[BOOK_CHANNEL];
+
+    /// Short lowercase name, e.g. "kraken"
+    fn name(&self) -> &'static str;
+
+    /// Open a new connection; nothing is subscribed yet
+    fn connect(&self) -> impl Future<Output = Result<Self::Connection>> + Send;
+
+    /// Extra delay before reconnecting after `error` ended a connection
+    /// 
+    /// `None` means the error is permanent and the feed shouldn't reconnect.
+    /// By default every error is retried straight away.
+    fn retry_delay(&self, _error: &anyhow::Error) -> Option<Duration> {
+        Some(Duration::ZERO)
+    }
+}
+
+/// An open connection to an exchange
+pub trait ExchangeConnection {
+    /// Subscribe to the order book of `pair`, optionally limited to `depth` levels
+    fn subscribe_book(&mut self, pair: &str, depth: Option<u32>) -> impl Future<Output = Result<()>> + Send;
+
+    /// Subscribe to `interval`-minute candles of `pair`
+    /// 
+    /// Exchanges without a candle channel can leave this as a no-op; the feed
+    /// then only gets candles built from inferred trades.
+    fn subscribe_ohlc(&mut self, _pair: &str, _interval: u32) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Subscribe to the executed trades of `pair`
+    /// 
+    /// Exchanges without a trade channel can leave this as a no-op; the feed's
+    /// last price is then inferred from book movements.
+    fn subscribe_trade(&mut self, _pair: &str) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Wait for the next event
+    /// 
+    /// Every frame received yields at least one event, so the caller can track
+    /// liveness; frames that carry none yield `BookEvent::Heartbeat`.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the connection fails or the exchange reports an error;
+    /// the connection should then be dropped and `Exchange::retry_delay` consulted.
+    fn next_book_event(&mut self) -> impl Future<Output = Result<BookEvent>> + Send;
+
+    /// Close the connection gracefully
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the close frame cannot be sent
+    fn close(&mut self) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Delays between attempts to reconnect to an exchange
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BackoffConfig {
+    /// Delay before the first retry
+    pub initial: Duration,
+    /// Factor the delay grows by after each failed retry
+    pub multiplier: f64,
+    /// Longest delay between two attempts
+    pub max: Duration,
+    /// Retries after the first attempt before giving up
+    pub max_retries: usize,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            multiplier: 2.0,
+            max: Duration::from_secs(60),
+            max_retries: 6,
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Delay before retry number `retry` (0 for the first), capped at `max`
+    pub fn delay(&self, retry: usize) -> Duration {
+        let exponent = i32::try_from(retry).unwrap_or(i32::MAX);
+        let secs = self.initial.as_secs_f64() * self.multiplier.powi(exponent);
+        // An overflowing (infinite) delay is capped like any other
+        Duration::from_secs_f64(secs.min(self.max.as_secs_f64()))
+    }
+
+    /// The delay before each of the `max_retries` retries, in order
+    pub fn delays(&self) -> impl Iterator<Item = Duration> + '_ {
+        (0..self.max_retries).map(|retry| self.delay(retry))
+    }
+}
+
+/// Reconnect with exponential backoff
+/// 
+/// The first attempt is made immediately; after each failure the feed waits
+/// `backoff.initial`, growing by `backoff.multiplier` up to `backoff.max`.
+/// Returns the last error after `backoff.max_retries` retries.
+pub async fn reconnect_with_backoff<E: Exchange>(
+    exchange: &E,
+    backoff: &BackoffConfig,
+) -> Result<E::Connection> {
+    let mut delays = backoff.delays().enumerate();
+
+    loop {
+        match exchange.connect().await {
+            Ok(conn) => {
+                return Ok(conn);
+            }
+            Err(e) => {
+                let Some((retry, delay)) = delays.next() else {
+                    return Err(anyhow::anyhow!(
+                        "Failed to reconnect after {} retries: {}",
+                        backoff.max_retries,
+                        e
+                    ));
+                };
+
+                tracing::warn!(
+                    attempt = retry + 1,
+                    max_retries = backoff.max_retries,
+                    error = %e,
+                    delay = ?delay,
+                    "Connection failed, retrying"
+                );
+
+                sleep(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_backoff_grows_then_caps_at_max() {
+        let backoff = BackoffConfig {
+            initial: Duration::from_millis(500),
+            multiplier: 2.0,
+            max: Duration::from_secs(3),
+            max_retries: 6,
+        };
+        let delays: Vec<u128> = backoff.delays().map(|delay| delay.as_millis()).collect();
+        assert_eq!(delays, vec![500, 1000, 2000, 3000, 3000, 3000]);
+
+        // Far-out retries stay capped instead of overflowing
+        assert_eq!(backoff.delay(10_000), Duration::from_secs(3));
+        assert_eq!(BackoffConfig { max_retries: 0, ..backoff }.delays().count(), 0);
+    }
+
+    /// An exchange whose connections always fail, counting the attempts
+    struct Unreachable(AtomicUsize);
+
+    struct NeverConnected;
+
+    impl ExchangeConnection for NeverConnected {
+        async fn subscribe_book(&mut self, _pair: &str, _depth: Option<u32>) -> Result<()> {
+            unreachable!()
+        }
+
+        async fn next_book_event(&mut self) -> Result<BookEvent> {
+            unreachable!()
+        }
+
+        async fn close(&mut self) -> Result<()> {
+            unreachable!()
+        }
+    }
+
+    impl Exchange for Unreachable {
+        type Connection = NeverConnected;
+
+        fn name(&self) -> &'static str {
+            "unreachable"
+        }
+
+        async fn connect(&self) -> Result<NeverConnected> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Err(anyhow::anyhow!("connection refused"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_gives_up_after_max_retries() {
+        let exchange = Unreachable(AtomicUsize::new(0));
+        let backoff = BackoffConfig {
+            initial: Duration::from_millis(1),
+            multiplier: 2.0,
+            max: Duration::from_millis(2),
+            max_retries: 3,
+        };
+        let error = reconnect_with_backoff(&exchange, &backoff).await.err().unwrap();
+        assert!(error.to_string().contains("after 3 retries"));
+        // The first attempt plus one per retry
+        assert_eq!(exchange.0.load(Ordering::SeqCst), 4);
+    }
+}
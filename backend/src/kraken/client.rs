@@ -1,45 +1,316 @@
+use crate::exchange::{BookEvent, Exchange, ExchangeConnection, ExecutedTrade};
+use crate::kraken::deflate::{accepts_deflate, InflateStream, PERMESSAGE_DEFLATE};
+use crate::kraken::diagnostics::FEED_DIAGNOSTICS;
+use crate::kraken::subscription::{BOOK_CHANNEL, OHLC_CHANNEL, TRADE_CHANNEL};
 use crate::kraken::types::{
-    BookMessage, OhlcMessage, SubscriptionRequest, SubscriptionStatus,
+    parse_book_delta, parse_book_delta_v2, parse_book_snapshot, parse_book_snapshot_v2, parse_ohlc_data, pong_reqid,
+    BookDelta, BookMessage, BookSnapshot, MethodResponseV2, OhlcMessage, PingRequest, PingRequestV2, SnapshotAssembler, SubscriptionParamsV2, SubscriptionRequest, SubscriptionRequestV2,
+    SubscriptionStatus, TradeData, TradeMessage,
 };
-use anyhow::{Context, Result, bail};
+use crate::orderbook::trades::TradeSide;
+use crate::kraken::errors::KrakenError;
+use anyhow::{Context, Result};
 use futures_util::{SinkExt, StreamExt};
 use serde_json;
+use std::collections::VecDeque;
 use std::time::Duration;
-use tokio::time::sleep;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::client::Response;
+use tokio_tungstenite::tungstenite::http::header::{HeaderValue, SEC_WEBSOCKET_EXTENSIONS};
+use tokio_tungstenite::{client_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 
 const KRAKEN_WS_URL: &str = "wss://ws.kraken.com/";
 
+const KRAKEN_WS_V2_URL: &str = "wss://ws.kraken.com/v2";
+
+/// How long snapshot frames are held waiting for more before being emitted
+const SNAPSHOT_FLUSH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Version of Kraken's WebSocket API to speak
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KrakenProtocol {
+    /// Positional array messages and `event` subscriptions (wss://ws.kraken.com/)
+    #[default]
+    V1,
+    /// Named-field JSON objects and `method` subscriptions (wss://ws.kraken.com/v2)
+    /// 
+    /// Only the book channel is parsed; v2 OHLC and trade frames are ignored.
+    V2,
+}
+
+impl KrakenProtocol {
+    /// Default endpoint for this protocol version
+    pub fn default_url(&self) -> &'static str {
+        match self {
+            KrakenProtocol::V1 => KRAKEN_WS_URL,
+            KrakenProtocol::V2 => KRAKEN_WS_V2_URL,
+        }
+    }
+}
+
 /// Default trading pair for the orderbook visualizer
-#[allow(dead_code)] // Will be used when integrating client
 pub const DEFAULT_TRADING_PAIR: &str = "ZEC/USD";
 
 /// Default book depth for orderbook subscription
 /// Kraken supports: 10, 25, 100, 500, 1000
 /// Using maximum depth for full orderbook visibility
-#[allow(dead_code)] // Will be used when integrating client
 pub const DEFAULT_BOOK_DEPTH: u32 = 1000;
 
 /// WebSocket client for connecting to Kraken API
 pub struct KrakenClient {
     url: String,
+    protocol: KrakenProtocol,
+    /// How often connections send an application-level ping, if at all
+    ping_interval: Option<Duration>,
+    /// Whether to offer permessage-deflate in the handshake
+    compression: bool,
+}
+
+impl Default for KrakenClient {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl KrakenClient {
     /// Create a new Kraken client
     pub fn new() -> Self {
+        Self::with_protocol(KrakenProtocol::default())
+    }
+
+    /// Create a new Kraken client speaking the given protocol version at its default URL
+    pub fn with_protocol(protocol: KrakenProtocol) -> Self {
         Self {
-            url: KRAKEN_WS_URL.to_string(),
+            url: protocol.default_url().to_string(),
+            protocol,
+            ping_interval: None,
+            compression: false,
         }
     }
 
     /// Create a new Kraken client with custom URL (for testing)
     pub fn with_url(url: String) -> Self {
-        Self { url }
+        Self {
+            url,
+            protocol: KrakenProtocol::default(),
+            ping_interval: None,
+            compression: false,
+        }
+    }
+
+    /// Have connections send Kraken a `ping` every `interval`
+    /// 
+    /// Kraken drops connections it considers idle; on quiet pairs answering
+    /// its pings isn't always enough to keep one open.
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = Some(interval);
+        self
+    }
+
+    /// Offer permessage-deflate when connecting, so Kraken may compress frames
+    /// 
+    /// Deep books send large snapshots and bursts of deltas; compressed they
+    /// take a fraction of the bandwidth. Kraken may still decline.
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.compression = enabled;
+        self
+    }
+
+    /// Open the socket and run the WebSocket handshake on it
+    async fn handshake(&self) -> Result<(KrakenSocket, Response)> {
+        let mut request = self.url.as_str().into_client_request()?;
+        if self.compression {
+            request.headers_mut().insert(SEC_WEBSOCKET_EXTENSIONS, HeaderValue::from_static(PERMESSAGE_DEFLATE));
+        }
+
+        let uri = request.uri();
+        let host = uri.host().context("URL has no host")?;
+        let host = host.trim_start_matches('[').trim_end_matches(']').to_string();
+        let tls = uri.scheme_str() == Some("wss");
+        let port = uri.port_u16().unwrap_or(if tls { 443 } else { 80 });
+
+        let socket = TcpStream::connect((host.as_str(), port)).await?;
+        let socket = if tls {
+            let connector = tokio_native_tls::TlsConnector::from(tokio_native_tls::native_tls::TlsConnector::new()?);
+            MaybeTlsStream::NativeTls(connector.connect(&host, socket).await?)
+        } else {
+            MaybeTlsStream::Plain(socket)
+        };
+        let socket = if self.compression {
+            InflateStream::new(socket)
+        } else {
+            InflateStream::passthrough(socket)
+        };
+
+        Ok(client_async(request, socket).await?)
+    }
+}
+
+/// WebSocket to Kraken, inflating compressed frames if compression was offered
+type KrakenSocket = WebSocketStream<InflateStream<MaybeTlsStream<TcpStream>>>;
+
+/// Active WebSocket connection to Kraken
+pub struct KrakenConnection {
+    write: futures_util::stream::SplitSink<KrakenSocket, Message>,
+    read: futures_util::stream::SplitStream<KrakenSocket>,
+    protocol: KrakenProtocol,
+    events: KrakenEventMapper,
+    /// Events mapped from a message but not yet returned
+    pending: VecDeque<BookEvent>,
+    /// Interval between pings sent by `next_book_event`, if enabled
+    ping_interval: Option<Duration>,
+    /// When `next_book_event` sends the next ping
+    next_ping_at: Option<Instant>,
+    /// Request id and send time of the newest ping not yet answered
+    outstanding_ping: Option<(u64, Instant)>,
+    /// Request id of the last ping sent
+    last_ping_reqid: u64,
+}
+
+impl KrakenConnection {
+    /// Serialize a subscription request in this connection's protocol version
+    fn subscription_message(
+        &self,
+        channel: &str,
+        pair: &str,
+        depth: Option<u32>,
+        interval: Option<u32>,
+    ) -> serde_json::Result<String> {
+        match self.protocol {
+            KrakenProtocol::V1 => serde_json::to_string(&SubscriptionRequest {
+                event: "subscribe".to_string(),
+                pair: vec![pair.to_string()],
+                subscription: crate::kraken::types::SubscriptionDetails {
+                    name: channel.to_string(),
+                    depth,
+                    interval,
+                },
+            }),
+            KrakenProtocol::V2 => serde_json::to_string(&SubscriptionRequestV2::subscribe(SubscriptionParamsV2 {
+                channel: channel.to_string(),
+                symbol: vec![pair.to_string()],
+                depth,
+                interval,
+            })),
+        }
+    }
+
+    /// Send Kraken an application-level `ping`
+    /// 
+    /// Kraken answers with a `pong` carrying the same request id, which
+    /// `next_message` returns as `KrakenMessage::Pong`.
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if the previous ping was never answered (the connection
+    /// is presumed dead and should be replaced), or if the ping cannot be sent
+    /// (connection closed or lost)
+    pub async fn send_ping(&mut self) -> Result<()> {
+        if let Some((reqid, sent_at)) = self.outstanding_ping {
+            return Err(anyhow::anyhow!(
+                "No pong received for ping {} after {:?}: connection presumed dead",
+                reqid,
+                sent_at.elapsed()
+            ));
+        }
+        self.last_ping_reqid += 1;
+        let reqid = self.last_ping_reqid;
+        let message = match self.protocol {
+            KrakenProtocol::V1 => serde_json::to_string(&PingRequest::new(reqid)),
+            KrakenProtocol::V2 => serde_json::to_string(&PingRequestV2::new(reqid)),
+        }
+        .context("Failed to serialize ping request")?;
+
+        self.write
+            .send(Message::Text(message))
+            .await
+            .context("Failed to send ping: connection may be closed")?;
+        self.outstanding_ping = Some((reqid, Instant::now()));
+        Ok(())
+    }
+
+    /// Match a `pong` against the outstanding ping
+    fn handle_pong(&mut self, reqid: u64) {
+        match self.outstanding_ping {
+            Some((sent_reqid, sent_at)) if sent_reqid == reqid => {
+                tracing::debug!(reqid, latency_ms = sent_at.elapsed().as_millis() as u64, "Pong received");
+                self.outstanding_ping = None;
+            }
+            _ => tracing::debug!(reqid, "Pong received for an unknown ping"),
+        }
+    }
+
+    /// Subscribe to the book channel for ZEC/USD pair (default configuration)
+    pub async fn subscribe_zec_usd(&mut self) -> Result<()> {
+        self.subscribe_book(DEFAULT_TRADING_PAIR, Some(DEFAULT_BOOK_DEPTH))
+            .await
+    }
+
+    /// Receive the next message from the WebSocket
+    /// 
+    /// # Errors
+    /// 
+    /// Returns an error if:
+    /// - WebSocket connection error occurs
+    /// - Subscription status contains an error message from Kraken (a `KrakenError`)
+    /// - Message is malformed and cannot be parsed (for critical messages)
+    /// - Pong response cannot be sent
+    pub async fn next_message(&mut self) -> Result<Option<KrakenMessage>> {
+        match self.read.next().await {
+            Some(Ok(Message::Text(text))) => {
+                parse_message(text)
+            }
+            Some(Ok(Message::Close(close_frame))) => {
+                if let Some(frame) = close_frame {
+                    tracing::info!(code = ?frame.code, reason = %frame.reason, "WebSocket closed by server");
+                } else {
+                    tracing::info!("WebSocket closed by server (no close frame)");
+                }
+                Ok(Some(KrakenMessage::Close))
+            }
+            Some(Ok(Message::Ping(data))) => {
+                // Respond to ping with pong to keep connection alive
+                self.write
+                    .send(Message::Pong(data))
+                    .await
+                    .context("Failed to send pong response: connection may be closed")?;
+                Ok(None)
+            }
+            Some(Ok(_)) => {
+                // Ignore other message types (Binary, Pong, etc.)
+                Ok(None)
+            }
+            Some(Err(e)) => {
+                Err(anyhow::anyhow!(
+                    "WebSocket connection error: {}. Connection may be lost or network issue occurred",
+                    e
+                ))
+            }
+            None => {
+                // Stream ended (connection closed)
+                tracing::info!("WebSocket stream ended (connection closed)");
+                Ok(Some(KrakenMessage::Close))
+            }
+        }
+    }
+}
+
+impl Exchange for KrakenClient {
+    type Connection = KrakenConnection;
+
+    const CHANNELS: &'static [&'static str] = &[BOOK_CHANNEL, OHLC_CHANNEL, TRADE_CHANNEL];
+
+    fn name(&self) -> &'static str {
+        "kraken"
     }
 
     /// Connect to Kraken WebSocket and return a handle to send/receive messages
     /// 
+    /// With compression enabled, permessage-deflate is offered in the handshake
+    /// and Kraken's compressed frames are inflated before tungstenite reads them.
+    /// 
     /// # Errors
     /// 
     /// Returns an error if:
@@ -47,66 +318,68 @@ impl KrakenClient {
     /// - TCP connection cannot be established
     /// - TLS handshake fails
     /// - WebSocket handshake fails
-    pub async fn connect(&self) -> Result<KrakenConnection> {
-        let (ws_stream, _) = connect_async(&self.url)
+    async fn connect(&self) -> Result<KrakenConnection> {
+        let (ws_stream, response) = self.handshake()
             .await
             .with_context(|| format!(
                 "Failed to connect to Kraken WebSocket at {}: check network connection and URL",
                 self.url
             ))?;
 
+        if self.compression {
+            let negotiated = response
+                .headers()
+                .get(SEC_WEBSOCKET_EXTENSIONS)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(accepts_deflate);
+            if !negotiated {
+                tracing::info!("Kraken declined permessage-deflate; frames will arrive uncompressed");
+            }
+        }
+
         let (write, read) = ws_stream.split();
 
         Ok(KrakenConnection {
             write,
             read,
-            url: self.url.clone(),
+            protocol: self.protocol,
+            events: KrakenEventMapper::default(),
+            pending: VecDeque::new(),
+            ping_interval: self.ping_interval,
+            next_ping_at: self.ping_interval.map(|interval| Instant::now() + interval),
+            outstanding_ping: None,
+            last_ping_reqid: 0,
         })
     }
-}
 
-/// Active WebSocket connection to Kraken
-pub struct KrakenConnection {
-    write: futures_util::stream::SplitSink<
-        tokio_tungstenite::WebSocketStream<
-            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
-        >,
-        Message,
-    >,
-    read: futures_util::stream::SplitStream<
-        tokio_tungstenite::WebSocketStream<
-            tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
-        >,
-    >,
-    url: String,
+    /// Back off according to the `KrakenError` kind, if Kraken reported one
+    fn retry_delay(&self, error: &anyhow::Error) -> Option<Duration> {
+        match error.downcast_ref::<KrakenError>() {
+            Some(error) => error.kind.retry_delay(),
+            None => Some(Duration::ZERO),
+        }
+    }
 }
 
-impl KrakenConnection {
+impl ExchangeConnection for KrakenConnection {
     /// Subscribe to the book channel for a trading pair
     /// 
+    /// Sends an `event` request on v1 and a `method` request on v2.
+    /// 
     /// # Errors
     /// 
     /// Returns an error if:
     /// - Subscription request cannot be serialized
     /// - Message cannot be sent over the WebSocket connection
     /// - Connection is closed or lost
-    pub async fn subscribe_book(
+    async fn subscribe_book(
         &mut self,
         pair: &str,
         depth: Option<u32>,
     ) -> Result<()> {
-        let subscription = SubscriptionRequest {
-            event: "subscribe".to_string(),
-            pair: vec![pair.to_string()],
-            subscription: crate::kraken::types::SubscriptionDetails {
-                name: "book".to_string(),
-                depth,
-                interval: None,
-            },
-        };
-
-        let message = serde_json::to_string(&subscription)
+        let message = self.subscription_message("book", pair, depth, None)
             .context("Failed to serialize subscription request: invalid subscription data")?;
+        self.events.set_depth(depth);
 
         self.write
             .send(Message::Text(message))
@@ -116,12 +389,6 @@ impl KrakenConnection {
         Ok(())
     }
 
-    /// Subscribe to the book channel for ZEC/USD pair (default configuration)
-    pub async fn subscribe_zec_usd(&mut self) -> Result<()> {
-        self.subscribe_book(DEFAULT_TRADING_PAIR, Some(DEFAULT_BOOK_DEPTH))
-            .await
-    }
-
     /// Subscribe to the OHLC (candlestick) channel for a trading pair
     /// 
     /// # Arguments
@@ -135,22 +402,12 @@ impl KrakenConnection {
     /// - Subscription request cannot be serialized
     /// - Message cannot be sent over the WebSocket connection
     /// - Connection is closed or lost
-    pub async fn subscribe_ohlc(
+    async fn subscribe_ohlc(
         &mut self,
         pair: &str,
         interval: u32,
     ) -> Result<()> {
-        let subscription = SubscriptionRequest {
-            event: "subscribe".to_string(),
-            pair: vec![pair.to_string()],
-            subscription: crate::kraken::types::SubscriptionDetails {
-                name: "ohlc".to_string(),
-                depth: None,
-                interval: Some(interval),
-            },
-        };
-
-        let message = serde_json::to_string(&subscription)
+        let message = self.subscription_message("ohlc", pair, None, Some(interval))
             .context("Failed to serialize OHLC subscription request: invalid subscription data")?;
 
         self.write
@@ -161,124 +418,69 @@ impl KrakenConnection {
         Ok(())
     }
 
-    /// Receive the next message from the WebSocket
+    /// Subscribe to the trade channel for a trading pair
     /// 
     /// # Errors
     /// 
     /// Returns an error if:
-    /// - WebSocket connection error occurs
-    /// - Subscription status contains an error message from Kraken
-    /// - Message is malformed and cannot be parsed (for critical messages)
-    /// - Pong response cannot be sent
-    pub async fn next_message(&mut self) -> Result<Option<KrakenMessage>> {
-        match self.read.next().await {
-            Some(Ok(Message::Text(text))) => {
-                // Validate that the text is valid JSON first
-                let json_value: serde_json::Value = serde_json::from_str(&text)
-                    .with_context(|| format!(
-                        "Received malformed JSON message from Kraken: {}",
-                        if text.len() > 200 { format!("{}...", &text[..200]) } else { text.clone() }
-                    ))?;
-
-                // Try to parse as subscription status first
-                if let Ok(status) = serde_json::from_value::<SubscriptionStatus>(json_value.clone()) {
-                    // Check for subscription errors
-                    if let Some(error_msg) = &status.errorMessage {
-                        bail!(
-                            "Kraken subscription error: {} (event: {}, status: {})",
-                            error_msg,
-                            status.event,
-                            status.status
-                        );
-                    }
-                    
-                    // Check if subscription was rejected
-                    if status.status == "error" {
-                        bail!(
-                            "Kraken subscription rejected: {} (event: {})",
-                            status.errorMessage.as_deref().unwrap_or("Unknown error"),
-                            status.event
-                        );
-                    }
-                    
-                    return Ok(Some(KrakenMessage::SubscriptionStatus(status)));
-                }
+    /// - Subscription request cannot be serialized
+    /// - Message cannot be sent over the WebSocket connection
+    /// - Connection is closed or lost
+    async fn subscribe_trade(&mut self, pair: &str) -> Result<()> {
+        let message = self.subscription_message(TRADE_CHANNEL, pair, None, None)
+            .context("Failed to serialize trade subscription request: invalid subscription data")?;
 
-                // Try to parse as array message (could be book or OHLC)
-                // Distinguish by checking the channel name (arr[2])
-                if let Some(arr) = json_value.as_array() {
-                    if arr.len() >= 3 {
-                        if let Some(channel_name) = arr[2].as_str() {
-                            if channel_name.starts_with("ohlc") {
-                                // OHLC message
-                                if let Ok(ohlc_msg) = serde_json::from_value::<OhlcMessage>(json_value.clone()) {
-                                    return Ok(Some(KrakenMessage::Ohlc(ohlc_msg)));
-                                }
-                            } else if channel_name.starts_with("book") {
-                                // Book message
-                                if let Ok(book_msg) = serde_json::from_value::<BookMessage>(json_value.clone()) {
-                                    return Ok(Some(KrakenMessage::Book(book_msg)));
-                                }
-                            }
-                        }
-                    }
-                }
+        self.write
+            .send(Message::Text(message))
+            .await
+            .context("Failed to send trade subscription request: connection may be closed")?;
 
-                // If we can't parse it as a known message type, log and return None
-                // This allows the system to continue processing other messages
-                // Skip logging heartbeat messages
-                if !text.contains("\"event\":\"heartbeat\"") {
-                    eprintln!(
-                        "Warning: Received unparseable message from Kraken (not subscription, book, or ohlc): {}",
-                        if text.len() > 200 { format!("{}...", &text[..200]) } else { text }
-                    );
-                }
-                Ok(None)
+        Ok(())
+    }
+
+    /// Wait for the next normalized event
+    /// 
+    /// One Kraken message may yield several events (a buffered snapshot followed
+    /// by the delta that completed it), so extra events are queued.
+    /// 
+    /// With a ping interval set, pings are sent from here while waiting, and a
+    /// ping still unanswered when the next is due ends the connection with an
+    /// error so the feed reconnects. Pongs, heartbeats and held snapshot frames
+    /// yield `BookEvent::Heartbeat`.
+    async fn next_book_event(&mut self) -> Result<BookEvent> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
             }
-            Some(Ok(Message::Close(close_frame))) => {
-                if let Some(frame) = close_frame {
-                    eprintln!(
-                        "WebSocket closed by server: code={:?}, reason={:?}",
-                        frame.code,
-                        frame.reason
-                    );
-                } else {
-                    eprintln!("WebSocket closed by server (no close frame)");
+            let ping_at = self.next_ping_at;
+            let flush_at = self.events.flush_deadline();
+            let message = tokio::select! {
+                message = self.next_message() => message?,
+                _ = tokio::time::sleep_until(ping_at.unwrap_or_else(Instant::now)), if ping_at.is_some() => {
+                    self.send_ping().await?;
+                    self.next_ping_at = self.ping_interval.map(|interval| Instant::now() + interval);
+                    continue;
                 }
-                Ok(Some(KrakenMessage::Close))
-            }
-            Some(Ok(Message::Ping(data))) => {
-                // Respond to ping with pong to keep connection alive
-                self.write
-                    .send(Message::Pong(data))
-                    .await
-                    .context("Failed to send pong response: connection may be closed")?;
-                Ok(None)
-            }
-            Some(Ok(_)) => {
-                // Ignore other message types (Binary, Pong, etc.)
-                Ok(None)
-            }
-            Some(Err(e)) => {
-                Err(anyhow::anyhow!(
-                    "WebSocket connection error: {}. Connection may be lost or network issue occurred",
-                    e
-                ))
+                _ = tokio::time::sleep_until(flush_at.unwrap_or_else(Instant::now)), if flush_at.is_some() => {
+                    self.pending.extend(self.events.flush());
+                    continue;
+                }
+            };
+            match message {
+                Some(KrakenMessage::Pong(reqid)) => {
+                    self.handle_pong(reqid);
+                    self.pending.extend(self.events.flush());
+                }
+                Some(message) => self.pending.extend(self.events.map(message)),
+                None => {}
             }
-            None => {
-                // Stream ended (connection closed)
-                eprintln!("WebSocket stream ended (connection closed)");
-                Ok(Some(KrakenMessage::Close))
+            if self.pending.is_empty() {
+                return Ok(BookEvent::Heartbeat);
             }
         }
     }
 
-    /// Close the connection gracefully
-    /// 
-    /// # Errors
-    /// 
-    /// Returns an error if the close frame cannot be sent
-    pub async fn close(&mut self) -> Result<()> {
+    async fn close(&mut self) -> Result<()> {
         self.write
             .close()
             .await
@@ -293,45 +495,240 @@ pub enum KrakenMessage {
     SubscriptionStatus(SubscriptionStatus),
     Book(BookMessage),
     Ohlc(OhlcMessage),
+    Trade(TradeMessage),
+    /// v2 reply to a `method` request such as `subscribe`
+    MethodResponseV2(MethodResponseV2),
+    /// v2 book snapshot or update, parsed by `parse_book_snapshot_v2`/`parse_book_delta_v2`
+    BookV2(serde_json::Value),
+    /// Reply to `KrakenConnection::send_ping`, with the ping's request id
+    Pong(u64),
     Close,
 }
 
-/// Reconnect with exponential backoff
-#[allow(dead_code)] // Will be used in task 7.4 for reconnection logic
-pub async fn reconnect_with_backoff(
-    client: &KrakenClient,
-    max_retries: usize,
-) -> Result<KrakenConnection> {
-    let mut retry_count = 0;
-    let mut delay = Duration::from_secs(1);
-
-    loop {
-        match client.connect().await {
-            Ok(conn) => {
-                return Ok(conn);
+/// Maps Kraken messages into normalized `BookEvent`s
+/// 
+/// Kraken may split the initial snapshot of a deep subscription (e.g. book-1000)
+/// across several frames, so snapshot frames are held and emitted as a single
+/// `BookEvent::Snapshot` once the subscribed depth is reached on both sides, or
+/// ahead of the next delta or non-book message. `flush_deadline` bounds how long
+/// a short snapshot of a quiet book is held.
+#[derive(Debug, Default)]
+pub struct KrakenEventMapper {
+    snapshot_frames: SnapshotAssembler,
+    /// Subscribed book depth, if known
+    depth: Option<usize>,
+    /// When the first held snapshot frame arrived
+    snapshot_started: Option<Instant>,
+}
+
+impl KrakenEventMapper {
+    /// Set the subscribed depth, at which a held snapshot is complete
+    pub fn set_depth(&mut self, depth: Option<u32>) {
+        self.depth = depth.map(|depth| depth as usize);
+    }
+
+    /// When the held snapshot should be emitted even if no more frames arrive
+    pub fn flush_deadline(&self) -> Option<Instant> {
+        self.snapshot_started.map(|started| started + SNAPSHOT_FLUSH_TIMEOUT)
+    }
+
+    /// Emit the held snapshot, if any
+    pub fn flush(&mut self) -> Option<BookEvent> {
+        self.snapshot_started = None;
+        self.snapshot_frames.take().map(BookEvent::Snapshot)
+    }
+
+    /// Map one message into the events it carries, possibly none
+    /// 
+    /// Unusable book, OHLC and trade messages are logged here and mapped to
+    /// `BookEvent::Malformed`.
+    pub fn map(&mut self, message: KrakenMessage) -> Vec<BookEvent> {
+        let events = match message {
+            KrakenMessage::Book(book_msg) => return self.map_book(book_msg),
+            KrakenMessage::BookV2(value) => return self.map_book_v2(&value),
+            KrakenMessage::Ohlc(OhlcMessage::ArrayFormat(arr)) => match arr.get(1).map(parse_ohlc_data) {
+                Some(Ok(candle)) => vec![BookEvent::Candle(candle)],
+                Some(Err(e)) => {
+                    tracing::warn!(error = %e, "Error parsing OHLC data");
+                    vec![BookEvent::Malformed]
+                }
+                None => Vec::new(),
+            },
+            KrakenMessage::Trade(trade_msg) => match trade_msg.trades() {
+                Ok(trades) => trades.into_iter().map(|trade| BookEvent::Trade(executed_trade(trade))).collect(),
+                Err(e) => {
+                    tracing::warn!(error = %e, "Error parsing trades");
+                    vec![BookEvent::Malformed]
+                }
+            },
+            KrakenMessage::SubscriptionStatus(status) => status.channel_status().map(BookEvent::Status).into_iter().collect(),
+            KrakenMessage::MethodResponseV2(response) => response.channel_status().map(BookEvent::Status).into_iter().collect(),
+            KrakenMessage::Pong(_) => Vec::new(),
+            KrakenMessage::Close => vec![BookEvent::Close],
+        };
+        // Snapshot frames are never interleaved with other messages, so the
+        // held snapshot is complete
+        self.flush().into_iter().chain(events).collect()
+    }
+
+    fn map_book(&mut self, book_msg: BookMessage) -> Vec<BookEvent> {
+        let Some(book_data) = book_msg.book_data() else {
+            FEED_DIAGNOSTICS.record_missing_book_data(&book_msg);
+            return vec![BookEvent::Malformed];
+        };
+        if book_msg.is_snapshot() {
+            self.map_snapshot_frame(parse_book_snapshot(&book_data))
+        } else {
+            self.map_delta(parse_book_delta(&book_data))
+        }
+    }
+
+    fn map_book_v2(&mut self, value: &serde_json::Value) -> Vec<BookEvent> {
+        if value.get("type").and_then(|message_type| message_type.as_str()) == Some("snapshot") {
+            self.map_snapshot_frame(parse_book_snapshot_v2(value))
+        } else {
+            self.map_delta(parse_book_delta_v2(value))
+        }
+    }
+
+    /// Hold a snapshot frame, emitting the snapshot if it is now complete
+    fn map_snapshot_frame(&mut self, frame: Result<BookSnapshot>) -> Vec<BookEvent> {
+        match frame {
+            Ok(frame) => {
+                self.snapshot_frames.push(frame);
+                self.snapshot_started.get_or_insert_with(Instant::now);
+                match self.depth {
+                    Some(depth) if self.snapshot_frames.has_depth(depth) => self.flush().into_iter().collect(),
+                    _ => Vec::new(),
+                }
             }
             Err(e) => {
-                if retry_count >= max_retries {
-                    return Err(anyhow::anyhow!(
-                        "Failed to reconnect after {} retries: {}",
-                        max_retries,
-                        e
-                    ));
-                }
+                tracing::warn!(error = %e, "Error parsing initial snapshot");
+                vec![BookEvent::Malformed]
+            }
+        }
+    }
 
-                eprintln!(
-                    "Connection failed (attempt {}/{}): {}. Retrying in {:?}...",
-                    retry_count + 1,
-                    max_retries,
-                    e,
-                    delay
-                );
-
-                sleep(delay).await;
-                retry_count += 1;
-                delay = delay * 2; // Exponential backoff
+    /// Map a delta, preceded by the held snapshot it completes
+    fn map_delta(&mut self, delta: Result<BookDelta>) -> Vec<BookEvent> {
+        let mut events: Vec<BookEvent> = self.flush().into_iter().collect();
+        match delta {
+            Ok(delta) => events.push(BookEvent::Delta(delta)),
+            Err(e) => {
+                tracing::warn!(error = %e, "Error parsing delta");
+                events.push(BookEvent::Malformed);
             }
         }
+        events
+    }
+}
+
+/// Normalize a Kraken trade, whose side the parser has checked is "b" or "s"
+fn executed_trade(trade: TradeData) -> ExecutedTrade {
+    ExecutedTrade {
+        price: trade.price,
+        volume: trade.volume,
+        time: trade.time,
+        side: if trade.side == "b" { TradeSide::Buy } else { TradeSide::Sell },
+    }
+}
+
+/// Parse a text frame from Kraken into a typed message
+/// 
+/// Returns `None` for messages of no interest (heartbeats, unknown channels).
+/// Pongs are returned as `KrakenMessage::Pong`.
+/// 
+/// # Errors
+/// 
+/// Returns an error if the frame isn't JSON, or is a subscription status
+/// carrying an error (a `KrakenError`).
+pub fn parse_message(text: String) -> Result<Option<KrakenMessage>> {
+    // Validate that the text is valid JSON first
+    let json_value: serde_json::Value = serde_json::from_str(&text)
+        .with_context(|| format!(
+            "Received malformed JSON message from Kraken: {}",
+            if text.len() > 200 { format!("{}...", &text[..200]) } else { text.clone() }
+        ))?;
+
+    if let Some(reqid) = pong_reqid(&json_value) {
+        return Ok(Some(KrakenMessage::Pong(reqid)));
+    }
+
+    // v2 frames are objects keyed by `method` (replies) or `channel` (data);
+    // v1 ones are `event` objects or arrays
+    if json_value.get("method").is_some() || json_value.get("channel").is_some() {
+        return parse_message_v2(json_value);
+    }
+
+    // Try to parse as subscription status first
+    if let Ok(status) = serde_json::from_value::<SubscriptionStatus>(json_value.clone()) {
+        // Errors are classified so the caller can decide whether to back off,
+        // retry or give up; the `KrakenError` is recoverable via `downcast_ref`
+        if status.errorMessage.is_some() || status.status == "error" {
+            let message = status.errorMessage.as_deref().unwrap_or("Unknown error");
+            return Err(anyhow::Error::new(KrakenError::new(message)).context(format!(
+                "Kraken subscription rejected (event: {}, status: {})",
+                status.event,
+                status.status
+            )));
+        }
+        
+        return Ok(Some(KrakenMessage::SubscriptionStatus(status)));
+    }
+
+    // Try to parse as array message (could be book, OHLC or trade)
+    // Distinguish by checking the channel name (arr[2])
+    if let Some(arr) = json_value.as_array() {
+        if arr.len() >= 3 {
+            if let Some(channel_name) = arr[2].as_str() {
+                if channel_name.starts_with("ohlc") {
+                    // OHLC message
+                    if let Ok(ohlc_msg) = serde_json::from_value::<OhlcMessage>(json_value.clone()) {
+                        return Ok(Some(KrakenMessage::Ohlc(ohlc_msg)));
+                    }
+                } else if channel_name.starts_with("book") {
+                    // Book message
+                    if let Ok(book_msg) = serde_json::from_value::<BookMessage>(json_value.clone()) {
+                        return Ok(Some(KrakenMessage::Book(book_msg)));
+                    }
+                } else if channel_name == TRADE_CHANNEL {
+                    if let Ok(trade_msg) = serde_json::from_value::<TradeMessage>(json_value.clone()) {
+                        return Ok(Some(KrakenMessage::Trade(trade_msg)));
+                    }
+                }
+            }
+        }
+    }
+
+    // If we can't parse it as a known message type, log and return None
+    // This allows the system to continue processing other messages
+    // Skip logging heartbeat messages
+    if !text.contains("\"event\":\"heartbeat\"") {
+        tracing::warn!(
+            message = %if text.len() > 200 { format!("{}...", &text[..200]) } else { text },
+            "Received unparseable message from Kraken (not subscription, book, ohlc or trade)"
+        );
+    }
+    Ok(None)
+}
+
+/// Parse a v2 frame into a typed message
+/// 
+/// Heartbeats, status updates and channels other than `book` return `None`.
+fn parse_message_v2(value: serde_json::Value) -> Result<Option<KrakenMessage>> {
+    if value.get("method").is_some() {
+        let response: MethodResponseV2 = serde_json::from_value(value)
+            .context("Received malformed method response from Kraken")?;
+        if !response.success {
+            let message = response.error.as_deref().unwrap_or("Unknown error");
+            return Err(anyhow::Error::new(KrakenError::new(message))
+                .context(format!("Kraken request rejected (method: {})", response.method)));
+        }
+        return Ok(Some(KrakenMessage::MethodResponseV2(response)));
+    }
+    match value.get("channel").and_then(|channel| channel.as_str()) {
+        Some("book") => Ok(Some(KrakenMessage::BookV2(value))),
+        _ => Ok(None),
     }
 }
 
@@ -339,6 +736,7 @@ pub async fn reconnect_with_backoff(
 mod tests {
     use super::*;
     use crate::kraken::types::SubscriptionStatus;
+    use tokio_tungstenite::tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
 
     #[tokio::test]
     #[ignore] // Requires network connection
@@ -386,5 +784,280 @@ mod tests {
         assert_eq!(status.errorMessage, None);
         assert_eq!(status.channel_id, Some(123));
     }
-}
 
+    fn message(json: &str) -> KrakenMessage {
+        parse_message(json.to_string()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_message_maps_to_snapshot_event() {
+        let mut events = KrakenEventMapper::default();
+
+        // Snapshot frames are held until the first delta when the depth is unknown
+        let snapshot = message(r#"[42, {"as": [["101.0", "1.5", "1.0"]], "bs": [["99.0", "2.0", "1.0"], ["98.0", "1.0", "1.0"]]}, "book-10", "BTC/USD"]"#);
+        assert!(events.map(snapshot).is_empty());
+
+        let delta = message(r#"[42, {"b": [["99.5", "1.0", "2.0"]], "c": "123"}, "book-10", "BTC/USD"]"#);
+        match events.map(delta).as_slice() {
+            [BookEvent::Snapshot(snapshot), BookEvent::Delta(delta)] => {
+                assert_eq!((snapshot.bids.len(), snapshot.asks.len()), (2, 1));
+                assert_eq!(delta.bids.len(), 1);
+                assert_eq!(delta.checksum, Some(123));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        // Later deltas map on their own
+        let delta = message(r#"[42, {"a": [["101.0", "0.0", "3.0"]]}, "book-10", "BTC/USD"]"#);
+        assert!(matches!(events.map(delta).as_slice(), [BookEvent::Delta(_)]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_held_snapshot_is_flushed_without_a_delta() {
+        let first = r#"[42, {"as": [["101.0", "1.5", "1.0"]], "bs": [["99.0", "2.0", "1.0"]]}, "book-2", "BTC/USD"]"#;
+        let second = r#"[42, {"as": [["102.0", "1.0", "1.0"]], "bs": [["98.0", "1.0", "1.0"]]}, "book-2", "BTC/USD"]"#;
+
+        // Reaching the subscribed depth on both sides completes the snapshot
+        let mut events = KrakenEventMapper::default();
+        events.set_depth(Some(2));
+        assert!(events.map(message(first)).is_empty());
+        match events.map(message(second)).as_slice() {
+            [BookEvent::Snapshot(snapshot)] => assert_eq!((snapshot.bids.len(), snapshot.asks.len()), (2, 2)),
+            other => panic!("unexpected events: {:?}", other),
+        }
+        assert_eq!(events.flush_deadline(), None);
+
+        // So does any non-book message
+        let mut events = KrakenEventMapper::default();
+        assert!(events.map(message(first)).is_empty());
+        let ack = message(r#"{"event": "subscriptionStatus", "status": "subscribed", "channelID": 42,
+            "pair": "BTC/USD", "subscription": {"name": "book", "depth": 2}}"#);
+        assert!(matches!(events.map(ack).as_slice(), [BookEvent::Snapshot(_), BookEvent::Status(_)]));
+
+        // A quiet book's short snapshot is due a fixed time after its first frame
+        let mut events = KrakenEventMapper::default();
+        events.set_depth(Some(10));
+        assert!(events.map(message(first)).is_empty());
+        let deadline = Instant::now() + SNAPSHOT_FLUSH_TIMEOUT;
+        assert_eq!(events.flush_deadline(), Some(deadline));
+        tokio::time::advance(SNAPSHOT_FLUSH_TIMEOUT / 2).await;
+        assert!(events.map(message(second)).is_empty());
+        assert_eq!(events.flush_deadline(), Some(deadline));
+        match events.flush() {
+            Some(BookEvent::Snapshot(snapshot)) => assert_eq!((snapshot.bids.len(), snapshot.asks.len()), (2, 2)),
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(events.flush().is_none());
+        assert_eq!(events.flush_deadline(), None);
+    }
+
+    /// Serve one WebSocket connection that sends `frames`, then answers the
+    /// first `pongs` pings and reads until closed
+    async fn mock_kraken(frames: Vec<&'static str>, mut pongs: usize) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for frame in frames {
+                socket.send(Message::Text(frame.to_string())).await.unwrap();
+            }
+            while let Some(Ok(frame)) = socket.next().await {
+                let Ok(request) = serde_json::from_str::<serde_json::Value>(frame.to_text().unwrap_or_default()) else {
+                    continue;
+                };
+                if request["event"] == "ping" && pongs > 0 {
+                    pongs -= 1;
+                    let pong = serde_json::json!({ "event": "pong", "reqid": request["reqid"] });
+                    socket.send(Message::Text(pong.to_string())).await.unwrap();
+                }
+            }
+        });
+        url
+    }
+
+    /// Handshake callback that records the offered extensions and accepts permessage-deflate
+    struct AcceptDeflate<'a>(&'a mut Option<String>);
+
+    impl Callback for AcceptDeflate<'_> {
+        fn on_request(self, request: &Request, mut response: Response) -> std::result::Result<Response, ErrorResponse> {
+            *self.0 = request.headers().get(SEC_WEBSOCKET_EXTENSIONS).map(|value| value.to_str().unwrap().to_string());
+            if self.0.as_deref().is_some_and(accepts_deflate) {
+                response.headers_mut().insert(SEC_WEBSOCKET_EXTENSIONS, HeaderValue::from_static(PERMESSAGE_DEFLATE));
+            }
+            Ok(response)
+        }
+    }
+
+    /// Serve one WebSocket connection that reports the extensions offered in its
+    /// handshake, then sends `frames`, compressed if permessage-deflate was offered
+    async fn mock_compressing_kraken(frames: Vec<&'static str>) -> (String, tokio::sync::oneshot::Receiver<Option<String>>) {
+        use crate::kraken::deflate::compress_message;
+        use flate2::{Compress, Compression};
+        use tokio_tungstenite::tungstenite::protocol::frame::coding::{Data, OpCode};
+        use tokio_tungstenite::tungstenite::protocol::frame::Frame;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let (offered_tx, offered_rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut offered = None;
+            let mut socket = tokio_tungstenite::accept_hdr_async(stream, AcceptDeflate(&mut offered)).await.unwrap();
+            let compress = offered.as_deref().is_some_and(accepts_deflate);
+            offered_tx.send(offered).unwrap();
+
+            let mut compressor = Compress::new(Compression::default(), false);
+            for frame in frames {
+                let message = if compress {
+                    let payload = compress_message(&mut compressor, frame.as_bytes());
+                    let mut frame = Frame::message(payload, OpCode::Data(Data::Text), true);
+                    frame.header_mut().rsv1 = true;
+                    Message::Frame(frame)
+                } else {
+                    Message::Text(frame.to_string())
+                };
+                socket.send(message).await.unwrap();
+            }
+            while let Some(Ok(_)) = socket.next().await {}
+        });
+        (url, offered_rx)
+    }
+
+    #[tokio::test]
+    async fn test_compressed_frames_are_parsed() {
+        for compression in [false, true] {
+            let (url, offered) = mock_compressing_kraken(vec![
+                r#"[42, {"as": [["101.0", "1.5", "1.0"], ["102.0", "0.5", "1.0"]], "bs": [["99.0", "2.0", "1.0"]]}, "book-10", "BTC/USD"]"#,
+                r#"[42, {"b": [["99.5", "1.0", "2.0"]], "c": "123"}, "book-10", "BTC/USD"]"#,
+            ]).await;
+            let mut connection = KrakenClient::with_url(url)
+                .with_compression(compression)
+                .connect()
+                .await
+                .unwrap();
+
+            // The flag decides whether the handshake offers compression
+            assert_eq!(offered.await.unwrap().as_deref(), compression.then_some(PERMESSAGE_DEFLATE));
+
+            assert!(matches!(connection.next_book_event().await.unwrap(), BookEvent::Heartbeat));
+            match connection.next_book_event().await.unwrap() {
+                BookEvent::Snapshot(snapshot) => assert_eq!((snapshot.bids.len(), snapshot.asks.len()), (1, 2)),
+                other => panic!("unexpected event: {:?}", other),
+            }
+            match connection.next_book_event().await.unwrap() {
+                BookEvent::Delta(delta) => assert_eq!(delta.checksum, Some(123)),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_missed_pong_ends_the_connection() {
+        let url = mock_kraken(Vec::new(), 1).await;
+        let mut connection = KrakenClient::with_url(url)
+            .with_ping_interval(Duration::from_secs(30))
+            .connect()
+            .await
+            .unwrap();
+
+        // The first ping is answered; the mock never answers the second
+        assert!(matches!(connection.next_book_event().await.unwrap(), BookEvent::Heartbeat));
+        let error = connection.next_book_event().await.unwrap_err();
+        assert!(error.to_string().contains("No pong received for ping 2"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_every_frame_yields_an_event() {
+        let url = mock_kraken(vec![
+            r#"{"event": "heartbeat"}"#,
+            r#"[42, {"as": [["101.0", "1.5", "1.0"]], "bs": [["99.0", "2.0", "1.0"]]}, "book-10", "BTC/USD"]"#,
+            r#"{"event": "pong", "reqid": 1}"#,
+        ], 0).await;
+        let mut connection = KrakenClient::with_url(url).connect().await.unwrap();
+
+        // Heartbeats and held snapshot frames carry no book data but show liveness
+        assert!(matches!(connection.next_book_event().await.unwrap(), BookEvent::Heartbeat));
+        assert!(matches!(connection.next_book_event().await.unwrap(), BookEvent::Heartbeat));
+        assert!(matches!(connection.next_book_event().await.unwrap(), BookEvent::Snapshot(_)));
+    }
+
+    #[test]
+    fn test_v2_messages_map_to_events() {
+        let mut events = KrakenEventMapper::default();
+        events.set_depth(Some(2));
+
+        let ack = message(r#"{"method": "subscribe", "result": {"channel": "book", "depth": 2, "snapshot": true,
+            "symbol": "MATIC/USD"}, "success": true, "time_in": "2023-10-06T17:35:55.000000Z"}"#);
+        match events.map(ack).as_slice() {
+            [BookEvent::Status(status)] => {
+                assert_eq!(status.channel, "book");
+                assert!(status.subscribed);
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        let snapshot = message(r#"{"channel": "book", "type": "snapshot", "data": [{"symbol": "MATIC/USD",
+            "bids": [{"price": 0.5666, "qty": 4831.75}, {"price": 0.5665, "qty": 6658.22}],
+            "asks": [{"price": 0.5668, "qty": 4410.79}, {"price": 0.5669, "qty": 4655.40}], "checksum": 2439117997}]}"#);
+        match events.map(snapshot).as_slice() {
+            [BookEvent::Snapshot(snapshot)] => assert_eq!((snapshot.bids.len(), snapshot.asks.len()), (2, 2)),
+            other => panic!("unexpected events: {:?}", other),
+        }
+        let update = message(r#"{"channel": "book", "type": "update", "data": [{"symbol": "MATIC/USD",
+            "bids": [{"price": 0.5657, "qty": 1098.39}], "asks": [], "checksum": 2114181697,
+            "timestamp": "2023-10-06T17:35:55.440295Z"}]}"#);
+        match events.map(update).as_slice() {
+            [BookEvent::Delta(delta)] => assert_eq!((delta.bids.len(), delta.asks.len()), (1, 0)),
+            other => panic!("unexpected events: {:?}", other),
+        }
+        let bad_update = message(r#"{"channel": "book", "type": "update", "data": [{"bids": "oops"}]}"#);
+        assert!(matches!(events.map(bad_update).as_slice(), [BookEvent::Malformed]));
+
+        // Heartbeats and unparsed channels are skipped
+        assert!(parse_message(r#"{"channel": "heartbeat"}"#.to_string()).unwrap().is_none());
+        assert!(parse_message(r#"{"channel": "trade", "type": "update", "data": []}"#.to_string()).unwrap().is_none());
+
+        // Rejections are Kraken errors
+        let error = parse_message(r#"{"method": "subscribe", "error": "Currency pair not supported", "success": false}"#.to_string()).unwrap_err();
+        assert!(error.downcast_ref::<KrakenError>().is_some());
+    }
+
+    #[test]
+    fn test_other_messages_map_to_events() {
+        let mut events = KrakenEventMapper::default();
+
+        let ack = message(r#"{"event": "subscriptionStatus", "status": "subscribed", "channelID": 42,
+            "pair": "BTC/USD", "subscription": {"name": "book", "depth": 10}}"#);
+        match events.map(ack).as_slice() {
+            [BookEvent::Status(status)] => {
+                assert_eq!(status.channel, "book");
+                assert!(status.subscribed);
+                assert_eq!(status.channel_id, Some(42));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+
+        let candle = message(r#"[43, ["1700000000.0", "1700000060.0", "100.0", "101.0", "99.0", "100.5", "100.2", "3.5", 12], "ohlc-1", "BTC/USD"]"#);
+        assert!(matches!(events.map(candle).as_slice(), [BookEvent::Candle(_)]));
+        let trades = message(r#"[44, [["100.1", "0.5", "1700000001.5", "b", "m", ""], ["100.0", "0.2", "1700000002.5", "s", "l", ""]], "trade", "BTC/USD"]"#);
+        match events.map(trades).as_slice() {
+            [BookEvent::Trade(first), BookEvent::Trade(second)] => {
+                assert_eq!((first.price, second.price), (100.1, 100.0));
+                assert_eq!((first.side, second.side), (TradeSide::Buy, TradeSide::Sell));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+        let pong = message(r#"{"event": "pong", "reqid": 3}"#);
+        assert!(matches!(pong, KrakenMessage::Pong(3)));
+        assert!(events.map(pong).is_empty());
+        let bad_delta = message(r#"[42, {"b": "oops"}, "book-10", "BTC/USD"]"#);
+        assert!(matches!(events.map(bad_delta).as_slice(), [BookEvent::Malformed]));
+        assert!(matches!(events.map(KrakenMessage::Close).as_slice(), [BookEvent::Close]));
+
+        // Kraken errors carry their kind through to the retry policy
+        let error = parse_message(r#"{"event": "subscriptionStatus", "status": "error", "errorMessage": "Currency pair not supported"}"#.to_string()).unwrap_err();
+        assert_eq!(KrakenClient::new().retry_delay(&error), None);
+        assert_eq!(KrakenClient::new().retry_delay(&anyhow::anyhow!("connection reset")), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,333 @@
+//! permessage-deflate (RFC 7692) decompression beneath tungstenite
+//!
+//! tungstenite doesn't implement the extension and rejects frames with RSV1
+//! set. `InflateStream` sits between the socket and tungstenite: it passes the
+//! handshake response through, then rewrites each compressed message from the
+//! server into one uncompressed frame before tungstenite parses it. Frames we
+//! send are never compressed, which the extension allows.
+
+use flate2::{Decompress, FlushDecompress, Status};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Extension offered in the handshake's `Sec-WebSocket-Extensions` header
+pub const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+
+/// Largest frame or inflated message accepted (tungstenite's own message limit)
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// Bytes read from the socket at a time
+const READ_CHUNK: usize = 8 * 1024;
+
+/// Tail of every compressed message, stripped by the sender
+const DEFLATE_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+const FIN: u8 = 0x80;
+const RSV1: u8 = 0x40;
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+
+/// Whether a handshake response's `Sec-WebSocket-Extensions` value accepts permessage-deflate
+pub fn accepts_deflate(extensions: &str) -> bool {
+    extensions
+        .split(',')
+        .filter_map(|extension| extension.split(';').next())
+        .any(|name| name.trim().eq_ignore_ascii_case(PERMESSAGE_DEFLATE))
+}
+
+/// Socket that inflates the server's permessage-deflate messages
+pub struct InflateStream<S> {
+    inner: S,
+    /// Forward bytes untouched, for connections that didn't offer compression
+    passthrough: bool,
+    /// Whether the HTTP handshake response has been forwarded
+    handshake_done: bool,
+    /// Bytes read from the socket that don't yet make up a whole frame
+    raw: Vec<u8>,
+    /// Bytes ready for tungstenite, starting at `out_pos`
+    out: Vec<u8>,
+    out_pos: usize,
+    /// Opcode and payload so far of a compressed message split across frames
+    message: Option<(u8, Vec<u8>)>,
+    /// Kept across messages, since the server may refer back to earlier ones
+    inflater: Decompress,
+}
+
+impl<S> InflateStream<S> {
+    /// Wrap a socket whose handshake offers permessage-deflate
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            passthrough: false,
+            handshake_done: false,
+            raw: Vec::new(),
+            out: Vec::new(),
+            out_pos: 0,
+            message: None,
+            inflater: Decompress::new(false),
+        }
+    }
+
+    /// Wrap a socket without inflating anything
+    pub fn passthrough(inner: S) -> Self {
+        Self {
+            passthrough: true,
+            ..Self::new(inner)
+        }
+    }
+
+    /// Move every complete frame in `raw` to `out`, inflating compressed messages
+    fn process(&mut self) -> io::Result<()> {
+        if !self.handshake_done {
+            let Some(end) = self.raw.windows(4).position(|window| window == b"\r\n\r\n") else {
+                return Ok(());
+            };
+            self.out.extend(self.raw.drain(..end + 4));
+            self.handshake_done = true;
+        }
+
+        let mut consumed = 0;
+        while let Some(header) = frame_header(&self.raw[consumed..])? {
+            let frame = &self.raw[consumed..consumed + header.len + header.payload_len];
+            consumed += frame.len();
+
+            let opcode = frame[0] & 0x0f;
+            let starts_message = frame[0] & RSV1 != 0 && matches!(opcode, OPCODE_TEXT | OPCODE_BINARY);
+            let continues_message = opcode == OPCODE_CONTINUATION && self.message.is_some();
+            if !starts_message && !continues_message {
+                // Control frames and uncompressed messages are tungstenite's to check
+                self.out.extend_from_slice(frame);
+                continue;
+            }
+
+            let (_, payload) = match &mut self.message {
+                Some(message) if continues_message => message,
+                message => message.insert((opcode, Vec::new())),
+            };
+            let start = payload.len();
+            payload.extend_from_slice(&frame[header.len..]);
+            if let Some(key) = header.mask {
+                for (i, byte) in payload[start..].iter_mut().enumerate() {
+                    *byte ^= key[i % 4];
+                }
+            }
+            if payload.len() > MAX_MESSAGE_SIZE {
+                return Err(invalid_data("compressed message too large"));
+            }
+
+            if frame[0] & FIN != 0 {
+                let (opcode, payload) = self.message.take().unwrap_or_default();
+                let inflated = inflate(&mut self.inflater, payload)?;
+                push_frame(&mut self.out, opcode, &inflated);
+            }
+        }
+        self.raw.drain(..consumed);
+        Ok(())
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for InflateStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.passthrough {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+
+        loop {
+            if this.out_pos < this.out.len() {
+                let len = buf.remaining().min(this.out.len() - this.out_pos);
+                buf.put_slice(&this.out[this.out_pos..this.out_pos + len]);
+                this.out_pos += len;
+                if this.out_pos == this.out.len() {
+                    this.out.clear();
+                    this.out_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+
+            this.process()?;
+            if !this.out.is_empty() {
+                continue;
+            }
+
+            let mut chunk = [0; READ_CHUNK];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+            if chunk.filled().is_empty() {
+                // Hand over a trailing partial frame so tungstenite reports the truncation
+                this.out.append(&mut this.raw);
+                if this.out.is_empty() {
+                    return Poll::Ready(Ok(()));
+                }
+                continue;
+            }
+            this.raw.extend_from_slice(chunk.filled());
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for InflateStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Layout of a frame's header
+struct FrameHeader {
+    len: usize,
+    payload_len: usize,
+    mask: Option<[u8; 4]>,
+}
+
+/// Header of the frame starting `bytes`, once all of the frame has arrived
+fn frame_header(bytes: &[u8]) -> io::Result<Option<FrameHeader>> {
+    let [_, second, rest @ ..] = bytes else {
+        return Ok(None);
+    };
+    let (mut header_len, payload_len) = match (second & 0x7f, rest) {
+        (126, [a, b, ..]) => (4, u16::from_be_bytes([*a, *b]) as u64),
+        (127, [a, b, c, d, e, f, g, h, ..]) => (10, u64::from_be_bytes([*a, *b, *c, *d, *e, *f, *g, *h])),
+        (126 | 127, _) => return Ok(None),
+        (len, _) => (2, len as u64),
+    };
+    if payload_len > MAX_MESSAGE_SIZE as u64 {
+        return Err(invalid_data("frame too large"));
+    }
+
+    let mask = if second & 0x80 != 0 {
+        let Some(key) = bytes.get(header_len..header_len + 4) else {
+            return Ok(None);
+        };
+        header_len += 4;
+        Some([key[0], key[1], key[2], key[3]])
+    } else {
+        None
+    };
+
+    if bytes.len() < header_len + payload_len as usize {
+        return Ok(None);
+    }
+    Ok(Some(FrameHeader {
+        len: header_len,
+        payload_len: payload_len as usize,
+        mask,
+    }))
+}
+
+/// Inflate one message's payload, continuing the stream of earlier messages
+fn inflate(inflater: &mut Decompress, mut payload: Vec<u8>) -> io::Result<Vec<u8>> {
+    payload.extend_from_slice(&DEFLATE_TRAILER);
+    let start = inflater.total_in();
+    let mut inflated = Vec::with_capacity(payload.len() * 4);
+    loop {
+        if inflated.len() == inflated.capacity() {
+            inflated.reserve(payload.len().max(READ_CHUNK));
+        }
+        let (before_in, before_out) = (inflater.total_in(), inflater.total_out());
+        let consumed = (before_in - start) as usize;
+        let status = inflater
+            .decompress_vec(&payload[consumed..], &mut inflated, FlushDecompress::Sync)
+            .map_err(|e| invalid_data(&format!("invalid compressed message: {}", e)))?;
+        if inflated.len() > MAX_MESSAGE_SIZE {
+            return Err(invalid_data("inflated message too large"));
+        }
+
+        let consumed = (inflater.total_in() - start) as usize;
+        let stalled = inflater.total_in() == before_in && inflater.total_out() == before_out;
+        let drained = consumed == payload.len() && inflated.len() < inflated.capacity();
+        if status == Status::StreamEnd || drained || stalled {
+            return Ok(inflated);
+        }
+    }
+}
+
+/// Append a single unmasked, uncompressed frame holding `payload`
+fn push_frame(out: &mut Vec<u8>, opcode: u8, payload: &[u8]) {
+    out.push(FIN | opcode);
+    match payload.len() {
+        len @ 0..=125 => out.push(len as u8),
+        len @ 126..=0xffff => {
+            out.push(126);
+            out.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            out.push(127);
+            out.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(payload);
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Compress `data` as one message's payload, the way a permessage-deflate server does
+#[cfg(test)]
+pub(crate) fn compress_message(compressor: &mut flate2::Compress, data: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::with_capacity(data.len() + 64);
+    compressor.compress_vec(data, &mut compressed, flate2::FlushCompress::Sync).unwrap();
+    assert!(compressed.ends_with(&DEFLATE_TRAILER));
+    compressed.truncate(compressed.len() - DEFLATE_TRAILER.len());
+    compressed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compress, Compression};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_accepts_deflate() {
+        assert!(accepts_deflate("permessage-deflate"));
+        assert!(accepts_deflate("x-webkit-deflate-frame, permessage-deflate; server_no_context_takeover"));
+        assert!(!accepts_deflate("x-webkit-deflate-frame"));
+        assert!(!accepts_deflate(""));
+    }
+
+    #[tokio::test]
+    async fn test_compressed_messages_are_inflated() {
+        let handshake = b"HTTP/1.1 101 Switching Protocols\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n";
+        let first = r#"[42, {"a": [["101.0", "1.5", "1.0"]]}, "book-10", "BTC/USD"]"#;
+        let second = r#"[42, {"a": [["101.0", "2.5", "2.0"]]}, "book-10", "BTC/USD"]"#;
+
+        // The second message refers back to the first, and is split around a ping
+        let mut compressor = Compress::new(Compression::default(), false);
+        let first_payload = compress_message(&mut compressor, first.as_bytes());
+        let second_payload = compress_message(&mut compressor, second.as_bytes());
+        let (head, tail) = second_payload.split_at(second_payload.len() / 2);
+        let mut sent = handshake.to_vec();
+        push_frame(&mut sent, OPCODE_TEXT, &first_payload);
+        sent[handshake.len()] |= RSV1;
+        sent.extend_from_slice(&[OPCODE_TEXT | RSV1, head.len() as u8]);
+        sent.extend_from_slice(head);
+        push_frame(&mut sent, 0x9, b"ping");
+        push_frame(&mut sent, OPCODE_CONTINUATION, tail);
+
+        // A small pipe delivers frames a few bytes at a time
+        let (mut server, client) = tokio::io::duplex(7);
+        tokio::spawn(async move {
+            server.write_all(&sent).await.unwrap();
+        });
+        let mut received = Vec::new();
+        InflateStream::new(client).read_to_end(&mut received).await.unwrap();
+
+        let mut expected = handshake.to_vec();
+        push_frame(&mut expected, OPCODE_TEXT, first.as_bytes());
+        push_frame(&mut expected, 0x9, b"ping");
+        push_frame(&mut expected, OPCODE_TEXT, second.as_bytes());
+        assert_eq!(received, expected);
+    }
+}
@@ -0,0 +1,77 @@
+//! Counters for structurally unexpected messages on the Kraken feeds
+//! 
+//! A feed that keeps sending book messages we can't extract data from would
+//! otherwise look like a quiet ticker. Every occurrence is counted, and a sample
+//! is logged with the message's shape so the cause can be diagnosed.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::kraken::types::BookMessage;
+
+/// Log the first malformed book message and then every this many
+pub const MALFORMED_LOG_EVERY: u64 = 100;
+
+/// Process-wide diagnostics shared by every Kraken task
+pub static FEED_DIAGNOSTICS: FeedDiagnostics = FeedDiagnostics::new();
+
+/// Counters of feed messages that couldn't be used
+#[derive(Debug, Default)]
+pub struct FeedDiagnostics {
+    /// Book messages without any book data object
+    malformed_book_messages: AtomicU64,
+}
+
+impl FeedDiagnostics {
+    /// Create diagnostics with every counter at zero
+    pub const fn new() -> Self {
+        Self {
+            malformed_book_messages: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a book message for which `book_data()` returned `None`
+    /// 
+    /// Logs the first occurrence and every `MALFORMED_LOG_EVERY`th one after it.
+    /// Returns whether this occurrence was logged.
+    pub fn record_missing_book_data(&self, message: &BookMessage) -> bool {
+        let count = self.malformed_book_messages.fetch_add(1, Ordering::Relaxed) + 1;
+        let logged = count == 1 || count.is_multiple_of(MALFORMED_LOG_EVERY);
+        if logged {
+            tracing::warn!(count, shape = %message.shape(), "Book message without book data");
+        }
+        logged
+    }
+
+    /// Number of book messages received without book data
+    pub fn malformed_book_messages(&self) -> u64 {
+        self.malformed_book_messages.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book_message(json: &str) -> BookMessage {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_missing_book_data_is_counted_and_sampled() {
+        let diagnostics = FeedDiagnostics::new();
+        let empty = book_message("[]");
+        let short = book_message(r#"[42, "book-25", "BTC/USD"]"#);
+        assert!(empty.book_data().is_none());
+        assert!(short.book_data().is_none());
+
+        // The first occurrence is logged, then only every MALFORMED_LOG_EVERY-th
+        assert!(diagnostics.record_missing_book_data(&empty));
+        assert!(!diagnostics.record_missing_book_data(&short));
+        assert_eq!(diagnostics.malformed_book_messages(), 2);
+
+        let logged = (2..MALFORMED_LOG_EVERY)
+            .filter(|_| diagnostics.record_missing_book_data(&short))
+            .count();
+        assert_eq!(logged, 1);
+        assert_eq!(diagnostics.malformed_book_messages(), MALFORMED_LOG_EVERY);
+    }
+}
@@ -0,0 +1,157 @@
+//! Classification of Kraken's free-text `errorMessage`s
+//! 
+//! Some conditions recur and call for different handling: rate limits need a
+//! longer back-off, an unknown pair will never succeed, and maintenance is
+//! retried after a pause. The raw message is kept alongside the kind for logging.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Known categories of Kraken error messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KrakenErrorKind {
+    /// Too many requests or subscriptions; back off before reconnecting
+    RateLimited,
+    /// The trading pair isn't supported; retrying won't help
+    UnknownPair,
+    /// Kraken (or the service) is in maintenance or unavailable; retry later
+    Maintenance,
+    /// Anything not recognised
+    Unknown,
+}
+
+/// Kraken error codes, `E<category>:<message>`, and the kind of each
+const ERROR_CODES: &[(&str, KrakenErrorKind)] = &[
+    ("EGeneral:Too many requests", KrakenErrorKind::RateLimited),
+    ("EAPI:Rate limit exceeded", KrakenErrorKind::RateLimited),
+    ("EQuery:Unknown asset pair", KrakenErrorKind::UnknownPair),
+    ("EService:Unavailable", KrakenErrorKind::Maintenance),
+    ("EService:Busy", KrakenErrorKind::Maintenance),
+    ("EService:Market in cancel_only mode", KrakenErrorKind::Maintenance),
+    ("EService:Market in post_only mode", KrakenErrorKind::Maintenance),
+];
+
+/// WebSocket subscription errors, which Kraken sends without a code, and the
+/// kind of each; the offending pair is appended, so they match as prefixes
+const SUBSCRIPTION_ERRORS: &[(&str, KrakenErrorKind)] = &[
+    ("Exceeded msg rate", KrakenErrorKind::RateLimited),
+    ("Currency pair not supported", KrakenErrorKind::UnknownPair),
+    ("Currency pair not in ISO 4217-A3 format", KrakenErrorKind::UnknownPair),
+];
+
+/// The `E<category>:<message>` code of an error, without any `:<detail>` suffix
+fn error_code(message: &str) -> Option<&str> {
+    if !message.starts_with('E') || !message.contains(':') {
+        return None;
+    }
+    Some(match message.match_indices(':').nth(1) {
+        Some((end, _)) => &message[..end],
+        None => message,
+    })
+}
+
+impl KrakenErrorKind {
+    /// Classify an `errorMessage` by its error code, or for uncoded subscription
+    /// errors by their known leading text
+    /// 
+    /// Only whole codes and known messages are recognised, so an unrelated error
+    /// that merely mentions e.g. "exceeded" or "maintenance" stays `Unknown`.
+    pub fn classify(message: &str) -> Self {
+        let message = message.trim();
+        let known = match error_code(message) {
+            Some(code) => ERROR_CODES.iter().find(|(known, _)| *known == code),
+            None => SUBSCRIPTION_ERRORS.iter().find(|(known, _)| message.starts_with(known)),
+        };
+        known.map_or(KrakenErrorKind::Unknown, |(_, kind)| *kind)
+    }
+
+    /// Extra delay before reconnecting, if this kind calls for one
+    /// 
+    /// `None` for `UnknownPair`, which shouldn't be retried at all.
+    pub fn retry_delay(&self) -> Option<Duration> {
+        match self {
+            KrakenErrorKind::RateLimited => Some(Duration::from_secs(30)),
+            KrakenErrorKind::Maintenance => Some(Duration::from_secs(60)),
+            KrakenErrorKind::Unknown => Some(Duration::ZERO),
+            KrakenErrorKind::UnknownPair => None,
+        }
+    }
+}
+
+/// An error reported by Kraken in a `subscriptionStatus` message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KrakenError {
+    pub kind: KrakenErrorKind,
+    /// Kraken's message, verbatim
+    pub message: String,
+}
+
+impl KrakenError {
+    /// Classify a raw Kraken error message
+    pub fn new(message: impl Into<String>) -> Self {
+        let message = message.into();
+        Self {
+            kind: KrakenErrorKind::classify(&message),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for KrakenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Kraken error ({:?}): {}", self.kind, self.message)
+    }
+}
+
+impl std::error::Error for KrakenError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_known_messages() {
+        let cases = [
+            ("EGeneral:Too many requests", KrakenErrorKind::RateLimited),
+            ("EAPI:Rate limit exceeded", KrakenErrorKind::RateLimited),
+            ("Exceeded msg rate", KrakenErrorKind::RateLimited),
+            ("Currency pair not supported DOGE/XYZ", KrakenErrorKind::UnknownPair),
+            ("Currency pair not in ISO 4217-A3 format ZECUSD", KrakenErrorKind::UnknownPair),
+            ("EQuery:Unknown asset pair", KrakenErrorKind::UnknownPair),
+            ("EService:Unavailable", KrakenErrorKind::Maintenance),
+            ("EService:Market in cancel_only mode:XBT/USD", KrakenErrorKind::Maintenance),
+            ("Subscription depth not supported", KrakenErrorKind::Unknown),
+            ("", KrakenErrorKind::Unknown),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(KrakenErrorKind::classify(message), kind, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_classify_ignores_incidental_keywords() {
+        // Each mentions a word tied to a known kind without being that error
+        for message in [
+            "Subscription depth exceeded maximum",
+            "EGeneral:Invalid arguments:depth exceeded",
+            "EOrder:Insufficient margin during maintenance window",
+            "Event(s) not found: post_only",
+            "EService:Unavailable soon",
+        ] {
+            assert_eq!(KrakenErrorKind::classify(message), KrakenErrorKind::Unknown, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_error_keeps_raw_message() {
+        let error = KrakenError::new("EGeneral:Too many requests");
+        assert_eq!(error.kind, KrakenErrorKind::RateLimited);
+        assert_eq!(error.message, "EGeneral:Too many requests");
+        assert!(error.to_string().contains("EGeneral:Too many requests"));
+
+        // Round-trips through anyhow so the reconnect loop can recover it
+        let error = anyhow::Error::from(KrakenError::new("Currency pair not supported")).context("Kraken subscription rejected");
+        assert_eq!(error.downcast_ref::<KrakenError>().map(|e| e.kind), Some(KrakenErrorKind::UnknownPair));
+        assert_eq!(KrakenErrorKind::UnknownPair.retry_delay(), None);
+    }
+}
@@ -1,3 +1,7 @@
 pub mod types;
 pub mod client;
+pub mod subscription;
+pub mod diagnostics;
+pub mod deflate;
 
+pub mod errors;
@@ -0,0 +1,128 @@
+//! Per-ticker record of what was requested from Kraken and what Kraken confirmed
+//! 
+//! The feed task resets the record on every (re)connect and marks channels as
+//! confirmed when their `subscriptionStatus` acknowledgements arrive.
+
+use serde::Serialize;
+use crate::exchange::ChannelStatus;
+
+/// Name of the order book channel
+pub const BOOK_CHANNEL: &str = "book";
+
+/// Name of the OHLC (candlestick) channel
+pub const OHLC_CHANNEL: &str = "ohlc";
+
+/// Name of the executed trade channel
+pub const TRADE_CHANNEL: &str = "trade";
+
+/// Acknowledgement state of a single subscribed channel
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelState {
+    pub name: String,
+    /// True once Kraken acknowledged the subscription with status "subscribed"
+    pub confirmed: bool,
+    /// Channel ID assigned by Kraken in the acknowledgement
+    pub channel_id: Option<u64>,
+}
+
+/// Subscription parameters and ack state of a ticker's Kraken feed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionState {
+    /// Kraken trading pair, e.g. "BTC/USD"
+    pub pair: String,
+    /// Requested book depth
+    pub depth: u32,
+    /// Requested OHLC interval in minutes
+    pub ohlc_interval: u32,
+    /// Channels subscribed to, in subscription order
+    pub channels: Vec<ChannelState>,
+}
+
+impl SubscriptionState {
+    /// Create the state for a subscription to `channels`, with nothing confirmed yet
+    /// 
+    /// `channels` are those the ticker's exchange streams (see `Exchange::CHANNELS`);
+    /// a channel the exchange never acknowledges would leave the ticker unconfirmed.
+    pub fn new(pair: &str, depth: u32, ohlc_interval: u32, channels: &[&str]) -> Self {
+        let channel = |name: &str| ChannelState {
+            name: name.to_string(),
+            confirmed: false,
+            channel_id: None,
+        };
+        Self {
+            pair: pair.to_string(),
+            depth,
+            ohlc_interval,
+            channels: channels.iter().map(|name| channel(name)).collect(),
+        }
+    }
+
+    /// Mark every channel unconfirmed, e.g. when the connection is re-established
+    pub fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.confirmed = false;
+            channel.channel_id = None;
+        }
+    }
+
+    /// Record a subscription acknowledgement
+    /// 
+    /// Each ticker has its own connection, so the echoed pair isn't compared
+    /// (Kraken may echo an alias such as "XBT/USD"). Unknown channels are ignored.
+    pub fn apply_status(&mut self, status: &ChannelStatus) {
+        if let Some(channel) = self.channels.iter_mut().find(|channel| channel.name == status.channel) {
+            channel.confirmed = status.subscribed;
+            channel.channel_id = status.channel_id.filter(|_| channel.confirmed);
+        }
+    }
+
+    /// Whether every channel has been confirmed
+    pub fn is_confirmed(&self) -> bool {
+        self.channels.iter().all(|channel| channel.confirmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(json: &str) -> ChannelStatus {
+        let status: crate::kraken::types::SubscriptionStatus = serde_json::from_str(json).unwrap();
+        status.channel_status().unwrap()
+    }
+
+    #[test]
+    fn test_acks_confirm_channels() {
+        let mut state = SubscriptionState::new("BTC/USD", 100, 1, &[BOOK_CHANNEL, OHLC_CHANNEL]);
+        assert!(!state.is_confirmed());
+
+        state.apply_status(&status(r#"{"event": "subscriptionStatus", "status": "subscribed", "channelID": 42,
+            "pair": "BTC/USD", "subscription": {"name": "book", "depth": 100}}"#));
+        assert_eq!(state.channels[0], ChannelState { name: "book".to_string(), confirmed: true, channel_id: Some(42) });
+        assert!(!state.channels[1].confirmed);
+
+        // Acks for channels that weren't requested are ignored
+        state.apply_status(&status(r#"{"event": "subscriptionStatus", "status": "subscribed", "channelID": 7,
+            "pair": "XBT/USD", "subscription": {"name": "spread"}}"#));
+        assert!(!state.channels[1].confirmed);
+
+        state.apply_status(&status(r#"{"event": "subscriptionStatus", "status": "subscribed", "channelID": 43,
+            "pair": "XBT/USD", "subscription": {"name": "ohlc", "interval": 1}}"#));
+        assert!(state.is_confirmed());
+
+        state.reset();
+        assert!(state.channels.iter().all(|channel| !channel.confirmed && channel.channel_id.is_none()));
+    }
+
+    #[test]
+    fn test_book_only_exchange_confirms_on_book_ack() {
+        use crate::exchange::ExchangeSource;
+
+        let mut state = SubscriptionState::new("BTC/USDT", 100, 1, ExchangeSource::Binance.channels());
+        assert_eq!(state.channels.len(), 1);
+        state.apply_status(&ChannelStatus { channel: BOOK_CHANNEL.to_string(), subscribed: true, channel_id: None });
+        assert!(state.is_confirmed());
+    }
+}
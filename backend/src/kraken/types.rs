@@ -1,4 +1,5 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::exchange::ChannelStatus;
 
 /// Subscription request to Kraken WebSocket API
 #[derive(Debug, Serialize)]
@@ -17,6 +18,84 @@ pub struct SubscriptionDetails {
     pub interval: Option<u32>,
 }
 
+/// Subscription request to Kraken WebSocket API v2
+/// Format: {"method": "subscribe", "params": {"channel": "book", "symbol": ["BTC/USD"], "depth": 10}}
+#[derive(Debug, Serialize)]
+pub struct SubscriptionRequestV2 {
+    pub method: String,
+    pub params: SubscriptionParamsV2,
+}
+
+impl SubscriptionRequestV2 {
+    /// Build a `subscribe` request
+    pub fn subscribe(params: SubscriptionParamsV2) -> Self {
+        Self {
+            method: "subscribe".to_string(),
+            params,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SubscriptionParamsV2 {
+    pub channel: String,
+    pub symbol: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<u32>,
+}
+
+/// Application-level ping to Kraken WebSocket API
+/// Format: {"event": "ping", "reqid": 1}
+#[derive(Debug, Serialize)]
+pub struct PingRequest {
+    pub event: String,
+    pub reqid: u64,
+}
+
+impl PingRequest {
+    /// Build a `ping` request, echoed back in the `pong`
+    pub fn new(reqid: u64) -> Self {
+        Self {
+            event: "ping".to_string(),
+            reqid,
+        }
+    }
+}
+
+/// Application-level ping to Kraken WebSocket API v2
+/// Format: {"method": "ping", "req_id": 1}
+#[derive(Debug, Serialize)]
+pub struct PingRequestV2 {
+    pub method: String,
+    pub req_id: u64,
+}
+
+impl PingRequestV2 {
+    /// Build a `ping` request, echoed back in the `pong`
+    pub fn new(req_id: u64) -> Self {
+        Self {
+            method: "ping".to_string(),
+            req_id,
+        }
+    }
+}
+
+/// Request id of a `pong` reply, or `None` if the value isn't a pong
+/// 
+/// Accepts both `{"event": "pong", "reqid": 1}` (v1) and
+/// `{"method": "pong", "req_id": 1}` (v2); the id is 0 if Kraken omitted it.
+pub fn pong_reqid(value: &serde_json::Value) -> Option<u64> {
+    if value.get("event").and_then(|event| event.as_str()) == Some("pong") {
+        return Some(value.get("reqid").and_then(|id| id.as_u64()).unwrap_or(0));
+    }
+    if value.get("method").and_then(|method| method.as_str()) == Some("pong") {
+        return Some(value.get("req_id").and_then(|id| id.as_u64()).unwrap_or(0));
+    }
+    None
+}
+
 /// Subscription status response from Kraken
 #[derive(Debug, Deserialize)]
 #[allow(non_snake_case)] // errorMessage matches Kraken API format
@@ -30,6 +109,48 @@ pub struct SubscriptionStatus {
     pub errorMessage: Option<String>,
 }
 
+impl SubscriptionStatus {
+    /// Normalized acknowledgement of the channel this status names, if any
+    pub fn channel_status(&self) -> Option<ChannelStatus> {
+        let details = self.subscription.as_ref()?;
+        Some(ChannelStatus {
+            channel: details.name.clone(),
+            subscribed: self.status == "subscribed",
+            channel_id: self.channel_id,
+        })
+    }
+}
+
+/// Reply to a v2 `method` request
+/// Format: {"method": "subscribe", "result": {"channel": "book", ...}, "success": true}
+/// or {"method": "subscribe", "error": "...", "success": false}
+#[derive(Debug, Deserialize)]
+pub struct MethodResponseV2 {
+    pub method: String,
+    pub success: bool,
+    pub error: Option<String>,
+    pub result: Option<MethodResultV2>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MethodResultV2 {
+    pub channel: Option<String>,
+}
+
+impl MethodResponseV2 {
+    /// Normalized acknowledgement of the channel a `subscribe` reply names, if any
+    pub fn channel_status(&self) -> Option<ChannelStatus> {
+        if self.method != "subscribe" {
+            return None;
+        }
+        Some(ChannelStatus {
+            channel: self.result.as_ref()?.channel.clone()?,
+            subscribed: self.success,
+            channel_id: None,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SubscriptionDetailsResponse {
     pub name: String,
@@ -43,16 +164,19 @@ pub struct PriceLevel {
     pub price: f64,
     pub volume: f64,
     pub timestamp: Option<f64>,
+    /// Set by Kraken's trailing `"r"` on levels republished during a resync;
+    /// such levels are authoritative and replace whatever the book holds
+    pub republish: bool,
 }
 
 /// Orderbook snapshot data structure
-/// Kraken sends snapshots as: [channelID, {b: [...], a: [...]}, "book-25", "ZEC/USD"]
-/// Note: "b" = bids, "a" = asks. Either field may be missing in individual messages.
+/// Kraken sends snapshots as: [channelID, {bs: [...], as: [...]}, "book-25", "ZEC/USD"]
+/// Note: "bs"/"b" = bids, "as"/"a" = asks. Either field may be missing in individual messages.
 #[derive(Debug, Deserialize)]
 pub struct BookSnapshot {
-    #[serde(rename = "b", default)]
+    #[serde(rename = "b", alias = "bs", default)]
     pub bids: Vec<serde_json::Value>, // Can be [price, volume, timestamp] or [price, volume, timestamp, "r"]
-    #[serde(rename = "a", default)]
+    #[serde(rename = "a", alias = "as", default)]
     pub asks: Vec<serde_json::Value>, // Can be [price, volume, timestamp] or [price, volume, timestamp, "r"]
 }
 
@@ -65,10 +189,28 @@ pub struct BookDelta {
     pub bids: Vec<serde_json::Value>, // Can be [price, volume, timestamp] or [price, volume, timestamp, "r"] - volume "0" means remove
     #[serde(rename = "a", default)]
     pub asks: Vec<serde_json::Value>, // Can be [price, volume, timestamp] or [price, volume, timestamp, "r"] - volume "0" means remove
+    /// CRC32 of the top 10 levels after applying this delta ("c", sent as a decimal string)
+    #[serde(rename = "c", default, deserialize_with = "deserialize_checksum")]
+    pub checksum: Option<u32>,
+}
+
+/// Deserialize Kraken's checksum, which is sent as a decimal string
+fn deserialize_checksum<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    match Option::<serde_json::Value>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(serde_json::Value::String(s)) => s.parse().map(Some).map_err(serde::de::Error::custom),
+        Some(serde_json::Value::Number(n)) => n
+            .as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom("checksum out of range")),
+        Some(other) => Err(serde::de::Error::custom(format!("invalid checksum: {}", other))),
+    }
 }
 
 /// Complete book message (snapshot or delta) as received from Kraken
 /// Format: [channelID, {bids: [...], asks: [...]}, "book-25", "ZEC/USD"]
+/// Updates touching both sides arrive as [channelID, {a: [...]}, {b: [...], c: "..."}, "book-25", "ZEC/USD"]
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum BookMessage {
@@ -98,6 +240,10 @@ pub struct OhlcData {
     pub volume: f64,
     /// Number of trades
     pub count: u64,
+    /// Average book imbalance sampled during the candle; only set on candles
+    /// built from inferred trades (absent from Kraken's and older candles)
+    #[serde(default, rename = "avgImbalance")]
+    pub avg_imbalance: Option<f64>,
 }
 
 /// OHLC message as received from Kraken
@@ -109,12 +255,49 @@ pub enum OhlcMessage {
     ArrayFormat(Vec<serde_json::Value>),
 }
 
+/// A trade executed on Kraken, from the trade channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeData {
+    /// Execution price
+    pub price: f64,
+    /// Executed volume
+    pub volume: f64,
+    /// Execution time (Unix seconds, with fractional part)
+    pub time: f64,
+    /// Side of the taker as Kraken sends it: `"b"` buy or `"s"` sell
+    pub side: String,
+}
+
+/// Trade message as received from Kraken
+/// Format: [channelID, [[price, volume, time, side, orderType, misc], ...], "trade", "ZEC/USD"]
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TradeMessage {
+    /// Array format: [channelID, trades, channelName, pair]
+    ArrayFormat(Vec<serde_json::Value>),
+}
+
+impl TradeMessage {
+    /// Parse every trade in the message, oldest first as Kraken sends them
+    pub fn trades(&self) -> Result<Vec<TradeData>, anyhow::Error> {
+        match self {
+            TradeMessage::ArrayFormat(arr) => arr
+                .get(1)
+                .and_then(|trades| trades.as_array())
+                .ok_or_else(|| anyhow::anyhow!("Trade message must carry an array of trades"))?
+                .iter()
+                .map(parse_trade_data)
+                .collect(),
+        }
+    }
+}
+
 impl BookMessage {
     /// Extract channel ID from the message
     pub fn channel_id(&self) -> Option<u64> {
         match self {
             BookMessage::ArrayFormat(arr) => {
-                if arr.len() > 0 {
+                if !arr.is_empty() {
                     arr[0].as_u64()
                 } else {
                     None
@@ -124,29 +307,118 @@ impl BookMessage {
     }
 
     /// Extract the book data (snapshot or delta) from the message
+    /// 
+    /// When asks and bids arrive as separate objects, they are merged into one.
     pub fn book_data(&self) -> Option<serde_json::Value> {
         match self {
             BookMessage::ArrayFormat(arr) => {
-                if arr.len() > 1 {
-                    Some(arr[1].clone())
-                } else {
-                    None
+                let mut objects = arr.iter().skip(1).filter_map(|v| v.as_object());
+                let mut data = objects.next()?.clone();
+                for object in objects {
+                    data.extend(object.iter().map(|(k, v)| (k.clone(), v.clone())));
                 }
+                Some(serde_json::Value::Object(data))
+            }
+        }
+    }
+
+    /// Describe the message's structure without its contents, for diagnostics
+    /// 
+    /// E.g. `array[3]: number, string, string`
+    pub fn shape(&self) -> String {
+        match self {
+            BookMessage::ArrayFormat(arr) => {
+                let kinds: Vec<&str> = arr
+                    .iter()
+                    .map(|value| match value {
+                        serde_json::Value::Null => "null",
+                        serde_json::Value::Bool(_) => "bool",
+                        serde_json::Value::Number(_) => "number",
+                        serde_json::Value::String(_) => "string",
+                        serde_json::Value::Array(_) => "array",
+                        serde_json::Value::Object(_) => "object",
+                    })
+                    .collect();
+                format!("array[{}]: {}", arr.len(), kinds.join(", "))
             }
         }
     }
 
-    /// Check if this is a snapshot (first message after subscription)
+    /// Check if this is a snapshot frame
+    /// 
+    /// Snapshots carry levels under the "as"/"bs" keys, deltas under "a"/"b".
     pub fn is_snapshot(&self) -> bool {
-        // Snapshots typically have more price levels than deltas
-        // We'll determine this based on the data size when processing
-        true // Will be determined by context in the client
+        match self {
+            BookMessage::ArrayFormat(arr) => arr
+                .iter()
+                .skip(1)
+                .filter_map(|v| v.as_object())
+                .any(|object| object.contains_key("as") || object.contains_key("bs")),
+        }
+    }
+}
+
+/// Accumulates an initial snapshot that Kraken split across several frames
+/// 
+/// Deep subscriptions (e.g. book-1000) may deliver the snapshot in more than one
+/// message. Frames are merged until the snapshot is complete (see `KrakenEventMapper`), then taken and
+/// applied as a single snapshot so later frames don't wipe out earlier ones.
+#[derive(Debug, Default)]
+pub struct SnapshotAssembler {
+    pending: Option<BookSnapshot>,
+}
+
+impl SnapshotAssembler {
+    /// Add a snapshot frame to the pending snapshot
+    pub fn push(&mut self, frame: BookSnapshot) {
+        match &mut self.pending {
+            Some(pending) => {
+                pending.bids.extend(frame.bids);
+                pending.asks.extend(frame.asks);
+            }
+            None => self.pending = Some(frame),
+        }
+    }
+
+    /// Take the consolidated snapshot, if any frames were received
+    pub fn take(&mut self) -> Option<BookSnapshot> {
+        self.pending.take()
+    }
+
+    /// Whether the pending snapshot has at least `depth` levels on both sides
+    pub fn has_depth(&self, depth: usize) -> bool {
+        self.pending
+            .as_ref()
+            .is_some_and(|pending| pending.bids.len() >= depth && pending.asks.len() >= depth)
     }
 }
 
+/// Build a price level, rejecting values that would poison the book
+/// 
+/// `"NaN"` and `"inf"` parse as valid f64s, but the engine's price keys assume
+/// finite prices, so non-finite or negative prices and volumes are errors.
+fn checked_price_level(price: f64, volume: f64, timestamp: Option<f64>, republish: bool) -> Result<PriceLevel, anyhow::Error> {
+    if !price.is_finite() || !volume.is_finite() {
+        return Err(anyhow::anyhow!("Price and volume must be finite"));
+    }
+    if price < 0.0 {
+        return Err(anyhow::anyhow!("Price must not be negative"));
+    }
+    if volume < 0.0 {
+        return Err(anyhow::anyhow!("Volume must not be negative"));
+    }
+    Ok(PriceLevel {
+        price,
+        volume,
+        timestamp,
+        republish,
+    })
+}
+
 /// Helper function to parse price level from Kraken format
 /// Format: [price, volume, timestamp] or [price, volume, timestamp, "r"]
 /// where price and volume are strings, timestamp is a string (can be empty), and "r" is optional
+/// and marks a republished level. Non-finite or negative prices and volumes are rejected.
 pub fn parse_price_level(level: &serde_json::Value) -> Result<PriceLevel, anyhow::Error> {
     let arr = level.as_array()
         .ok_or_else(|| anyhow::anyhow!("Price level must be an array"))?;
@@ -174,11 +446,19 @@ pub fn parse_price_level(level: &serde_json::Value) -> Result<PriceLevel, anyhow
         None
     };
 
-    Ok(PriceLevel {
-        price,
-        volume,
-        timestamp,
-    })
+    let republish = arr.get(3).and_then(|flag| flag.as_str()) == Some("r");
+
+    checked_price_level(price, volume, timestamp, republish)
+}
+
+/// Number of decimal places in a price level's price and volume strings
+/// 
+/// Kraken sends fixed-precision strings per pair; the precision is needed to
+/// rebuild the exact strings its book checksum is computed over.
+pub fn price_level_precision(level: &serde_json::Value) -> Option<(usize, usize)> {
+    let decimals = |s: &str| s.split_once('.').map_or(0, |(_, fraction)| fraction.len());
+    let arr = level.as_array()?;
+    Some((decimals(arr.first()?.as_str()?), decimals(arr.get(1)?.as_str()?)))
 }
 
 /// Helper function to parse book snapshot from JSON value
@@ -193,6 +473,79 @@ pub fn parse_book_delta(value: &serde_json::Value) -> Result<BookDelta, anyhow::
     Ok(delta)
 }
 
+/// Book message from Kraken WebSocket API v2
+/// Format: {"channel": "book", "type": "snapshot" | "update", "data": [{"symbol": ..., "bids": [...], "asks": [...]}]}
+#[derive(Debug, Deserialize)]
+pub struct BookMessageV2 {
+    pub channel: String,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub data: Vec<BookDataV2>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BookDataV2 {
+    pub symbol: String,
+    #[serde(default)]
+    pub bids: Vec<PriceLevelV2>,
+    #[serde(default)]
+    pub asks: Vec<PriceLevelV2>,
+    pub checksum: Option<u32>,
+    /// RFC 3339 time of the update (updates only)
+    pub timestamp: Option<String>,
+}
+
+/// v2 price level; prices and quantities are JSON numbers rather than strings
+#[derive(Debug, Deserialize)]
+pub struct PriceLevelV2 {
+    pub price: f64,
+    pub qty: f64,
+}
+
+impl PriceLevelV2 {
+    /// Convert to the v1 `[price, volume, timestamp]` level format the engine consumes
+    fn to_v1_level(&self) -> serde_json::Value {
+        serde_json::json!([self.price.to_string(), self.qty.to_string(), ""])
+    }
+}
+
+/// Parse a v2 book envelope of the expected `type`, returning (bids, asks) in v1 level format
+fn parse_book_levels_v2(
+    value: &serde_json::Value,
+    expected_type: &str,
+) -> Result<(Vec<serde_json::Value>, Vec<serde_json::Value>), anyhow::Error> {
+    let message: BookMessageV2 = serde_json::from_value(value.clone())?;
+    if message.channel != "book" {
+        return Err(anyhow::anyhow!("Expected book channel, got {}", message.channel));
+    }
+    if message.message_type != expected_type {
+        return Err(anyhow::anyhow!("Expected book {}, got {}", expected_type, message.message_type));
+    }
+
+    let mut bids = Vec::new();
+    let mut asks = Vec::new();
+    for data in &message.data {
+        bids.extend(data.bids.iter().map(PriceLevelV2::to_v1_level));
+        asks.extend(data.asks.iter().map(PriceLevelV2::to_v1_level));
+    }
+    Ok((bids, asks))
+}
+
+/// Parse a v2 `{"channel":"book","type":"snapshot",...}` message into a snapshot
+pub fn parse_book_snapshot_v2(value: &serde_json::Value) -> Result<BookSnapshot, anyhow::Error> {
+    let (bids, asks) = parse_book_levels_v2(value, "snapshot")?;
+    Ok(BookSnapshot { bids, asks })
+}
+
+/// Parse a v2 `{"channel":"book","type":"update",...}` message into a delta
+/// 
+/// The checksum is not carried over: v2 checksums are computed over a different
+/// string format than the v1 one `OrderbookEngine::checksum` implements.
+pub fn parse_book_delta_v2(value: &serde_json::Value) -> Result<BookDelta, anyhow::Error> {
+    let (bids, asks) = parse_book_levels_v2(value, "update")?;
+    Ok(BookDelta { bids, asks, checksum: None })
+}
+
 /// Helper function to parse OHLC data from JSON value
 /// Format: [time, etime, open, high, low, close, vwap, volume, count]
 pub fn parse_ohlc_data(value: &serde_json::Value) -> Result<OhlcData, anyhow::Error> {
@@ -248,6 +601,43 @@ pub fn parse_ohlc_data(value: &serde_json::Value) -> Result<OhlcData, anyhow::Er
         vwap,
         volume,
         count,
+        avg_imbalance: None,
+    })
+}
+
+/// Helper function to parse one trade from JSON value
+/// Format: [price, volume, time, side, orderType, misc] where side is "b" or "s"
+pub fn parse_trade_data(value: &serde_json::Value) -> Result<TradeData, anyhow::Error> {
+    let arr = value.as_array()
+        .ok_or_else(|| anyhow::anyhow!("Trade must be an array"))?;
+
+    if arr.len() < 4 {
+        return Err(anyhow::anyhow!("Trade array must have at least 4 elements, got {}", arr.len()));
+    }
+
+    let number = |index: usize, name: &str| -> Result<f64, anyhow::Error> {
+        let value = arr[index].as_str()
+            .ok_or_else(|| anyhow::anyhow!("{} must be a string", name))?
+            .parse::<f64>()?;
+        if !value.is_finite() {
+            return Err(anyhow::anyhow!("{} must be finite", name));
+        }
+        Ok(value)
+    };
+    let price = number(0, "price")?;
+    let volume = number(1, "volume")?;
+    let time = number(2, "time")?;
+
+    let side = match arr[3].as_str() {
+        Some(side @ ("b" | "s")) => side.to_string(),
+        other => return Err(anyhow::anyhow!("side must be \"b\" or \"s\", got {:?}", other)),
+    };
+
+    Ok(TradeData {
+        price,
+        volume,
+        time,
+        side,
     })
 }
 
@@ -255,6 +645,34 @@ pub fn parse_ohlc_data(value: &serde_json::Value) -> Result<OhlcData, anyhow::Er
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_trade_message() {
+        let message: TradeMessage = serde_json::from_str(r#"[0, [
+            ["5541.20000", "0.15850568", "1534614057.321597", "s", "l", ""],
+            ["6060.00000", "0.02455000", "1534614057.324998", "b", "m", ""]
+        ], "trade", "XBT/USD"]"#).unwrap();
+        let trades = message.trades().unwrap();
+        assert_eq!(trades, vec![
+            TradeData { price: 5541.2, volume: 0.15850568, time: 1534614057.321597, side: "s".to_string() },
+            TradeData { price: 6060.0, volume: 0.02455, time: 1534614057.324998, side: "b".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_trade_data_rejects_malformed_trades() {
+        for trade in [
+            serde_json::json!("5541.2"),
+            serde_json::json!(["5541.2", "0.1", "1534614057.3"]),
+            serde_json::json!([5541.2, "0.1", "1534614057.3", "b", "l", ""]),
+            serde_json::json!(["5541.2", "0.1", "1534614057.3", "x", "l", ""]),
+            serde_json::json!(["NaN", "0.1", "1534614057.3", "b", "l", ""]),
+        ] {
+            assert!(parse_trade_data(&trade).is_err(), "{} should be rejected", trade);
+        }
+        let message: TradeMessage = serde_json::from_str(r#"[0, "oops", "trade", "XBT/USD"]"#).unwrap();
+        assert!(message.trades().is_err());
+    }
+
     #[test]
     fn test_parse_price_level() {
         let level = serde_json::json!(["42000.5", "1.25", "1234567890.123"]);
@@ -262,6 +680,7 @@ mod tests {
         assert_eq!(price_level.price, 42000.5);
         assert_eq!(price_level.volume, 1.25);
         assert_eq!(price_level.timestamp, Some(1234567890.123));
+        assert!(!price_level.republish);
     }
 
     #[test]
@@ -280,6 +699,202 @@ mod tests {
         assert_eq!(price_level.price, 42000.5);
         assert_eq!(price_level.volume, 1.25);
         assert_eq!(price_level.timestamp, Some(1234567890.123));
+        assert!(price_level.republish);
+    }
+
+    #[test]
+    fn test_parse_price_level_rejects_non_finite_and_negative() {
+        for level in [
+            serde_json::json!(["NaN", "1.0", ""]),
+            serde_json::json!(["inf", "1.0", ""]),
+            serde_json::json!(["-5", "1.0", ""]),
+            serde_json::json!(["100.0", "NaN", ""]),
+            serde_json::json!(["100.0", "-inf", ""]),
+            serde_json::json!(["100.0", "-1.5", ""]),
+        ] {
+            assert!(parse_price_level(&level).is_err(), "{} should be rejected", level);
+        }
+        // Zero volume still removes a level
+        assert_eq!(parse_price_level(&serde_json::json!(["100.0", "0.0", ""])).unwrap().volume, 0.0);
+    }
+
+    #[test]
+    fn test_parse_book_delta_checksum() {
+        let delta = parse_book_delta(&serde_json::json!({
+            "a": [["0.05005", "0.00000500", "1582905487.684110"]],
+            "c": "974947235"
+        })).unwrap();
+        assert_eq!(delta.checksum, Some(974947235));
+
+        let delta = parse_book_delta(&serde_json::json!({ "b": [] })).unwrap();
+        assert_eq!(delta.checksum, None);
+
+        assert!(parse_book_delta(&serde_json::json!({ "c": "not-a-number" })).is_err());
+    }
+
+    #[test]
+    fn test_book_data_merges_split_update() {
+        let message = BookMessage::ArrayFormat(vec![
+            serde_json::json!(1234),
+            serde_json::json!({ "a": [["5541.30000", "2.50700000", "1534614248.456738"]] }),
+            serde_json::json!({ "b": [["5541.20000", "1.52900000", "1534614248.765567"]], "c": "974942666" }),
+            serde_json::json!("book-10"),
+            serde_json::json!("XBT/USD"),
+        ]);
+        let delta = parse_book_delta(&message.book_data().unwrap()).unwrap();
+        assert_eq!(delta.asks.len(), 1);
+        assert_eq!(delta.bids.len(), 1);
+        assert_eq!(delta.checksum, Some(974942666));
+    }
+
+    #[test]
+    fn test_book_message_shape() {
+        let message: BookMessage = serde_json::from_str(r#"[42, {"a": []}, "book-25", "BTC/USD"]"#).unwrap();
+        assert_eq!(message.shape(), "array[4]: number, object, string, string");
+        assert_eq!(BookMessage::ArrayFormat(vec![]).shape(), "array[0]: ");
+    }
+
+    #[test]
+    fn test_parse_snapshot_keys() {
+        let snapshot = parse_book_snapshot(&serde_json::json!({
+            "as": [["5541.30000", "2.50700000", "1534614248.123678"]],
+            "bs": [["5541.20000", "1.52900000", "1534614248.765567"]]
+        })).unwrap();
+        assert_eq!(snapshot.asks.len(), 1);
+        assert_eq!(snapshot.bids.len(), 1);
+    }
+
+    #[test]
+    fn test_price_level_precision() {
+        let level = serde_json::json!(["0.05005", "0.00000500", "1582905487.684110"]);
+        assert_eq!(price_level_precision(&level), Some((5, 8)));
+        assert_eq!(price_level_precision(&serde_json::json!(["42000", "1.5", ""])), Some((0, 1)));
+        assert_eq!(price_level_precision(&serde_json::json!([])), None);
+    }
+
+    #[test]
+    fn test_multi_frame_snapshot_is_consolidated() {
+        use crate::orderbook::engine::OrderbookEngine;
+
+        let frames: Vec<BookMessage> = [
+            r#"[0, {"as": [["101.0", "1.0", "1.0"]], "bs": [["100.0", "1.0", "1.0"]]}, "book-1000", "XBT/USD"]"#,
+            r#"[0, {"as": [["102.0", "2.0", "1.0"]], "bs": [["99.0", "2.0", "1.0"]]}, "book-1000", "XBT/USD"]"#,
+        ]
+        .iter()
+        .map(|raw| serde_json::from_str(raw).unwrap())
+        .collect();
+        let delta: BookMessage =
+            serde_json::from_str(r#"[0, {"a": [["101.0", "0.5", "2.0"]]}, "book-1000", "XBT/USD"]"#).unwrap();
+
+        let mut assembler = SnapshotAssembler::default();
+        for frame in &frames {
+            assert!(frame.is_snapshot());
+            assembler.push(parse_book_snapshot(&frame.book_data().unwrap()).unwrap());
+        }
+        assert!(!delta.is_snapshot());
+
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&assembler.take().unwrap()).unwrap();
+        assert!(assembler.take().is_none());
+
+        let bids: Vec<f64> = engine.iter_bids().map(|(price, _)| price).collect();
+        let asks: Vec<f64> = engine.iter_asks().map(|(price, _)| price).collect();
+        assert_eq!(bids, vec![100.0, 99.0]);
+        assert_eq!(asks, vec![101.0, 102.0]);
+    }
+
+    const V2_SNAPSHOT: &str = r#"{
+        "channel": "book",
+        "type": "snapshot",
+        "data": [{
+            "symbol": "MATIC/USD",
+            "bids": [{"price": 0.5666, "qty": 4831.75496356}, {"price": 0.5665, "qty": 6658.22734739}],
+            "asks": [{"price": 0.5668, "qty": 4410.79769741}, {"price": 0.5669, "qty": 4655.40412487}],
+            "checksum": 2439117997
+        }]
+    }"#;
+
+    const V2_UPDATE: &str = r#"{
+        "channel": "book",
+        "type": "update",
+        "data": [{
+            "symbol": "MATIC/USD",
+            "bids": [{"price": 0.5657, "qty": 1098.3947558}],
+            "asks": [],
+            "checksum": 2114181697,
+            "timestamp": "2023-10-06T17:35:55.440295Z"
+        }]
+    }"#;
+
+    #[test]
+    fn test_parse_book_snapshot_v2() {
+        let value: serde_json::Value = serde_json::from_str(V2_SNAPSHOT).unwrap();
+        let snapshot = parse_book_snapshot_v2(&value).unwrap();
+        assert_eq!(snapshot.bids.len(), 2);
+        assert_eq!(snapshot.asks.len(), 2);
+
+        // Numeric fields round-trip through the v1 level format exactly
+        let best_bid = parse_price_level(&snapshot.bids[0]).unwrap();
+        assert_eq!(best_bid, PriceLevel { price: 0.5666, volume: 4831.75496356, timestamp: None, republish: false });
+        let best_ask = parse_price_level(&snapshot.asks[0]).unwrap();
+        assert_eq!(best_ask.price, 0.5668);
+        assert_eq!(best_ask.volume, 4410.79769741);
+
+        // An update is not a snapshot
+        let update: serde_json::Value = serde_json::from_str(V2_UPDATE).unwrap();
+        assert!(parse_book_snapshot_v2(&update).is_err());
+    }
+
+    #[test]
+    fn test_parse_book_delta_v2() {
+        let value: serde_json::Value = serde_json::from_str(V2_UPDATE).unwrap();
+        let delta = parse_book_delta_v2(&value).unwrap();
+        assert!(delta.asks.is_empty());
+        assert_eq!(
+            parse_price_level(&delta.bids[0]).unwrap(),
+            PriceLevel { price: 0.5657, volume: 1098.3947558, timestamp: None, republish: false }
+        );
+        assert_eq!(delta.checksum, None);
+
+        let snapshot: serde_json::Value = serde_json::from_str(V2_SNAPSHOT).unwrap();
+        assert!(parse_book_delta_v2(&snapshot).is_err());
+        let ticker = serde_json::json!({"channel": "ticker", "type": "update", "data": []});
+        assert!(parse_book_delta_v2(&ticker).is_err());
+    }
+
+    #[test]
+    fn test_subscription_request_v2_serialization() {
+        let request = SubscriptionRequestV2::subscribe(SubscriptionParamsV2 {
+            channel: "book".to_string(),
+            symbol: vec!["BTC/USD".to_string()],
+            depth: Some(10),
+            interval: None,
+        });
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({"method": "subscribe", "params": {"channel": "book", "symbol": ["BTC/USD"], "depth": 10}})
+        );
+    }
+
+    #[test]
+    fn test_ping_request_serialization() {
+        assert_eq!(
+            serde_json::to_value(PingRequest::new(7)).unwrap(),
+            serde_json::json!({"event": "ping", "reqid": 7})
+        );
+        assert_eq!(
+            serde_json::to_value(PingRequestV2::new(7)).unwrap(),
+            serde_json::json!({"method": "ping", "req_id": 7})
+        );
+    }
+
+    #[test]
+    fn test_pong_reqid() {
+        assert_eq!(pong_reqid(&serde_json::json!({"event": "pong", "reqid": 7})), Some(7));
+        assert_eq!(pong_reqid(&serde_json::json!({"method": "pong", "req_id": 8, "time_in": "x", "time_out": "y"})), Some(8));
+        assert_eq!(pong_reqid(&serde_json::json!({"event": "pong"})), Some(0));
+        assert_eq!(pong_reqid(&serde_json::json!({"event": "heartbeat"})), None);
+        assert_eq!(pong_reqid(&serde_json::json!([1, {}, "book-10", "BTC/USD"])), None);
     }
 
     #[test]
@@ -290,6 +905,7 @@ mod tests {
             subscription: SubscriptionDetails {
                 name: "book".to_string(),
                 depth: Some(25),
+                interval: None,
             },
         };
 
@@ -0,0 +1,13 @@
+//! Orderbook arena library
+//! 
+//! The server binary (main.rs) is built on these modules; exposing them as a
+//! library also lets the benchmarks in `benches/` drive the engine directly.
+
+pub mod exchange;
+pub mod kraken;
+pub mod orderbook;
+pub mod config;
+pub mod api;
+pub mod arena;
+pub mod metrics;
+pub mod shutdown;
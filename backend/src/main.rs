@@ -1,22 +1,41 @@
-mod kraken;
-mod orderbook;
-mod config;
-mod api;
-
+use std::io::IsTerminal;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use std::collections::HashMap;
 use tokio::net::TcpListener;
 use tokio::sync::{broadcast, RwLock, Mutex};
-use crate::api::routes::{AppState, TickerData};
-use crate::kraken::client::{KrakenClient, KrakenMessage};
-use crate::kraken::types::{OhlcData, OhlcMessage, parse_book_snapshot, parse_book_delta, parse_ohlc_data};
-use crate::orderbook::engine::OrderbookEngine;
-use crate::orderbook::store::SnapshotStore;
-use crate::orderbook::integration::start_snapshot_storage_task;
-
-/// Mapping from ticker symbol to Kraken trading pair
-fn ticker_to_pair(ticker: &str) -> String {
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use orderbook_arena::api::routes::{AppState, TickerData};
+use orderbook_arena::api::websocket::ConnectionLimiter;
+use orderbook_arena::arena::analytics::ArenaAnalytics;
+use orderbook_arena::arena::arbitrage::ArbitrageDetector;
+use orderbook_arena::exchange::{reconnect_with_backoff, BookEvent, Exchange, ExchangeConnection, ExchangeSource};
+use orderbook_arena::exchange::binance::BinanceClient;
+use orderbook_arena::exchange::health::ConnectionHealth;
+use orderbook_arena::kraken::client::KrakenClient;
+use orderbook_arena::kraken::subscription::{SubscriptionState, OHLC_CHANNEL};
+use orderbook_arena::metrics::{TickerCounter, METRICS};
+use orderbook_arena::shutdown::{Shutdown, ShutdownSignal, SHUTDOWN_GRACE};
+use orderbook_arena::kraken::types::OhlcData;
+use orderbook_arena::config::{Config, SharedConfig};
+use orderbook_arena::orderbook::engine::{BookUpdate, DeltaOutcome, OrderbookEngine, OrderbookState};
+use orderbook_arena::orderbook::ohlc::{OhlcAggregator, CANDLE_IMBALANCE_DEPTH};
+use orderbook_arena::orderbook::store::SnapshotStore;
+use orderbook_arena::orderbook::trades::TradeStore;
+use orderbook_arena::orderbook::integration::start_snapshot_storage_task;
+
+/// Trading pair to subscribe to for a ticker on `source`
+fn ticker_to_pair(ticker: &str, source: ExchangeSource, pair_overrides: &HashMap<String, String>) -> String {
+    // Overrides are Kraken pairs such as XBT/USD; other clients map `{ticker}/USD` themselves
+    if source == ExchangeSource::Kraken {
+        if let Some(pair) = pair_overrides.get(ticker) {
+            return pair.clone();
+        }
+    }
     match ticker {
         "BTC" => "BTC/USD".to_string(),
         "ETH" => "ETH/USD".to_string(),
@@ -26,174 +45,820 @@ fn ticker_to_pair(ticker: &str) -> String {
     }
 }
 
-/// Start a Kraken connection for a specific ticker
-fn start_kraken_task(ticker: String, ticker_data: TickerData, book_depth: u32, ohlc_interval: u32) {
+/// Interval in minutes of the exchange candles each feed subscribes to
+const OHLC_INTERVAL_MINUTES: u32 = 1;
+
+/// Registry of ticker symbol to ticker data, shared with the API
+type TickerRegistry = Arc<Mutex<HashMap<String, TickerData>>>;
+
+/// Outcome of publishing an orderbook state to a ticker's broadcast channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PublishOutcome {
+    /// Delivered to this many subscribers
+    Delivered(usize),
+    /// No WebSocket clients are connected, so the update was dropped
+    NoSubscribers,
+}
+
+/// Broadcast an orderbook update, treating a send with no subscribers as benign
+/// 
+/// A broadcast send only fails when the channel has no receivers. That is normal
+/// before any client connects (or after the last one leaves), so the update is
+/// just dropped and the feed carries on.
+fn publish_update(sender: &broadcast::Sender<BookUpdate>, update: BookUpdate) -> PublishOutcome {
+    match sender.send(update) {
+        Ok(receivers) => PublishOutcome::Delivered(receivers),
+        Err(_) => {
+            tracing::trace!("No subscribers, orderbook update dropped");
+            PublishOutcome::NoSubscribers
+        }
+    }
+}
+
+/// What the feed task does with a book event, given the ticker's freeze flag
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FreezeAction {
+    /// Apply the message as usual
+    Apply,
+    /// Drop the message, leaving the frozen book as it is
+    Drop,
+    /// Messages were dropped while frozen: resubscribe to catch up
+    Resync,
+}
+
+/// Decide how to handle a book event
+/// 
+/// `dropped` records whether any message was dropped since the last resync.
+fn freeze_action(frozen: bool, dropped: &mut bool) -> FreezeAction {
+    if frozen {
+        *dropped = true;
+        FreezeAction::Drop
+    } else if std::mem::take(dropped) {
+        FreezeAction::Resync
+    } else {
+        FreezeAction::Apply
+    }
+}
+
+/// Start an exchange feed for a specific ticker
+/// 
+/// The task only sees the exchange's normalized `BookEvent`s, so it runs the
+/// same way for any `Exchange`. The ticker's data is looked up from the registry on every (re)connect, so a
+/// replaced registration is picked up and a removed ticker stops the task.
+/// Every applied book update also runs arbitrage detection for the ticker and
+/// samples its venues' mids for lead-lag analysis, and
+/// trades inferred from deltas are recorded in the trade store. On exchanges
+/// without a candle channel they are also rolled into 1-minute candles published
+/// on the ticker's OHLC channel, along with the book imbalance averaged over each
+/// candle; otherwise only the exchange's own candles are published there, so
+/// clients never get two interleaved candle series. Executed trades, where the
+/// exchange streams them, set the book's last price in place of inference.
+/// Book depth and reconnect backoff are read from `config` on each connect and
+/// gap handling on each gap. While the ticker
+/// is frozen its book events are dropped, and it resubscribes once unfrozen.
+/// Every message received is stamped in `health` for /health and /ready.
+/// Once `shutdown` fires, the task closes its exchange connection and returns.
+/// 
+/// The task runs in a `feed` span carrying the exchange, ticker and pair, so
+/// its log events don't repeat them.
+#[allow(clippy::too_many_arguments)]
+fn start_exchange_task<E: Exchange + Send + Sync + 'static>(
+    exchange: E,
+    ticker: String,
+    trading_pair: String,
+    tickers: TickerRegistry,
+    arena: Arc<ArenaAnalytics>,
+    arbitrage: Arc<ArbitrageDetector>,
+    trade_store: Arc<TradeStore>,
+    health: Arc<ConnectionHealth>,
+    config: SharedConfig,
+    mut shutdown: ShutdownSignal,
+) -> tokio::task::JoinHandle<()> {
+    let span = tracing::info_span!("feed", exchange = exchange.name(), ticker = %ticker, pair = %trading_pair);
     tokio::spawn(async move {
-        let client = KrakenClient::new();
-        let trading_pair = ticker_to_pair(&ticker);
-        eprintln!("Starting Kraken task for ticker {} ({})", ticker, trading_pair);
+        let mut candles = OhlcAggregator::default();
+        // Full books are written into this under the engine lock, reusing its
+        // vectors, and cloned for broadcast only once the lock is released
+        let mut full_state = OrderbookState::default();
+        let infer_candles = !E::CHANNELS.contains(&OHLC_CHANNEL);
+        let metrics = METRICS.ticker(&ticker);
+        tracing::info!("Starting feed task");
         
         loop {
-            match client.connect().await {
+            if shutdown.is_triggered() {
+                return;
+            }
+            let ticker_data = match tickers.lock().await.get(&ticker).cloned() {
+                Some(ticker_data) => ticker_data,
+                None => {
+                    tracing::info!("Ticker is no longer registered, stopping feed task");
+                    return;
+                }
+            };
+            let (book_depth, backoff) = {
+                let config = config.borrow();
+                (config.book_depth, config.reconnect_backoff())
+            };
+
+            let connected = tokio::select! {
+                connected = reconnect_with_backoff(&exchange, &backoff) => connected,
+                _ = shutdown.triggered() => return,
+            };
+            match connected {
                 Ok(mut connection) => {
-                    eprintln!("Connected to Kraken WebSocket for {}", ticker);
+                    tracing::info!("Connected to exchange");
+                    if let Some(subscription) = &ticker_data.subscription {
+                        subscription.write().await.reset();
+                    }
                     
                     // Subscribe to book channel
                     if let Err(e) = connection.subscribe_book(&trading_pair, Some(book_depth)).await {
-                        eprintln!("Failed to subscribe to book channel for {}: {}", ticker, e);
+                        tracing::error!(error = %e, "Failed to subscribe to book channel");
                         tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
                         continue;
                     }
                     
                     // Subscribe to OHLC channel
-                    if let Err(e) = connection.subscribe_ohlc(&trading_pair, ohlc_interval).await {
-                        eprintln!("Failed to subscribe to OHLC channel for {}: {}", ticker, e);
+                    if let Err(e) = connection.subscribe_ohlc(&trading_pair, OHLC_INTERVAL_MINUTES).await {
+                        tracing::error!(error = %e, "Failed to subscribe to OHLC channel");
+                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                        continue;
+                    }
+                    
+                    // Subscribe to trade channel, for the executed last price
+                    if let Err(e) = connection.subscribe_trade(&trading_pair).await {
+                        tracing::error!(error = %e, "Failed to subscribe to trade channel");
                         tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
                         continue;
                     }
                     
-                    // Track if we've received the initial snapshot
-                    // Kraken sends a full snapshot as the first message, then deltas
+                    // Track if we've received the initial snapshot; deltas before it are ignored
                     let mut received_initial_snapshot = false;
+                    let mut dropped_while_frozen = false;
+                    
+                    // A book that already received data is stale: discard it and broadcast the
+                    // empty, resyncing book so clients know it is resetting until the snapshot lands
+                    let resync_started = {
+                        let mut engine_guard = ticker_data.engine.write().await;
+                        // A frozen book is left alone; it resyncs once unfrozen
+                        if engine_guard.update_seq() > 0 && !ticker_data.is_frozen() {
+                            engine_guard.clear();
+                            engine_guard.begin_resync();
+                            engine_guard.get_current_state_into(&mut full_state);
+                            true
+                        } else {
+                            false
+                        }
+                    };
+                    if resync_started {
+                        let update = BookUpdate::Full(full_state.clone());
+                        tracing::info!("Resync started, awaiting fresh snapshot");
+                        publish_update(&ticker_data.orderbook_updates, update);
+                    }
                     
-                    // Process messages
+                    // Process events
                     loop {
-                        match connection.next_message().await {
-                            Ok(Some(KrakenMessage::Book(book_msg))) => {
-                                if let Some(book_data) = book_msg.book_data() {
-                                    if !received_initial_snapshot {
-                                        // First message: treat as full snapshot
-                                        match parse_book_snapshot(&book_data) {
-                                            Ok(snapshot) => {
-                                                eprintln!("[{}] Received initial snapshot: {} bids, {} asks", ticker, snapshot.bids.len(), snapshot.asks.len());
-                                                let mut engine_guard = ticker_data.engine.write().await;
-                                                if let Err(e) = engine_guard.apply_snapshot(&snapshot) {
-                                                    eprintln!("[{}] Error applying snapshot: {}", ticker, e);
-                                                } else {
-                                                    received_initial_snapshot = true;
-                                                    let state = engine_guard.get_current_state();
-                                                    let _ = ticker_data.orderbook_updates.send(state);
-                                                }
-                                            }
-                                            Err(e) => {
-                                                eprintln!("[{}] Error parsing initial snapshot: {}", ticker, e);
-                                            }
+                        let event = tokio::select! {
+                            event = connection.next_book_event() => event,
+                            _ = shutdown.triggered() => {
+                                if let Err(e) = connection.close().await {
+                                    tracing::debug!(error = %e, "Failed to close exchange connection");
+                                }
+                                tracing::info!("Shutting down, exchange connection closed");
+                                return;
+                            }
+                        };
+                        // Every frame, heartbeats and pongs included, shows the feed is alive
+                        if event.is_ok() {
+                            metrics.increment(TickerCounter::MessagesReceived);
+                            health.record_message(&ticker).await;
+                        }
+                        // Book events are dropped while the ticker is frozen
+                        let event = match event {
+                            Ok(event @ (BookEvent::Snapshot(_) | BookEvent::Delta(_))) => {
+                                match freeze_action(ticker_data.is_frozen(), &mut dropped_while_frozen) {
+                                    FreezeAction::Apply => Ok(event),
+                                    FreezeAction::Drop => continue,
+                                    FreezeAction::Resync => {
+                                        tracing::info!("Book unfrozen, resubscribing for a fresh snapshot");
+                                        break;
+                                    }
+                                }
+                            }
+                            other => other,
+                        };
+                        match event {
+                            Ok(BookEvent::Snapshot(snapshot)) => {
+                                tracing::info!(bids = snapshot.bids.len(), asks = snapshot.asks.len(), "Received initial snapshot");
+                                let applied = {
+                                    let mut engine_guard = ticker_data.engine.write().await;
+                                    match engine_guard.apply_snapshot(&snapshot) {
+                                        Ok(()) => {
+                                            engine_guard.get_current_state_into(&mut full_state);
+                                            true
                                         }
-                                    } else {
-                                        // Subsequent messages: treat as deltas
-                                        match parse_book_delta(&book_data) {
-                                            Ok(delta) => {
-                                                let mut engine_guard = ticker_data.engine.write().await;
-                                                if let Err(e) = engine_guard.apply_delta(&delta) {
-                                                    eprintln!("[{}] Error applying delta: {}", ticker, e);
-                                                } else {
-                                                    let state = engine_guard.get_current_state();
-                                                    let _ = ticker_data.orderbook_updates.send(state);
-                                                }
-                                            }
-                                            Err(e) => {
-                                                eprintln!("[{}] Error parsing delta: {}", ticker, e);
-                                            }
+                                        Err(e) => {
+                                            tracing::error!(error = %e, "Error applying snapshot");
+                                            false
                                         }
                                     }
+                                };
+                                if applied {
+                                    received_initial_snapshot = true;
+                                    let update = BookUpdate::Full(full_state.clone());
+                                    publish_update(&ticker_data.orderbook_updates, update);
+                                    arbitrage.check(&ticker).await;
+                                    arena.record_mids(&ticker).await;
                                 }
                             }
-                            Ok(Some(KrakenMessage::Ohlc(ohlc_msg))) => {
-                                // Parse and broadcast OHLC data
-                                let OhlcMessage::ArrayFormat(arr) = ohlc_msg;
-                                if arr.len() >= 2 {
-                                    match parse_ohlc_data(&arr[1]) {
-                                        Ok(ohlc_data) => {
-                                            let _ = ticker_data.ohlc_updates.send(ohlc_data);
+                            Ok(BookEvent::Delta(_)) if !received_initial_snapshot => {
+                                tracing::debug!("Ignoring book update received before the initial snapshot");
+                            }
+                            Ok(BookEvent::Delta(delta)) => {
+                                // Only the changed levels are broadcast; clients apply them
+                                // to the full book they received on connect
+                                let (changes, trades, checksum_ok, outcome, imbalance) = {
+                                    let mut engine_guard = ticker_data.engine.write().await;
+                                    match engine_guard.apply_delta(&delta) {
+                                        Ok(outcome) => {
+                                            metrics.increment(TickerCounter::DeltasApplied);
+                                            (
+                                                Some(engine_guard.take_changes()),
+                                                engine_guard.take_trades(),
+                                                delta.checksum.is_none_or(|expected| engine_guard.verify_checksum(expected)),
+                                                outcome,
+                                                engine_guard.imbalance(CANDLE_IMBALANCE_DEPTH).filter(|_| infer_candles),
+                                            )
                                         }
                                         Err(e) => {
-                                            eprintln!("[{}] Error parsing OHLC data: {}", ticker, e);
+                                            tracing::error!(error = %e, "Error applying delta");
+                                            (None, Vec::new(), true, DeltaOutcome::default(), None)
                                         }
                                     }
+                                };
+                                // Sampled at exchange time, the clock trade timestamps and candles use
+                                if let (Some(timestamp), Some(imbalance)) = (outcome.max_timestamp, imbalance) {
+                                    candles.sample_imbalance(timestamp, imbalance);
+                                }
+                                for trade in trades {
+                                    tracing::debug!(price = trade.price, volume = trade.volume, timestamp_ms = trade.timestamp_ms, "Trade detected");
+                                    if infer_candles {
+                                        if let Some(candle) = candles.record(trade.timestamp_ms as f64 / 1000.0, trade.price, trade.volume) {
+                                            let _ = ticker_data.ohlc_updates.send(candle);
+                                        }
+                                    }
+                                    trade_store.store_trade(&ticker, trade).await;
+                                }
+                                if !checksum_ok {
+                                    // Local book diverged from the exchange's; reconnecting resubscribes and
+                                    // marks the book resyncing until the fresh snapshot lands
+                                    tracing::warn!("Book checksum mismatch, resubscribing for a fresh snapshot");
+                                    break;
+                                }
+                                if outcome.possible_gap {
+                                    tracing::warn!(max_timestamp = ?outcome.max_timestamp, "Possible gap: delta timestamp is older than the newest seen");
+                                    if config.borrow().resubscribe_on_gap {
+                                        tracing::info!("Resubscribing for a fresh snapshot after possible gap");
+                                        break;
+                                    }
+                                }
+                                if let Some(changes) = changes {
+                                    let update = BookUpdate::Diff(changes);
+                                    publish_update(&ticker_data.orderbook_updates, update);
+                                    arbitrage.check(&ticker).await;
+                                    arena.record_mids(&ticker).await;
                                 }
                             }
-                            Ok(Some(KrakenMessage::SubscriptionStatus(status))) => {
-                                eprintln!("[{}] Subscription status: {:?}", ticker, status);
+                            Ok(BookEvent::Candle(candle)) => {
+                                let _ = ticker_data.ohlc_updates.send(candle);
                             }
-                            Ok(Some(KrakenMessage::Close)) => {
-                                eprintln!("[{}] Kraken connection closed", ticker);
-                                break;
+                            // A frozen book's last price is held too
+                            Ok(BookEvent::Trade(_)) if ticker_data.is_frozen() => {}
+                            Ok(BookEvent::Trade(trade)) => {
+                                let mut engine_guard = ticker_data.engine.write().await;
+                                engine_guard.use_trade_prices();
+                                engine_guard.set_last_price(trade.price);
                             }
-                            Ok(None) => {
-                                // Unknown message type, continue
+                            Ok(BookEvent::Status(status)) => {
+                                tracing::info!(channel = %status.channel, subscribed = status.subscribed, "Subscription status");
+                                if let Some(subscription) = &ticker_data.subscription {
+                                    subscription.write().await.apply_status(&status);
+                                }
+                            }
+                            Ok(BookEvent::Malformed) => {
+                                metrics.increment(TickerCounter::ParseFailures);
+                            }
+                            Ok(BookEvent::Heartbeat) => {}
+                            Ok(BookEvent::Close) => {
+                                tracing::info!("Exchange connection closed");
+                                break;
                             }
                             Err(e) => {
-                                eprintln!("[{}] Error receiving message from Kraken: {}", ticker, e);
+                                tracing::error!(error = format!("{:#}", e), "Error receiving from exchange");
+                                match exchange.retry_delay(&e) {
+                                    None => {
+                                        tracing::error!("Exchange rejected the pair permanently, stopping feed task");
+                                        return;
+                                    }
+                                    Some(delay) if !delay.is_zero() => {
+                                        tracing::warn!(delay = ?delay, "Waiting before reconnecting");
+                                        tokio::time::sleep(delay).await;
+                                    }
+                                    Some(_) => {}
+                                }
                                 break;
                             }
                         }
                     }
                 }
                 Err(e) => {
-                    eprintln!("[{}] Failed to connect to Kraken: {}. Retrying in 5 seconds...", ticker, e);
-                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    // Backoff exhausted; start a new backoff cycle
+                    tracing::error!(error = %e, "Failed to connect to exchange");
                 }
             }
         }
-    });
+    }.instrument(span))
+}
+
+/// Start a feed that keeps an arena-only venue's book for a ticker
+/// 
+/// Unlike `start_exchange_task`, the book is not registered as a ticker, so
+/// nothing is published, stored or health-checked: the task only applies book
+/// events to `engine`, checks the asset for arbitrage and samples its venue
+/// mids after each one, which gives cross-venue analytics such as lead-lag a
+/// second series to compare.
+#[allow(clippy::too_many_arguments)]
+fn start_venue_task<E: Exchange + Send + Sync + 'static>(
+    exchange: E,
+    ticker: String,
+    trading_pair: String,
+    engine: Arc<RwLock<OrderbookEngine>>,
+    arena: Arc<ArenaAnalytics>,
+    arbitrage: Arc<ArbitrageDetector>,
+    config: SharedConfig,
+    mut shutdown: ShutdownSignal,
+) -> tokio::task::JoinHandle<()> {
+    let span = tracing::info_span!("venue", exchange = exchange.name(), ticker = %ticker, pair = %trading_pair);
+    tokio::spawn(async move {
+        tracing::info!("Starting arena venue feed task");
+        loop {
+            let (book_depth, backoff) = {
+                let config = config.borrow();
+                (config.book_depth, config.reconnect_backoff())
+            };
+            let mut connection = tokio::select! {
+                connected = reconnect_with_backoff(&exchange, &backoff) => match connected {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        tracing::error!(error = %e, "Failed to connect to exchange");
+                        continue;
+                    }
+                },
+                _ = shutdown.triggered() => return,
+            };
+            if let Err(e) = connection.subscribe_book(&trading_pair, Some(book_depth)).await {
+                tracing::error!(error = %e, "Failed to subscribe to book channel");
+                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                continue;
+            }
+
+            let mut received_initial_snapshot = false;
+            loop {
+                let event = tokio::select! {
+                    event = connection.next_book_event() => event,
+                    _ = shutdown.triggered() => {
+                        if let Err(e) = connection.close().await {
+                            tracing::debug!(error = %e, "Failed to close exchange connection");
+                        }
+                        return;
+                    }
+                };
+                let applied = match event {
+                    Ok(BookEvent::Snapshot(snapshot)) => {
+                        received_initial_snapshot = true;
+                        engine.write().await.apply_snapshot(&snapshot).map(|_| ())
+                    }
+                    Ok(BookEvent::Delta(delta)) if received_initial_snapshot => {
+                        engine.write().await.apply_delta(&delta).map(|_| ())
+                    }
+                    Ok(BookEvent::Close) => {
+                        tracing::info!("Exchange connection closed");
+                        break;
+                    }
+                    Ok(_) => continue,
+                    Err(e) => {
+                        tracing::error!(error = format!("{:#}", e), "Error receiving from exchange");
+                        match exchange.retry_delay(&e) {
+                            None => {
+                                tracing::error!("Exchange rejected the pair permanently, stopping venue task");
+                                return;
+                            }
+                            Some(delay) => tokio::time::sleep(delay).await,
+                        }
+                        break;
+                    }
+                };
+                match applied {
+                    // This venue's book can cross the primary's, so it is checked too
+                    Ok(()) => {
+                        arbitrage.check(&ticker).await;
+                        arena.record_mids(&ticker).await;
+                    }
+                    Err(e) => tracing::error!(error = %e, "Error applying book event"),
+                }
+            }
+        }
+    }.instrument(span))
+}
+
+/// Filter used when `RUST_LOG` is unset or invalid: this crate at info, the HTTP trace layer at debug
+const DEFAULT_LOG_FILTER: &str = "orderbook_arena=info,tower_http=debug";
+
+/// Build the log filter from `RUST_LOG`-style directives
+/// 
+/// Directives are `EnvFilter`'s, so besides `orderbook_arena=debug` they can
+/// select spans by field, e.g. `[feed{ticker=BTC}]=debug` for one ticker's feed.
+fn log_filter(directives: Option<&str>) -> EnvFilter {
+    match directives {
+        Some(directives) => EnvFilter::try_new(directives).unwrap_or_else(|e| {
+            eprintln!("Ignoring invalid RUST_LOG {:?}: {}", directives, e);
+            EnvFilter::new(DEFAULT_LOG_FILTER)
+        }),
+        None => EnvFilter::new(DEFAULT_LOG_FILTER),
+    }
+}
+
+/// Install the global tracing subscriber, writing to stderr, filtered by `RUST_LOG`
+fn init_tracing() {
+    let directives = std::env::var("RUST_LOG").ok();
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_ansi(std::io::stderr().is_terminal()))
+        .with(log_filter(directives.as_deref()))
+        .init();
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let config = config::Config::from_env();
+    init_tracing();
+    let config = Config::from_env();
+    if let Err(errors) = config.validate() {
+        for error in &errors {
+            tracing::error!(problem = %error, "Invalid configuration");
+        }
+        std::process::exit(1);
+    }
     
     // Create shared state
-    let snapshot_store = Arc::new(SnapshotStore::new());
+    let mut snapshot_store = SnapshotStore::new()
+        .with_clock_skew_policy(config.clock_skew_policy)
+        .with_keyframe_interval(config.snapshot_keyframe_interval);
+    if let Some(dir) = &config.snapshot_persistence_dir {
+        snapshot_store = snapshot_store.with_persistence(dir.clone())?;
+    }
+    let snapshot_store = Arc::new(snapshot_store);
+    let trade_store = Arc::new(TradeStore::new());
     
     // Initialize tickers map with default tickers
     let tickers_map = Arc::new(Mutex::new(HashMap::new()));
+    let arena = Arc::new(ArenaAnalytics::new());
+    let arbitrage = Arc::new(ArbitrageDetector::new(arena.clone(), config.arbitrage_threshold_bps));
+    let connection_health = Arc::new(ConnectionHealth::new());
+    let shutdown = Arc::new(Shutdown::new());
+    // Tasks and handlers read tunable fields from here, so they can be changed live
+    let shared_config = SharedConfig::new(config.clone());
     
-    // Start Kraken connections for all supported tickers
+    // Start exchange feeds for all supported tickers
     let supported_tickers = vec!["ZEC", "BTC", "ETH", "XMR"];
     for ticker in supported_tickers {
-        let engine = Arc::new(RwLock::new(OrderbookEngine::new()));
-        let (orderbook_updates_tx, _) = broadcast::channel::<crate::orderbook::engine::OrderbookState>(100);
+        let mut engine = OrderbookEngine::new(config.tick_sizes.get(ticker).copied())
+            .with_max_depth(config.book_depth as usize)
+            .with_max_levels(config.max_levels)
+            .with_timestamp_policy(config.timestamp_policy_for(ticker))
+            .with_smoothing_alpha(config.smoothing_alpha);
+        if let Some(scale) = config.display_scales.get(ticker) {
+            engine = engine.with_display_scale(*scale);
+        }
+        let engine = Arc::new(RwLock::new(engine));
+        let (orderbook_updates_tx, _) = broadcast::channel::<BookUpdate>(100);
         let (ohlc_updates_tx, _) = broadcast::channel::<OhlcData>(100);
         
+        let source = config.source_for(ticker);
+        let trading_pair = ticker_to_pair(ticker, source, &config.pair_overrides);
+        let subscription = SubscriptionState::new(&trading_pair, config.book_depth, OHLC_INTERVAL_MINUTES, source.channels());
         let ticker_data = TickerData {
             orderbook_updates: orderbook_updates_tx,
             ohlc_updates: ohlc_updates_tx,
             engine: engine.clone(),
+            subscription: Some(Arc::new(RwLock::new(subscription))),
+            frozen: Arc::new(AtomicBool::new(false)),
         };
         
         // Store in map
         {
             let mut tickers = tickers_map.lock().await;
-            tickers.insert(ticker.to_string(), ticker_data.clone());
+            tickers.insert(ticker.to_string(), ticker_data);
+        }
+        
+        // Track this ticker's book as a venue in the arena
+        arena.register_venue(ticker, source.name(), engine.clone()).await;
+        
+        // A second exchange's book for the same asset, tracked only in the arena
+        if let Some(arena_source) = config.arena_source_for(ticker) {
+            let venue_engine = OrderbookEngine::new(config.tick_sizes.get(ticker).copied())
+                .with_max_depth(config.book_depth as usize)
+                .with_max_levels(config.max_levels)
+                .with_timestamp_policy(config.timestamp_policy_for(ticker));
+            let venue_engine = Arc::new(RwLock::new(venue_engine));
+            arena.register_venue(ticker, arena_source.name(), venue_engine.clone()).await;
+            let venue_pair = ticker_to_pair(ticker, arena_source, &config.pair_overrides);
+            let venue = match arena_source {
+                ExchangeSource::Kraken => start_venue_task(
+                    KrakenClient::new().with_compression(config.kraken_compression),
+                    ticker.to_string(),
+                    venue_pair,
+                    venue_engine,
+                    arena.clone(),
+                    arbitrage.clone(),
+                    shared_config.clone(),
+                    shutdown.signal(),
+                ),
+                ExchangeSource::Binance => start_venue_task(
+                    BinanceClient::new(&venue_pair),
+                    ticker.to_string(),
+                    venue_pair,
+                    venue_engine,
+                    arena.clone(),
+                    arbitrage.clone(),
+                    shared_config.clone(),
+                    shutdown.signal(),
+                ),
+            };
+            shutdown.register(venue).await;
         }
         
-        // Start Kraken connection task for this ticker with 1-minute OHLC as default
-        start_kraken_task(ticker.to_string(), ticker_data.clone(), config.book_depth, 1);
+        // Start the feed task for this ticker on its configured exchange
+        let feed = match source {
+            ExchangeSource::Kraken => start_exchange_task(
+                {
+                    let client = KrakenClient::new().with_compression(config.kraken_compression);
+                    match config.ping_interval_secs {
+                        Some(secs) => client.with_ping_interval(std::time::Duration::from_secs(secs)),
+                        None => client,
+                    }
+                },
+                ticker.to_string(),
+                trading_pair,
+                tickers_map.clone(),
+                arena.clone(),
+                arbitrage.clone(),
+                trade_store.clone(),
+                connection_health.clone(),
+                shared_config.clone(),
+                shutdown.signal(),
+            ),
+            ExchangeSource::Binance => start_exchange_task(
+                BinanceClient::new(&trading_pair),
+                ticker.to_string(),
+                trading_pair,
+                tickers_map.clone(),
+                arena.clone(),
+                arbitrage.clone(),
+                trade_store.clone(),
+                connection_health.clone(),
+                shared_config.clone(),
+                shutdown.signal(),
+            ),
+        };
+        shutdown.register(feed).await;
         
         // Start snapshot storage task for this ticker
-        start_snapshot_storage_task(ticker.to_string(), engine.clone(), snapshot_store.clone(), config.clone());
+        let storage = start_snapshot_storage_task(
+            ticker.to_string(),
+            engine.clone(),
+            snapshot_store.clone(),
+            trade_store.clone(),
+            shared_config.clone(),
+        );
+        shutdown.register_abortable(storage).await;
     }
     
     // Create AppState
     let app_state = AppState {
         snapshot_store,
+        trade_store,
         tickers: tickers_map,
+        config: shared_config,
+        arena,
+        arbitrage,
+        connection_health,
+        websocket_connections: Arc::new(ConnectionLimiter::new()),
     };
     
     // Create router with REST routes and WebSocket handler
-    let app = api::routes::create_router(app_state);
+    let app = orderbook_arena::api::routes::create_router(app_state, &config.allowed_origins);
     
-    // Bind to the configured port
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    // Bind to the configured address and port
+    let addr = SocketAddr::new(config.bind_address, config.port);
     let listener = TcpListener::bind(addr).await?;
     
-    eprintln!("Server listening on http://{}", addr);
-    eprintln!("WebSocket endpoint: ws://{}/live?ticker=<TICKER>", addr);
-    eprintln!("REST endpoints:");
-    eprintln!("  GET /snapshot/:ticker/:timestamp");
-    eprintln!("  GET /history/:ticker");
+    tracing::info!("Server listening on http://{}", addr);
+    tracing::info!("WebSocket endpoint: ws://{}/live?ticker=<TICKER>", addr);
+    tracing::info!("WebSocket endpoint: ws://{}/arbitrage?asset=<ASSET>", addr);
+    tracing::info!("REST endpoints:");
+    tracing::info!("  GET /snapshot/:ticker/:timestamp?mode=nearest");
+    tracing::info!("  GET /snapshots/:ticker?from=&to=");
+    tracing::info!("  GET /export/:ticker");
+    tracing::info!("  GET /diff/:ticker?from=&to=");
+    tracing::info!("  GET /history");
+    tracing::info!("  GET /history/:ticker");
+    tracing::info!("  GET /stats/:ticker");
+    tracing::info!("  GET /health");
+    tracing::info!("  GET /ready");
+    tracing::info!("  GET /metrics");
+    tracing::info!("  GET /tickers");
+    tracing::info!("  GET /sse/:ticker");
+    tracing::info!("  GET /orderbook/:ticker");
+    tracing::info!("  GET /spread/:ticker");
+    tracing::info!("  GET /spread_history/:ticker");
+    tracing::info!("  GET /depth/:ticker?levels=N");
+    tracing::info!("  GET /depth_curve/:ticker");
+    tracing::info!("  GET /imbalance/:ticker?depth=N");
+    tracing::info!("  GET /liquidity/:ticker?bps=N");
+    tracing::info!("  GET /trades/:ticker/csv?start=&end=");
+    tracing::info!("  GET /subscriptions/:ticker");
+    tracing::info!("  GET /ticker_info/:ticker");
+    tracing::info!("  GET /instruments/:ticker");
+    tracing::info!("  GET /arena/:asset/imbalance");
+    tracing::info!("  GET /arena/:asset/mid");
+    tracing::info!("  GET /arena/:asset/leadlag?window=N&max_lag=M");
+    tracing::info!("  GET /arena/health");
+    tracing::info!("  GET /admin/selfcheck (requires ADMIN_TOKEN)");
+    tracing::info!("  PATCH /admin/config (requires ADMIN_TOKEN)");
+    tracing::info!("  POST /admin/tickers/:ticker/freeze, /admin/tickers/:ticker/unfreeze (requires ADMIN_TOKEN)");
     
-    axum::serve(listener, app).await?;
+    // Ctrl-C stops the feeds and storage tasks and drains the server
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            if let Err(e) = tokio::signal::ctrl_c().await {
+                tracing::error!(error = %e, "Failed to listen for Ctrl-C, graceful shutdown disabled");
+                return;
+            }
+            tracing::info!("Shutdown requested");
+            shutdown.trigger();
+        }
+    });
+    
+    // Open WebSocket streams can hold the drain up, so it gets the same grace period as the tasks
+    let mut server_signal = shutdown.signal();
+    let mut drain_signal = shutdown.signal();
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown(async move { server_signal.triggered().await });
+    tokio::select! {
+        served = std::future::IntoFuture::into_future(server) => served?,
+        _ = async move {
+            drain_signal.triggered().await;
+            tokio::time::sleep(SHUTDOWN_GRACE).await;
+        } => tracing::warn!("Connections still open after the grace period, closing them"),
+    }
     
+    let aborted = shutdown.finish(SHUTDOWN_GRACE).await;
+    tracing::info!(aborted_feeds = aborted, "Shutdown complete");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_state() -> BookUpdate {
+        BookUpdate::Full(OrderbookEngine::default().get_current_state())
+    }
+
+    fn ticker_data() -> TickerData {
+        let (orderbook_updates, _) = broadcast::channel::<BookUpdate>(100);
+        let (ohlc_updates, _) = broadcast::channel::<OhlcData>(100);
+        TickerData {
+            orderbook_updates,
+            ohlc_updates,
+            engine: Arc::new(RwLock::new(OrderbookEngine::default())),
+            subscription: None,
+            frozen: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    #[test]
+    fn test_publish_update_outcomes() {
+        let sender = ticker_data().orderbook_updates;
+        // Other handles on the sender don't affect the outcome
+        let _held = sender.clone();
+
+        // No clients connected yet: benign
+        assert_eq!(sender.receiver_count(), 0);
+        assert_eq!(publish_update(&sender, empty_state()), PublishOutcome::NoSubscribers);
+
+        // A connected client receives the update
+        let mut receiver = sender.subscribe();
+        assert_eq!(publish_update(&sender, empty_state()), PublishOutcome::Delivered(1));
+        assert!(receiver.try_recv().is_ok());
+
+        // The last client leaving makes it benign again
+        drop(receiver);
+        assert_eq!(sender.receiver_count(), 0);
+        assert_eq!(publish_update(&sender, empty_state()), PublishOutcome::NoSubscribers);
+    }
+
+    /// Messages of the events a subscriber filtered by `directives` lets through
+    fn logged_with(directives: &str, emit: impl FnOnce()) -> String {
+        #[derive(Clone, Default)]
+        struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().with_writer(move || writer.clone()).with_ansi(false))
+            .with(log_filter(Some(directives)));
+        tracing::subscriber::with_default(subscriber, emit);
+        let logged = captured.0.lock().unwrap().clone();
+        String::from_utf8(logged).unwrap()
+    }
+
+    #[test]
+    fn test_log_filter_directives() {
+        let emit = || {
+            tracing::debug!("crate debug");
+            tracing::debug!(target: "tower_http::trace", "other crate debug");
+            tracing::info_span!("feed", ticker = %"BTC").in_scope(|| tracing::debug!("BTC feed debug"));
+            tracing::info_span!("feed", ticker = %"ETH").in_scope(|| tracing::debug!("ETH feed debug"));
+        };
+
+        // The directive from the docs matches this crate's events, and only them
+        let logged = logged_with("orderbook_arena=debug", emit);
+        assert!(logged.contains("crate debug"));
+        assert!(!logged.contains("other crate debug"));
+        assert!(logged.contains("BTC feed debug") && logged.contains("ETH feed debug"));
+
+        // A span field selects a single ticker
+        let logged = logged_with("info,[feed{ticker=BTC}]=debug", emit);
+        assert!(logged.contains("BTC feed debug"));
+        assert!(!logged.contains("ETH feed debug"));
+        assert!(!logged.contains("crate debug"));
+    }
+
+    #[test]
+    fn test_ticker_to_pair_prefers_overrides() {
+        let config = Config::new().with_pair_override("SOL", "SOL/EUR").with_pair_override("BTC", "XBT/USD");
+        assert_eq!(ticker_to_pair("SOL", ExchangeSource::Kraken, &config.pair_overrides), "SOL/EUR");
+        assert_eq!(ticker_to_pair("BTC", ExchangeSource::Kraken, &config.pair_overrides), "XBT/USD");
+        assert_eq!(ticker_to_pair("ETH", ExchangeSource::Kraken, &config.pair_overrides), "ETH/USD");
+        assert_eq!(ticker_to_pair("DOGE", ExchangeSource::Kraken, &config.pair_overrides), "DOGE/USD");
+
+        // Overrides name Kraken pairs, so other exchanges ignore them
+        assert_eq!(ticker_to_pair("BTC", ExchangeSource::Binance, &config.pair_overrides), "BTC/USD");
+    }
+
+    #[test]
+    fn test_frozen_book_ignores_deltas_until_unfrozen() {
+        use orderbook_arena::kraken::types::{BookDelta, BookSnapshot};
+        use std::sync::atomic::Ordering;
+
+        let data = ticker_data();
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "1.0"])],
+            asks: vec![serde_json::json!(["101.0", "1.0", "1.0"])],
+        }).unwrap();
+        let delta = BookDelta { bids: vec![serde_json::json!(["100.5", "2.0", "2.0"])], asks: vec![], checksum: None };
+
+        // Apply the delta only when the task would
+        let mut dropped = false;
+        let mut handle = |engine: &mut OrderbookEngine| {
+            let action = freeze_action(data.is_frozen(), &mut dropped);
+            if action == FreezeAction::Apply {
+                engine.apply_delta(&delta).unwrap();
+            }
+            action
+        };
+
+        data.frozen.store(true, Ordering::Relaxed);
+        let frozen_state = engine.get_current_state();
+        assert_eq!(handle(&mut engine), FreezeAction::Drop);
+        assert_eq!(handle(&mut engine), FreezeAction::Drop);
+        assert_eq!(serde_json::to_value(engine.get_current_state().bids).unwrap(), serde_json::to_value(frozen_state.bids).unwrap());
+
+        // The first message after unfreezing triggers a resync, then updates apply again
+        data.frozen.store(false, Ordering::Relaxed);
+        assert_eq!(handle(&mut engine), FreezeAction::Resync);
+        assert_eq!(handle(&mut engine), FreezeAction::Apply);
+        assert_eq!(engine.top_bids().first(), Some(&(100.5, 2.0)));
+    }
+}
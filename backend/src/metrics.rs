@@ -0,0 +1,194 @@
+//! Process-wide counters exported in Prometheus text format at /metrics
+//! 
+//! Counters are plain atomics so recording is cheap on the hot path; per-ticker
+//! counters are registered once, and tasks keep the returned handle so recording
+//! never takes the registry lock. Gauges derived from the books themselves
+//! (such as depth) are computed when scraped rather than tracked here.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Process-wide metrics shared by the Kraken tasks, storage tasks and API
+pub static METRICS: Metrics = Metrics::new();
+
+/// Per-ticker counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TickerCounter {
+    /// Messages received from the exchange for the ticker
+    MessagesReceived,
+    /// Book deltas applied to the ticker's engine
+    DeltasApplied,
+    /// Snapshots written to the snapshot store
+    SnapshotsStored,
+    /// Exchange messages that couldn't be parsed or had no usable data
+    ParseFailures,
+}
+
+impl TickerCounter {
+    const ALL: [TickerCounter; 4] = [
+        TickerCounter::MessagesReceived,
+        TickerCounter::DeltasApplied,
+        TickerCounter::SnapshotsStored,
+        TickerCounter::ParseFailures,
+    ];
+
+    /// Metric name and help text
+    fn describe(&self) -> (&'static str, &'static str) {
+        match self {
+            TickerCounter::MessagesReceived => ("orderbook_messages_received_total", "Messages received from the exchange"),
+            TickerCounter::DeltasApplied => ("orderbook_deltas_applied_total", "Book deltas applied"),
+            TickerCounter::SnapshotsStored => ("orderbook_snapshots_stored_total", "Snapshots written to the snapshot store"),
+            TickerCounter::ParseFailures => ("orderbook_parse_failures_total", "Exchange messages that could not be parsed"),
+        }
+    }
+}
+
+/// Counters of one ticker, indexed like `TickerCounter::ALL`
+type TickerCounters = [AtomicU64; TickerCounter::ALL.len()];
+
+/// Registry of every exported counter
+#[derive(Debug)]
+pub struct Metrics {
+    tickers: Mutex<BTreeMap<String, Arc<TickerCounters>>>,
+    /// WebSocket connections accepted since startup
+    websocket_connections: AtomicU64,
+    /// WebSocket clients currently connected
+    websocket_clients: AtomicU64,
+}
+
+impl Metrics {
+    /// Create a registry with every counter at zero
+    pub const fn new() -> Self {
+        Self {
+            tickers: Mutex::new(BTreeMap::new()),
+            websocket_connections: AtomicU64::new(0),
+            websocket_clients: AtomicU64::new(0),
+        }
+    }
+
+    /// Counters of a ticker, registering it on first use
+    /// 
+    /// Takes the registry lock, so look a ticker up once per task and keep the handle.
+    pub fn ticker(&self, ticker: &str) -> TickerMetrics {
+        let mut tickers = self.tickers.lock().unwrap();
+        let counters = match tickers.get(ticker) {
+            Some(counters) => counters.clone(),
+            None => tickers.entry(ticker.to_string()).or_default().clone(),
+        };
+        TickerMetrics { counters }
+    }
+
+    /// Count a WebSocket client until the returned guard is dropped
+    pub fn websocket_connected(&'static self) -> WebSocketClientGuard {
+        self.websocket_connections.fetch_add(1, Ordering::Relaxed);
+        self.websocket_clients.fetch_add(1, Ordering::Relaxed);
+        WebSocketClientGuard { metrics: self }
+    }
+
+    /// Render every metric in Prometheus text format
+    /// 
+    /// `book_depths` holds (ticker, bid levels, ask levels) for the depth gauge.
+    pub fn render(&self, book_depths: &[(String, usize, usize)]) -> String {
+        let mut out = String::new();
+        let tickers: Vec<(String, Arc<TickerCounters>)> = {
+            let tickers = self.tickers.lock().unwrap();
+            tickers.iter().map(|(ticker, counters)| (ticker.clone(), counters.clone())).collect()
+        };
+
+        for counter in TickerCounter::ALL {
+            let (name, help) = counter.describe();
+            header(&mut out, name, help, "counter");
+            for (ticker, counters) in &tickers {
+                let value = counters[counter as usize].load(Ordering::Relaxed);
+                let _ = writeln!(out, "{}{{ticker=\"{}\"}} {}", name, escape(ticker), value);
+            }
+        }
+
+        header(&mut out, "websocket_connections_total", "WebSocket connections accepted", "counter");
+        let _ = writeln!(out, "websocket_connections_total {}", self.websocket_connections.load(Ordering::Relaxed));
+        header(&mut out, "websocket_clients", "WebSocket clients currently connected", "gauge");
+        let _ = writeln!(out, "websocket_clients {}", self.websocket_clients.load(Ordering::Relaxed));
+
+        header(&mut out, "orderbook_depth_levels", "Price levels currently in the book", "gauge");
+        for (ticker, bids, asks) in book_depths {
+            let ticker = escape(ticker);
+            let _ = writeln!(out, "orderbook_depth_levels{{ticker=\"{}\",side=\"bid\"}} {}", ticker, bids);
+            let _ = writeln!(out, "orderbook_depth_levels{{ticker=\"{}\",side=\"ask\"}} {}", ticker, asks);
+        }
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle to one ticker's counters, incremented without locking
+#[derive(Debug, Clone)]
+pub struct TickerMetrics {
+    counters: Arc<TickerCounters>,
+}
+
+impl TickerMetrics {
+    /// Increment one of the ticker's counters
+    pub fn increment(&self, counter: TickerCounter) {
+        self.counters[counter as usize].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Keeps a WebSocket client counted in `websocket_clients` while alive
+pub struct WebSocketClientGuard {
+    metrics: &'static Metrics,
+}
+
+impl Drop for WebSocketClientGuard {
+    fn drop(&mut self) {
+        self.metrics.websocket_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escape a label value per the Prometheus text format
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counters_and_gauges() {
+        static LOCAL: Metrics = Metrics::new();
+        let btc = LOCAL.ticker("BTC");
+        btc.increment(TickerCounter::MessagesReceived);
+        btc.increment(TickerCounter::MessagesReceived);
+        LOCAL.ticker("ETH").increment(TickerCounter::ParseFailures);
+        // Looking a ticker up again shares its counters
+        LOCAL.ticker("BTC").increment(TickerCounter::DeltasApplied);
+        let guard = LOCAL.websocket_connected();
+
+        let text = LOCAL.render(&[("BTC".to_string(), 3, 2)]);
+        assert!(text.contains("# TYPE orderbook_messages_received_total counter\n"));
+        assert!(text.contains("orderbook_messages_received_total{ticker=\"BTC\"} 2\n"));
+        assert!(text.contains("orderbook_messages_received_total{ticker=\"ETH\"} 0\n"));
+        assert!(text.contains("orderbook_parse_failures_total{ticker=\"ETH\"} 1\n"));
+        assert!(text.contains("orderbook_deltas_applied_total{ticker=\"BTC\"} 1\n"));
+        assert!(text.contains("websocket_clients 1\n"));
+        assert!(text.contains("orderbook_depth_levels{ticker=\"BTC\",side=\"ask\"} 2\n"));
+
+        drop(guard);
+        let text = LOCAL.render(&[]);
+        assert!(text.contains("websocket_connections_total 1\n"));
+        assert!(text.contains("websocket_clients 0\n"));
+        assert_eq!(escape("a\"b"), "a\\\"b");
+    }
+}
@@ -1,20 +1,181 @@
 use std::collections::BTreeMap;
-use std::cmp::Ordering;
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::kraken::types::{BookSnapshot, BookDelta, parse_price_level};
+use std::ops::Bound::{Excluded, Unbounded};
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::kraken::types::{BookSnapshot, BookDelta, PriceLevel, parse_price_level, price_level_precision};
+use crate::orderbook::snapshot::Snapshot;
+use crate::orderbook::trades::{DetectedTrade, TradeSide};
+use crate::orderbook::mid_range::RollingRange;
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
 
-/// Wrapper for f64 that implements Ord for use in BTreeMap
-/// Prices in orderbooks are always valid numbers (no NaN), so this is safe
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-pub(crate) struct Price(f64);
+/// Default resolution of price keys, finer than any Kraken pair's price precision
+pub const DEFAULT_PRICE_RESOLUTION: f64 = 1e-9;
 
-impl Eq for Price {}
+/// Fixed-point price key for the book maps: the price in units of the engine's
+/// price resolution
+/// 
+/// Integer keys have a total order and exact equality, so there is no float
+/// `Ord` workaround and no way for NaN to corrupt the map ordering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Price(i64);
+
+/// Conversion between `f64` prices and fixed-point `Price` keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PriceScale {
+    /// Key units per unit of price (the inverse of the resolution, a whole number)
+    units: f64,
+}
+
+impl PriceScale {
+    /// Scale for a resolution of the form 1/n; coarser resolutions are treated as 1
+    fn new(resolution: f64) -> Self {
+        Self {
+            units: (1.0 / resolution).round().max(1.0),
+        }
+    }
+
+    /// Key of a price, rounded to the nearest unit
+    /// 
+    /// Fails for prices whose key doesn't fit in an `i64` (or that aren't finite),
+    /// rather than saturating them onto the extremes of the book.
+    fn key(&self, price: f64) -> Result<Price> {
+        let units = (price * self.units).round();
+        // i64::MAX as f64 rounds up to 2^63, which is itself out of range; NaN fails both
+        if units >= i64::MIN as f64 && units < i64::MAX as f64 {
+            Ok(Price(units as i64))
+        } else {
+            Err(anyhow::anyhow!("Price {} is outside the representable range", price))
+        }
+    }
+
+    /// Price of a key
+    /// 
+    /// Dividing by the whole-number unit count gives back exactly the `f64`
+    /// that was parsed, for any price with no more decimals than the resolution.
+    fn price(&self, key: Price) -> f64 {
+        key.0 as f64 / self.units
+    }
+}
+
+impl Default for PriceScale {
+    fn default() -> Self {
+        Self::new(DEFAULT_PRICE_RESOLUTION)
+    }
+}
+
+/// Whether `tick_size` is a whole multiple of the price key `resolution`
+/// 
+/// If it isn't, prices on adjacent ticks can round to the same key and be
+/// merged into one level. `resolution` is rounded to 1/n as the engine does.
+pub fn tick_fits_resolution(tick_size: f64, resolution: f64) -> bool {
+    let units = tick_size * PriceScale::new(resolution).units;
+    let whole = units.round();
+    whole >= 1.0 && (units - whole).abs() <= 1e-6 * whole
+}
+
+/// Side of the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Bid,
+    Ask,
+}
+
+/// Number of best levels per side kept in the top-of-book cache
+/// 
+/// Covers /depth's default of 10 levels, so its common case never walks the book.
+pub const TOP_N: usize = 10;
+
+/// Weight of the newest last price in the smoothed price (see `with_smoothing_alpha`)
+pub const DEFAULT_SMOOTHING_ALPHA: f64 = 0.2;
+
+/// Most price levels kept across both sides unless `with_max_levels` says otherwise
+/// 
+/// Well beyond Kraken's deepest subscription (1000 per side): it only guards
+/// against levels that are never deleted piling up over long runs.
+pub const DEFAULT_MAX_LEVELS: usize = 10_000;
+
+/// Number of levels per side covered by Kraken's book checksum
+const CHECKSUM_LEVELS: usize = 10;
+
+/// Cached best levels of one side of the book as (price, volume), best first
+/// 
+/// Maintained incrementally alongside the `BTreeMap` so hot top-of-book reads
+/// don't need to walk the map. If the cache holds fewer than `TOP_N` levels, it
+/// holds every level on that side.
+#[derive(Debug, Clone, Default)]
+struct TopLevels {
+    levels: Vec<(f64, f64)>,
+    /// True for bids (best = highest), false for asks (best = lowest)
+    descending: bool,
+    /// Conversion of the side's map keys
+    scale: PriceScale,
+}
+
+impl TopLevels {
+    fn new(descending: bool, scale: PriceScale) -> Self {
+        Self {
+            levels: Vec::with_capacity(TOP_N),
+            descending,
+            scale,
+        }
+    }
+
+    /// Whether price `a` ranks ahead of price `b` on this side
+    fn is_better(&self, a: f64, b: f64) -> bool {
+        if self.descending { a > b } else { a < b }
+    }
+
+    /// Rebuild from scratch from the side's map
+    fn rebuild(&mut self, book: &BTreeMap<Price, f64>) {
+        self.levels.clear();
+        let scale = self.scale;
+        let levels = book.iter().map(|(price, volume)| (scale.price(*price), *volume));
+        if self.descending {
+            self.levels.extend(levels.rev().take(TOP_N));
+        } else {
+            self.levels.extend(levels.take(TOP_N));
+        }
+    }
+
+    /// Apply a single level change that has already been applied to `book`
+    fn update(&mut self, price: f64, volume: f64, book: &BTreeMap<Price, f64>) {
+        if let Some(pos) = self.levels.iter().position(|(p, _)| *p == price) {
+            if volume == 0.0 {
+                self.levels.remove(pos);
+                self.promote_next(book);
+            } else {
+                self.levels[pos].1 = volume;
+            }
+        } else if volume > 0.0 {
+            let has_room = self.levels.len() < TOP_N;
+            let beats_worst = self.levels.last().is_some_and(|(worst, _)| self.is_better(price, *worst));
+            if has_room || beats_worst {
+                let pos = self.levels
+                    .iter()
+                    .position(|(p, _)| self.is_better(price, *p))
+                    .unwrap_or(self.levels.len());
+                self.levels.insert(pos, (price, volume));
+                self.levels.truncate(TOP_N);
+            }
+        }
+    }
 
-impl Ord for Price {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
+    /// After a removal, pull in the next-best level beyond the cached ones
+    fn promote_next(&mut self, book: &BTreeMap<Price, f64>) {
+        // Cached prices come from the map's keys, so they always convert back
+        let Ok(worst) = self.levels.last().map(|(worst, _)| self.scale.key(*worst)).transpose() else {
+            return;
+        };
+        let next = match (worst, self.descending) {
+            (None, true) => book.iter().next_back(),
+            (None, false) => book.iter().next(),
+            (Some(worst), true) => book.range(..worst).next_back(),
+            (Some(worst), false) => book.range((Excluded(worst), Unbounded)).next(),
+        };
+        if let Some((price, volume)) = next {
+            self.levels.push((self.scale.price(*price), *volume));
+        }
     }
 }
 
@@ -25,14 +186,227 @@ pub struct PriceLevelEntry {
     pub volume: f64,
 }
 
+/// Multiply the prices of `levels` by a display scale
+pub fn scale_levels(levels: &mut [PriceLevelEntry], scale: f64) {
+    for level in levels {
+        level.price *= scale;
+    }
+}
+
+/// One rung of a cumulative depth ladder
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LadderLevel {
+    pub price: f64,
+    pub volume: f64,
+    /// Volume of this rung and every rung nearer the mid
+    pub cumulative: f64,
+}
+
+/// Running volume totals of (price, volume) rungs, in the given order
+pub fn cumulative_ladder(levels: impl IntoIterator<Item = (f64, f64)>) -> Vec<LadderLevel> {
+    let mut cumulative = 0.0;
+    levels
+        .into_iter()
+        .map(|(price, volume)| {
+            cumulative += volume;
+            LadderLevel { price, volume, cumulative }
+        })
+        .collect()
+}
+
+/// Which sides of a book have levels, so clients can tell a one-sided book
+/// apart from a broken one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BookStatus {
+    /// Both bids and asks
+    Full,
+    BidsOnly,
+    AsksOnly,
+    /// No levels at all, e.g. before the first snapshot
+    #[default]
+    Empty,
+}
+
+impl BookStatus {
+    /// Status of a book given whether each side has levels
+    pub fn from_sides(has_bids: bool, has_asks: bool) -> Self {
+        match (has_bids, has_asks) {
+            (true, true) => BookStatus::Full,
+            (true, false) => BookStatus::BidsOnly,
+            (false, true) => BookStatus::AsksOnly,
+            (false, false) => BookStatus::Empty,
+        }
+    }
+}
+
 /// Orderbook state response in the required JSON format
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct OrderbookState {
     pub timestamp: i64,
     #[serde(rename = "lastPrice")]
     pub last_price: Option<f64>,
     pub bids: Vec<PriceLevelEntry>,
     pub asks: Vec<PriceLevelEntry>,
+    /// True while the book is being rebuilt from a fresh snapshot
+    pub resyncing: bool,
+    /// Average of best bid and best ask, if both sides are present
+    #[serde(rename = "midPrice")]
+    pub mid_price: Option<f64>,
+    /// Best ask minus best bid, if both sides are present (negative when crossed)
+    pub spread: Option<f64>,
+    /// Mid weighted by top-of-book volumes, if both sides are present
+    pub microprice: Option<f64>,
+    /// Milliseconds since the book last received a snapshot or delta, if it has
+    #[serde(rename = "ageMs")]
+    pub age_ms: Option<i64>,
+    /// Exponential moving average of the last price, once it has been set
+    #[serde(rename = "smoothedPrice")]
+    pub smoothed_price: Option<f64>,
+    /// Total volume resting on the bid side, over every level
+    #[serde(rename = "bidVolume")]
+    pub bid_volume: f64,
+    /// Total volume resting on the ask side, over every level
+    #[serde(rename = "askVolume")]
+    pub ask_volume: f64,
+    /// Which sides of the book have levels
+    #[serde(rename = "bookStatus")]
+    pub book_status: BookStatus,
+}
+
+impl OrderbookState {
+    /// This state with every price multiplied by a ticker's display scale
+    pub fn scaled(mut self, scale: f64) -> Self {
+        if scale == 1.0 {
+            return self;
+        }
+        scale_levels(&mut self.bids, scale);
+        scale_levels(&mut self.asks, scale);
+        for price in [
+            &mut self.last_price,
+            &mut self.mid_price,
+            &mut self.spread,
+            &mut self.microprice,
+            &mut self.smoothed_price,
+        ] {
+            *price = price.map(|price| price * scale);
+        }
+        self
+    }
+}
+
+/// Price levels changed since the previous `take_changes` call
+/// 
+/// Volumes are absolute, so applying a diff is idempotent; a volume of 0 means
+/// the level was removed.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderbookDelta {
+    pub timestamp: i64,
+    #[serde(rename = "lastPrice")]
+    pub last_price: Option<f64>,
+    /// Changed bids in descending order
+    pub bids: Vec<PriceLevelEntry>,
+    /// Changed asks in ascending order
+    pub asks: Vec<PriceLevelEntry>,
+}
+
+impl OrderbookDelta {
+    /// This delta with every price multiplied by a ticker's display scale
+    pub fn scaled(mut self, scale: f64) -> Self {
+        if scale == 1.0 {
+            return self;
+        }
+        scale_levels(&mut self.bids, scale);
+        scale_levels(&mut self.asks, scale);
+        self.last_price = self.last_price.map(|price| price * scale);
+        self
+    }
+}
+
+/// Result of applying a delta to the book
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DeltaOutcome {
+    /// Newest price-level timestamp carried by the delta, if any
+    /// 
+    /// Republished levels are skipped: they keep their original timestamps.
+    pub max_timestamp: Option<f64>,
+    /// True when every level in the delta is older than the newest update seen
+    /// before it, suggesting deltas were dropped or arrived out of order
+    pub possible_gap: bool,
+}
+
+/// Handling of delta levels timestamped older than the newest level seen on their side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampPolicy {
+    /// Skip the level, counting it as rejected
+    Reject,
+    /// Apply the level as usual
+    #[default]
+    Accept,
+    /// Apply the level, counting it as out of order
+    Count,
+}
+
+impl FromStr for TimestampPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "accept" => Ok(Self::Accept),
+            "count" => Ok(Self::Count),
+            _ => Err(anyhow::anyhow!("unknown timestamp policy: {}", s)),
+        }
+    }
+}
+
+/// Update published on a ticker's orderbook channel
+#[derive(Debug, Clone)]
+pub enum BookUpdate {
+    /// The complete book, sent after snapshots and resync transitions
+    Full(OrderbookState),
+    /// Only the levels changed by a delta
+    Diff(OrderbookDelta),
+}
+
+/// Current Unix time in seconds
+fn unix_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+/// Current wall-clock time in Unix milliseconds
+fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// The later of two optional timestamps
+fn max_timestamp(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Build a trade inferred from a level's volume dropping from `old_volume`
+/// 
+/// Uses the exchange's level timestamp when present, otherwise the local clock.
+fn inferred_trade(level: &PriceLevel, old_volume: f64, side: TradeSide) -> DetectedTrade {
+    let timestamp_ms = match level.timestamp {
+        Some(timestamp) => (timestamp * 1000.0) as i64,
+        None => unix_millis(),
+    };
+    DetectedTrade {
+        timestamp_ms,
+        price: level.price,
+        volume: old_volume - level.volume,
+        side,
+    }
 }
 
 /// Orderbook engine that maintains the current state of bids and asks
@@ -50,165 +424,1029 @@ pub struct OrderbookEngine {
     
     /// Last traded price
     last_price: Option<f64>,
+
+    /// Set once real trades feed `last_price`, which deltas then stop inferring
+    last_price_from_trades: bool,
+
+    /// Volume taken off the best bid/ask by deltas since the last `take_traded_volume`
+    traded_volume: f64,
+
+    /// When `last_price` last changed value (or when the book first received data)
+    last_price_changed_at: Option<Instant>,
+
+    /// Exponential moving average of `last_price`, updated as deltas change it
+    smoothed_price: Option<f64>,
+
+    /// Weight of each new last price in `smoothed_price`, in (0, 1]
+    smoothing_alpha: f64,
+
+    /// When the book last received a snapshot or delta
+    last_book_update_at: Option<Instant>,
+
+    /// Wall-clock time of the last snapshot or delta in Unix milliseconds, for
+    /// the `ageMs` clients see
+    last_update_system_ms: Option<i64>,
+
+    /// Bucketed low/high of the mid price, recorded on every book update
+    mid_range: RollingRange,
+
+    /// Minimum price increment for this ticker, if known
+    tick_size: Option<f64>,
+
+    /// Conversion of prices to the fixed-point keys of `bids` and `asks`
+    price_scale: PriceScale,
+
+    /// Factor applied to prices in emitted state and deltas (1 = unscaled)
+    display_scale: f64,
+
+    /// Maximum levels kept per side; worse levels are dropped after each update
+    max_depth: Option<usize>,
+
+    /// Hard cap on levels across both sides; the farthest from the mid are dropped beyond it
+    max_levels: usize,
+
+    /// Set once the book has been trimmed to `max_levels`, until it next has room,
+    /// so the cap is warned about once per crossing rather than on every update
+    over_level_cap: bool,
+
+    /// Newest price-level timestamp seen in the last snapshot or any delta since
+    last_update_ts: Option<f64>,
+
+    /// Handling of delta levels older than the newest seen on their side
+    timestamp_policy: TimestampPolicy,
+
+    /// Newest timestamp of an applied bid level since the last snapshot
+    max_bid_ts: Option<f64>,
+
+    /// Newest timestamp of an applied ask level since the last snapshot
+    max_ask_ts: Option<f64>,
+
+    /// Delta levels skipped under `TimestampPolicy::Reject`
+    rejected_updates: u64,
+
+    /// Out-of-order delta levels applied under `TimestampPolicy::Count`
+    out_of_order_updates: u64,
+
+    /// Incremented on every state change, used to detect unchanged books
+    update_seq: u64,
+
+    /// True between `begin_resync` and the next applied snapshot
+    resyncing: bool,
+
+    /// Cached best `TOP_N` bids (descending)
+    top_bids: TopLevels,
+
+    /// Cached best `TOP_N` asks (ascending)
+    top_asks: TopLevels,
+
+    /// Decimal places of (price, volume) as sent by the exchange, learned from
+    /// the book data and used to rebuild the strings Kraken checksums
+    precision: Option<(usize, usize)>,
+
+    /// Bid levels changed since the last `take_changes` (volume 0 = removed)
+    changed_bids: BTreeMap<Price, f64>,
+
+    /// Ask levels changed since the last `take_changes` (volume 0 = removed)
+    changed_asks: BTreeMap<Price, f64>,
+
+    /// Trades inferred since the last `take_trades`
+    trades: Vec<DetectedTrade>,
 }
 
 impl OrderbookEngine {
-    /// Create a new empty orderbook engine
-    pub fn new() -> Self {
+    /// Create a new empty orderbook engine for a ticker with the given minimum price increment
+    /// 
+    /// `tick_size` is only used for tick-based metrics such as `spread_ticks`; pass `None`
+    /// when it isn't known. Price keys use `DEFAULT_PRICE_RESOLUTION` unless set with
+    /// `with_price_resolution`, and the tick size must be a whole multiple of the key
+    /// resolution (see `tick_fits_resolution`) so every on-tick price has its own key.
+    pub fn new(tick_size: Option<f64>) -> Self {
+        let price_scale = PriceScale::default();
         Self {
             bids: BTreeMap::new(),
             asks: BTreeMap::new(),
             last_price: None,
+            last_price_from_trades: false,
+            traded_volume: 0.0,
+            last_price_changed_at: None,
+            smoothed_price: None,
+            smoothing_alpha: DEFAULT_SMOOTHING_ALPHA,
+            last_book_update_at: None,
+            last_update_system_ms: None,
+            mid_range: RollingRange::default(),
+            tick_size,
+            price_scale,
+            display_scale: 1.0,
+            max_depth: None,
+            max_levels: DEFAULT_MAX_LEVELS,
+            over_level_cap: false,
+            last_update_ts: None,
+            timestamp_policy: TimestampPolicy::default(),
+            max_bid_ts: None,
+            max_ask_ts: None,
+            rejected_updates: 0,
+            out_of_order_updates: 0,
+            update_seq: 0,
+            resyncing: false,
+            top_bids: TopLevels::new(true, price_scale),
+            top_asks: TopLevels::new(false, price_scale),
+            precision: None,
+            changed_bids: BTreeMap::new(),
+            changed_asks: BTreeMap::new(),
+            trades: Vec::new(),
         }
     }
 
-    /// Get the current last traded price
-    pub fn last_price(&self) -> Option<f64> {
-        self.last_price
+    /// Key prices to `resolution` instead of `DEFAULT_PRICE_RESOLUTION`
+    /// 
+    /// `resolution` should be of the form 1/n, e.g. 1e-5. It must be no coarser than
+    /// the exchange's price precision, or distinct levels would share a key, and the
+    /// tick size must be a whole multiple of it. Levels already in the book keep
+    /// their keys, so set it before applying any data.
+    pub fn with_price_resolution(mut self, resolution: f64) -> Self {
+        self.price_scale = PriceScale::new(resolution);
+        self.top_bids = TopLevels::new(true, self.price_scale);
+        self.top_asks = TopLevels::new(false, self.price_scale);
+        self
     }
 
-    /// Set the last traded price
-    pub fn set_last_price(&mut self, price: f64) {
-        self.last_price = Some(price);
+    /// Get the configured tick size, if any
+    pub fn tick_size(&self) -> Option<f64> {
+        self.tick_size
     }
 
-    /// Get a mutable reference to the bids map (for internal use)
-    pub fn bids_mut(&mut self) -> &mut BTreeMap<Price, f64> {
-        &mut self.bids
+    /// Set the factor API and WebSocket output multiplies this book's prices by,
+    /// e.g. 1e8 to display in sats
+    /// 
+    /// The engine itself never applies it: every accessor, emitted state and
+    /// stored snapshot keeps the exchange's prices.
+    pub fn with_display_scale(mut self, scale: f64) -> Self {
+        self.display_scale = scale;
+        self
     }
 
-    /// Get a mutable reference to the asks map (for internal use)
-    pub fn asks_mut(&mut self) -> &mut BTreeMap<Price, f64> {
-        &mut self.asks
+    /// Keep at most `depth` levels per side, e.g. the depth subscribed to
+    /// 
+    /// Kraken doesn't always send deletes for levels pushed out of the
+    /// subscribed depth, so without a limit they would linger in the book.
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
     }
 
-    /// Apply a snapshot to the orderbook, replacing all existing state
+    /// Keep at most `levels` price levels across both sides (default: `DEFAULT_MAX_LEVELS`)
     /// 
-    /// This method clears the current bids and asks, then populates them
-    /// with the data from the snapshot. This is used for the initial snapshot
-    /// message from Kraken.
-    pub fn apply_snapshot(&mut self, snapshot: &BookSnapshot) -> Result<()> {
-        // Clear existing state
-        self.bids.clear();
-        self.asks.clear();
+    /// A safety valve independent of `with_max_depth`: once exceeded, the levels
+    /// farthest from the mid are dropped and a warning is logged.
+    pub fn with_max_levels(mut self, levels: usize) -> Self {
+        self.max_levels = levels;
+        self
+    }
 
-        // Process bids
-        for bid_level in &snapshot.bids {
-            let price_level = parse_price_level(bid_level)?;
-            // Only insert if volume is greater than zero
-            if price_level.volume > 0.0 {
-                self.bids.insert(Price(price_level.price), price_level.volume);
-            }
-        }
+    /// Set the weight of each new last price in the smoothed price (default: `DEFAULT_SMOOTHING_ALPHA`)
+    /// 
+    /// `alpha` should be in (0, 1]; 1 tracks the last price exactly, smaller
+    /// values smooth harder. `Config::validate` checks the configured alpha.
+    pub fn with_smoothing_alpha(mut self, alpha: f64) -> Self {
+        self.smoothing_alpha = alpha;
+        self
+    }
 
-        // Process asks
-        for ask_level in &snapshot.asks {
-            let price_level = parse_price_level(ask_level)?;
-            // Only insert if volume is greater than zero
-            if price_level.volume > 0.0 {
-                self.asks.insert(Price(price_level.price), price_level.volume);
-            }
-        }
+    /// Set how delta levels older than the newest seen on their side are handled
+    pub fn with_timestamp_policy(mut self, policy: TimestampPolicy) -> Self {
+        self.timestamp_policy = policy;
+        self
+    }
 
-        Ok(())
+    /// Get the timestamp policy
+    pub fn timestamp_policy(&self) -> TimestampPolicy {
+        self.timestamp_policy
     }
 
-    /// Get the best bid price (highest bid)
-    fn best_bid(&self) -> Option<f64> {
-        self.bids.iter().rev().next().map(|(p, _)| p.0)
+    /// Delta levels skipped for being older than their side's newest (`TimestampPolicy::Reject`)
+    pub fn rejected_updates(&self) -> u64 {
+        self.rejected_updates
     }
 
-    /// Get the best ask price (lowest ask)
-    fn best_ask(&self) -> Option<f64> {
-        self.asks.iter().next().map(|(p, _)| p.0)
+    /// Out-of-order delta levels applied anyway (`TimestampPolicy::Count`)
+    pub fn out_of_order_updates(&self) -> u64 {
+        self.out_of_order_updates
     }
 
-    /// Apply a delta update to the orderbook
-    /// 
-    /// This method processes incremental updates from Kraken. For each price level:
-    /// - If volume is 0, the price level is removed
-    /// - If volume > 0, the price level is updated (or inserted if it doesn't exist)
+    /// Apply the timestamp policy to a delta level, returning false if it must be skipped
     /// 
-    /// Trades are detected when:
-    /// 1. Volume decreases at the best bid or best ask price (indicates a trade executed)
-    /// 2. The best bid or best ask price changes (indicates the top level was consumed)
-    pub fn apply_delta(&mut self, delta: &BookDelta) -> Result<()> {
-        // Get current best bid and ask before processing delta
-        let best_bid_before = self.best_bid();
-        let best_ask_before = self.best_ask();
-
-        // Process bid updates
-        for bid_level in &delta.bids {
-            let price_level = parse_price_level(bid_level)?;
-            let price = Price(price_level.price);
-
-            // Check if this is a trade at the best bid (volume decrease indicates trade)
-            if let Some(best_bid) = best_bid_before {
-                if price_level.price == best_bid {
-                    let old_volume = self.bids.get(&price).copied().unwrap_or(0.0);
-                    // If volume decreased (but not to zero), it's likely a trade
-                    if price_level.volume < old_volume && price_level.volume > 0.0 {
-                        self.last_price = Some(price_level.price);
-                    }
+    /// Levels without a timestamp can't be judged and are always admitted, as are
+    /// republished levels: they carry their original (older) timestamps during a
+    /// resync but are authoritative, so they replace the level regardless.
+    fn admit_level(&mut self, side: Side, timestamp: Option<f64>, republish: bool) -> bool {
+        let newest = match side {
+            Side::Bid => &mut self.max_bid_ts,
+            Side::Ask => &mut self.max_ask_ts,
+        };
+        let out_of_order = !republish
+            && matches!((timestamp, *newest), (Some(ts), Some(seen)) if ts < seen);
+        if out_of_order {
+            match self.timestamp_policy {
+                TimestampPolicy::Reject => {
+                    self.rejected_updates += 1;
+                    return false;
                 }
-            }
-
-            if price_level.volume == 0.0 {
-                // Remove the price level if volume is zero
-                self.bids.remove(&price);
-            } else {
-                // Update or insert the price level
-                self.bids.insert(price, price_level.volume);
+                TimestampPolicy::Count => self.out_of_order_updates += 1,
+                TimestampPolicy::Accept => {}
             }
         }
+        *newest = max_timestamp(*newest, timestamp);
+        true
+    }
 
-        // Process ask updates
-        for ask_level in &delta.asks {
-            let price_level = parse_price_level(ask_level)?;
-            let price = Price(price_level.price);
+    /// Get the maximum levels kept per side, if limited
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
 
-            // Check if this is a trade at the best ask (volume decrease indicates trade)
-            if let Some(best_ask) = best_ask_before {
-                if price_level.price == best_ask {
-                    let old_volume = self.asks.get(&price).copied().unwrap_or(0.0);
-                    // If volume decreased (but not to zero), it's likely a trade
-                    if price_level.volume < old_volume && price_level.volume > 0.0 {
-                        self.last_price = Some(price_level.price);
-                    }
-                }
-            }
+    /// Drop the worst levels beyond `max_depth` on each side, recording them as removed
+    fn trim_to_max_depth(&mut self) {
+        let Some(depth) = self.max_depth else {
+            return;
+        };
+        while self.bids.len() > depth {
+            // Bids are keyed ascending, so the lowest (worst) bid is first
+            let (key, _) = self.bids.pop_first().expect("bids is non-empty");
+            self.changed_bids.insert(key, 0.0);
+            self.top_bids.update(self.price_scale.price(key), 0.0, &self.bids);
+        }
+        while self.asks.len() > depth {
+            let (key, _) = self.asks.pop_last().expect("asks is non-empty");
+            self.changed_asks.insert(key, 0.0);
+            self.top_asks.update(self.price_scale.price(key), 0.0, &self.asks);
+        }
+    }
 
-            if price_level.volume == 0.0 {
-                // Remove the price level if volume is zero
-                self.asks.remove(&price);
+    /// Drop the levels farthest from the mid until the book is within `max_levels`
+    /// 
+    /// Dropped levels are recorded as removed. The first trim after the book had
+    /// room is a warning in the caller's span, which for feed tasks carries the
+    /// ticker; trims while it stays at the cap are only logged at debug level.
+    fn trim_to_max_levels(&mut self) {
+        let levels = self.bids.len() + self.asks.len();
+        if levels < self.max_levels {
+            self.over_level_cap = false;
+        }
+        let excess = levels.saturating_sub(self.max_levels);
+        if excess == 0 {
+            return;
+        }
+        let mid = self.mid_price();
+        for _ in 0..excess {
+            let worst_bid = self.bids.keys().next().map(|key| self.price_scale.price(*key));
+            let worst_ask = self.asks.keys().next_back().map(|key| self.price_scale.price(*key));
+            let drop_bid = match (worst_bid, worst_ask, mid) {
+                (Some(bid), Some(ask), Some(mid)) => mid - bid >= ask - mid,
+                (bid, _, _) => bid.is_some(),
+            };
+            if drop_bid {
+                let (key, _) = self.bids.pop_first().expect("bids is non-empty");
+                self.changed_bids.insert(key, 0.0);
+                self.top_bids.update(self.price_scale.price(key), 0.0, &self.bids);
             } else {
-                // Update or insert the price level
-                self.asks.insert(price, price_level.volume);
+                let (key, _) = self.asks.pop_last().expect("asks is non-empty");
+                self.changed_asks.insert(key, 0.0);
+                self.top_asks.update(self.price_scale.price(key), 0.0, &self.asks);
             }
         }
+        if self.over_level_cap {
+            tracing::debug!(dropped = excess, max_levels = self.max_levels, "Book still at its level cap");
+        } else {
+            self.over_level_cap = true;
+            tracing::warn!(dropped = excess, max_levels = self.max_levels,
+                           "Book exceeded its level cap, dropped the levels farthest from the mid");
+        }
+    }
 
-        // Also update last_price if best bid or ask changed (indicates a trade consumed the level)
-        let best_bid_after = self.best_bid();
-        let best_ask_after = self.best_ask();
+    /// Get the display scale API and WebSocket output applies to this book's prices
+    pub fn display_scale(&self) -> f64 {
+        self.display_scale
+    }
 
-        // If best bid changed, update last_price to the new best bid
-        if best_bid_before != best_bid_after {
-            if let Some(new_best_bid) = best_bid_after {
-                self.last_price = Some(new_best_bid);
-            }
+    /// Get the current last traded price
+    pub fn last_price(&self) -> Option<f64> {
+        self.last_price
+    }
+
+    /// Exponential moving average of the last price
+    /// 
+    /// Seeded with the first last price set by a delta or trade, then moved toward
+    /// each new one by `smoothing_alpha`. Returns `None` until then.
+    pub fn smoothed_price(&self) -> Option<f64> {
+        self.smoothed_price
+    }
+
+    /// Take `last_price` only from `set_last_price` from now on
+    /// 
+    /// For feeds with a trade channel: book movements are a poor proxy for the
+    /// executed price, so deltas stop inferring it. Trades are still inferred.
+    pub fn use_trade_prices(&mut self) {
+        self.last_price_from_trades = true;
+    }
+
+    /// Set the last traded price
+    pub fn set_last_price(&mut self, price: f64) {
+        let changed = self.last_price != Some(price);
+        self.last_price = Some(price);
+        if changed {
+            self.last_price_changed_at = Some(Instant::now());
+            self.update_seq += 1;
+            self.smooth_last_price();
         }
+    }
 
-        // If best ask changed, update last_price to the new best ask
-        if best_ask_before != best_ask_after {
-            if let Some(new_best_ask) = best_ask_after {
-                self.last_price = Some(new_best_ask);
-            }
+    /// Move `smoothed_price` toward the new `last_price`, seeding it if unset
+    fn smooth_last_price(&mut self) {
+        if let Some(price) = self.last_price {
+            self.smoothed_price = Some(match self.smoothed_price {
+                Some(smoothed) => smoothed + self.smoothing_alpha * (price - smoothed),
+                None => price,
+            });
         }
+    }
 
-        Ok(())
+    /// Sequence number incremented on every change to the book or last price
+    /// 
+    /// Two reads with the same sequence number observed the same state.
+    pub fn update_seq(&self) -> u64 {
+        self.update_seq
     }
 
-    /// Get the current orderbook state in the required JSON format
+    /// Newest price-level timestamp (Kraken seconds) seen since the last snapshot
+    pub fn last_update_ts(&self) -> Option<f64> {
+        self.last_update_ts
+    }
+
+    /// Record book activity, starting the last-price clock on the first update
+    fn mark_book_updated(&mut self, last_price_before: Option<f64>) {
+        let now = Instant::now();
+        self.last_book_update_at = Some(now);
+        self.last_update_system_ms = Some(unix_millis());
+        if let Some(mid) = self.mid_price() {
+            self.mid_range.record(now, mid);
+        }
+        self.update_seq += 1;
+        if self.last_price_changed_at.is_none() || self.last_price != last_price_before {
+            self.last_price_changed_at = Some(now);
+        }
+    }
+
+    /// Low and high of the mid price over the last `window`, as (low, high)
+    /// 
+    /// Resolved to `MID_BUCKET`s and kept for at most `MID_RETENTION`. Returns
+    /// `None` if the book had no two-sided update within the window.
+    pub fn rolling_mid_range(&self, window: Duration) -> Option<(f64, f64)> {
+        self.mid_range.range(window, Instant::now())
+    }
+
+    /// Time elapsed since `last_price` last changed, `None` before any book data
+    pub fn time_since_last_price_change(&self) -> Option<Duration> {
+        self.last_price_changed_at.map(|changed_at| changed_at.elapsed())
+    }
+
+    /// Time elapsed since the book last received a snapshot or delta, `None` before any
+    pub fn time_since_last_book_update(&self) -> Option<Duration> {
+        self.last_book_update_at.map(|updated_at| updated_at.elapsed())
+    }
+
+    /// Check whether `last_price` appears stuck while the book keeps updating
+    /// 
+    /// Returns true when the book has been updated within `threshold` but `last_price`
+    /// has not changed for at least `threshold`. This usually means the trade-detection
+    /// heuristic is not firing for this pair.
+    pub fn last_price_stuck(&self, threshold: Duration) -> bool {
+        match (self.last_book_update_at, self.last_price_changed_at) {
+            (Some(updated_at), Some(changed_at)) => {
+                updated_at.elapsed() < threshold && changed_at.elapsed() >= threshold
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the cached best `TOP_N` bids as (price, volume), highest first
+    /// 
+    /// O(1); kept up to date by `apply_snapshot` and `apply_delta`. Direct edits
+    /// through `bids_mut` bypass the cache.
+    pub fn top_bids(&self) -> &[(f64, f64)] {
+        &self.top_bids.levels
+    }
+
+    /// Get the cached best `TOP_N` asks as (price, volume), lowest first
+    /// 
+    /// O(1); kept up to date by `apply_snapshot` and `apply_delta`. Direct edits
+    /// through `asks_mut` bypass the cache.
+    pub fn top_asks(&self) -> &[(f64, f64)] {
+        &self.top_asks.levels
+    }
+
+    /// Get a mutable reference to the bids map (for tests)
+    #[cfg(test)]
+    pub(crate) fn bids_mut(&mut self) -> &mut BTreeMap<Price, f64> {
+        &mut self.bids
+    }
+
+    /// Get a mutable reference to the asks map (for tests)
+    #[cfg(test)]
+    pub(crate) fn asks_mut(&mut self) -> &mut BTreeMap<Price, f64> {
+        &mut self.asks
+    }
+
+    /// Apply a snapshot to the orderbook, replacing all existing state
+    /// 
+    /// Every level is parsed and keyed before the book is touched, so a
+    /// malformed or out-of-range level fails the whole snapshot and leaves the
+    /// previous book in place. This is used for the initial snapshot message
+    /// from Kraken.
+    pub fn apply_snapshot(&mut self, snapshot: &BookSnapshot) -> Result<()> {
+        let (bids, max_bid_ts) = self.parse_snapshot_side(&snapshot.bids)?;
+        let (asks, max_ask_ts) = self.parse_snapshot_side(&snapshot.asks)?;
+
+        // Replace existing state; a snapshot is always published in full
+        self.bids = bids;
+        self.asks = asks;
+        if let Some(precision) = snapshot.bids.iter().chain(&snapshot.asks).find_map(price_level_precision) {
+            self.precision = Some(precision);
+        }
+
+        // The snapshot is the new baseline for gap detection and the timestamp policy
+        self.max_bid_ts = max_bid_ts;
+        self.max_ask_ts = max_ask_ts;
+        self.last_update_ts = max_timestamp(max_bid_ts, max_ask_ts);
+
+        // Trimming reads the mid from the cache and keeps it up to date
+        self.top_bids.rebuild(&self.bids);
+        self.top_asks.rebuild(&self.asks);
+        self.trim_to_max_depth();
+        self.trim_to_max_levels();
+        // Levels trimmed from a snapshot were never published, so aren't changes
+        self.changed_bids.clear();
+        self.changed_asks.clear();
+        self.resyncing = false;
+        self.mark_book_updated(self.last_price);
+
+        Ok(())
+    }
+
+    /// Key one side of a snapshot, skipping empty levels, with its newest level timestamp
+    fn parse_snapshot_side(&self, levels: &[serde_json::Value]) -> Result<(BTreeMap<Price, f64>, Option<f64>)> {
+        let mut side = BTreeMap::new();
+        let mut newest_ts = None;
+        for level in levels {
+            let price_level = parse_price_level(level)?;
+            newest_ts = max_timestamp(newest_ts, price_level.timestamp);
+            if price_level.volume > 0.0 {
+                side.insert(self.price_scale.key(price_level.price)?, price_level.volume);
+            }
+        }
+        Ok((side, newest_ts))
+    }
+
+    /// Replace the book with a stored snapshot's levels and last price
+    /// 
+    /// Like `apply_snapshot`, but from already-parsed levels, e.g. out of a `SnapshotStore`.
+    /// Fails, leaving the book unchanged, if a price is outside the key range.
+    pub fn load_snapshot(&mut self, snapshot: &Snapshot) -> Result<()> {
+        let last_price_before = self.last_price;
+        let scale = self.price_scale;
+        let bids = snapshot
+            .bids
+            .iter()
+            .filter(|level| level.volume > 0.0)
+            .map(|level| Ok((scale.key(level.price)?, level.volume)))
+            .collect::<Result<_>>()?;
+        let asks = snapshot
+            .asks
+            .iter()
+            .filter(|level| level.volume > 0.0)
+            .map(|level| Ok((scale.key(level.price)?, level.volume)))
+            .collect::<Result<_>>()?;
+        self.bids = bids;
+        self.asks = asks;
+        self.changed_bids.clear();
+        self.changed_asks.clear();
+        self.top_bids.rebuild(&self.bids);
+        self.top_asks.rebuild(&self.asks);
+        self.last_price = snapshot.last_price;
+        self.max_bid_ts = None;
+        self.max_ask_ts = None;
+        self.resyncing = false;
+        self.mark_book_updated(last_price_before);
+        Ok(())
+    }
+
+    /// Replay stored snapshots through a fresh engine, oldest first
+    /// 
+    /// For each snapshot the engine is loaded with its book and `on_snapshot` is
+    /// called with the snapshot and the engine, so analytics (spread, imbalance,
+    /// VWAP, ...) can be computed over recorded history. Snapshots are sorted by
+    /// timestamp first; the engine holding the last one is returned, or the
+    /// first snapshot that can't be loaded stops the replay with its error.
+    pub fn replay_snapshots<F>(snapshots: &[Snapshot], mut on_snapshot: F) -> Result<Self>
+    where
+        F: FnMut(&Snapshot, &OrderbookEngine),
+    {
+        let mut ordered: Vec<&Snapshot> = snapshots.iter().collect();
+        ordered.sort_by_key(|snapshot| snapshot.timestamp);
+
+        let mut engine = Self::default();
+        for snapshot in ordered {
+            engine.load_snapshot(snapshot)?;
+            on_snapshot(snapshot, &engine);
+        }
+        Ok(engine)
+    }
+
+    /// Compute Kraken's CRC32 book checksum over the top 10 asks and bids
+    /// 
+    /// Each level contributes its price then volume, formatted at the exchange's
+    /// precision with the decimal point and leading zeros removed; asks (ascending)
+    /// precede bids (descending). Returns `None` until the precision is known.
+    pub fn checksum(&self) -> Option<u32> {
+        let (price_decimals, volume_decimals) = self.precision?;
+        let field = |value: f64, decimals: usize| {
+            let formatted = format!("{:.*}", decimals, value).replace('.', "");
+            formatted.trim_start_matches('0').to_string()
+        };
+
+        let mut hasher = crc32fast::Hasher::new();
+        for (price, volume) in self
+            .iter_asks()
+            .take(CHECKSUM_LEVELS)
+            .chain(self.iter_bids().take(CHECKSUM_LEVELS))
+        {
+            hasher.update(field(price, price_decimals).as_bytes());
+            hasher.update(field(volume, volume_decimals).as_bytes());
+        }
+        Some(hasher.finalize())
+    }
+
+    /// Check the local book against a checksum sent by Kraken
+    pub fn verify_checksum(&self, expected: u32) -> bool {
+        self.checksum() == Some(expected)
+    }
+
+    /// Discard the book: empties bids and asks and resets `last_price`
+    /// 
+    /// Used when the feed reconnects, so the pre-disconnect book isn't served as
+    /// current while the fresh snapshot is pending. Pending changes and inferred
+    /// trades are dropped too; the exchange's precision and tick size are kept.
+    pub fn clear(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.top_bids.rebuild(&self.bids);
+        self.top_asks.rebuild(&self.asks);
+        self.changed_bids.clear();
+        self.changed_asks.clear();
+        self.trades.clear();
+        self.last_price = None;
+        self.last_price_changed_at = None;
+        self.smoothed_price = None;
+        self.last_update_ts = None;
+        self.max_bid_ts = None;
+        self.max_ask_ts = None;
+        self.over_level_cap = false;
+        self.update_seq += 1;
+    }
+
+    /// Mark the book as resyncing until the next snapshot is applied
+    /// 
+    /// Clients see `resyncing: true` in the emitted state so they can show a
+    /// loading state instead of treating the book as authoritative.
+    pub fn begin_resync(&mut self) {
+        self.resyncing = true;
+        self.update_seq += 1;
+    }
+
+    /// Whether the book is waiting for a fresh snapshot
+    pub fn is_resyncing(&self) -> bool {
+        self.resyncing
+    }
+
+    /// Check whether both sides of the book are empty
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    /// Whether both sides of the book have at least `min_levels` levels
+    pub fn has_min_levels(&self, min_levels: usize) -> bool {
+        self.bids.len() >= min_levels && self.asks.len() >= min_levels
+    }
+
+    /// Iterate bids as (price, volume) pairs in descending order (highest price first)
+    /// 
+    /// Borrows the underlying map, so no allocation takes place.
+    pub fn iter_bids(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.bids.iter().rev().map(|(price, volume)| (self.price_scale.price(*price), *volume))
+    }
+
+    /// Iterate asks as (price, volume) pairs in ascending order (lowest price first)
+    /// 
+    /// Borrows the underlying map, so no allocation takes place.
+    pub fn iter_asks(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.asks.iter().map(|(price, volume)| (self.price_scale.price(*price), *volume))
+    }
+
+    /// Iterate one side of the book best level first
+    fn iter_side(&self, side: Side) -> Box<dyn Iterator<Item = (f64, f64)> + '_> {
+        match side {
+            Side::Bid => Box::new(self.iter_bids()),
+            Side::Ask => Box::new(self.iter_asks()),
+        }
+    }
+
+    /// Iterate the best `n` levels of one side as (price, volume), best first
+    /// 
+    /// Served from the top-of-book cache when `n <= TOP_N`, so the common
+    /// shallow reads don't walk the map.
+    pub fn best_levels(&self, side: Side, n: usize) -> Box<dyn Iterator<Item = (f64, f64)> + '_> {
+        if n > TOP_N {
+            return Box::new(self.iter_side(side).take(n));
+        }
+        let cached = match side {
+            Side::Bid => self.top_bids(),
+            Side::Ask => self.top_asks(),
+        };
+        Box::new(cached.iter().take(n).copied())
+    }
+
+    /// Get the best bid price (highest bid)
+    pub fn best_bid(&self) -> Option<f64> {
+        self.best_bid_level().map(|(price, _)| price)
+    }
+
+    /// Get the best ask price (lowest ask)
+    pub fn best_ask(&self) -> Option<f64> {
+        self.best_ask_level().map(|(price, _)| price)
+    }
+
+    /// Get the best bid as a (price, volume) pair, from the top-of-book cache
+    pub fn best_bid_level(&self) -> Option<(f64, f64)> {
+        self.top_bids().first().copied()
+    }
+
+    /// Get the best ask as a (price, volume) pair, from the top-of-book cache
+    pub fn best_ask_level(&self) -> Option<(f64, f64)> {
+        self.top_asks().first().copied()
+    }
+
+    /// Volume resting at exactly `price` on one side, or 0.0 if there is no such level
+    /// 
+    /// The price is rounded to the engine's price resolution before the lookup,
+    /// so it matches a level only when both share a fixed-point key; there is no
+    /// nearest-level search. At the default resolution that is an exact match for
+    /// any price as quoted by the exchange.
+    pub fn volume_at(&self, side: Side, price: f64) -> f64 {
+        let book = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        // A price outside the key range can't have a level
+        self.price_scale.key(price).ok().and_then(|key| book.get(&key)).copied().unwrap_or(0.0)
+    }
+
+    /// Get the mid price (average of best bid and best ask)
+    /// 
+    /// Returns `None` when either side of the book is empty.
+    pub fn mid_price(&self) -> Option<f64> {
+        Some((self.best_bid()? + self.best_ask()?) / 2.0)
+    }
+
+    /// Mid price weighted by the volumes at the best bid and best ask
+    /// 
+    /// `(best_bid * ask_vol + best_ask * bid_vol) / (bid_vol + ask_vol)`, which
+    /// leans toward the thinner side, where the next price move is more likely.
+    /// Returns `None` when either side of the book is empty.
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid, bid_volume) = self.best_bid_level()?;
+        let (ask, ask_volume) = self.best_ask_level()?;
+        Some((bid * ask_volume + ask * bid_volume) / (bid_volume + ask_volume))
+    }
+
+    /// Get the bid-ask spread (best ask minus best bid)
+    /// 
+    /// Returns `None` when either side of the book is empty. The raw difference
+    /// is returned as-is, so a crossed book (best bid above best ask) yields a
+    /// negative spread rather than being clamped or hidden.
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()? - self.best_bid()?)
+    }
+
+    /// Spread in ticks, rounded to the nearest whole tick, with a flag that is
+    /// false when the raw spread was not an integer multiple of the tick size
+    fn spread_in_ticks(&self) -> Option<(u64, bool)> {
+        let tick_size = self.tick_size?;
+        let spread = self.spread()?;
+        if spread < 0.0 {
+            // Crossed book: there is no meaningful non-negative tick count
+            return None;
+        }
+        let ticks = spread / tick_size;
+        let rounded = ticks.round();
+        Some((rounded as u64, (ticks - rounded).abs() < 1e-6))
+    }
+
+    /// Get the bid-ask spread expressed in ticks of the configured tick size
+    /// 
+    /// Returns `None` when either side is empty, no tick size is configured, or the
+    /// book is crossed. Spreads that are not an exact multiple of the tick size are
+    /// rounded to the nearest tick; see `spread_tick_aligned`.
+    pub fn spread_ticks(&self) -> Option<u64> {
+        self.spread_in_ticks().map(|(ticks, _)| ticks)
+    }
+
+    /// Whether the current spread is an exact multiple of the tick size
+    /// 
+    /// `Some(false)` means `spread_ticks` was rounded. `None` under the same
+    /// conditions as `spread_ticks`.
+    pub fn spread_tick_aligned(&self) -> Option<bool> {
+        self.spread_in_ticks().map(|(_, aligned)| aligned)
+    }
+
+    /// Total volume resting on one side of the book, 0.0 if the side is empty
+    pub fn total_volume(&self, side: Side) -> f64 {
+        let levels = match side {
+            Side::Bid => &self.bids,
+            Side::Ask => &self.asks,
+        };
+        levels.values().sum()
+    }
+
+    /// Volume imbalance over the top `depth` levels of each side
+    /// 
+    /// Returns `(bid_vol - ask_vol) / (bid_vol + ask_vol)`, ranging from -1 (asks only)
+    /// to 1 (bids only). Sides with fewer than `depth` levels contribute what they have.
+    /// Returns `None` when both sides are empty.
+    pub fn imbalance(&self, depth: usize) -> Option<f64> {
+        let bid_volume: f64 = self.best_levels(Side::Bid, depth).map(|(_, volume)| volume).sum();
+        let ask_volume: f64 = self.best_levels(Side::Ask, depth).map(|(_, volume)| volume).sum();
+        let total = bid_volume + ask_volume;
+        if total <= 0.0 {
+            return None;
+        }
+        Some((bid_volume - ask_volume) / total)
+    }
+
+    /// Bid and ask volume priced within `bps` basis points of the mid price
+    /// 
+    /// Levels exactly `bps` away are included. The mid is taken from the book as
+    /// it stands, not from a cached state. Returns `(bid_volume, ask_volume)`, or
+    /// `None` when either side of the book is empty.
+    /// 
+    /// The band edges are computed exactly on the price keys (with `bps` to a
+    /// ten-thousandth of a basis point), so float rounding can't drop a level
+    /// sitting on the boundary.
+    pub fn liquidity_within_bps(&self, bps: f64) -> Option<(f64, f64)> {
+        // With the mid as (bid + ask) / 2, a bid key k is in the band when
+        // k * 2 * PARTS >= (bid + ask) * (PARTS - bps * 10^4), and an ask key when
+        // k * 2 * PARTS <= (bid + ask) * (PARTS + bps * 10^4)
+        const PARTS: i128 = 10_000 * 10_000;
+        let (best_bid, _) = self.bids.last_key_value()?;
+        let (best_ask, _) = self.asks.first_key_value()?;
+        let twice_mid = best_bid.0 as i128 + best_ask.0 as i128;
+        let band = (bps * 10_000.0).round() as i128;
+        let to_key = |edge: i128| Price(edge.clamp(i64::MIN as i128, i64::MAX as i128) as i64);
+        // Ceiling division for the lower edge, floor for the upper
+        let lower_scaled = twice_mid.saturating_mul(PARTS.saturating_sub(band));
+        let lower = -(-lower_scaled).div_euclid(2 * PARTS);
+        let upper = twice_mid.saturating_mul(PARTS.saturating_add(band)).div_euclid(2 * PARTS);
+
+        let bid_volume = self.bids.range(to_key(lower)..).map(|(_, volume)| volume).sum();
+        let ask_volume = self.asks.range(..=to_key(upper)).map(|(_, volume)| volume).sum();
+        Some((bid_volume, ask_volume))
+    }
+
+    /// Volume-weighted average price over the top `depth` levels of one side
+    /// 
+    /// Returns `None` if the side is empty (or `depth` is 0).
+    pub fn vwap(&self, side: Side, depth: usize) -> Option<f64> {
+        let (notional, volume) = self
+            .iter_side(side)
+            .take(depth)
+            .fold((0.0, 0.0), |(notional, total), (price, volume)| (notional + price * volume, total + volume));
+        (volume > 0.0).then(|| notional / volume)
+    }
+
+    /// Average fill price for `quantity` taken from one side, best level first
+    /// 
+    /// Use `Side::Ask` to price a buy and `Side::Bid` to price a sell. Returns
+    /// `None` if the side can't fill the whole quantity or `quantity` isn't positive.
+    pub fn vwap_for_quantity(&self, side: Side, quantity: f64) -> Option<f64> {
+        if quantity <= 0.0 {
+            return None;
+        }
+        let mut remaining = quantity;
+        let mut notional = 0.0;
+        for (price, volume) in self.iter_side(side) {
+            let fill = volume.min(remaining);
+            notional += price * fill;
+            remaining -= fill;
+            if remaining <= 0.0 {
+                return Some(notional / quantity);
+            }
+        }
+        None
+    }
+
+    /// Running volume totals of one side as (price, cumulative volume), best price first
+    /// 
+    /// Each level's total includes every better-priced level on the side.
+    pub fn cumulative_depth(&self, side: Side) -> Vec<(f64, f64)> {
+        cumulative_ladder(self.iter_side(side))
+            .into_iter()
+            .map(|rung| (rung.price, rung.cumulative))
+            .collect()
+    }
+
+    /// Aggregate one side into `buckets` equal-width price bands within `within_bps` of the mid
+    /// 
+    /// Returns (price, volume) per bucket, nearest the mid first. A bucket's price
+    /// is its edge farthest from the mid, and it holds the levels between that
+    /// edge (inclusive) and the previous bucket's. Empty buckets have volume 0, so
+    /// the ladder always has `buckets` rungs. Returns an empty ladder when either
+    /// side of the book is empty or `buckets` or `within_bps` isn't positive.
+    pub fn aggregate_levels(&self, side: Side, within_bps: f64, buckets: usize) -> Vec<(f64, f64)> {
+        let Some(mid) = self.mid_price() else {
+            return Vec::new();
+        };
+        if buckets == 0 || within_bps <= 0.0 {
+            return Vec::new();
+        }
+        let band = mid * within_bps / 10_000.0;
+        let width = band / buckets as f64;
+        let direction = match side {
+            Side::Bid => -1.0,
+            Side::Ask => 1.0,
+        };
+
+        let mut volumes = vec![0.0; buckets];
+        for (price, volume) in self.iter_side(side) {
+            let distance = (price - mid).abs();
+            if distance > band {
+                break;
+            }
+            let bucket = ((distance / width).ceil() as usize).saturating_sub(1).min(buckets - 1);
+            volumes[bucket] += volume;
+        }
+        volumes
+            .into_iter()
+            .enumerate()
+            .map(|(i, volume)| (mid + direction * width * (i + 1) as f64, volume))
+            .collect()
+    }
+
+    /// Apply a delta update to the orderbook
+    /// 
+    /// This method processes incremental updates from Kraken. For each price level:
+    /// - If volume is 0, the price level is removed
+    /// - If volume > 0, the price level is updated (or inserted if it doesn't exist)
+    /// 
+    /// Trades are detected when:
+    /// 1. Volume decreases at the best bid or best ask price (indicates a trade executed)
+    /// 2. The best bid or best ask price changes (indicates the top level was consumed)
+    /// 
+    /// The returned outcome flags a possible gap when the delta's newest level
+    /// timestamp is older than `last_update_ts`. Individual levels older than the
+    /// newest applied on their side are handled per the `TimestampPolicy`.
+    pub fn apply_delta(&mut self, delta: &BookDelta) -> Result<DeltaOutcome> {
+        // Get current best bid and ask before processing delta
+        let best_bid_before = self.best_bid();
+        let best_ask_before = self.best_ask();
+        let last_price_before = self.last_price;
+        if self.precision.is_none() {
+            self.precision = delta.bids.iter().chain(&delta.asks).find_map(price_level_precision);
+        }
+
+        let mut delta_max_ts = None;
+
+        // Process bid updates
+        for bid_level in &delta.bids {
+            let mut price_level = parse_price_level(bid_level)?;
+            // Work with the price as stored, so cache and trade prices match the map keys
+            let price = self.price_scale.key(price_level.price)?;
+            price_level.price = self.price_scale.price(price);
+            // Republished levels carry their original, older timestamps
+            if !price_level.republish {
+                delta_max_ts = max_timestamp(delta_max_ts, price_level.timestamp);
+            }
+            if !self.admit_level(Side::Bid, price_level.timestamp, price_level.republish) {
+                continue;
+            }
+
+            // Check if this is a trade at the best bid (volume decrease indicates trade);
+            // a republished level restates the book rather than reporting a change
+            if let Some(best_bid) = best_bid_before.filter(|_| !price_level.republish) {
+                if price_level.price == best_bid {
+                    let old_volume = self.bids.get(&price).copied().unwrap_or(0.0);
+                    // Any decrease at the touch, removals included, counts as traded volume
+                    self.traded_volume += (old_volume - price_level.volume).max(0.0);
+                    // If volume decreased (but not to zero), it's likely a trade
+                    if price_level.volume < old_volume && price_level.volume > 0.0 {
+                        if !self.last_price_from_trades {
+                            self.last_price = Some(price_level.price);
+                        }
+                        // A seller hit the bid
+                        self.trades.push(inferred_trade(&price_level, old_volume, TradeSide::Sell));
+                    }
+                }
+            }
+
+            if price_level.volume == 0.0 {
+                // Remove the price level if volume is zero
+                self.bids.remove(&price);
+            } else {
+                // Update or insert the price level
+                self.bids.insert(price, price_level.volume);
+            }
+            self.top_bids.update(price_level.price, price_level.volume, &self.bids);
+            self.changed_bids.insert(price, price_level.volume);
+        }
+
+        // Process ask updates
+        for ask_level in &delta.asks {
+            let mut price_level = parse_price_level(ask_level)?;
+            // Work with the price as stored, so cache and trade prices match the map keys
+            let price = self.price_scale.key(price_level.price)?;
+            price_level.price = self.price_scale.price(price);
+            // Republished levels carry their original, older timestamps
+            if !price_level.republish {
+                delta_max_ts = max_timestamp(delta_max_ts, price_level.timestamp);
+            }
+            if !self.admit_level(Side::Ask, price_level.timestamp, price_level.republish) {
+                continue;
+            }
+
+            // Check if this is a trade at the best ask (volume decrease indicates trade);
+            // a republished level restates the book rather than reporting a change
+            if let Some(best_ask) = best_ask_before.filter(|_| !price_level.republish) {
+                if price_level.price == best_ask {
+                    let old_volume = self.asks.get(&price).copied().unwrap_or(0.0);
+                    // Any decrease at the touch, removals included, counts as traded volume
+                    self.traded_volume += (old_volume - price_level.volume).max(0.0);
+                    // If volume decreased (but not to zero), it's likely a trade
+                    if price_level.volume < old_volume && price_level.volume > 0.0 {
+                        if !self.last_price_from_trades {
+                            self.last_price = Some(price_level.price);
+                        }
+                        // A buyer lifted the ask
+                        self.trades.push(inferred_trade(&price_level, old_volume, TradeSide::Buy));
+                    }
+                }
+            }
+
+            if price_level.volume == 0.0 {
+                // Remove the price level if volume is zero
+                self.asks.remove(&price);
+            } else {
+                // Update or insert the price level
+                self.asks.insert(price, price_level.volume);
+            }
+            self.top_asks.update(price_level.price, price_level.volume, &self.asks);
+            self.changed_asks.insert(price, price_level.volume);
+        }
+
+        // Also update last_price if best bid or ask changed (indicates a trade consumed the level)
+        let best_bid_after = self.best_bid();
+        let best_ask_after = self.best_ask();
+
+        // If best bid changed, update last_price to the new best bid
+        if best_bid_before != best_bid_after && !self.last_price_from_trades {
+            if let Some(new_best_bid) = best_bid_after {
+                self.last_price = Some(new_best_bid);
+            }
+        }
+
+        // If best ask changed, update last_price to the new best ask
+        if best_ask_before != best_ask_after && !self.last_price_from_trades {
+            if let Some(new_best_ask) = best_ask_after {
+                self.last_price = Some(new_best_ask);
+            }
+        }
+
+        if self.last_price != last_price_before {
+            self.smooth_last_price();
+        }
+
+        // Trim after trade detection so dropped levels aren't mistaken for trades
+        self.trim_to_max_depth();
+        self.trim_to_max_levels();
+
+        self.mark_book_updated(last_price_before);
+
+        let possible_gap = matches!((delta_max_ts, self.last_update_ts), (Some(newest), Some(seen)) if newest < seen);
+        self.last_update_ts = max_timestamp(self.last_update_ts, delta_max_ts);
+
+        Ok(DeltaOutcome {
+            max_timestamp: delta_max_ts,
+            possible_gap,
+        })
+    }
+
+    /// Get the current orderbook state in the required JSON format
     /// 
     /// Returns orderbook data with:
     /// - timestamp: Current Unix timestamp
@@ -216,43 +1454,121 @@ impl OrderbookEngine {
     /// - bids: Sorted in descending order by price (highest first)
     /// - asks: Sorted in ascending order by price (lowest first)
     pub fn get_current_state(&self) -> OrderbookState {
+        self.state_with_levels(Vec::with_capacity(self.bids.len()), Vec::with_capacity(self.asks.len()))
+    }
+
+    /// Like `get_current_state`, but refilling `buf` in place
+    /// 
+    /// The level vectors of `buf` are cleared and reused, so a caller that keeps
+    /// one state around only allocates when the book outgrows it.
+    pub fn get_current_state_into(&self, buf: &mut OrderbookState) {
+        let bids = std::mem::take(&mut buf.bids);
+        let asks = std::mem::take(&mut buf.asks);
+        *buf = self.state_with_levels(bids, asks);
+    }
+
+    /// Current state, with its levels written into the given vectors after clearing them
+    fn state_with_levels(&self, mut bids: Vec<PriceLevelEntry>, mut asks: Vec<PriceLevelEntry>) -> OrderbookState {
         // Get current timestamp
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let timestamp = unix_timestamp();
 
         // Collect bids in descending order (highest price first)
-        let bids: Vec<PriceLevelEntry> = self.bids
-            .iter()
-            .rev()
-            .map(|(price, volume)| PriceLevelEntry {
-                price: price.0,
-                volume: *volume,
-            })
-            .collect();
+        bids.clear();
+        bids.extend(self.iter_bids().map(|(price, volume)| PriceLevelEntry { price, volume }));
 
         // Collect asks in ascending order (lowest price first)
-        let asks: Vec<PriceLevelEntry> = self.asks
-            .iter()
-            .map(|(price, volume)| PriceLevelEntry {
-                price: price.0,
-                volume: *volume,
-            })
-            .collect();
+        asks.clear();
+        asks.extend(self.iter_asks().map(|(price, volume)| PriceLevelEntry { price, volume }));
 
         OrderbookState {
             timestamp,
             last_price: self.last_price,
             bids,
             asks,
+            resyncing: self.resyncing,
+            mid_price: self.mid_price(),
+            spread: self.spread(),
+            microprice: self.microprice(),
+            age_ms: self.last_update_system_ms.map(|updated_ms| (unix_millis() - updated_ms).max(0)),
+            smoothed_price: self.smoothed_price,
+            bid_volume: self.total_volume(Side::Bid),
+            ask_volume: self.total_volume(Side::Ask),
+            book_status: BookStatus::from_sides(!self.bids.is_empty(), !self.asks.is_empty()),
+        }
+    }
+
+    /// Check the book's internal invariants, returning the first one that fails
+    /// 
+    /// Checked in order: every level has a finite positive price and volume, the
+    /// book is not crossed, and the top-of-book cache matches the price maps.
+    pub fn self_check(&self) -> std::result::Result<(), String> {
+        for (side, levels) in [("bid", &self.bids), ("ask", &self.asks)] {
+            for (key, volume) in levels {
+                let price = self.price_scale.price(*key);
+                if !(key.0 > 0 && key.0 < i64::MAX) {
+                    return Err(format!("{} level has invalid price {}", side, price));
+                }
+                if !(volume.is_finite() && *volume > 0.0) {
+                    return Err(format!("{} level at {} has invalid volume {}", side, price, volume));
+                }
+            }
+        }
+
+        // Read from the maps, since the cache is what is checked below
+        if let (Some(bid), Some(ask)) = (self.iter_bids().next(), self.iter_asks().next()) {
+            let (bid, ask) = (bid.0, ask.0);
+            if bid >= ask {
+                return Err(format!("book is crossed: best bid {} >= best ask {}", bid, ask));
+            }
+        }
+
+        let expected_bids: Vec<(f64, f64)> = self.iter_bids().take(TOP_N).collect();
+        if self.top_bids() != expected_bids.as_slice() {
+            return Err("top-of-book bid cache does not match the bid levels".to_string());
+        }
+        let expected_asks: Vec<(f64, f64)> = self.iter_asks().take(TOP_N).collect();
+        if self.top_asks() != expected_asks.as_slice() {
+            return Err("top-of-book ask cache does not match the ask levels".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Take the volume removed from the best bid/ask by deltas since the last call
+    /// 
+    /// Counts every decrease at the touch, including levels removed outright,
+    /// so it can slightly overstate trading when top-of-book orders are cancelled.
+    pub fn take_traded_volume(&mut self) -> f64 {
+        std::mem::take(&mut self.traded_volume)
+    }
+
+    /// Take the trades inferred by deltas since the last call, oldest first
+    pub fn take_trades(&mut self) -> Vec<DetectedTrade> {
+        std::mem::take(&mut self.trades)
+    }
+
+    /// Take the price levels changed by deltas since the last call
+    /// 
+    /// Applying a snapshot discards pending changes, since snapshots are
+    /// published in full.
+    pub fn take_changes(&mut self) -> OrderbookDelta {
+        let price_scale = self.price_scale;
+        let to_entries = |levels: BTreeMap<Price, f64>| levels
+            .into_iter()
+            .map(move |(price, volume)| PriceLevelEntry { price: price_scale.price(price), volume });
+
+        OrderbookDelta {
+            timestamp: unix_timestamp(),
+            last_price: self.last_price,
+            bids: to_entries(std::mem::take(&mut self.changed_bids)).rev().collect(),
+            asks: to_entries(std::mem::take(&mut self.changed_asks)).collect(),
         }
     }
 }
 
 impl Default for OrderbookEngine {
     fn default() -> Self {
-        Self::new()
+        Self::new(None)
     }
 }
 
@@ -260,9 +1576,14 @@ impl Default for OrderbookEngine {
 mod tests {
     use super::*;
 
+    /// Map key of a price in an engine with the default resolution
+    fn key(price: f64) -> Price {
+        PriceScale::default().key(price).unwrap()
+    }
+
     #[test]
     fn test_new_orderbook() {
-        let engine = OrderbookEngine::new();
+        let engine = OrderbookEngine::default();
         assert_eq!(engine.last_price(), None);
         // Verify bids and asks are empty by checking length through mut access
         let mut engine = engine;
@@ -272,52 +1593,124 @@ mod tests {
 
     #[test]
     fn test_set_last_price() {
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         engine.set_last_price(42000.0);
         assert_eq!(engine.last_price(), Some(42000.0));
     }
 
+    #[test]
+    fn test_traded_volume_accumulates_top_of_book_decreases() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "5.0", "1.0"]), serde_json::json!(["99.0", "4.0", "1.0"])],
+            asks: vec![serde_json::json!(["101.0", "3.0", "1.0"])],
+        }).unwrap();
+        assert_eq!(engine.take_traded_volume(), 0.0);
+
+        // 2.0 off the best bid, 1.0 off the best ask; the deeper bid doesn't count
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["100.0", "3.0", "2.0"]), serde_json::json!(["99.0", "1.0", "2.0"])],
+            asks: vec![serde_json::json!(["101.0", "2.0", "2.0"])],
+            checksum: None,
+        }).unwrap();
+        // The best bid is removed outright: its remaining 3.0 counts too
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["100.0", "0.0", "3.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        // Volume added at the touch doesn't offset anything
+        engine.apply_delta(&BookDelta {
+            bids: vec![],
+            asks: vec![serde_json::json!(["101.0", "9.0", "4.0"])],
+            checksum: None,
+        }).unwrap();
+
+        assert_eq!(engine.take_traded_volume(), 6.0);
+        assert_eq!(engine.take_traded_volume(), 0.0);
+    }
+
+    #[test]
+    fn test_trade_prices_replace_inferred_last_price() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "2.0", "1.0"])],
+            asks: vec![serde_json::json!(["101.0", "2.0", "1.0"])],
+        }).unwrap();
+        engine.use_trade_prices();
+        engine.set_last_price(100.4);
+
+        // A volume cut at the touch and a new best bid no longer move the last price
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["100.0", "1.0", "2.0"]), serde_json::json!(["100.2", "1.0", "2.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(engine.last_price(), Some(100.4));
+        assert_eq!(engine.take_trades().len(), 1);
+        assert_eq!(engine.smoothed_price(), Some(100.4));
+    }
+
     #[test]
     fn test_bids_ordering() {
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         // Add bids in random order
-        engine.bids_mut().insert(Price(41980.0), 1.2);
-        engine.bids_mut().insert(Price(41990.0), 2.5);
-        engine.bids_mut().insert(Price(41970.0), 0.8);
+        engine.bids_mut().insert(key(41980.0), 1.2);
+        engine.bids_mut().insert(key(41990.0), 2.5);
+        engine.bids_mut().insert(key(41970.0), 0.8);
         
         // When iterating in reverse, should get descending order
-        let prices: Vec<f64> = engine.bids_mut().iter().rev().map(|(p, _)| p.0).collect();
+        let prices: Vec<f64> = engine.bids_mut().iter().rev().map(|(p, _)| PriceScale::default().price(*p)).collect();
         assert_eq!(prices, vec![41990.0, 41980.0, 41970.0]);
     }
 
     #[test]
     fn test_asks_ordering() {
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         // Add asks in random order
-        engine.asks_mut().insert(Price(42020.0), 0.8);
-        engine.asks_mut().insert(Price(42010.0), 3.1);
-        engine.asks_mut().insert(Price(42030.0), 1.5);
+        engine.asks_mut().insert(key(42020.0), 0.8);
+        engine.asks_mut().insert(key(42010.0), 3.1);
+        engine.asks_mut().insert(key(42030.0), 1.5);
         
         // When iterating forward, should get ascending order
-        let prices: Vec<f64> = engine.asks_mut().iter().map(|(p, _)| p.0).collect();
+        let prices: Vec<f64> = engine.asks_mut().keys().map(|p| PriceScale::default().price(*p)).collect();
         assert_eq!(prices, vec![42010.0, 42020.0, 42030.0]);
     }
 
+    #[test]
+    fn test_iter_bids_and_asks_order() {
+        let mut engine = OrderbookEngine::default();
+        engine.bids_mut().insert(key(41980.0), 1.2);
+        engine.bids_mut().insert(key(41990.0), 2.5);
+        engine.bids_mut().insert(key(41970.0), 0.8);
+        engine.asks_mut().insert(key(42020.0), 0.8);
+        engine.asks_mut().insert(key(42010.0), 3.1);
+        engine.asks_mut().insert(key(42030.0), 1.5);
+
+        // Bids descend from the best (highest) price
+        let bids: Vec<(f64, f64)> = engine.iter_bids().collect();
+        assert_eq!(bids, vec![(41990.0, 2.5), (41980.0, 1.2), (41970.0, 0.8)]);
+
+        // Asks ascend from the best (lowest) price
+        let asks: Vec<(f64, f64)> = engine.iter_asks().collect();
+        assert_eq!(asks, vec![(42010.0, 3.1), (42020.0, 0.8), (42030.0, 1.5)]);
+    }
+
     #[test]
     fn test_apply_snapshot() {
         use crate::kraken::types::BookSnapshot;
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Create a snapshot with some bids and asks
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
-                ["41980.0".to_string(), "1.2".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "1.2", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
-                ["42020.0".to_string(), "0.8".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+                serde_json::json!(["42020.0", "0.8", "1234567890.0"]),
             ],
         };
         
@@ -326,36 +1719,36 @@ mod tests {
         
         // Verify bids were populated (in descending order when iterated in reverse)
         assert_eq!(engine.bids_mut().len(), 2);
-        let bid_prices: Vec<f64> = engine.bids_mut().iter().rev().map(|(p, _)| p.0).collect();
+        let bid_prices: Vec<f64> = engine.bids_mut().iter().rev().map(|(p, _)| PriceScale::default().price(*p)).collect();
         assert_eq!(bid_prices, vec![41990.0, 41980.0]);
-        assert_eq!(engine.bids_mut().get(&Price(41990.0)), Some(&2.5));
-        assert_eq!(engine.bids_mut().get(&Price(41980.0)), Some(&1.2));
+        assert_eq!(engine.bids_mut().get(&key(41990.0)), Some(&2.5));
+        assert_eq!(engine.bids_mut().get(&key(41980.0)), Some(&1.2));
         
         // Verify asks were populated (in ascending order)
         assert_eq!(engine.asks_mut().len(), 2);
-        let ask_prices: Vec<f64> = engine.asks_mut().iter().map(|(p, _)| p.0).collect();
+        let ask_prices: Vec<f64> = engine.asks_mut().keys().map(|p| PriceScale::default().price(*p)).collect();
         assert_eq!(ask_prices, vec![42010.0, 42020.0]);
-        assert_eq!(engine.asks_mut().get(&Price(42010.0)), Some(&3.1));
-        assert_eq!(engine.asks_mut().get(&Price(42020.0)), Some(&0.8));
+        assert_eq!(engine.asks_mut().get(&key(42010.0)), Some(&3.1));
+        assert_eq!(engine.asks_mut().get(&key(42020.0)), Some(&0.8));
     }
 
     #[test]
     fn test_apply_snapshot_clears_existing() {
         use crate::kraken::types::BookSnapshot;
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Add some initial data
-        engine.bids_mut().insert(Price(50000.0), 10.0);
-        engine.asks_mut().insert(Price(30000.0), 5.0);
+        engine.bids_mut().insert(key(50000.0), 10.0);
+        engine.asks_mut().insert(key(30000.0), 5.0);
         
         // Create a new snapshot
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
             ],
         };
         
@@ -363,29 +1756,29 @@ mod tests {
         engine.apply_snapshot(&snapshot).unwrap();
         
         // Verify old data is gone
-        assert_eq!(engine.bids_mut().get(&Price(50000.0)), None);
-        assert_eq!(engine.asks_mut().get(&Price(30000.0)), None);
+        assert_eq!(engine.bids_mut().get(&key(50000.0)), None);
+        assert_eq!(engine.asks_mut().get(&key(30000.0)), None);
         
         // Verify new data is present
-        assert_eq!(engine.bids_mut().get(&Price(41990.0)), Some(&2.5));
-        assert_eq!(engine.asks_mut().get(&Price(42010.0)), Some(&3.1));
+        assert_eq!(engine.bids_mut().get(&key(41990.0)), Some(&2.5));
+        assert_eq!(engine.asks_mut().get(&key(42010.0)), Some(&3.1));
     }
 
     #[test]
     fn test_apply_snapshot_filters_zero_volume() {
         use crate::kraken::types::BookSnapshot;
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Create a snapshot with zero volume entries
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
-                ["41980.0".to_string(), "0.0".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "0.0", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
-                ["42020.0".to_string(), "0.0".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+                serde_json::json!(["42020.0", "0.0", "1234567890.0"]),
             ],
         };
         
@@ -394,27 +1787,27 @@ mod tests {
         
         // Verify zero volume entries were filtered out
         assert_eq!(engine.bids_mut().len(), 1);
-        assert_eq!(engine.bids_mut().get(&Price(41990.0)), Some(&2.5));
-        assert_eq!(engine.bids_mut().get(&Price(41980.0)), None);
+        assert_eq!(engine.bids_mut().get(&key(41990.0)), Some(&2.5));
+        assert_eq!(engine.bids_mut().get(&key(41980.0)), None);
         
         assert_eq!(engine.asks_mut().len(), 1);
-        assert_eq!(engine.asks_mut().get(&Price(42010.0)), Some(&3.1));
-        assert_eq!(engine.asks_mut().get(&Price(42020.0)), None);
+        assert_eq!(engine.asks_mut().get(&key(42010.0)), Some(&3.1));
+        assert_eq!(engine.asks_mut().get(&key(42020.0)), None);
     }
 
     #[test]
     fn test_apply_delta_updates_existing() {
         use crate::kraken::types::{BookSnapshot, BookDelta};
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // First, apply a snapshot to set initial state
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
             ],
         };
         engine.apply_snapshot(&snapshot).unwrap();
@@ -422,32 +1815,33 @@ mod tests {
         // Apply a delta that updates existing price levels
         let delta = BookDelta {
             bids: vec![
-                ["41990.0".to_string(), "5.0".to_string(), "1234567891.0".to_string()],
+                serde_json::json!(["41990.0", "5.0", "1234567891.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "1.5".to_string(), "1234567891.0".to_string()],
+                serde_json::json!(["42010.0", "1.5", "1234567891.0"]),
             ],
+            checksum: None,
         };
         engine.apply_delta(&delta).unwrap();
         
         // Verify volumes were updated
-        assert_eq!(engine.bids_mut().get(&Price(41990.0)), Some(&5.0));
-        assert_eq!(engine.asks_mut().get(&Price(42010.0)), Some(&1.5));
+        assert_eq!(engine.bids_mut().get(&key(41990.0)), Some(&5.0));
+        assert_eq!(engine.asks_mut().get(&key(42010.0)), Some(&1.5));
     }
 
     #[test]
     fn test_apply_delta_inserts_new() {
         use crate::kraken::types::{BookSnapshot, BookDelta};
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Set initial state
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
             ],
         };
         engine.apply_snapshot(&snapshot).unwrap();
@@ -455,39 +1849,40 @@ mod tests {
         // Apply a delta that adds new price levels
         let delta = BookDelta {
             bids: vec![
-                ["41980.0".to_string(), "1.2".to_string(), "1234567891.0".to_string()],
+                serde_json::json!(["41980.0", "1.2", "1234567891.0"]),
             ],
             asks: vec![
-                ["42020.0".to_string(), "0.8".to_string(), "1234567891.0".to_string()],
+                serde_json::json!(["42020.0", "0.8", "1234567891.0"]),
             ],
+            checksum: None,
         };
         engine.apply_delta(&delta).unwrap();
         
         // Verify new levels were added
         assert_eq!(engine.bids_mut().len(), 2);
-        assert_eq!(engine.bids_mut().get(&Price(41980.0)), Some(&1.2));
-        assert_eq!(engine.bids_mut().get(&Price(41990.0)), Some(&2.5));
+        assert_eq!(engine.bids_mut().get(&key(41980.0)), Some(&1.2));
+        assert_eq!(engine.bids_mut().get(&key(41990.0)), Some(&2.5));
         
         assert_eq!(engine.asks_mut().len(), 2);
-        assert_eq!(engine.asks_mut().get(&Price(42010.0)), Some(&3.1));
-        assert_eq!(engine.asks_mut().get(&Price(42020.0)), Some(&0.8));
+        assert_eq!(engine.asks_mut().get(&key(42010.0)), Some(&3.1));
+        assert_eq!(engine.asks_mut().get(&key(42020.0)), Some(&0.8));
     }
 
     #[test]
     fn test_apply_delta_removes_zero_volume() {
         use crate::kraken::types::{BookSnapshot, BookDelta};
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Set initial state with multiple levels
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
-                ["41980.0".to_string(), "1.2".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "1.2", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
-                ["42020.0".to_string(), "0.8".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+                serde_json::json!(["42020.0", "0.8", "1234567890.0"]),
             ],
         };
         engine.apply_snapshot(&snapshot).unwrap();
@@ -495,38 +1890,39 @@ mod tests {
         // Apply a delta that removes a price level (volume = 0)
         let delta = BookDelta {
             bids: vec![
-                ["41980.0".to_string(), "0.0".to_string(), "1234567891.0".to_string()],
+                serde_json::json!(["41980.0", "0.0", "1234567891.0"]),
             ],
             asks: vec![
-                ["42020.0".to_string(), "0.0".to_string(), "1234567891.0".to_string()],
+                serde_json::json!(["42020.0", "0.0", "1234567891.0"]),
             ],
+            checksum: None,
         };
         engine.apply_delta(&delta).unwrap();
         
         // Verify removed levels are gone
         assert_eq!(engine.bids_mut().len(), 1);
-        assert_eq!(engine.bids_mut().get(&Price(41980.0)), None);
-        assert_eq!(engine.bids_mut().get(&Price(41990.0)), Some(&2.5));
+        assert_eq!(engine.bids_mut().get(&key(41980.0)), None);
+        assert_eq!(engine.bids_mut().get(&key(41990.0)), Some(&2.5));
         
         assert_eq!(engine.asks_mut().len(), 1);
-        assert_eq!(engine.asks_mut().get(&Price(42020.0)), None);
-        assert_eq!(engine.asks_mut().get(&Price(42010.0)), Some(&3.1));
+        assert_eq!(engine.asks_mut().get(&key(42020.0)), None);
+        assert_eq!(engine.asks_mut().get(&key(42010.0)), Some(&3.1));
     }
 
     #[test]
     fn test_apply_delta_mixed_operations() {
         use crate::kraken::types::{BookSnapshot, BookDelta};
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Set initial state
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
-                ["41980.0".to_string(), "1.2".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "1.2", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
             ],
         };
         engine.apply_snapshot(&snapshot).unwrap();
@@ -534,42 +1930,43 @@ mod tests {
         // Apply a delta with mixed operations: update, insert, remove
         let delta = BookDelta {
             bids: vec![
-                ["41990.0".to_string(), "5.0".to_string(), "1234567891.0".to_string()], // update
-                ["41980.0".to_string(), "0.0".to_string(), "1234567891.0".to_string()], // remove
-                ["41970.0".to_string(), "0.5".to_string(), "1234567891.0".to_string()], // insert
+                serde_json::json!(["41990.0", "5.0", "1234567891.0"]), // update
+                serde_json::json!(["41980.0", "0.0", "1234567891.0"]), // remove
+                serde_json::json!(["41970.0", "0.5", "1234567891.0"]), // insert
             ],
             asks: vec![
-                ["42010.0".to_string(), "1.5".to_string(), "1234567891.0".to_string()], // update
-                ["42020.0".to_string(), "2.0".to_string(), "1234567891.0".to_string()], // insert
+                serde_json::json!(["42010.0", "1.5", "1234567891.0"]), // update
+                serde_json::json!(["42020.0", "2.0", "1234567891.0"]), // insert
             ],
+            checksum: None,
         };
         engine.apply_delta(&delta).unwrap();
         
         // Verify all operations worked
         assert_eq!(engine.bids_mut().len(), 2);
-        assert_eq!(engine.bids_mut().get(&Price(41990.0)), Some(&5.0)); // updated
-        assert_eq!(engine.bids_mut().get(&Price(41980.0)), None); // removed
-        assert_eq!(engine.bids_mut().get(&Price(41970.0)), Some(&0.5)); // inserted
+        assert_eq!(engine.bids_mut().get(&key(41990.0)), Some(&5.0)); // updated
+        assert_eq!(engine.bids_mut().get(&key(41980.0)), None); // removed
+        assert_eq!(engine.bids_mut().get(&key(41970.0)), Some(&0.5)); // inserted
         
         assert_eq!(engine.asks_mut().len(), 2);
-        assert_eq!(engine.asks_mut().get(&Price(42010.0)), Some(&1.5)); // updated
-        assert_eq!(engine.asks_mut().get(&Price(42020.0)), Some(&2.0)); // inserted
+        assert_eq!(engine.asks_mut().get(&key(42010.0)), Some(&1.5)); // updated
+        assert_eq!(engine.asks_mut().get(&key(42020.0)), Some(&2.0)); // inserted
     }
 
     #[test]
     fn test_apply_delta_updates_last_price_on_bid_trade() {
         use crate::kraken::types::{BookSnapshot, BookDelta};
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Set initial state with best bid at 41990
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
-                ["41980.0".to_string(), "1.2".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "1.2", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
             ],
         };
         engine.apply_snapshot(&snapshot).unwrap();
@@ -577,9 +1974,10 @@ mod tests {
         // Apply a delta that decreases volume at best bid (indicates a trade)
         let delta = BookDelta {
             bids: vec![
-                ["41990.0".to_string(), "1.5".to_string(), "1234567891.0".to_string()], // volume decreased from 2.5 to 1.5
+                serde_json::json!(["41990.0", "1.5", "1234567891.0"]), // volume decreased from 2.5 to 1.5
             ],
             asks: vec![],
+            checksum: None,
         };
         engine.apply_delta(&delta).unwrap();
         
@@ -587,20 +1985,45 @@ mod tests {
         assert_eq!(engine.last_price(), Some(41990.0));
     }
 
+    #[test]
+    fn test_take_trades_records_inferred_trades() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![serde_json::json!(["42010.0", "3.1", "1234567890.0"])],
+        }).unwrap();
+
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["41990.0", "1.5", "1234567891.25"])],
+            asks: vec![serde_json::json!(["42010.0", "3.0", "1234567891.5"])],
+            checksum: None,
+        }).unwrap();
+
+        let trades = engine.take_trades();
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0], DetectedTrade { timestamp_ms: 1234567891250, price: 41990.0, volume: 1.0, side: TradeSide::Sell });
+        assert_eq!(trades[1].side, TradeSide::Buy);
+        assert_eq!(trades[1].price, 42010.0);
+        assert!((trades[1].volume - 0.1).abs() < 1e-9);
+
+        // Drained by the call
+        assert!(engine.take_trades().is_empty());
+    }
+
     #[test]
     fn test_apply_delta_updates_last_price_on_ask_trade() {
         use crate::kraken::types::{BookSnapshot, BookDelta};
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Set initial state with best ask at 42010
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
-                ["42020.0".to_string(), "1.2".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+                serde_json::json!(["42020.0", "1.2", "1234567890.0"]),
             ],
         };
         engine.apply_snapshot(&snapshot).unwrap();
@@ -609,8 +2032,9 @@ mod tests {
         let delta = BookDelta {
             bids: vec![],
             asks: vec![
-                ["42010.0".to_string(), "2.0".to_string(), "1234567891.0".to_string()], // volume decreased from 3.1 to 2.0
+                serde_json::json!(["42010.0", "2.0", "1234567891.0"]), // volume decreased from 3.1 to 2.0
             ],
+            checksum: None,
         };
         engine.apply_delta(&delta).unwrap();
         
@@ -622,112 +2046,640 @@ mod tests {
     fn test_apply_delta_updates_last_price_when_best_bid_consumed() {
         use crate::kraken::types::{BookSnapshot, BookDelta};
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Set initial state with best bid at 41990
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
-                ["41980.0".to_string(), "1.2".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "1.2", "1234567890.0"]),
+            ],
+            asks: vec![
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+            ],
+        };
+        engine.apply_snapshot(&snapshot).unwrap();
+        
+        // Apply a delta that removes the best bid (consumed by trade)
+        let delta = BookDelta {
+            bids: vec![
+                serde_json::json!(["41990.0", "0.0", "1234567891.0"]), // remove best bid
+            ],
+            asks: vec![],
+            checksum: None,
+        };
+        engine.apply_delta(&delta).unwrap();
+        
+        // Verify last_price was updated to the new best bid (41980)
+        assert_eq!(engine.last_price(), Some(41980.0));
+    }
+
+    #[test]
+    fn test_apply_delta_updates_last_price_when_best_ask_consumed() {
+        use crate::kraken::types::{BookSnapshot, BookDelta};
+        
+        let mut engine = OrderbookEngine::default();
+        
+        // Set initial state with best ask at 42010
+        let snapshot = BookSnapshot {
+            bids: vec![
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+            ],
+            asks: vec![
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+                serde_json::json!(["42020.0", "1.2", "1234567890.0"]),
+            ],
+        };
+        engine.apply_snapshot(&snapshot).unwrap();
+        
+        // Apply a delta that removes the best ask (consumed by trade)
+        let delta = BookDelta {
+            bids: vec![],
+            asks: vec![
+                serde_json::json!(["42010.0", "0.0", "1234567891.0"]), // remove best ask
+            ],
+            checksum: None,
+        };
+        engine.apply_delta(&delta).unwrap();
+        
+        // Verify last_price was updated to the new best ask (42020)
+        assert_eq!(engine.last_price(), Some(42020.0));
+    }
+
+    #[test]
+    fn test_apply_delta_does_not_update_last_price_for_non_trade_updates() {
+        use crate::kraken::types::{BookSnapshot, BookDelta};
+        
+        let mut engine = OrderbookEngine::default();
+        
+        // Set initial state
+        let snapshot = BookSnapshot {
+            bids: vec![
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+            ],
+            asks: vec![
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+            ],
+        };
+        engine.apply_snapshot(&snapshot).unwrap();
+        engine.set_last_price(42000.0);
+        
+        // Apply a delta that adds a new price level (not at best bid/ask)
+        let delta = BookDelta {
+            bids: vec![
+                serde_json::json!(["41980.0", "1.2", "1234567891.0"]), // new level, not best bid
+            ],
+            asks: vec![
+                serde_json::json!(["42020.0", "0.8", "1234567891.0"]), // new level, not best ask
+            ],
+            checksum: None,
+        };
+        engine.apply_delta(&delta).unwrap();
+        
+        // Verify last_price was not changed (no trade detected)
+        assert_eq!(engine.last_price(), Some(42000.0));
+    }
+
+    #[test]
+    fn test_last_price_stuck_while_book_updates() {
+        use crate::kraken::types::{BookSnapshot, BookDelta};
+
+        let mut engine = OrderbookEngine::default();
+        let threshold = Duration::from_millis(50);
+        assert!(!engine.last_price_stuck(threshold));
+
+        let snapshot = BookSnapshot {
+            bids: vec![
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "1.2", "1234567890.0"]),
+            ],
+            asks: vec![
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+                serde_json::json!(["42020.0", "1.2", "1234567890.0"]),
+            ],
+        };
+        engine.apply_snapshot(&snapshot).unwrap();
+        engine.set_last_price(42000.0);
+        assert!(!engine.last_price_stuck(threshold));
+
+        std::thread::sleep(Duration::from_millis(60));
+
+        // Keep the book active with updates away from the top of book
+        let delta = BookDelta {
+            bids: vec![
+                serde_json::json!(["41980.0", "1.5", "1234567891.0"]),
+            ],
+            asks: vec![
+                serde_json::json!(["42020.0", "1.5", "1234567891.0"]),
+            ],
+            checksum: None,
+        };
+        engine.apply_delta(&delta).unwrap();
+
+        assert_eq!(engine.last_price(), Some(42000.0));
+        assert!(engine.last_price_stuck(threshold));
+        assert!(engine.time_since_last_price_change().unwrap() >= threshold);
+
+        // A price change clears the flag
+        engine.set_last_price(42010.0);
+        assert!(!engine.last_price_stuck(threshold));
+    }
+
+    #[test]
+    fn test_last_price_not_stuck_when_book_idle() {
+        use crate::kraken::types::BookSnapshot;
+
+        let mut engine = OrderbookEngine::default();
+        let snapshot = BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![serde_json::json!(["42010.0", "3.1", "1234567890.0"])],
+        };
+        engine.apply_snapshot(&snapshot).unwrap();
+
+        std::thread::sleep(Duration::from_millis(60));
+
+        // No recent book activity, so a constant price is not suspicious
+        assert!(!engine.last_price_stuck(Duration::from_millis(50)));
+    }
+
+    #[test]
+    fn test_spread_ticks() {
+        use crate::kraken::types::BookSnapshot;
+
+        let snapshot = BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![serde_json::json!(["41992.5", "3.1", "1234567890.0"])],
+        };
+
+        // No tick size configured
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&snapshot).unwrap();
+        assert_eq!(engine.spread_ticks(), None);
+
+        // 2.5 spread / 0.5 tick = 5 ticks exactly
+        let mut engine = OrderbookEngine::new(Some(0.5));
+        assert_eq!(engine.spread_ticks(), None);
+        engine.apply_snapshot(&snapshot).unwrap();
+        assert_eq!(engine.spread_ticks(), Some(5));
+        assert_eq!(engine.spread_tick_aligned(), Some(true));
+
+        // 2.5 spread / 1.0 tick rounds to 3 ticks and is flagged as unaligned
+        let mut engine = OrderbookEngine::new(Some(1.0));
+        engine.apply_snapshot(&snapshot).unwrap();
+        assert_eq!(engine.spread_ticks(), Some(3));
+        assert_eq!(engine.spread_tick_aligned(), Some(false));
+    }
+
+    /// Example book from Kraken's checksum guide, with its published checksum
+    fn kraken_checksum_example() -> (BookSnapshot, u32) {
+        let level = |price: &str| serde_json::json!([price, "0.00000500", "1582905487.684110"]);
+        let snapshot = BookSnapshot {
+            asks: ["0.05005", "0.05010", "0.05015", "0.05020", "0.05025",
+                   "0.05030", "0.05035", "0.05040", "0.05045", "0.05050"]
+                .into_iter().map(level).collect(),
+            bids: ["0.05000", "0.04995", "0.04990", "0.04980", "0.04975",
+                   "0.04970", "0.04965", "0.04960", "0.04955", "0.04950"]
+                .into_iter().map(level).collect(),
+        };
+        (snapshot, 974947235)
+    }
+
+    #[test]
+    fn test_verify_checksum_known_book() {
+        let (snapshot, expected) = kraken_checksum_example();
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.checksum(), None);
+
+        engine.apply_snapshot(&snapshot).unwrap();
+        assert_eq!(engine.checksum(), Some(expected));
+        assert!(engine.verify_checksum(expected));
+        assert!(!engine.verify_checksum(expected + 1));
+    }
+
+    #[test]
+    fn test_verify_checksum_ignores_levels_beyond_top_10() {
+        let (snapshot, expected) = kraken_checksum_example();
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&snapshot).unwrap();
+
+        // A level deeper than the top 10 does not change the checksum
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["0.04900", "1.00000000", "1582905490.0"])],
+            asks: vec![],
+            checksum: Some(expected),
+        }).unwrap();
+        assert!(engine.verify_checksum(expected));
+
+        // A change at the touch does
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["0.05000", "0.00000400", "1582905490.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert!(!engine.verify_checksum(expected));
+    }
+
+    #[test]
+    fn test_take_changes_returns_modified_levels_only() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "1.2", "1234567890.0"]),
+            ],
+            asks: vec![serde_json::json!(["42010.0", "3.1", "1234567890.0"])],
+        }).unwrap();
+
+        // Snapshot levels are not reported as changes
+        let changes = engine.take_changes();
+        assert!(changes.bids.is_empty() && changes.asks.is_empty());
+
+        engine.apply_delta(&BookDelta {
+            bids: vec![
+                serde_json::json!(["41970.0", "1.0", "1234567891.0"]),
+                serde_json::json!(["41990.0", "2.0", "1234567891.0"]),
+            ],
+            asks: vec![serde_json::json!(["42010.0", "0.0", "1234567891.0"])],
+            checksum: None,
+        }).unwrap();
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["41990.0", "1.5", "1234567892.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+
+        let changes = engine.take_changes();
+        let bids: Vec<(f64, f64)> = changes.bids.iter().map(|l| (l.price, l.volume)).collect();
+        let asks: Vec<(f64, f64)> = changes.asks.iter().map(|l| (l.price, l.volume)).collect();
+        // Latest volume per level, bids descending, removals as volume 0
+        assert_eq!(bids, vec![(41990.0, 1.5), (41970.0, 1.0)]);
+        assert_eq!(asks, vec![(42010.0, 0.0)]);
+
+        // Changes are drained by the call
+        let changes = engine.take_changes();
+        assert!(changes.bids.is_empty() && changes.asks.is_empty());
+    }
+
+    #[test]
+    fn test_self_check() {
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.self_check(), Ok(()));
+
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![serde_json::json!(["42010.0", "3.1", "1234567890.0"])],
+        }).unwrap();
+        assert_eq!(engine.self_check(), Ok(()));
+
+        // Editing the map directly bypasses the top-of-book cache
+        engine.asks_mut().insert(key(42005.0), 1.0);
+        assert!(engine.self_check().unwrap_err().contains("ask cache"));
+
+        // A crossed book is reported before the cache mismatch
+        engine.bids_mut().insert(key(42020.0), 1.0);
+        assert!(engine.self_check().unwrap_err().contains("crossed"));
+
+        // Invalid volumes are reported first
+        engine.bids_mut().insert(key(41000.0), -1.0);
+        assert!(engine.self_check().unwrap_err().contains("invalid volume"));
+    }
+
+    #[test]
+    fn test_mid_price_and_spread() {
+        use crate::kraken::types::BookSnapshot;
+
+        // Empty book
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.mid_price(), None);
+        assert_eq!(engine.spread(), None);
+
+        // One-sided book
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![],
+        }).unwrap();
+        assert_eq!(engine.mid_price(), None);
+        assert_eq!(engine.spread(), None);
+
+        // Two-sided book
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![serde_json::json!(["42010.0", "3.1", "1234567890.0"])],
+        }).unwrap();
+        assert_eq!(engine.mid_price(), Some(42000.0));
+        assert_eq!(engine.spread(), Some(20.0));
+
+        let state = engine.get_current_state();
+        assert_eq!(state.mid_price, Some(42000.0));
+        assert_eq!(state.spread, Some(20.0));
+
+        // Crossed book reports the raw (negative) difference
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["42020.0", "2.5", "1234567890.0"])],
+            asks: vec![serde_json::json!(["42010.0", "3.1", "1234567890.0"])],
+        }).unwrap();
+        assert_eq!(engine.spread(), Some(-10.0));
+    }
+
+    /// Engine with bids 100 x 1, 99 x 3 and asks 101 x 2, 102 x 2
+    fn vwap_engine() -> OrderbookEngine {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![
+                serde_json::json!(["100.0", "1.0", "1.0"]),
+                serde_json::json!(["99.0", "3.0", "1.0"]),
+            ],
+            asks: vec![
+                serde_json::json!(["101.0", "2.0", "1.0"]),
+                serde_json::json!(["102.0", "2.0", "1.0"]),
+            ],
+        }).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_vwap_over_depth() {
+        let engine = vwap_engine();
+        assert_eq!(engine.vwap(Side::Bid, 1), Some(100.0));
+        assert_eq!(engine.vwap(Side::Bid, 2), Some(99.25));
+        assert_eq!(engine.vwap(Side::Ask, 10), Some(101.5));
+        assert_eq!(engine.vwap(Side::Ask, 0), None);
+        assert_eq!(OrderbookEngine::default().vwap(Side::Bid, 10), None);
+    }
+
+    #[test]
+    fn test_vwap_for_quantity() {
+        let engine = vwap_engine();
+        // Filled entirely at the best level
+        assert_eq!(engine.vwap_for_quantity(Side::Ask, 1.0), Some(101.0));
+        // Walks into the second level: (2 * 101 + 1 * 102) / 3
+        assert_eq!(engine.vwap_for_quantity(Side::Ask, 3.0), Some(304.0 / 3.0));
+        // Exactly the whole side
+        assert_eq!(engine.vwap_for_quantity(Side::Bid, 4.0), Some(99.25));
+        // More than the side holds exhausts the book
+        assert_eq!(engine.vwap_for_quantity(Side::Bid, 4.5), None);
+        assert_eq!(engine.vwap_for_quantity(Side::Bid, 0.0), None);
+    }
+
+    #[test]
+    fn test_cumulative_depth_runs_outward_from_best() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![
+                serde_json::json!(["98.0", "3.0", "1.0"]),
+                serde_json::json!(["100.0", "1.0", "1.0"]),
+                serde_json::json!(["99.0", "2.0", "1.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["103.0", "0.5", "1.0"]),
+                serde_json::json!(["101.0", "1.5", "1.0"]),
+                serde_json::json!(["102.0", "2.5", "1.0"]),
             ],
-        };
-        engine.apply_snapshot(&snapshot).unwrap();
-        
-        // Apply a delta that removes the best bid (consumed by trade)
-        let delta = BookDelta {
-            bids: vec![
-                ["41990.0".to_string(), "0.0".to_string(), "1234567891.0".to_string()], // remove best bid
-            ],
-            asks: vec![],
-        };
-        engine.apply_delta(&delta).unwrap();
-        
-        // Verify last_price was updated to the new best bid (41980)
-        assert_eq!(engine.last_price(), Some(41980.0));
+        }).unwrap();
+
+        assert_eq!(engine.cumulative_depth(Side::Bid), vec![(100.0, 1.0), (99.0, 3.0), (98.0, 6.0)]);
+        assert_eq!(engine.cumulative_depth(Side::Ask), vec![(101.0, 1.5), (102.0, 4.0), (103.0, 4.5)]);
+        assert!(OrderbookEngine::default().cumulative_depth(Side::Bid).is_empty());
     }
 
     #[test]
-    fn test_apply_delta_updates_last_price_when_best_ask_consumed() {
-        use crate::kraken::types::{BookSnapshot, BookDelta};
-        
-        let mut engine = OrderbookEngine::new();
-        
-        // Set initial state with best ask at 42010
-        let snapshot = BookSnapshot {
+    fn test_aggregate_levels_into_cumulative_ladder() {
+        let engine = vwap_engine();
+        // Mid 100.5, band 200 bps = 2.01, so two buckets 1.005 wide per side
+        let bids = engine.aggregate_levels(Side::Bid, 200.0, 2);
+        let asks = engine.aggregate_levels(Side::Ask, 200.0, 2);
+        assert_eq!(bids.iter().map(|(_, volume)| *volume).collect::<Vec<_>>(), vec![1.0, 3.0]);
+        assert_eq!(asks.iter().map(|(_, volume)| *volume).collect::<Vec<_>>(), vec![2.0, 2.0]);
+        assert!((bids[1].0 - 98.49).abs() < 1e-9);
+        assert!((asks[0].0 - 101.505).abs() < 1e-9);
+
+        let ladder = cumulative_ladder(bids.iter().copied());
+        assert_eq!(ladder.iter().map(|level| level.cumulative).collect::<Vec<_>>(), vec![1.0, 4.0]);
+
+        // Levels outside the band are left out; empty buckets are kept
+        let narrow = engine.aggregate_levels(Side::Ask, 100.0, 4);
+        assert_eq!(narrow.iter().map(|(_, volume)| *volume).collect::<Vec<_>>(), vec![0.0, 2.0, 0.0, 0.0]);
+        assert!(OrderbookEngine::default().aggregate_levels(Side::Bid, 100.0, 4).is_empty());
+        assert!(engine.aggregate_levels(Side::Bid, 100.0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_liquidity_within_bps() {
+        use crate::kraken::types::BookSnapshot;
+
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.liquidity_within_bps(100.0), None);
+
+        // Mid is 100, so 100 bps is the inclusive band [99, 101]
+        engine.apply_snapshot(&BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["99.5", "1.0", "1.0"]),
+                serde_json::json!(["99.0", "2.0", "1.0"]),
+                serde_json::json!(["98.0", "4.0", "1.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
-                ["42020.0".to_string(), "1.2".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["100.5", "1.0", "1.0"]),
+                serde_json::json!(["101.0", "3.0", "1.0"]),
+                serde_json::json!(["102.0", "5.0", "1.0"]),
             ],
-        };
-        engine.apply_snapshot(&snapshot).unwrap();
-        
-        // Apply a delta that removes the best ask (consumed by trade)
-        let delta = BookDelta {
+        }).unwrap();
+        assert_eq!(engine.liquidity_within_bps(100.0), Some((3.0, 4.0)));
+        assert_eq!(engine.liquidity_within_bps(0.0), Some((0.0, 0.0)));
+        assert_eq!(engine.liquidity_within_bps(200.0), Some((7.0, 9.0)));
+
+        // Removing the best ask moves the mid to 100.25, shifting the band to ~[99.25, 101.25]
+        engine.apply_delta(&BookDelta {
             bids: vec![],
-            asks: vec![
-                ["42010.0".to_string(), "0.0".to_string(), "1234567891.0".to_string()], // remove best ask
-            ],
-        };
-        engine.apply_delta(&delta).unwrap();
-        
-        // Verify last_price was updated to the new best ask (42020)
-        assert_eq!(engine.last_price(), Some(42020.0));
+            asks: vec![serde_json::json!(["100.5", "0.0", "2.0"])],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(engine.liquidity_within_bps(100.0), Some((1.0, 3.0)));
+
+        // One-sided book
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["99.5", "1.0", "1.0"])],
+            asks: vec![],
+        }).unwrap();
+        assert_eq!(engine.liquidity_within_bps(100.0), None);
+
+        // Levels on the band edge stay in even when float arithmetic would
+        // put the edge a hair inside them: mid 0.8, 1250 bps is exactly 0.7..0.9
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["0.7", "2.0", "1.0"])],
+            asks: vec![serde_json::json!(["0.9", "3.0", "1.0"])],
+        }).unwrap();
+        assert_eq!(engine.liquidity_within_bps(1250.0), Some((2.0, 3.0)));
+        assert_eq!(engine.liquidity_within_bps(1249.0), Some((0.0, 0.0)));
     }
 
     #[test]
-    fn test_apply_delta_does_not_update_last_price_for_non_trade_updates() {
-        use crate::kraken::types::{BookSnapshot, BookDelta};
-        
-        let mut engine = OrderbookEngine::new();
-        
-        // Set initial state
-        let snapshot = BookSnapshot {
+    fn test_imbalance() {
+        use crate::kraken::types::BookSnapshot;
+
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.imbalance(10), None);
+
+        // Only the top `depth` levels count: 3 bid vs 1 ask at depth 1
+        engine.apply_snapshot(&BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["100.0", "3.0", "1.0"]),
+                serde_json::json!(["99.0", "5.0", "1.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["101.0", "1.0", "1.0"]),
+                serde_json::json!(["102.0", "1.0", "1.0"]),
             ],
-        };
-        engine.apply_snapshot(&snapshot).unwrap();
+        }).unwrap();
+        assert_eq!(engine.imbalance(1), Some(0.5));
+        assert_eq!(engine.imbalance(2), Some(0.6));
+        // Depth beyond the available levels uses whatever is there
+        assert_eq!(engine.imbalance(50), Some(0.6));
+
+        // One-sided books are fully imbalanced
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "3.0", "1.0"])],
+            asks: vec![],
+        }).unwrap();
+        assert_eq!(engine.imbalance(10), Some(1.0));
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![],
+            asks: vec![serde_json::json!(["101.0", "2.0", "1.0"])],
+        }).unwrap();
+        assert_eq!(engine.imbalance(10), Some(-1.0));
+    }
+
+    #[test]
+    fn test_clear_resets_book_and_last_price() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![serde_json::json!(["42010.0", "3.1", "1234567890.0"])],
+        }).unwrap();
         engine.set_last_price(42000.0);
-        
-        // Apply a delta that adds a new price level (not at best bid/ask)
-        let delta = BookDelta {
-            bids: vec![
-                ["41980.0".to_string(), "1.2".to_string(), "1234567891.0".to_string()], // new level, not best bid
-            ],
-            asks: vec![
-                ["42020.0".to_string(), "0.8".to_string(), "1234567891.0".to_string()], // new level, not best ask
-            ],
-        };
-        engine.apply_delta(&delta).unwrap();
-        
-        // Verify last_price was not changed (no trade detected)
-        assert_eq!(engine.last_price(), Some(42000.0));
+        let seq = engine.update_seq();
+
+        engine.clear();
+        assert!(engine.is_empty());
+        assert_eq!(engine.iter_bids().count(), 0);
+        assert_eq!(engine.iter_asks().count(), 0);
+        assert!(engine.top_bids().is_empty());
+        assert!(engine.top_asks().is_empty());
+        assert_eq!(engine.last_price(), None);
+        assert!(engine.update_seq() > seq);
+
+        let state = engine.get_current_state();
+        assert!(state.bids.is_empty() && state.asks.is_empty());
+        assert_eq!(state.last_price, None);
+    }
+
+    #[test]
+    fn test_resync_flag_cleared_by_snapshot() {
+        use crate::kraken::types::BookSnapshot;
+
+        let mut engine = OrderbookEngine::default();
+        assert!(!engine.get_current_state().resyncing);
+
+        engine.begin_resync();
+        assert!(engine.is_resyncing());
+        assert!(engine.get_current_state().resyncing);
+
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![],
+        }).unwrap();
+        assert!(!engine.is_resyncing());
+        assert!(!engine.get_current_state().resyncing);
+    }
+
+    #[test]
+    fn test_top_cache_matches_recomputed_after_deltas() {
+        use crate::kraken::types::{BookSnapshot, BookDelta};
+
+        fn level(price: f64, volume: f64) -> serde_json::Value {
+            serde_json::json!([price.to_string(), volume.to_string(), "1234567890.0"])
+        }
+
+        fn assert_cache_matches(engine: &OrderbookEngine) {
+            let bids: Vec<(f64, f64)> = engine.iter_bids().take(TOP_N).collect();
+            let asks: Vec<(f64, f64)> = engine.iter_asks().take(TOP_N).collect();
+            assert_eq!(engine.top_bids(), bids.as_slice());
+            assert_eq!(engine.top_asks(), asks.as_slice());
+        }
+
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: (0..TOP_N + 3).map(|i| level(100.0 - i as f64, 1.0)).collect(),
+            asks: (0..TOP_N + 3).map(|i| level(101.0 + i as f64, 1.0)).collect(),
+        }).unwrap();
+        assert_cache_matches(&engine);
+
+        // Remove the top levels repeatedly, forcing promotion from deeper in the book
+        for i in 0..3 {
+            engine.apply_delta(&BookDelta {
+                bids: vec![level(100.0 - i as f64, 0.0)],
+                asks: vec![level(101.0 + i as f64, 0.0)],
+                checksum: None,
+            }).unwrap();
+            assert_cache_matches(&engine);
+        }
+
+        // Deterministic pseudo-random mix of inserts, updates and removals
+        let mut seed: u64 = 42;
+        for _ in 0..500 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let offset = (seed >> 33) % (TOP_N as u64 + 7);
+            let volume = if (seed >> 20).is_multiple_of(3) { 0.0 } else { ((seed >> 40) % 9 + 1) as f64 };
+            engine.apply_delta(&BookDelta {
+                bids: vec![level(100.0 - offset as f64, volume)],
+                asks: vec![level(101.0 + offset as f64, volume)],
+                checksum: None,
+            }).unwrap();
+            assert_cache_matches(&engine);
+        }
+
+        // Emptying a side empties its cache
+        let bid_prices: Vec<f64> = engine.iter_bids().map(|(p, _)| p).collect();
+        engine.apply_delta(&BookDelta {
+            bids: bid_prices.into_iter().map(|p| level(p, 0.0)).collect(),
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert!(engine.top_bids().is_empty());
+        assert_cache_matches(&engine);
+    }
+
+    #[test]
+    fn test_best_levels_match_the_book_within_and_beyond_the_cache() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: (0..TOP_N + 5).map(|i| serde_json::json!([format!("{}.0", 100 - i), "1.0", "1.0"])).collect(),
+            asks: (0..3).map(|i| serde_json::json!([format!("{}.0", 101 + i), "2.0", "1.0"])).collect(),
+        }).unwrap();
+
+        for n in [1, TOP_N, TOP_N + 2, TOP_N + 20] {
+            assert!(engine.best_levels(Side::Bid, n).eq(engine.iter_bids().take(n)), "bids, n = {}", n);
+            assert!(engine.best_levels(Side::Ask, n).eq(engine.iter_asks().take(n)), "asks, n = {}", n);
+        }
+        assert_eq!(engine.best_bid_level(), Some((100.0, 1.0)));
+        assert_eq!(engine.best_ask_level(), Some((101.0, 2.0)));
     }
 
     #[test]
     fn test_get_current_state() {
         use crate::kraken::types::BookSnapshot;
         
-        let mut engine = OrderbookEngine::new();
+        let mut engine = OrderbookEngine::default();
         
         // Set initial state
         let snapshot = BookSnapshot {
             bids: vec![
-                ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
-                ["41980.0".to_string(), "1.2".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                serde_json::json!(["41980.0", "1.2", "1234567890.0"]),
             ],
             asks: vec![
-                ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
-                ["42020.0".to_string(), "0.8".to_string(), "1234567890.0".to_string()],
+                serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+                serde_json::json!(["42020.0", "0.8", "1234567890.0"]),
             ],
         };
         engine.apply_snapshot(&snapshot).unwrap();
@@ -759,7 +2711,7 @@ mod tests {
 
     #[test]
     fn test_get_current_state_empty_orderbook() {
-        let engine = OrderbookEngine::new();
+        let engine = OrderbookEngine::default();
         let state = engine.get_current_state();
         
         // Verify timestamp is set
@@ -772,5 +2724,636 @@ mod tests {
         assert_eq!(state.bids.len(), 0);
         assert_eq!(state.asks.len(), 0);
     }
-}
 
+    #[test]
+    fn test_display_scale_is_applied_only_by_scaled() {
+        let mut engine = OrderbookEngine::default().with_display_scale(1e8);
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![
+                serde_json::json!(["0.00001233", "100.0", "1234567890.0"]),
+                serde_json::json!(["0.00001234", "50.0", "1234567890.0"]),
+            ],
+            asks: vec![serde_json::json!(["0.00001236", "75.0", "1234567890.0"])],
+        }).unwrap();
+        engine.set_last_price(0.00001235);
+
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-6;
+        assert_eq!(engine.display_scale(), 1e8);
+
+        // Emitted state keeps exchange prices, for storage and analytics
+        let state = engine.get_current_state();
+        assert_eq!(state.bids[0].price, 0.00001234);
+        assert_eq!(state.last_price, Some(0.00001235));
+
+        let state = state.scaled(engine.display_scale());
+        let bids: Vec<f64> = state.bids.iter().map(|l| l.price).collect();
+        assert!(close(bids[0], 1234.0) && close(bids[1], 1233.0));
+        assert_eq!(state.bids[0].volume, 50.0);
+        assert!(close(state.asks[0].price, 1236.0));
+        assert!(close(state.last_price.unwrap(), 1235.0));
+        assert!(close(state.spread.unwrap(), 2.0));
+
+        // Internal keys and accessors keep exchange prices and ordering
+        assert_eq!(engine.iter_bids().map(|(price, _)| price).collect::<Vec<_>>(), vec![0.00001234, 0.00001233]);
+        assert_eq!(engine.top_asks()[0].0, 0.00001236);
+
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["0.00001235", "10.0", "1234567891.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        let changes = engine.take_changes();
+        assert_eq!(changes.bids[0].price, 0.00001235);
+        let changes = changes.scaled(engine.display_scale());
+        assert!(close(changes.bids[0].price, 1235.0));
+        assert!(close(changes.last_price.unwrap(), 1235.0));
+        assert_eq!(engine.iter_bids().next(), Some((0.00001235, 10.0)));
+    }
+
+    #[test]
+    fn test_delta_outcome_flags_out_of_order_timestamps() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "1000.5"])],
+            asks: vec![serde_json::json!(["101.0", "1.0", "1000.0"])],
+        }).unwrap();
+        assert_eq!(engine.last_update_ts(), Some(1000.5));
+
+        let delta = |bids: Vec<serde_json::Value>, asks: Vec<serde_json::Value>| BookDelta { bids, asks, checksum: None };
+
+        // In-order delta advances the newest timestamp
+        let outcome = engine.apply_delta(&delta(vec![serde_json::json!(["100.0", "2.0", "1001.0"])], vec![])).unwrap();
+        assert_eq!(outcome, DeltaOutcome { max_timestamp: Some(1001.0), possible_gap: false });
+        assert_eq!(engine.last_update_ts(), Some(1001.0));
+
+        // Older than anything seen: possible gap, and the newest timestamp doesn't move back
+        let outcome = engine.apply_delta(&delta(
+            vec![serde_json::json!(["99.0", "1.0", "1000.8"])],
+            vec![serde_json::json!(["102.0", "1.0", "1000.9"])],
+        )).unwrap();
+        assert_eq!(outcome, DeltaOutcome { max_timestamp: Some(1000.9), possible_gap: true });
+        assert_eq!(engine.last_update_ts(), Some(1001.0));
+
+        // One fresh level in the delta is enough; equal timestamps are not a gap
+        let outcome = engine.apply_delta(&delta(
+            vec![serde_json::json!(["99.0", "2.0", "999.0"])],
+            vec![serde_json::json!(["102.0", "2.0", "1001.0"])],
+        )).unwrap();
+        assert!(!outcome.possible_gap);
+
+        // Levels without timestamps can't be judged
+        let outcome = engine.apply_delta(&delta(vec![serde_json::json!(["99.0", "3.0", ""])], vec![])).unwrap();
+        assert_eq!(outcome, DeltaOutcome::default());
+
+        // A new snapshot resets the baseline
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "900.0"])],
+            asks: vec![],
+        }).unwrap();
+        assert!(!engine.apply_delta(&delta(vec![serde_json::json!(["100.0", "2.0", "950.0"])], vec![])).unwrap().possible_gap);
+    }
+
+    #[test]
+    fn test_replay_snapshots_in_chronological_order() {
+        let level = |price: f64, volume: f64| PriceLevelEntry { price, volume };
+        let snapshot = |timestamp: i64, bid: f64, ask: f64| Snapshot::new(
+            "BTC".to_string(),
+            timestamp,
+            Some(bid),
+            vec![level(bid, 1.0), level(bid - 1.0, 2.0)],
+            vec![level(ask, 1.5)],
+        );
+        // Stored out of order, as a store iteration might yield them
+        let history = vec![snapshot(3000, 102.0, 103.0), snapshot(1000, 100.0, 101.0), snapshot(2000, 100.0, 102.0)];
+
+        let mut series = Vec::new();
+        let engine = OrderbookEngine::replay_snapshots(&history, |snapshot, engine| {
+            series.push((snapshot.timestamp, engine.spread(), engine.last_price()));
+        }).unwrap();
+
+        assert_eq!(series, vec![
+            (1000, Some(1.0), Some(100.0)),
+            (2000, Some(2.0), Some(100.0)),
+            (3000, Some(1.0), Some(102.0)),
+        ]);
+        // The returned engine holds the newest book, with caches rebuilt
+        assert_eq!(engine.top_bids(), &[(102.0, 1.0), (101.0, 2.0)]);
+        assert_eq!(engine.self_check(), Ok(()));
+    }
+
+    #[test]
+    fn test_price_keys_round_trip_exactly() {
+        let scale = PriceScale::default();
+        for price in ["0.000008123", "0.00001234", "0.1", "41990.0", "42010.1", "104999.9", "3.14159265"] {
+            let price: f64 = price.parse().unwrap();
+            assert_eq!(scale.price(key(price)), price);
+        }
+        // Integer keys order like the prices they represent
+        assert!(key(0.3) > key(0.1 + 0.1));
+        assert_eq!(key(0.1 + 0.2), key(0.3));
+    }
+
+    #[test]
+    fn test_prices_outside_key_range_are_rejected() {
+        // At the default resolution keys run out just above 9.2 billion
+        let scale = PriceScale::default();
+        assert!(scale.key(9e9).is_ok());
+        assert!(scale.key(1e10).is_err());
+        assert!(scale.key(-1e10).is_err());
+        assert!(scale.key(f64::NAN).is_err());
+
+        let mut engine = OrderbookEngine::default();
+        let result = engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["20000000000.0", "1.0", "1.0"])],
+            asks: vec![],
+        });
+        assert!(result.unwrap_err().to_string().contains("outside the representable range"));
+
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "1.0"])],
+            asks: vec![serde_json::json!(["101.0", "1.0", "1.0"])],
+        }).unwrap();
+        assert!(engine.apply_delta(&BookDelta {
+            bids: vec![],
+            asks: vec![serde_json::json!(["20000000000.0", "1.0", "2.0"])],
+            checksum: None,
+        }).is_err());
+        assert_eq!(engine.volume_at(Side::Ask, 2e10), 0.0);
+        assert_eq!(engine.best_ask(), Some(101.0));
+
+        // A stored snapshot that can't be keyed leaves the book as it was
+        let mut stored = Snapshot::from_orderbook_state("BTC".to_string(), engine.get_current_state());
+        stored.asks[0].price = 2e10;
+        assert!(engine.load_snapshot(&stored).is_err());
+        assert_eq!(engine.best_ask(), Some(101.0));
+    }
+
+    #[test]
+    fn test_failed_snapshot_leaves_previous_book() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "1.0"]), serde_json::json!(["99.0", "2.0", "1.0"])],
+            asks: vec![serde_json::json!(["101.0", "1.0", "1.0"])],
+        }).unwrap();
+        engine.begin_resync();
+        let seq = engine.update_seq();
+
+        // Valid levels come before the out-of-range ask, which fails the whole snapshot
+        let result = engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["200.0", "5.0", "2.0"])],
+            asks: vec![serde_json::json!(["201.0", "5.0", "2.0"]), serde_json::json!(["20000000000.0", "1.0", "2.0"])],
+        });
+        assert!(result.is_err());
+
+        assert_eq!(engine.iter_bids().collect::<Vec<_>>(), vec![(100.0, 1.0), (99.0, 2.0)]);
+        assert_eq!(engine.iter_asks().collect::<Vec<_>>(), vec![(101.0, 1.0)]);
+        assert_eq!(engine.top_bids(), &[(100.0, 1.0), (99.0, 2.0)]);
+        assert_eq!(engine.top_asks(), &[(101.0, 1.0)]);
+        assert_eq!(engine.mid_price(), Some(100.5));
+        assert_eq!(engine.update_seq(), seq);
+        assert!(engine.is_resyncing());
+    }
+
+    #[test]
+    fn test_tick_fits_resolution() {
+        assert!(tick_fits_resolution(0.5, DEFAULT_PRICE_RESOLUTION));
+        assert!(tick_fits_resolution(0.00001, DEFAULT_PRICE_RESOLUTION));
+        assert!(tick_fits_resolution(0.05, 0.01));
+        assert!(tick_fits_resolution(25.0, 0.01));
+        // Finer than the resolution, or between two of its steps
+        assert!(!tick_fits_resolution(0.001, 0.01));
+        assert!(!tick_fits_resolution(0.015, 0.01));
+        assert!(!tick_fits_resolution(1e-10, DEFAULT_PRICE_RESOLUTION));
+    }
+
+    #[test]
+    fn test_price_resolution_sets_key_granularity() {
+        let mut engine = OrderbookEngine::default().with_price_resolution(1e-2);
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![
+                serde_json::json!(["100.004", "1.0", "1.0"]),
+                serde_json::json!(["100.02", "2.0", "1.0"]),
+            ],
+            asks: vec![serde_json::json!(["100.5", "1.0", "1.0"])],
+        }).unwrap();
+        // Levels are keyed to the nearest hundredth, and reported as stored
+        assert_eq!(engine.iter_bids().collect::<Vec<_>>(), vec![(100.02, 2.0), (100.0, 1.0)]);
+
+        // A delta at a price within the same hundredth updates that level
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["100.001", "0.0", "2.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(engine.iter_bids().collect::<Vec<_>>(), vec![(100.02, 2.0)]);
+        assert_eq!(engine.top_bids(), &[(100.02, 2.0)]);
+        assert_eq!(engine.self_check(), Ok(()));
+    }
+
+    #[test]
+    fn test_max_depth_trims_worst_levels() {
+        let mut engine = OrderbookEngine::default().with_max_depth(25);
+        engine.apply_snapshot(&BookSnapshot {
+            bids: (0..30).map(|i| serde_json::json!([format!("{}.0", 100 - i), "1.0", "1.0"])).collect(),
+            asks: (0..30).map(|i| serde_json::json!([format!("{}.0", 101 + i), "1.0", "1.0"])).collect(),
+        }).unwrap();
+        assert_eq!(engine.bids_mut().len(), 25);
+        assert_eq!(engine.asks_mut().len(), 25);
+        // The best levels are kept: bids 100..=76, asks 101..=125
+        assert_eq!(engine.bids_mut().keys().next(), Some(&key(76.0)));
+        assert_eq!(engine.asks_mut().keys().next_back(), Some(&key(125.0)));
+        // Levels trimmed from the snapshot aren't reported as changes
+        let changes = engine.take_changes();
+        assert!(changes.bids.is_empty() && changes.asks.is_empty());
+
+        // A new best bid pushes out the worst bid, which is reported as removed
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["100.5", "1.0", "2.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(engine.bids_mut().len(), 25);
+        assert!(!engine.bids_mut().contains_key(&key(76.0)));
+        let changes = engine.take_changes();
+        let removed: Vec<_> = changes.bids.iter().filter(|level| level.volume == 0.0).map(|level| level.price).collect();
+        assert_eq!(removed, vec![76.0]);
+        assert!(engine.take_trades().is_empty());
+        assert_eq!(engine.best_bid(), Some(100.5));
+        assert_eq!(engine.self_check(), Ok(()));
+    }
+
+    #[test]
+    fn test_get_current_state_into_matches_allocating_version() {
+        let mut engine = OrderbookEngine::default().with_display_scale(10.0);
+        engine.apply_snapshot(&BookSnapshot {
+            bids: (0..20).map(|i| serde_json::json!([format!("{}.0", 100 - i), "1.5", "1.0"])).collect(),
+            asks: (0..20).map(|i| serde_json::json!([format!("{}.0", 101 + i), "2.5", "1.0"])).collect(),
+        }).unwrap();
+        engine.set_last_price(100.0);
+
+        // Clocks may tick between the two calls
+        let comparable = |mut state: OrderbookState| {
+            state.timestamp = 0;
+            state.age_ms = None;
+            serde_json::to_value(state).unwrap()
+        };
+
+        let mut buf = engine.get_current_state();
+        let capacity = buf.bids.capacity();
+        engine.apply_delta(&BookDelta {
+            bids: (0..10).map(|i| serde_json::json!([format!("{}.0", 100 - i), "0.0", "2.0"])).collect(),
+            asks: vec![serde_json::json!(["101.0", "4.0", "2.0"])],
+            checksum: None,
+        }).unwrap();
+
+        engine.get_current_state_into(&mut buf);
+        assert_eq!(buf.bids.len(), 10);
+        assert_eq!(buf.bids.capacity(), capacity);
+        assert_eq!(comparable(buf), comparable(engine.get_current_state()));
+
+        let mut empty = OrderbookState::default();
+        engine.get_current_state_into(&mut empty);
+        assert_eq!(comparable(empty), comparable(engine.get_current_state()));
+    }
+
+    #[test]
+    fn test_max_levels_drops_levels_farthest_from_mid() {
+        let mut engine = OrderbookEngine::default().with_max_levels(10);
+        // Mid 100.5; asks reach further out than bids
+        engine.apply_snapshot(&BookSnapshot {
+            bids: (0..4).map(|i| serde_json::json!([format!("{}.0", 100 - i), "1.0", "1.0"])).collect(),
+            asks: (0..8).map(|i| serde_json::json!([format!("{}.0", 101 + 2 * i), "1.0", "1.0"])).collect(),
+        }).unwrap();
+        assert_eq!(engine.bids_mut().len() + engine.asks_mut().len(), 10);
+        // The two farthest asks (113, 115) went; every bid is nearer the mid than they were
+        assert_eq!(engine.bids_mut().len(), 4);
+        assert_eq!(engine.asks_mut().keys().next_back(), Some(&key(111.0)));
+
+        // Distinct levels keep arriving without deletes: the book stays bounded
+        for i in 0..20 {
+            engine.apply_delta(&BookDelta {
+                bids: vec![serde_json::json!([format!("{}.0", 96 - i), "1.0", format!("{}.0", 2 + i)])],
+                asks: vec![],
+                checksum: None,
+            }).unwrap();
+            assert_eq!(engine.bids_mut().len() + engine.asks_mut().len(), 10);
+        }
+        // Asks 111 and 109 make way for bids 96 and 95; from 94 on, the new bid is
+        // at least as far from the mid as the worst ask (107), so it is dropped itself
+        assert_eq!(engine.bids_mut().keys().next(), Some(&key(95.0)));
+        assert_eq!(engine.asks_mut().keys().next_back(), Some(&key(107.0)));
+        assert_eq!(engine.best_bid(), Some(100.0));
+        assert_eq!(engine.best_ask(), Some(101.0));
+        let changes = engine.take_changes();
+        assert!(changes.bids.iter().any(|level| level.volume == 0.0));
+        assert_eq!(engine.self_check(), Ok(()));
+
+        // Only crossing the cap warns; a removal gives the book room again
+        assert!(engine.over_level_cap);
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["95.0", "0.0", "30.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert!(!engine.over_level_cap);
+    }
+
+    #[test]
+    fn test_timestamp_policies_on_out_of_order_delta() {
+        let run = |policy: TimestampPolicy| {
+            let mut engine = OrderbookEngine::default().with_timestamp_policy(policy);
+            engine.apply_snapshot(&BookSnapshot {
+                bids: vec![serde_json::json!(["100.0", "1.0", "1000.0"])],
+                asks: vec![serde_json::json!(["101.0", "1.0", "1000.0"])],
+            }).unwrap();
+            engine.apply_delta(&BookDelta {
+                bids: vec![serde_json::json!(["99.0", "1.0", "1002.0"])],
+                asks: vec![],
+                checksum: None,
+            }).unwrap();
+            // Older than the newest bid, but not the newest ask
+            engine.apply_delta(&BookDelta {
+                bids: vec![serde_json::json!(["98.0", "1.0", "1001.0"])],
+                asks: vec![serde_json::json!(["102.0", "1.0", "1001.0"])],
+                checksum: None,
+            }).unwrap();
+            engine
+        };
+
+        let engine = run(TimestampPolicy::Reject);
+        assert_eq!(engine.iter_bids().count(), 2);
+        assert_eq!(engine.iter_asks().count(), 2);
+        assert_eq!((engine.rejected_updates(), engine.out_of_order_updates()), (1, 0));
+
+        let engine = run(TimestampPolicy::Accept);
+        assert_eq!(engine.iter_bids().count(), 3);
+        assert_eq!((engine.rejected_updates(), engine.out_of_order_updates()), (0, 0));
+
+        let engine = run(TimestampPolicy::Count);
+        assert_eq!(engine.iter_bids().count(), 3);
+        assert_eq!((engine.rejected_updates(), engine.out_of_order_updates()), (0, 1));
+    }
+
+    #[test]
+    fn test_republished_levels_replace_despite_old_timestamps() {
+        let run = |republish: bool| {
+            let mut engine = OrderbookEngine::default().with_timestamp_policy(TimestampPolicy::Reject);
+            engine.apply_snapshot(&BookSnapshot {
+                bids: vec![serde_json::json!(["100.0", "1.0", "1000.0"])],
+                asks: vec![serde_json::json!(["101.0", "1.0", "1000.0"])],
+            }).unwrap();
+            engine.apply_delta(&BookDelta {
+                bids: vec![serde_json::json!(["100.0", "3.0", "1002.0"])],
+                asks: vec![],
+                checksum: None,
+            }).unwrap();
+            // Restates the best bid with a timestamp older than the side's newest
+            let mut level = serde_json::json!(["100.0", "2.0", "1001.0"]);
+            if republish {
+                level.as_array_mut().unwrap().push(serde_json::json!("r"));
+            }
+            engine.apply_delta(&BookDelta { bids: vec![level], asks: vec![], checksum: None }).unwrap();
+            engine
+        };
+
+        // A plain out-of-order level is rejected
+        let engine = run(false);
+        assert_eq!(engine.iter_bids().next(), Some((100.0, 3.0)));
+        assert_eq!(engine.rejected_updates(), 1);
+
+        // A republished one replaces the level, and isn't taken for a trade
+        let mut engine = run(true);
+        assert_eq!(engine.iter_bids().next(), Some((100.0, 2.0)));
+        assert_eq!(engine.rejected_updates(), 0);
+        assert!(engine.take_trades().is_empty());
+
+        // Nor does its old timestamp look like a gap
+        let outcome = engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["100.0", "2.5", "1001.0", "r"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(outcome, DeltaOutcome { max_timestamp: None, possible_gap: false });
+        assert_eq!(engine.last_update_ts(), Some(1002.0));
+    }
+
+    #[test]
+    fn test_snapshot_resets_timestamp_policy_baseline() {
+        let mut engine = OrderbookEngine::default().with_timestamp_policy(TimestampPolicy::Reject);
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "1000.0"])],
+            asks: vec![],
+        }).unwrap();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "900.0"])],
+            asks: vec![],
+        }).unwrap();
+
+        // Untimestamped levels are always applied; 950 is newer than the new snapshot
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["99.0", "1.0", "950.0"]), serde_json::json!(["98.0", "1.0", ""])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(engine.iter_bids().count(), 3);
+        assert_eq!(engine.rejected_updates(), 0);
+        assert_eq!("COUNT".parse::<TimestampPolicy>().unwrap(), TimestampPolicy::Count);
+        assert!("drop".parse::<TimestampPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_total_volume_per_side() {
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.total_volume(Side::Bid), 0.0);
+        assert_eq!(engine.total_volume(Side::Ask), 0.0);
+
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![
+                serde_json::json!(["100.0", "1.5", "1.0"]),
+                serde_json::json!(["99.0", "2.25", "1.0"]),
+                serde_json::json!(["98.0", "0.25", "1.0"]),
+            ],
+            asks: vec![
+                serde_json::json!(["101.0", "0.5", "1.0"]),
+                serde_json::json!(["102.0", "3.0", "1.0"]),
+            ],
+        }).unwrap();
+        assert_eq!(engine.total_volume(Side::Bid), 4.0);
+        assert_eq!(engine.total_volume(Side::Ask), 3.5);
+
+        // Follows deltas, and streams with the state
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["98.0", "0.0", "2.0"])],
+            asks: vec![serde_json::json!(["103.0", "1.0", "2.0"])],
+            checksum: None,
+        }).unwrap();
+        let state = engine.get_current_state();
+        assert_eq!((state.bid_volume, state.ask_volume), (3.75, 4.5));
+        let json = serde_json::to_value(&state).unwrap();
+        assert_eq!((json["bidVolume"].as_f64(), json["askVolume"].as_f64()), (Some(3.75), Some(4.5)));
+    }
+
+    #[test]
+    fn test_best_levels_report_top_of_book_volume() {
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.best_bid_level(), None);
+        assert_eq!(engine.best_ask_level(), None);
+
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["99.0", "5.0", "1.0"]), serde_json::json!(["100.0", "3.0", "1.0"])],
+            asks: vec![serde_json::json!(["102.0", "7.0", "1.0"]), serde_json::json!(["101.0", "2.5", "1.0"])],
+        }).unwrap();
+        assert_eq!(engine.best_bid_level(), Some((100.0, 3.0)));
+        assert_eq!(engine.best_ask_level(), Some((101.0, 2.5)));
+        assert_eq!(engine.best_bid(), Some(100.0));
+        assert_eq!(engine.best_ask(), Some(101.0));
+
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["100.0", "0.0", "2.0"])],
+            asks: vec![serde_json::json!(["101.0", "4.0", "2.0"])],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(engine.best_bid_level(), Some((99.0, 5.0)));
+        assert_eq!(engine.best_ask_level(), Some((101.0, 4.0)));
+    }
+
+    #[test]
+    fn test_volume_at_matches_exact_price_only() {
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.volume_at(Side::Bid, 100.0), 0.0);
+
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["99.5", "5.0", "1.0"]), serde_json::json!(["100.1", "3.0", "1.0"])],
+            asks: vec![serde_json::json!(["101.2", "2.5", "1.0"]), serde_json::json!(["102.0", "7.0", "1.0"])],
+        }).unwrap();
+        assert_eq!(engine.volume_at(Side::Bid, 100.1), 3.0);
+        assert_eq!(engine.volume_at(Side::Bid, 99.5), 5.0);
+        assert_eq!(engine.volume_at(Side::Ask, 101.2), 2.5);
+        assert_eq!(engine.volume_at(Side::Ask, 102.0), 7.0);
+
+        // Prices between levels, or on the other side, have nothing resting
+        assert_eq!(engine.volume_at(Side::Bid, 100.0), 0.0);
+        assert_eq!(engine.volume_at(Side::Bid, 101.2), 0.0);
+        assert_eq!(engine.volume_at(Side::Ask, 101.0), 0.0);
+        assert_eq!(engine.volume_at(Side::Ask, 100.1), 0.0);
+
+        // The query is rounded to the price resolution like the book's keys
+        let mut coarse = OrderbookEngine::default().with_price_resolution(0.01);
+        coarse.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.10", "3.0", "1.0"])],
+            asks: vec![],
+        }).unwrap();
+        assert_eq!(coarse.volume_at(Side::Bid, 100.104), 3.0);
+        assert_eq!(coarse.volume_at(Side::Bid, 100.11), 0.0);
+    }
+
+    #[test]
+    fn test_microprice_leans_toward_thinner_side() {
+        let mut engine = OrderbookEngine::default();
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "3.0", "1.0"])],
+            asks: vec![],
+        }).unwrap();
+        assert_eq!(engine.microprice(), None);
+
+        // Thin ask: buyers are more likely to lift it, so the fair price sits above the mid
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "3.0", "1.0"]), serde_json::json!(["99.0", "50.0", "1.0"])],
+            asks: vec![serde_json::json!(["102.0", "1.0", "1.0"])],
+        }).unwrap();
+        assert_eq!(engine.mid_price(), Some(101.0));
+        assert_eq!(engine.microprice(), Some(101.5));
+        assert_eq!(engine.get_current_state().microprice, Some(101.5));
+
+        // Balanced top of book matches the mid
+        engine.apply_delta(&BookDelta {
+            bids: vec![],
+            asks: vec![serde_json::json!(["102.0", "3.0", "2.0"])],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(engine.microprice(), engine.mid_price());
+    }
+
+    #[test]
+    fn test_age_ms_grows_until_next_update() {
+        let mut engine = OrderbookEngine::default();
+        assert_eq!(engine.get_current_state().age_ms, None);
+
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["100.0", "1.0", "1.0"])],
+            asks: vec![serde_json::json!(["101.0", "1.0", "1.0"])],
+        }).unwrap();
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["99.0", "1.0", "2.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        let age_ms = engine.get_current_state().age_ms.unwrap();
+        assert!(age_ms >= 50, "ageMs {} after sleeping 50ms", age_ms);
+
+        // The next delta resets the age
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["98.0", "1.0", "3.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert!(engine.get_current_state().age_ms.unwrap() < age_ms);
+    }
+
+    #[test]
+    fn test_book_status_reflects_populated_sides() {
+        let status = |bids: Vec<serde_json::Value>, asks: Vec<serde_json::Value>| {
+            let mut engine = OrderbookEngine::default();
+            engine.apply_snapshot(&BookSnapshot { bids, asks }).unwrap();
+            serde_json::to_value(engine.get_current_state()).unwrap()["bookStatus"].clone()
+        };
+        let bid = || vec![serde_json::json!(["100.0", "1.0", "1.0"])];
+        let ask = || vec![serde_json::json!(["101.0", "1.0", "1.0"])];
+
+        assert_eq!(status(bid(), ask()), "full");
+        assert_eq!(status(bid(), vec![]), "bids_only");
+        assert_eq!(status(vec![], ask()), "asks_only");
+        assert_eq!(status(vec![], vec![]), "empty");
+        assert_eq!(OrderbookEngine::default().get_current_state().book_status, BookStatus::Empty);
+    }
+
+    #[test]
+    fn test_smoothed_price_seeds_then_trails_last_price() {
+        let mut engine = OrderbookEngine::default().with_smoothing_alpha(0.5);
+        engine.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["50.0", "1.0", "1.0"])],
+            asks: vec![],
+        }).unwrap();
+        assert_eq!(engine.smoothed_price(), None);
+
+        // Each delta brings in a lower best ask, which becomes the last price
+        let mut last_price = 0.0;
+        for step in 0..30 {
+            last_price = 110.0 - step as f64;
+            engine.apply_delta(&BookDelta {
+                bids: vec![],
+                asks: vec![serde_json::json!([format!("{}.0", last_price), "1.0", "2.0"])],
+                checksum: None,
+            }).unwrap();
+            if step == 0 {
+                // The first last price seeds the average as-is
+                assert_eq!(engine.smoothed_price(), Some(110.0));
+            }
+        }
+        assert_eq!(engine.last_price(), Some(last_price));
+
+        // A steady 1.0 fall per update leaves the average (1 - alpha) / alpha = 1.0 behind
+        let smoothed = engine.smoothed_price().unwrap();
+        assert!((smoothed - (last_price + 1.0)).abs() < 1e-6, "smoothed {} vs last {}", smoothed, last_price);
+        assert_eq!(engine.get_current_state().smoothed_price, Some(smoothed));
+
+        // Unchanged last price leaves the average alone
+        engine.apply_delta(&BookDelta {
+            bids: vec![serde_json::json!(["49.0", "1.0", "3.0"])],
+            asks: vec![],
+            checksum: None,
+        }).unwrap();
+        assert_eq!(engine.smoothed_price(), Some(smoothed));
+    }
+}
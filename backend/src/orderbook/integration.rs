@@ -1,46 +1,219 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::Instrument;
+use tokio::time::{interval, interval_at, Duration, Instant, MissedTickBehavior};
 use crate::orderbook::engine::OrderbookEngine;
 use crate::orderbook::snapshot::Snapshot;
 use crate::orderbook::store::SnapshotStore;
-use crate::config::Config;
+use crate::orderbook::trades::TradeStore;
+use crate::config::SharedConfig;
+use crate::metrics::{TickerCounter, METRICS};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// How often to check whether the engine has received its first data
+const FIRST_DATA_POLL_MS: u64 = 100;
+
+/// How often to compare the top of book against the last stored snapshot, when enabled
+const TOP_OF_BOOK_POLL_MS: u64 = 100;
+
+/// Minimum time between a stored snapshot and an on-change one, so no two share a timestamp
+const MIN_CHANGE_SPACING: Duration = Duration::from_secs(1);
+
+/// Map of ticker symbol to its orderbook engine
+pub type EngineMap = HashMap<String, Arc<RwLock<OrderbookEngine>>>;
+
+/// Remembers the engine sequence number seen at each ticker's last capture
+#[derive(Debug, Default)]
+pub struct ChangeTracker {
+    last_captured: HashMap<String, u64>,
+}
+
+impl ChangeTracker {
+    /// Create a tracker that treats every ticker as changed
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Capture a snapshot of every engine, keyed by ticker
+/// 
+/// With a `ChangeTracker`, tickers whose engine has not changed since the tracker's
+/// previous capture are skipped without serializing their book, and the returned map
+/// only contains the changed tickers. Engine locks are taken one at a time.
+pub async fn snapshot_all(engines: &EngineMap, mut tracker: Option<&mut ChangeTracker>) -> HashMap<String, Snapshot> {
+    let mut snapshots = HashMap::new();
+
+    for (ticker, engine) in engines {
+        let engine_guard = engine.read().await;
+        let seq = engine_guard.update_seq();
+
+        if let Some(tracker) = tracker.as_deref_mut() {
+            if tracker.last_captured.get(ticker) == Some(&seq) {
+                continue;
+            }
+            tracker.last_captured.insert(ticker.clone(), seq);
+        }
+
+        let state = engine_guard.get_current_state();
+        drop(engine_guard);
+        snapshots.insert(ticker.clone(), Snapshot::from_orderbook_state(ticker.clone(), state));
+    }
+
+    snapshots
+}
+
+/// Best bid and best ask, either of which may be missing
+type TopOfBook = (Option<f64>, Option<f64>);
+
+/// Whether the top of book moved more than `threshold_bps` from `stored`
+/// 
+/// A side that appeared or emptied always counts as a move.
+pub fn top_of_book_moved(stored: TopOfBook, current: TopOfBook, threshold_bps: f64) -> bool {
+    let moved = |stored: Option<f64>, current: Option<f64>| match (stored, current) {
+        (Some(stored), Some(current)) => ((current - stored) / stored).abs() * 10_000.0 > threshold_bps,
+        (stored, current) => stored.is_some() != current.is_some(),
+    };
+    moved(stored.0, current.0) || moved(stored.1, current.1)
+}
+
+/// Why the storage task woke up
+enum StorageTrigger {
+    Interval,
+    FirstData,
+    TopOfBookChange,
+}
+
 /// Start a background task that periodically stores snapshots from the orderbook engine
 /// 
 /// This function spawns a tokio task that:
-/// 1. Stores a snapshot of the current orderbook state at the configured interval
-/// 2. Cleans up snapshots older than the retention period
+/// 1. Stores a snapshot of the current orderbook state at the ticker's configured interval,
+///    with the volume traded at the top of book since the previous one
+/// 2. Cleans up snapshots older than the ticker's configured retention, and inferred
+///    trades older than the trade retention
+/// 
+/// An empty book is never stored. When `snapshot_on_first_data` is enabled, a snapshot
+/// is also stored as soon as the engine first becomes non-empty, so history starts
+/// without waiting a full interval.
+/// 
+/// When `snapshot_on_change` is enabled, a snapshot is also stored whenever the best
+/// bid or ask has moved more than `snapshot_change_bps` since the last stored one,
+/// at most once a second. The top of book is checked under the engine's read lock;
+/// the write lock is only taken to store.
+/// 
+/// The interval, on-change settings and retention periods are read from the shared config while
+/// running, so changes made via PATCH /admin/config take effect without a restart.
+/// 
+/// The task runs in a `snapshot_storage` span carrying the ticker.
 /// 
 /// Returns a handle that can be used to abort the task.
 pub fn start_snapshot_storage_task(
     ticker: String,
     engine: Arc<RwLock<OrderbookEngine>>,
     store: Arc<SnapshotStore>,
-    config: Config,
+    trade_store: Arc<TradeStore>,
+    config: SharedConfig,
 ) -> tokio::task::JoinHandle<()> {
-    let interval_secs = config.snapshot_interval_secs;
-    let retention_secs = config.snapshot_retention_secs;
-
+    let span = tracing::info_span!("snapshot_storage", ticker = %ticker);
     tokio::spawn(async move {
+        let metrics = METRICS.ticker(&ticker);
+        let mut config_changes = config.subscribe();
+        let (mut interval_secs, mut awaiting_first_data, mut change_bps) = {
+            let config = config_changes.borrow_and_update();
+            (
+                config.snapshot_interval_for(&ticker),
+                config.snapshot_on_first_data,
+                config.snapshot_on_change.then_some(config.snapshot_change_bps),
+            )
+        };
         let mut interval_timer = interval(Duration::from_secs(interval_secs));
         interval_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut first_data_timer = interval(Duration::from_millis(FIRST_DATA_POLL_MS));
+        first_data_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut change_timer = interval(Duration::from_millis(TOP_OF_BOOK_POLL_MS));
+        change_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut last_stored_top: Option<TopOfBook> = None;
+        let mut last_stored_at: Option<Instant> = None;
 
         loop {
-            interval_timer.tick().await;
+            let trigger = tokio::select! {
+                _ = interval_timer.tick() => StorageTrigger::Interval,
+                _ = first_data_timer.tick(), if awaiting_first_data => StorageTrigger::FirstData,
+                _ = change_timer.tick(), if change_bps.is_some() => StorageTrigger::TopOfBookChange,
+                // The task holds a sender itself, so the channel never closes while it runs
+                Ok(()) = config_changes.changed() => {
+                    let (configured, configured_change_bps) = {
+                        let config = config_changes.borrow_and_update();
+                        (
+                            config.snapshot_interval_for(&ticker),
+                            config.snapshot_on_change.then_some(config.snapshot_change_bps),
+                        )
+                    };
+                    change_bps = configured_change_bps;
+                    // Restart the timer when the interval changed, counting from now
+                    if configured != interval_secs {
+                        tracing::info!(from_secs = interval_secs, to_secs = configured, "Snapshot interval changed");
+                        interval_secs = configured;
+                        let period = Duration::from_secs(interval_secs);
+                        interval_timer = interval_at(Instant::now() + period, period);
+                        interval_timer.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    }
+                    continue;
+                }
+            };
+            let (retention_secs, trade_retention_secs) = {
+                let config = config.borrow();
+                (config.snapshot_retention_for(&ticker), config.trade_retention_secs)
+            };
 
-            // Get current state from engine
-            let state = {
+            // Decide under a read lock, so polls that store nothing don't hold up the feed
+            // task's deltas. An empty book is never stored, so history starts with real data
+            let wanted = {
                 let engine_guard = engine.read().await;
-                engine_guard.get_current_state()
+                // Read from the engine, as the stored top is, so both use the same units
+                let current = (engine_guard.best_bid(), engine_guard.best_ask());
+                let moved = match trigger {
+                    // Nothing stored yet compares as an empty book. Snapshot timestamps are whole
+                    // seconds, so a move within a second of the last store waits for a later poll
+                    StorageTrigger::TopOfBookChange => {
+                        last_stored_at.is_none_or(|stored_at| stored_at.elapsed() >= MIN_CHANGE_SPACING)
+                            && change_bps.is_some_and(|change_bps| {
+                                top_of_book_moved(last_stored_top.unwrap_or_default(), current, change_bps)
+                            })
+                    }
+                    _ => true,
+                };
+                moved && !engine_guard.is_empty()
             };
 
-            // Convert to snapshot and store
-            let snapshot = Snapshot::from_orderbook_state(ticker.clone(), state);
-            eprintln!("[{}] Storing snapshot at timestamp: {}, bids: {}, asks: {}", 
-                      ticker, snapshot.timestamp, snapshot.bids.len(), snapshot.asks.len());
-            store.store_snapshot(snapshot).await;
+            // Get current state from engine, and the volume traded since the last snapshot
+            let captured = if wanted {
+                let mut engine_guard = engine.write().await;
+                // The book may have changed since the check; store it as it is now
+                (!engine_guard.is_empty()).then(|| {
+                    last_stored_top = Some((engine_guard.best_bid(), engine_guard.best_ask()));
+                    last_stored_at = Some(Instant::now());
+                    (engine_guard.get_current_state(), engine_guard.take_traded_volume())
+                })
+            } else {
+                None
+            };
+
+            match captured {
+                Some((state, traded_volume)) => {
+                    awaiting_first_data = false;
+                    let snapshot = Snapshot {
+                        traded_volume,
+                        ..Snapshot::from_orderbook_state(ticker.clone(), state)
+                    };
+                    tracing::debug!(timestamp = snapshot.timestamp, bids = snapshot.bids.len(), asks = snapshot.asks.len(), "Storing snapshot");
+                    store.store_snapshot(snapshot).await;
+                    metrics.increment(TickerCounter::SnapshotsStored);
+                }
+                // Retention still runs on every interval tick
+                None if matches!(trigger, StorageTrigger::Interval) => {}
+                None => continue,
+            }
 
             // Clean up old snapshots for this ticker
             let now_timestamp = SystemTime::now()
@@ -51,24 +224,52 @@ pub fn start_snapshot_storage_task(
 
             let removed_count = store.remove_older_than(cutoff_timestamp, Some(&ticker)).await;
             if removed_count > 0 {
-                eprintln!("[{}] Cleaned up {} old snapshots (now: {}, cutoff: {}, retention: {}s)", 
-                          ticker, removed_count, now_timestamp, cutoff_timestamp, retention_secs);
+                tracing::info!(removed = removed_count, now = now_timestamp, cutoff = cutoff_timestamp, retention_secs, "Cleaned up old snapshots");
+            }
+
+            // Clean up old trades for this ticker (trade timestamps are in milliseconds)
+            let trade_cutoff_ms = (now_timestamp - trade_retention_secs) * 1000;
+            let removed_trades = trade_store.remove_older_than(trade_cutoff_ms, Some(&ticker)).await;
+            if removed_trades > 0 {
+                tracing::info!(removed = removed_trades, cutoff_ms = trade_cutoff_ms, retention_secs = trade_retention_secs, "Cleaned up old trades");
             }
         }
-    })
+    }.instrument(span))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
     use crate::orderbook::engine::OrderbookEngine;
     use crate::kraken::types::BookSnapshot;
 
+    fn shared(config: Config) -> SharedConfig {
+        SharedConfig::new(config)
+    }
+
+    // The storage-task tests run with the clock paused, so each sleep jumps
+    // straight to the task's next first-data poll or interval tick.
+
+    /// A one-level book at the given bid and ask prices.
+    fn book(bid: &str, ask: &str) -> BookSnapshot {
+        BookSnapshot {
+            bids: vec![serde_json::json!([bid, "1.0", "1234567890.0"])],
+            asks: vec![serde_json::json!([ask, "1.0", "1234567890.0"])],
+        }
+    }
+
+    /// The best bid of the newest snapshot stored for `ticker`, if any.
+    async fn newest_bid(store: &SnapshotStore, ticker: &str) -> Option<f64> {
+        let (_min, max) = store.get_history_range(ticker).await?;
+        store.get_snapshot(ticker, max).await?.bids.first().map(|level| level.price)
+    }
+
     #[tokio::test]
     async fn test_snapshot_storage_task_stores_snapshots() {
-        let engine = Arc::new(RwLock::new(OrderbookEngine::new()));
+        let engine = Arc::new(RwLock::new(OrderbookEngine::default()));
         let store = Arc::new(SnapshotStore::new());
-        let config = Config::new().with_snapshot_interval(1); // 1 second for faster test
+        let config = shared(Config::new().with_snapshot_interval(1)); // 1 second for faster test
         let ticker = "BTC".to_string();
 
         // Populate engine with some data
@@ -76,10 +277,10 @@ mod tests {
             let mut engine_guard = engine.write().await;
             let snapshot = BookSnapshot {
                 bids: vec![
-                    ["41990.0".to_string(), "2.5".to_string(), "1234567890.0".to_string()],
+                    serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
                 ],
                 asks: vec![
-                    ["42010.0".to_string(), "3.1".to_string(), "1234567890.0".to_string()],
+                    serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
                 ],
             };
             engine_guard.apply_snapshot(&snapshot).unwrap();
@@ -87,7 +288,7 @@ mod tests {
         }
 
         // Start the snapshot storage task
-        let handle = start_snapshot_storage_task(ticker.clone(), engine.clone(), store.clone(), config);
+        let handle = start_snapshot_storage_task(ticker.clone(), engine.clone(), store.clone(), Arc::new(TradeStore::new()), config);
 
         // Wait a bit for at least one snapshot to be stored
         tokio::time::sleep(tokio::time::Duration::from_millis(1100)).await;
@@ -112,5 +313,286 @@ mod tests {
             assert_eq!(snapshot.asks.len(), 1);
         }
     }
+
+    #[tokio::test]
+    async fn test_snapshot_all_omits_unchanged_tickers() {
+        let btc = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let eth = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let engines: EngineMap = HashMap::from([
+            ("BTC".to_string(), btc.clone()),
+            ("ETH".to_string(), eth.clone()),
+        ]);
+        let mut tracker = ChangeTracker::new();
+
+        // First capture includes every ticker
+        let first = snapshot_all(&engines, Some(&mut tracker)).await;
+        assert_eq!(first.len(), 2);
+
+        // Nothing changed
+        assert!(snapshot_all(&engines, Some(&mut tracker)).await.is_empty());
+
+        // Only BTC changes
+        btc.write().await.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![],
+        }).unwrap();
+        let partial = snapshot_all(&engines, Some(&mut tracker)).await;
+        assert_eq!(partial.len(), 1);
+        assert_eq!(partial["BTC"].bids.len(), 1);
+
+        // Without a tracker every ticker is always captured
+        assert_eq!(snapshot_all(&engines, None).await.len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_snapshot_stored_on_first_data() {
+        let engine = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let store = Arc::new(SnapshotStore::new());
+        // Interval far longer than the test so only the first-data path can store data
+        let config = shared(Config::new().with_snapshot_interval(60));
+        let ticker = "BTC".to_string();
+
+        let handle = start_snapshot_storage_task(ticker.clone(), engine.clone(), store.clone(), Arc::new(TradeStore::new()), config);
+
+        // Populate the engine after the task has started
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        {
+            let mut engine_guard = engine.write().await;
+            let snapshot = BookSnapshot {
+                bids: vec![
+                    serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                ],
+                asks: vec![
+                    serde_json::json!(["42010.0", "3.1", "1234567890.0"]),
+                ],
+            };
+            engine_guard.apply_snapshot(&snapshot).unwrap();
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        handle.abort();
+
+        let (_min, max) = store.get_history_range(&ticker).await.unwrap();
+        let snapshot = store.get_snapshot(&ticker, max).await.unwrap();
+        assert_eq!(snapshot.bids.len(), 1);
+        assert_eq!(snapshot.asks.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_storage_task_prunes_expired_trades() {
+        use crate::orderbook::trades::{DetectedTrade, TradeSide};
+
+        let trade_store = Arc::new(TradeStore::new());
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+        for timestamp_ms in [now_ms - 120_000, now_ms] {
+            trade_store.store_trade("BTC", DetectedTrade {
+                timestamp_ms,
+                price: 100.0,
+                volume: 1.0,
+                side: TradeSide::Buy,
+            }).await;
+        }
+
+        let config = shared(Config::new()
+            .with_snapshot_interval(60)
+            .with_trade_retention(60)
+            .with_snapshot_on_first_data(false));
+        let engine = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let handle = start_snapshot_storage_task(
+            "BTC".to_string(),
+            engine,
+            Arc::new(SnapshotStore::new()),
+            trade_store.clone(),
+            config,
+        );
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        handle.abort();
+
+        let remaining = trade_store.get_range("BTC", i64::MIN, i64::MAX).await;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].timestamp_ms, now_ms);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_live_interval_change_alters_cadence() {
+        let store = Arc::new(SnapshotStore::new());
+        let config = shared(Config::new()
+            .with_snapshot_interval(60)
+            .with_snapshot_on_first_data(false));
+        let engine = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let handle = start_snapshot_storage_task("BTC".to_string(), engine.clone(), store.clone(), Arc::new(TradeStore::new()), config.clone());
+
+        // Only the immediate first tick, with nothing to store, happens at a 60s interval
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        engine.write().await.apply_snapshot(&book("100.0", "101.0")).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+        assert_eq!(newest_bid(&store, "BTC").await, None);
+
+        // The change wakes the task at once, which then ticks every second
+        config.send_modify(|config| config.snapshot_interval_secs = 1);
+        tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+        handle.abort();
+        assert_eq!(newest_bid(&store, "BTC").await, Some(100.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_per_ticker_snapshot_intervals() {
+        let store = Arc::new(SnapshotStore::new());
+        let config = shared(Config::new()
+            .with_snapshot_interval(60)
+            .with_snapshot_interval_override("BTC", 1)
+            .with_snapshot_on_first_data(false));
+
+        let engines: Vec<_> = ["BTC", "XMR"]
+            .into_iter()
+            .map(|ticker| (ticker, Arc::new(RwLock::new(OrderbookEngine::default()))))
+            .collect();
+        let handles: Vec<_> = engines
+            .iter()
+            .map(|(ticker, engine)| {
+                start_snapshot_storage_task(ticker.to_string(), engine.clone(), store.clone(), Arc::new(TradeStore::new()), config.clone())
+            })
+            .collect();
+
+        // Both skip their immediate first tick with an empty book, which then fills
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        for (_, engine) in &engines {
+            engine.write().await.apply_snapshot(&book("100.0", "101.0")).unwrap();
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+        for handle in handles {
+            handle.abort();
+        }
+
+        // BTC ticked again at 1s; XMR falls back to the 60s global interval
+        assert_eq!(newest_bid(&store, "BTC").await, Some(100.0));
+        assert_eq!(newest_bid(&store, "XMR").await, None);
+    }
+
+    #[test]
+    fn test_top_of_book_moved_threshold() {
+        let stored = (Some(100.0), Some(101.0));
+        // 5 bps on the bid, under a 10 bps threshold
+        assert!(!top_of_book_moved(stored, (Some(100.05), Some(101.0)), 10.0));
+        assert!(!top_of_book_moved(stored, stored, 10.0));
+        // 20 bps on either side
+        assert!(top_of_book_moved(stored, (Some(99.8), Some(101.0)), 10.0));
+        assert!(top_of_book_moved(stored, (Some(100.0), Some(101.202)), 10.0));
+        // A side appearing or emptying always counts
+        assert!(top_of_book_moved(stored, (Some(100.0), None), 10.0));
+        assert!(top_of_book_moved((None, None), (Some(100.0), None), 10.0));
+        assert!(!top_of_book_moved((None, None), (None, None), 10.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_snapshot_stored_when_top_of_book_moves() {
+        let engine = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let store = Arc::new(SnapshotStore::new());
+        // Only the immediate first tick, with an empty book, comes from the interval
+        let config = shared(Config::new()
+            .with_snapshot_interval(60)
+            .with_snapshot_on_first_data(false)
+            .with_snapshot_on_change(10.0));
+
+        let handle = start_snapshot_storage_task("BTC".to_string(), engine.clone(), store.clone(), Arc::new(TradeStore::new()), config);
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(newest_bid(&store, "BTC").await, None);
+
+        // The bid side appearing is a move
+        engine.write().await.apply_snapshot(&book("100.0", "101.0")).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+        assert_eq!(newest_bid(&store, "BTC").await, Some(100.0));
+
+        // 5 bps stays under the threshold
+        engine.write().await.apply_snapshot(&book("100.05", "101.0")).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+        assert_eq!(newest_bid(&store, "BTC").await, Some(100.0));
+
+        // 50 bps from the last stored bid crosses it
+        engine.write().await.apply_snapshot(&book("99.5", "101.0")).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        handle.abort();
+        assert_eq!(newest_bid(&store, "BTC").await, Some(99.5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_on_change_snapshots_are_a_second_apart() {
+        let engine = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let store = Arc::new(SnapshotStore::new());
+        let config = shared(Config::new()
+            .with_snapshot_interval(60)
+            .with_snapshot_on_first_data(false)
+            .with_snapshot_on_change(10.0));
+
+        let handle = start_snapshot_storage_task("BTC".to_string(), engine.clone(), store.clone(), Arc::new(TradeStore::new()), config);
+        engine.write().await.apply_snapshot(&book("100.0", "101.0")).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+        assert_eq!(newest_bid(&store, "BTC").await, Some(100.0));
+
+        // A second move within the same second is held back
+        engine.write().await.apply_snapshot(&book("99.0", "101.0")).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert_eq!(newest_bid(&store, "BTC").await, Some(100.0));
+        assert_eq!(store.len().await, 1);
+
+        // ...and stored once a second has passed since the first
+        tokio::time::sleep(tokio::time::Duration::from_millis(800)).await;
+        handle.abort();
+        assert_eq!(newest_bid(&store, "BTC").await, Some(99.0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_first_data_disabled_waits_for_interval() {
+        let engine = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let store = Arc::new(SnapshotStore::new());
+        let config = shared(Config::new()
+            .with_snapshot_interval(60)
+            .with_snapshot_on_first_data(false));
+        let ticker = "BTC".to_string();
+
+        let handle = start_snapshot_storage_task(ticker.clone(), engine.clone(), store.clone(), Arc::new(TradeStore::new()), config);
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        {
+            let mut engine_guard = engine.write().await;
+            let snapshot = BookSnapshot {
+                bids: vec![
+                    serde_json::json!(["41990.0", "2.5", "1234567890.0"]),
+                ],
+                asks: vec![],
+            };
+            engine_guard.apply_snapshot(&snapshot).unwrap();
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        handle.abort();
+
+        // The immediate first interval tick found an empty book, so nothing was stored
+        assert!(store.is_empty().await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_empty_book_is_never_stored() {
+        let engine = Arc::new(RwLock::new(OrderbookEngine::default()));
+        let store = Arc::new(SnapshotStore::new());
+        let config = shared(Config::new().with_snapshot_interval(1));
+        let ticker = "BTC".to_string();
+
+        // Interval ticks and first-data polls alike find the book empty
+        let handle = start_snapshot_storage_task(ticker.clone(), engine.clone(), store.clone(), Arc::new(TradeStore::new()), config);
+        tokio::time::sleep(tokio::time::Duration::from_millis(2500)).await;
+        assert!(store.is_empty().await);
+
+        engine.write().await.apply_snapshot(&BookSnapshot {
+            bids: vec![serde_json::json!(["41990.0", "2.5", "1234567890.0"])],
+            asks: vec![],
+        }).unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        handle.abort();
+
+        let snapshots = store.get_snapshots(&ticker).await;
+        assert!(!snapshots.is_empty());
+        assert!(snapshots.iter().all(|snapshot| !snapshot.bids.is_empty()));
+    }
 }
 
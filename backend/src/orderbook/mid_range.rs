@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Width of each bucket of the rolling mid range
+pub const MID_BUCKET: Duration = Duration::from_secs(1);
+
+/// How far back the rolling mid range is kept
+pub const MID_RETENTION: Duration = Duration::from_secs(3600);
+
+/// Low and high of the values recorded within one bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Bucket {
+    /// Buckets elapsed since the tracker's origin
+    index: u64,
+    low: f64,
+    high: f64,
+}
+
+/// Rolling low/high of a series, bucketed in time to bound memory
+/// 
+/// Only one low/high pair is kept per bucket, so memory is bounded by
+/// `retention / bucket` no matter how often values are recorded. Windows are
+/// resolved to whole buckets, so a range may include up to one bucket more.
+#[derive(Debug, Clone)]
+pub struct RollingRange {
+    bucket: Duration,
+    retention: Duration,
+    /// Start of bucket 0, set by the first recorded value
+    origin: Option<Instant>,
+    /// Oldest first
+    buckets: VecDeque<Bucket>,
+}
+
+impl RollingRange {
+    /// Create a tracker with the given bucket width and retention
+    pub fn new(bucket: Duration, retention: Duration) -> Self {
+        Self {
+            bucket: bucket.max(Duration::from_millis(1)),
+            retention,
+            origin: None,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    /// Bucket index of `at`, clamped to the origin
+    fn index(&self, origin: Instant, at: Instant) -> u64 {
+        (at.saturating_duration_since(origin).as_nanos() / self.bucket.as_nanos()) as u64
+    }
+
+    /// Record a value observed at `now`
+    pub fn record(&mut self, now: Instant, value: f64) {
+        let origin = *self.origin.get_or_insert(now);
+        let index = self.index(origin, now);
+
+        match self.buckets.back_mut() {
+            Some(bucket) if bucket.index >= index => {
+                bucket.low = bucket.low.min(value);
+                bucket.high = bucket.high.max(value);
+            }
+            _ => self.buckets.push_back(Bucket { index, low: value, high: value }),
+        }
+
+        let retained = (self.retention.as_nanos() / self.bucket.as_nanos()) as u64;
+        while self.buckets.front().is_some_and(|bucket| bucket.index + retained < index) {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Low and high of the values recorded within `window` before `now`
+    /// 
+    /// Returns `None` if nothing was recorded in the window. Windows longer than
+    /// the retention cover only what's retained.
+    pub fn range(&self, window: Duration, now: Instant) -> Option<(f64, f64)> {
+        let origin = self.origin?;
+        let cutoff = now.checked_sub(window).map_or(0, |cutoff| self.index(origin, cutoff));
+        self.buckets
+            .iter()
+            .filter(|bucket| bucket.index >= cutoff)
+            .fold(None, |range, bucket| match range {
+                None => Some((bucket.low, bucket.high)),
+                Some((low, high)) => Some((bucket.low.min(low), bucket.high.max(high))),
+            })
+    }
+}
+
+impl Default for RollingRange {
+    fn default() -> Self {
+        Self::new(MID_BUCKET, MID_RETENTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_across_window_boundary() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut range = RollingRange::default();
+
+        // A spike early on, then a series drifting within a narrower band
+        range.record(at(0), 100.0);
+        range.record(at(10), 120.0);
+        range.record(at(10), 80.0);
+        for (secs, mid) in [(200, 101.0), (250, 99.5), (300, 103.0), (400, 102.0)] {
+            range.record(at(secs), mid);
+        }
+
+        // A 5 minute window at t=400 starts at t=100: the early spike has rolled out
+        assert_eq!(range.range(Duration::from_secs(300), at(400)), Some((99.5, 103.0)));
+        // A longer window still includes it
+        assert_eq!(range.range(Duration::from_secs(400), at(400)), Some((80.0, 120.0)));
+        // Nothing recorded within the last minute at t=500
+        assert_eq!(range.range(Duration::from_secs(60), at(500)), None);
+        assert_eq!(RollingRange::default().range(Duration::from_secs(60), start), None);
+    }
+
+    #[test]
+    fn test_memory_bounded_by_buckets() {
+        let start = Instant::now();
+        let mut range = RollingRange::new(Duration::from_secs(1), Duration::from_secs(60));
+        for millis in (0..120_000).step_by(10) {
+            range.record(start + Duration::from_millis(millis), millis as f64);
+        }
+        // Many values per bucket, and buckets older than the retention dropped
+        assert!(range.buckets.len() <= 61);
+        assert_eq!(range.range(Duration::from_secs(3600), start + Duration::from_secs(120)).map(|(low, _)| low), Some(59_000.0));
+    }
+}
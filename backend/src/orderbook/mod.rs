@@ -1,5 +1,8 @@
 pub mod engine;
 pub mod snapshot;
 pub mod store;
+pub mod trades;
+pub mod ohlc;
+pub mod mid_range;
 pub mod integration;
 
@@ -0,0 +1,184 @@
+use crate::kraken::types::OhlcData;
+
+/// Length of the candles built from inferred trades, in seconds
+pub const CANDLE_INTERVAL_SECS: i64 = 60;
+
+/// Levels per side the book imbalance sampled into candles is computed over
+pub const CANDLE_IMBALANCE_DEPTH: usize = 10;
+
+/// Running total of the imbalance samples taken in one interval
+#[derive(Debug, Clone, Copy, Default)]
+struct ImbalanceSamples {
+    start: i64,
+    sum: f64,
+    count: u64,
+}
+
+impl ImbalanceSamples {
+    /// Average of the samples if they belong to the interval at `start`
+    fn average_for(&self, start: i64) -> Option<f64> {
+        (self.start == start && self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// Rolls trades inferred by `apply_delta` into fixed-interval OHLC candles
+/// 
+/// Each trade updates the candle of the interval it falls in. A trade in a later
+/// interval completes the current candle, which is returned so it can be
+/// published; intervals without trades produce no candle.
+/// 
+/// Book imbalance samples are averaged per interval into the candle's
+/// `avg_imbalance`, including samples taken before the interval's first trade.
+#[derive(Debug, Clone)]
+pub struct OhlcAggregator {
+    interval_secs: i64,
+    /// Candle of the newest interval seen, still open
+    current: Option<OhlcData>,
+    /// Sum of price * volume in the current candle, for its VWAP
+    notional: f64,
+    /// Imbalance samples of the newest interval sampled
+    imbalance: ImbalanceSamples,
+}
+
+impl OhlcAggregator {
+    /// Create an aggregator producing candles of `interval_secs` seconds
+    pub fn new(interval_secs: i64) -> Self {
+        Self {
+            interval_secs: interval_secs.max(1),
+            current: None,
+            notional: 0.0,
+            imbalance: ImbalanceSamples::default(),
+        }
+    }
+
+    /// Start of the interval `timestamp` (Unix seconds) falls in
+    fn interval_start(&self, timestamp: f64) -> i64 {
+        (timestamp as i64).div_euclid(self.interval_secs) * self.interval_secs
+    }
+
+    /// Record the book's imbalance at `timestamp` (Unix seconds)
+    /// 
+    /// Samples older than the newest sampled interval are folded into it, like
+    /// late trades. The open candle's `avg_imbalance` is kept up to date.
+    pub fn sample_imbalance(&mut self, timestamp: f64, imbalance: f64) {
+        let start = self.interval_start(timestamp);
+        if self.imbalance.count == 0 || start > self.imbalance.start {
+            self.imbalance = ImbalanceSamples { start, sum: 0.0, count: 0 };
+        }
+        self.imbalance.sum += imbalance;
+        self.imbalance.count += 1;
+
+        if let Some(candle) = self.current.as_mut() {
+            if let Some(average) = self.imbalance.average_for(candle.time as i64) {
+                candle.avg_imbalance = Some(average);
+            }
+        }
+    }
+
+    /// Record a trade at `timestamp` (Unix seconds)
+    /// 
+    /// Returns the completed candle when the trade opens a new interval. Trades
+    /// older than the open candle's interval are folded into the open candle.
+    pub fn record(&mut self, timestamp: f64, price: f64, volume: f64) -> Option<OhlcData> {
+        let start = self.interval_start(timestamp);
+
+        if let Some(candle) = self.current.as_mut().filter(|candle| start <= candle.time as i64) {
+            candle.high = candle.high.max(price);
+            candle.low = candle.low.min(price);
+            candle.close = price;
+            candle.volume += volume;
+            candle.count += 1;
+            self.notional += price * volume;
+            candle.vwap = if candle.volume > 0.0 { self.notional / candle.volume } else { price };
+            return None;
+        }
+
+        self.notional = price * volume;
+        self.current.replace(OhlcData {
+            time: start as f64,
+            etime: (start + self.interval_secs) as f64,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            vwap: price,
+            volume,
+            count: 1,
+            avg_imbalance: self.imbalance.average_for(start),
+        })
+    }
+
+    /// The candle still being built, if any trade has been recorded
+    pub fn current(&self) -> Option<&OhlcData> {
+        self.current.as_ref()
+    }
+}
+
+impl Default for OhlcAggregator {
+    fn default() -> Self {
+        Self::new(CANDLE_INTERVAL_SECS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candles_roll_over_at_minute_boundary() {
+        let mut aggregator = OhlcAggregator::default();
+        assert!(aggregator.record(120.5, 100.0, 1.0).is_none());
+        assert!(aggregator.record(130.0, 103.0, 1.0).is_none());
+        assert!(aggregator.record(150.0, 98.0, 2.0).is_none());
+        assert!(aggregator.record(179.9, 101.0, 0.0).is_none());
+
+        // The first trade of the next minute completes the candle
+        let candle = aggregator.record(180.0, 105.0, 0.5).unwrap();
+        assert_eq!((candle.time, candle.etime), (120.0, 180.0));
+        assert_eq!((candle.open, candle.high, candle.low, candle.close), (100.0, 103.0, 98.0, 101.0));
+        assert_eq!(candle.volume, 4.0);
+        assert_eq!(candle.count, 4);
+        // (100 + 103 + 196) / 4
+        assert_eq!(candle.vwap, 99.75);
+
+        let open = aggregator.current().unwrap();
+        assert_eq!((open.time, open.open, open.close), (180.0, 105.0, 105.0));
+
+        // A late trade from the previous minute joins the open candle; skipped
+        // minutes produce no candles
+        assert!(aggregator.record(175.0, 104.0, 0.5).is_none());
+        let candle = aggregator.record(400.0, 110.0, 1.0).unwrap();
+        assert_eq!((candle.time, candle.low, candle.close, candle.count), (180.0, 104.0, 104.0, 2));
+    }
+
+    #[test]
+    fn test_candles_average_imbalance_samples() {
+        let mut aggregator = OhlcAggregator::default();
+        // Samples before the first trade of the interval still count
+        aggregator.sample_imbalance(121.0, 0.5);
+        assert!(aggregator.record(125.0, 100.0, 1.0).is_none());
+        aggregator.sample_imbalance(130.0, -0.25);
+        aggregator.sample_imbalance(170.0, 0.5);
+        assert_eq!(aggregator.current().unwrap().avg_imbalance, Some(0.25));
+
+        // Samples of the next minute don't leak into the open candle
+        aggregator.sample_imbalance(185.0, -1.0);
+        let candle = aggregator.record(190.0, 101.0, 1.0).unwrap();
+        assert_eq!(candle.avg_imbalance, Some(0.25));
+        assert_eq!(aggregator.current().unwrap().avg_imbalance, Some(-1.0));
+
+        // A minute without samples has no average
+        let candle = aggregator.record(300.0, 102.0, 1.0).unwrap();
+        assert_eq!(candle.avg_imbalance, Some(-1.0));
+        assert_eq!(aggregator.current().unwrap().avg_imbalance, None);
+    }
+
+    #[test]
+    fn test_avg_imbalance_is_optional_when_deserializing() {
+        let candle: OhlcData = serde_json::from_str(
+            r#"{"time":60.0,"etime":120.0,"open":1.0,"high":2.0,"low":0.5,"close":1.5,"vwap":1.2,"volume":3.0,"count":4}"#,
+        ).unwrap();
+        assert_eq!(candle.avg_imbalance, None);
+        assert_eq!(serde_json::to_value(&candle).unwrap()["avgImbalance"], serde_json::Value::Null);
+    }
+}
@@ -1,5 +1,8 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use serde::{Deserialize, Serialize};
-use crate::orderbook::engine::{PriceLevelEntry, OrderbookState};
+use crate::orderbook::engine::{scale_levels, PriceLevelEntry, OrderbookState};
 
 /// Snapshot of orderbook state at a specific point in time
 /// 
@@ -22,6 +25,10 @@ pub struct Snapshot {
     
     /// Asks (sell orders) sorted in ascending order by price (lowest first)
     pub asks: Vec<PriceLevelEntry>,
+    
+    /// Volume taken off the top of book since the ticker's previous snapshot
+    #[serde(rename = "tradedVolume", default)]
+    pub traded_volume: f64,
 }
 
 impl Snapshot {
@@ -39,10 +46,44 @@ impl Snapshot {
             last_price,
             bids,
             asks,
+            traded_volume: 0.0,
+        }
+    }
+
+    /// Hash of the snapshot's book content: ticker, last price, traded volume and every level
+    /// 
+    /// The timestamp is excluded, so the same book captured at two times hashes equal.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.ticker.hash(&mut hasher);
+        self.last_price.map(f64::to_bits).hash(&mut hasher);
+        self.traded_volume.to_bits().hash(&mut hasher);
+        for side in [&self.bids, &self.asks] {
+            side.len().hash(&mut hasher);
+            for level in side {
+                level.price.to_bits().hash(&mut hasher);
+                level.volume.to_bits().hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// This snapshot with every price multiplied by its ticker's display scale
+    /// 
+    /// Snapshots are stored in exchange prices; this is for API output only.
+    pub fn scaled(mut self, scale: f64) -> Self {
+        if scale == 1.0 {
+            return self;
         }
+        scale_levels(&mut self.bids, scale);
+        scale_levels(&mut self.asks, scale);
+        self.last_price = self.last_price.map(|price| price * scale);
+        self
     }
 
     /// Create a snapshot from an OrderbookState with the given ticker
+    /// 
+    /// The traded volume starts at zero; set it from `OrderbookEngine::take_traded_volume`.
     pub fn from_orderbook_state(ticker: String, state: OrderbookState) -> Self {
         Self {
             ticker,
@@ -50,7 +91,140 @@ impl Snapshot {
             last_price: state.last_price,
             bids: state.bids,
             asks: state.asks,
+            traded_volume: 0.0,
         }
     }
 }
 
+
+/// A level present in both snapshots whose volume differs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct LevelChange {
+    pub price: f64,
+    #[serde(rename = "fromVolume")]
+    pub from_volume: f64,
+    #[serde(rename = "toVolume")]
+    pub to_volume: f64,
+}
+
+/// Level changes on one side of the book between two snapshots
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SideDiff {
+    /// Levels only in the later snapshot, at their new volume
+    pub added: Vec<PriceLevelEntry>,
+    /// Levels only in the earlier snapshot, at their old volume
+    pub removed: Vec<PriceLevelEntry>,
+    /// Levels in both snapshots with a different volume
+    pub changed: Vec<LevelChange>,
+}
+
+impl SideDiff {
+    /// True if the side is identical in both snapshots
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Per-level differences between two snapshots of the same ticker
+#[derive(Debug, Clone, Serialize)]
+pub struct BookDiff {
+    pub ticker: String,
+    /// Timestamp of the earlier snapshot
+    pub from: i64,
+    /// Timestamp of the later snapshot
+    pub to: i64,
+    pub bids: SideDiff,
+    pub asks: SideDiff,
+}
+
+/// Compute how the book changed from snapshot `a` to snapshot `b`
+/// 
+/// Levels are matched by exact price. Added and changed levels keep `b`'s
+/// ordering, removed levels keep `a`'s, so each list stays best price first.
+pub fn diff_snapshots(a: &Snapshot, b: &Snapshot) -> BookDiff {
+    BookDiff {
+        ticker: b.ticker.clone(),
+        from: a.timestamp,
+        to: b.timestamp,
+        bids: diff_side(&a.bids, &b.bids),
+        asks: diff_side(&a.asks, &b.asks),
+    }
+}
+
+fn diff_side(before: &[PriceLevelEntry], after: &[PriceLevelEntry]) -> SideDiff {
+    let volumes = |levels: &[PriceLevelEntry]| -> HashMap<u64, f64> {
+        levels.iter().map(|level| (level.price.to_bits(), level.volume)).collect()
+    };
+    let (old, new) = (volumes(before), volumes(after));
+
+    let mut diff = SideDiff::default();
+    for level in after {
+        match old.get(&level.price.to_bits()) {
+            None => diff.added.push(level.clone()),
+            Some(&from_volume) if from_volume != level.volume => diff.changed.push(LevelChange {
+                price: level.price,
+                from_volume,
+                to_volume: level.volume,
+            }),
+            Some(_) => {}
+        }
+    }
+    diff.removed = before
+        .iter()
+        .filter(|level| !new.contains_key(&level.price.to_bits()))
+        .cloned()
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(levels: &[(f64, f64)]) -> Vec<PriceLevelEntry> {
+        levels.iter().map(|&(price, volume)| PriceLevelEntry { price, volume }).collect()
+    }
+
+    fn prices(levels: &[PriceLevelEntry]) -> Vec<f64> {
+        levels.iter().map(|level| level.price).collect()
+    }
+
+    #[test]
+    fn test_diff_snapshots_added_removed_and_changed_levels() {
+        let a = Snapshot::new(
+            "BTC".to_string(),
+            1000,
+            None,
+            levels(&[(100.0, 1.0), (99.0, 2.0), (98.0, 3.0)]),
+            levels(&[(101.0, 1.0), (102.0, 2.0)]),
+        );
+        let b = Snapshot::new(
+            "BTC".to_string(),
+            2000,
+            None,
+            levels(&[(100.5, 0.5), (100.0, 1.0), (99.0, 4.0)]),
+            levels(&[(101.0, 0.25), (103.0, 5.0)]),
+        );
+
+        let diff = diff_snapshots(&a, &b);
+        assert_eq!((diff.from, diff.to), (1000, 2000));
+
+        assert_eq!(prices(&diff.bids.added), vec![100.5]);
+        assert_eq!(diff.bids.added[0].volume, 0.5);
+        assert_eq!(prices(&diff.bids.removed), vec![98.0]);
+        assert_eq!(diff.bids.removed[0].volume, 3.0);
+        assert_eq!(diff.bids.changed, vec![LevelChange { price: 99.0, from_volume: 2.0, to_volume: 4.0 }]);
+
+        assert_eq!(prices(&diff.asks.added), vec![103.0]);
+        assert_eq!(prices(&diff.asks.removed), vec![102.0]);
+        assert_eq!(diff.asks.changed, vec![LevelChange { price: 101.0, from_volume: 1.0, to_volume: 0.25 }]);
+    }
+
+    #[test]
+    fn test_diff_of_identical_snapshots_is_empty() {
+        let book = Snapshot::new("BTC".to_string(), 1000, None, levels(&[(100.0, 1.0)]), levels(&[(101.0, 1.0)]));
+        let diff = diff_snapshots(&book, &book);
+        assert!(diff.bids.is_empty());
+        assert!(diff.asks.is_empty());
+    }
+}
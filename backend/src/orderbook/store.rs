@@ -1,15 +1,478 @@
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use anyhow::Context;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use crate::orderbook::engine::PriceLevelEntry;
 use crate::orderbook::snapshot::Snapshot;
 
+/// How to handle a snapshot whose timestamp is earlier than the newest stored
+/// snapshot for its ticker (e.g. after the system clock jumps backward)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClockSkewPolicy {
+    /// Drop the snapshot
+    Reject,
+    /// Store it one second after the newest snapshot, keeping history monotonic
+    /// 
+    /// Snapshot timestamps are whole Unix seconds, so one second is the smallest
+    /// step that keeps the clamped snapshot distinct from the newest.
+    #[default]
+    Clamp,
+    /// Store it at its own timestamp
+    Accept,
+}
+
+impl FromStr for ClockSkewPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reject" => Ok(Self::Reject),
+            "clamp" => Ok(Self::Clamp),
+            "accept" => Ok(Self::Accept),
+            _ => Err(anyhow::anyhow!("unknown clock skew policy: {}", s)),
+        }
+    }
+}
+
+/// Default seconds within which a snapshot identical to the ticker's newest one is a duplicate
+pub const DUPLICATE_WINDOW_SECS: u64 = 2;
+
+/// Most queued snapshots the writer task inserts under one write lock
+const MAX_WRITE_BATCH: usize = 64;
+
+/// Changes to a ticker's book relative to its previous stored snapshot
+#[derive(Debug, Clone)]
+struct SnapshotDelta {
+    last_price: Option<f64>,
+    traded_volume: f64,
+    /// Added or changed bid levels; volume 0 means the level was removed
+    bids: Vec<PriceLevelEntry>,
+    /// Added or changed ask levels; volume 0 means the level was removed
+    asks: Vec<PriceLevelEntry>,
+    /// Best bid of the full book, so top-of-book history needs no replay
+    best_bid: Option<f64>,
+    /// Best ask of the full book
+    best_ask: Option<f64>,
+}
+
+/// A stored entry: a full book (keyframe) or a delta against the ticker's previous entry
+#[derive(Debug, Clone)]
+enum StoredSnapshot {
+    Full(Snapshot),
+    Delta(SnapshotDelta),
+}
+
+impl StoredSnapshot {
+    /// Best bid and ask of the book this entry stands for
+    fn top_of_book(&self) -> (Option<f64>, Option<f64>) {
+        match self {
+            Self::Full(snapshot) => (
+                snapshot.bids.first().map(|level| level.price),
+                snapshot.asks.first().map(|level| level.price),
+            ),
+            Self::Delta(delta) => (delta.best_bid, delta.best_ask),
+        }
+    }
+}
+
+/// A ticker's stored entries by timestamp, with what writes need to extend them
+#[derive(Debug, Default)]
+struct TickerHistory {
+    entries: BTreeMap<i64, StoredSnapshot>,
+    /// Deltas stored after the newest keyframe
+    deltas_since_keyframe: usize,
+    /// The newest entry in full, which the next snapshot is diffed against
+    newest: Option<Snapshot>,
+}
+
+impl TickerHistory {
+    /// Count the deltas after the newest keyframe from scratch
+    /// 
+    /// Only needed when an entry other than the newest changes; appends keep
+    /// the count up to date themselves.
+    fn recount_deltas(&mut self) {
+        self.deltas_since_keyframe = self
+            .entries
+            .values()
+            .rev()
+            .take_while(|stored| matches!(stored, StoredSnapshot::Delta(_)))
+            .count();
+    }
+}
+
+/// Map from ticker to its stored entries
+type SnapshotMap = HashMap<String, TickerHistory>;
+
+/// Levels of `next` that differ from `previous`, plus zero-volume entries for removed levels
+fn diff_levels(previous: &[PriceLevelEntry], next: &[PriceLevelEntry]) -> Vec<PriceLevelEntry> {
+    let old: HashMap<u64, f64> = previous.iter().map(|l| (l.price.to_bits(), l.volume)).collect();
+    let new: HashMap<u64, f64> = next.iter().map(|l| (l.price.to_bits(), l.volume)).collect();
+
+    let mut changes: Vec<PriceLevelEntry> = next
+        .iter()
+        .filter(|level| old.get(&level.price.to_bits()) != Some(&level.volume))
+        .cloned()
+        .collect();
+    changes.extend(
+        previous
+            .iter()
+            .filter(|level| !new.contains_key(&level.price.to_bits()))
+            .map(|level| PriceLevelEntry { price: level.price, volume: 0.0 }),
+    );
+    changes
+}
+
+/// Apply level changes from `diff_levels`, keeping bids descending or asks ascending
+fn apply_levels(levels: &[PriceLevelEntry], changes: &[PriceLevelEntry], descending: bool) -> Vec<PriceLevelEntry> {
+    let mut book: HashMap<u64, f64> = levels.iter().map(|l| (l.price.to_bits(), l.volume)).collect();
+    for change in changes {
+        if change.volume == 0.0 {
+            book.remove(&change.price.to_bits());
+        } else {
+            book.insert(change.price.to_bits(), change.volume);
+        }
+    }
+
+    let mut result: Vec<PriceLevelEntry> = book
+        .into_iter()
+        .map(|(price, volume)| PriceLevelEntry { price: f64::from_bits(price), volume })
+        .collect();
+    if descending {
+        result.sort_by(|a, b| b.price.total_cmp(&a.price));
+    } else {
+        result.sort_by(|a, b| a.price.total_cmp(&b.price));
+    }
+    result
+}
+
+/// Rebuild the full snapshot stored at `timestamp` in a ticker's history
+/// 
+/// A delta is resolved by replaying every delta since the ticker's preceding keyframe.
+fn reconstruct(history: &TickerHistory, ticker: &str, timestamp: i64) -> Option<Snapshot> {
+    if let StoredSnapshot::Full(snapshot) = history.entries.get(&timestamp)? {
+        return Some(snapshot.clone());
+    }
+
+    // Walk back to the keyframe, collecting deltas newest first
+    let mut deltas = Vec::new();
+    let mut keyframe = None;
+    for (_, stored) in history.entries.range(..=timestamp).rev() {
+        match stored {
+            StoredSnapshot::Full(snapshot) => {
+                keyframe = Some(snapshot);
+                break;
+            }
+            StoredSnapshot::Delta(delta) => deltas.push(delta),
+        }
+    }
+
+    let keyframe = keyframe?;
+    let mut bids = keyframe.bids.clone();
+    let mut asks = keyframe.asks.clone();
+    let mut last_price = keyframe.last_price;
+    let mut traded_volume = keyframe.traded_volume;
+    for delta in deltas.into_iter().rev() {
+        bids = apply_levels(&bids, &delta.bids, true);
+        asks = apply_levels(&asks, &delta.asks, false);
+        last_price = delta.last_price;
+        traded_volume = delta.traded_volume;
+    }
+    let mut snapshot = Snapshot::new(ticker.to_string(), timestamp, last_price, bids, asks);
+    snapshot.traded_volume = traded_volume;
+    Some(snapshot)
+}
+
 /// In-memory storage for orderbook snapshots indexed by (ticker, timestamp)
 /// 
 /// This store maintains snapshots in memory for time-travel functionality.
-/// Snapshots are indexed by (ticker, timestamp) tuple for fast retrieval.
+/// Each ticker's snapshots are kept ordered by timestamp for fast retrieval.
+/// With `with_persistence`, every snapshot is also written to disk as
+/// `{ticker}_{timestamp}.json` so history survives restarts.
+/// 
+/// With `with_keyframe_interval(k)`, only every k-th snapshot of a ticker is
+/// kept in full; the ones in between are kept as deltas against their
+/// predecessor and reconstructed on read.
+/// 
+/// Writes are batched: `store_snapshot` queues the snapshot for a single
+/// writer task, which drains everything queued (up to `MAX_WRITE_BATCH`) and
+/// inserts it under one write lock. Readers never see a snapshot later than
+/// the moment its `store_snapshot` call returns; until then they may miss it
+/// for at most one batch.
 pub struct SnapshotStore {
-    /// Map from (ticker, timestamp) to snapshot
-    snapshots: Arc<RwLock<HashMap<(String, i64), Snapshot>>>,
+    /// Each ticker's snapshots and deltas, ordered by timestamp
+    snapshots: Arc<RwLock<SnapshotMap>>,
+    /// How incoming snapshots are stored
+    settings: WriteSettings,
+    /// Queue of the writer task, spawned on the first store
+    writer: OnceLock<mpsc::UnboundedSender<PendingStore>>,
+}
+
+/// Settings the writer task applies to each incoming snapshot
+#[derive(Debug, Clone)]
+struct WriteSettings {
+    /// Handling of snapshots that arrive with a backward timestamp
+    clock_skew_policy: ClockSkewPolicy,
+    /// Directory snapshots are mirrored to, if persistence is enabled
+    persistence_dir: Option<PathBuf>,
+    /// Identical snapshots this many seconds from the newest one are skipped
+    duplicate_window_secs: u64,
+    /// A full snapshot is kept every this many snapshots per ticker (1 = always)
+    keyframe_interval: usize,
+}
+
+/// A snapshot queued for the writer task
+struct PendingStore {
+    snapshot: Snapshot,
+    /// Signalled once the snapshot is stored (and persisted, if enabled)
+    stored: oneshot::Sender<()>,
+}
+
+/// A full snapshot to mirror to disk once the write lock is released
+struct PendingFile {
+    path: PathBuf,
+    json: serde_json::Result<Vec<u8>>,
+}
+
+impl PendingFile {
+    async fn write(self) {
+        let written = match self.json {
+            Ok(json) => tokio::fs::write(&self.path, json).await.map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            tracing::error!(path = %self.path.display(), error = %e, "Failed to persist snapshot");
+        }
+    }
+}
+
+/// Store one snapshot into the map, returning the file to persist it to, if any
+/// 
+/// See `SnapshotStore::store_snapshot` for clock skew, duplicate and keyframe handling.
+fn insert_snapshot(snapshots: &mut SnapshotMap, settings: &WriteSettings, mut snapshot: Snapshot) -> Option<PendingFile> {
+    let history = snapshots.entry(snapshot.ticker.clone()).or_default();
+    let newest = history.entries.keys().next_back().copied();
+    if let Some(newest) = newest.filter(|newest| snapshot.timestamp < *newest) {
+        match settings.clock_skew_policy {
+            ClockSkewPolicy::Reject => {
+                tracing::warn!(ticker = %snapshot.ticker, timestamp = snapshot.timestamp, newest,
+                               "Rejecting snapshot earlier than the newest stored (clock moved backward?)");
+                return None;
+            }
+            ClockSkewPolicy::Clamp => {
+                tracing::warn!(ticker = %snapshot.ticker, timestamp = snapshot.timestamp, newest,
+                               "Clamping snapshot earlier than the newest stored (clock moved backward?)");
+                snapshot.timestamp = newest + 1;
+            }
+            ClockSkewPolicy::Accept => {
+                tracing::warn!(ticker = %snapshot.ticker, timestamp = snapshot.timestamp, newest,
+                               "Accepting snapshot earlier than the newest stored (clock moved backward?)");
+            }
+        }
+    }
+
+    if let Some(previous) = &history.newest {
+        if previous.timestamp.abs_diff(snapshot.timestamp) <= settings.duplicate_window_secs
+            && previous.content_hash() == snapshot.content_hash()
+        {
+            tracing::debug!(ticker = %snapshot.ticker, timestamp = snapshot.timestamp, previous = previous.timestamp,
+                            "Skipping snapshot identical to the previous one");
+            return None;
+        }
+    }
+
+    // Files always hold the full snapshot. Serialize under the lock, but
+    // leave the disk write until it is released
+    let file = settings.persistence_dir.as_ref().map(|dir| PendingFile {
+        path: snapshot_path(dir, &snapshot.ticker, snapshot.timestamp),
+        json: serde_json::to_vec(&snapshot),
+    });
+
+    if newest.is_some_and(|newest| snapshot.timestamp < newest) {
+        // Out-of-order snapshots are keyframes. The entry after one was diffed
+        // against what preceded it, so it becomes a keyframe too
+        if let Some((&next, StoredSnapshot::Delta(_))) = history.entries.range(snapshot.timestamp + 1..).next() {
+            if let Some(full) = reconstruct(history, &snapshot.ticker, next) {
+                history.entries.insert(next, StoredSnapshot::Full(full));
+            }
+        }
+        history.entries.insert(snapshot.timestamp, StoredSnapshot::Full(snapshot));
+        history.recount_deltas();
+        return file;
+    }
+
+    // Deltas only ever extend the newest entry
+    let stored = match history.newest.as_ref().filter(|previous| previous.timestamp < snapshot.timestamp) {
+        Some(previous) if history.deltas_since_keyframe + 1 < settings.keyframe_interval => {
+            history.deltas_since_keyframe += 1;
+            StoredSnapshot::Delta(SnapshotDelta {
+                last_price: snapshot.last_price,
+                traded_volume: snapshot.traded_volume,
+                bids: diff_levels(&previous.bids, &snapshot.bids),
+                asks: diff_levels(&previous.asks, &snapshot.asks),
+                best_bid: snapshot.bids.first().map(|level| level.price),
+                best_ask: snapshot.asks.first().map(|level| level.price),
+            })
+        }
+        _ => {
+            history.deltas_since_keyframe = 0;
+            StoredSnapshot::Full(snapshot.clone())
+        }
+    };
+    history.entries.insert(snapshot.timestamp, stored);
+    history.newest = Some(snapshot);
+    file
+}
+
+/// Drain queued snapshots in batches, taking the write lock once per batch
+/// 
+/// Once the lock is released the batch's files are written concurrently, and
+/// each caller is signalled as soon as its own file is written, so one slow
+/// write doesn't hold up the others. Runs until the store (and with it the
+/// queue's sender) is dropped.
+async fn run_writer(
+    snapshots: Arc<RwLock<SnapshotMap>>,
+    settings: WriteSettings,
+    mut queue: mpsc::UnboundedReceiver<PendingStore>,
+) {
+    let mut batch = Vec::with_capacity(MAX_WRITE_BATCH);
+    while queue.recv_many(&mut batch, MAX_WRITE_BATCH).await > 0 {
+        let mut map = snapshots.write().await;
+        let mut writes: Vec<(Option<PendingFile>, oneshot::Sender<()>)> = batch
+            .drain(..)
+            .map(|pending| (insert_snapshot(&mut map, &settings, pending.snapshot), pending.stored))
+            .collect();
+        drop(map);
+
+        // A snapshot replaced later in the batch isn't written, so no two
+        // concurrent writes share a file
+        let mut paths = HashSet::new();
+        for (file, _) in writes.iter_mut().rev() {
+            if file.as_ref().is_some_and(|file| !paths.insert(file.path.clone())) {
+                *file = None;
+            }
+        }
+
+        futures_util::future::join_all(writes.into_iter().map(|(file, stored)| async move {
+            if let Some(file) = file {
+                file.write().await;
+            }
+            // The caller may have stopped waiting; the snapshot is stored regardless
+            let _ = stored.send(());
+        })).await;
+    }
+}
+
+/// Path of the file a snapshot is persisted to
+fn snapshot_path(dir: &Path, ticker: &str, timestamp: i64) -> PathBuf {
+    dir.join(format!("{}_{}.json", ticker, timestamp))
+}
+
+/// Load every `{ticker}_{timestamp}.json` snapshot file in a directory
+/// 
+/// Files that are unreadable or don't parse are skipped with a warning, so a
+/// single corrupt file doesn't prevent startup.
+fn load_snapshots(dir: &Path) -> anyhow::Result<HashMap<(String, i64), Snapshot>> {
+    let mut snapshots = HashMap::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let loaded = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(serde_json::from_slice::<Snapshot>(&bytes)?));
+        match loaded {
+            Ok(snapshot) => {
+                snapshots.insert((snapshot.ticker.clone(), snapshot.timestamp), snapshot);
+            }
+            Err(e) => tracing::warn!(path = %path.display(), error = %e, "Skipping unreadable snapshot file"),
+        }
+    }
+    Ok(snapshots)
+}
+
+/// The snapshot stored at `timestamp`, given its predecessor's full snapshot
+/// 
+/// Returns `None` for a delta with no predecessor to apply it to.
+fn replay_entry(ticker: &str, timestamp: i64, stored: &StoredSnapshot, previous: Option<&Snapshot>) -> Option<Snapshot> {
+    match (stored, previous) {
+        (StoredSnapshot::Full(snapshot), _) => Some(snapshot.clone()),
+        (StoredSnapshot::Delta(delta), Some(previous)) => Some(Snapshot {
+            traded_volume: delta.traded_volume,
+            ..Snapshot::new(
+                ticker.to_string(),
+                timestamp,
+                delta.last_price,
+                apply_levels(&previous.bids, &delta.bids, true),
+                apply_levels(&previous.asks, &delta.asks, false),
+            )
+        }),
+        (StoredSnapshot::Delta(_), None) => None,
+    }
+}
+
+/// Rebuild the given entries (ascending), replaying each delta onto its predecessor
+/// 
+/// Deltas before the first keyframe can't be rebuilt and are skipped.
+fn replay_in_order<'a>(ticker: &str, entries: impl Iterator<Item = (&'a i64, &'a StoredSnapshot)>) -> Vec<Snapshot> {
+    let mut history: Vec<Snapshot> = Vec::new();
+    for (&timestamp, stored) in entries {
+        if let Some(snapshot) = replay_entry(ticker, timestamp, stored, history.last()) {
+            history.push(snapshot);
+        }
+    }
+    history
+}
+
+/// Lazily rebuilds a ticker's snapshots in a time window, oldest first
+/// 
+/// Created by `SnapshotStore::history`. Each `next_batch` call takes the read
+/// lock only while it rebuilds that batch, and only the last snapshot is kept
+/// between calls, so walking a long history neither buffers it in memory nor
+/// holds off writers. Snapshots stored or removed between batches are seen
+/// or skipped like any other read.
+pub struct HistoryCursor {
+    snapshots: Arc<RwLock<SnapshotMap>>,
+    ticker: String,
+    /// Timestamp the next batch starts at, `None` once the window is exhausted
+    next: Option<i64>,
+    to: i64,
+    /// Last snapshot returned, which the next delta is applied to
+    previous: Option<Snapshot>,
+}
+
+impl HistoryCursor {
+    /// Rebuild up to `limit` more snapshots; empty once the window is exhausted
+    pub async fn next_batch(&mut self, limit: usize) -> Vec<Snapshot> {
+        let snapshots = self.snapshots.read().await;
+        let (Some(next), Some(history)) = (self.next, snapshots.get(&self.ticker)) else {
+            return Vec::new();
+        };
+        let mut batch: Vec<Snapshot> = Vec::with_capacity(limit.min(history.entries.len()));
+        for (&timestamp, stored) in history.entries.range(next..=self.to) {
+            if batch.len() == limit {
+                break;
+            }
+            let previous = batch.last().or(self.previous.as_ref());
+            // The window may open on a delta: rebuild it from its keyframe
+            let snapshot = match previous {
+                Some(_) => replay_entry(&self.ticker, timestamp, stored, previous),
+                None => reconstruct(history, &self.ticker, timestamp),
+            };
+            batch.extend(snapshot);
+        }
+        // A short batch means the rest of the window had nothing more to rebuild
+        self.next = match batch.last() {
+            Some(last) if batch.len() == limit && last.timestamp < self.to => Some(last.timestamp + 1),
+            _ => None,
+        };
+        if let Some(last) = batch.last() {
+            self.previous = Some(last.clone());
+        }
+        batch
+    }
 }
 
 impl SnapshotStore {
@@ -17,25 +480,172 @@ impl SnapshotStore {
     pub fn new() -> Self {
         Self {
             snapshots: Arc::new(RwLock::new(HashMap::new())),
+            settings: WriteSettings {
+                clock_skew_policy: ClockSkewPolicy::default(),
+                persistence_dir: None,
+                duplicate_window_secs: DUPLICATE_WINDOW_SECS,
+                keyframe_interval: 1,
+            },
+            writer: OnceLock::new(),
         }
     }
 
+    /// Mirror snapshots to JSON files in `dir`, loading any already there
+    /// 
+    /// The directory is created if missing. Returns an error if it can't be
+    /// created or listed; individual corrupt files are skipped.
+    pub fn with_persistence(mut self, dir: PathBuf) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        let loaded = load_snapshots(&dir)?;
+        tracing::info!(count = loaded.len(), dir = %dir.display(), "Loaded persisted snapshots");
+
+        let mut snapshots = self.snapshots.try_write().expect("store is not shared before it is built");
+        for ((ticker, timestamp), snapshot) in loaded {
+            let history = snapshots.entry(ticker).or_default();
+            if history.newest.as_ref().is_none_or(|newest| newest.timestamp < timestamp) {
+                history.newest = Some(snapshot.clone());
+            }
+            history.entries.insert(timestamp, StoredSnapshot::Full(snapshot));
+        }
+        drop(snapshots);
+        self.settings.persistence_dir = Some(dir);
+        Ok(self)
+    }
+
+    /// Set the window within which identical snapshots are skipped (default: `DUPLICATE_WINDOW_SECS`)
+    pub fn with_duplicate_window(mut self, window_secs: u64) -> Self {
+        self.settings.duplicate_window_secs = window_secs;
+        self
+    }
+
+    /// Keep a full snapshot every `interval` snapshots per ticker and deltas in between
+    /// 
+    /// An interval of 0 or 1 stores every snapshot in full (the default).
+    pub fn with_keyframe_interval(mut self, interval: usize) -> Self {
+        self.settings.keyframe_interval = interval.max(1);
+        self
+    }
+
+    /// Set how snapshots with backward timestamps are handled (default: `Clamp`)
+    pub fn with_clock_skew_policy(mut self, policy: ClockSkewPolicy) -> Self {
+        self.settings.clock_skew_policy = policy;
+        self
+    }
+
     /// Store a snapshot with (ticker, timestamp) as the key
     /// 
     /// If a snapshot with the same (ticker, timestamp) already exists, it will be replaced.
+    /// A timestamp earlier than the newest stored one for the ticker is handled
+    /// according to the store's `ClockSkewPolicy`.
+    /// 
+    /// Storing is idempotent: a snapshot whose `content_hash` matches the ticker's
+    /// newest snapshot within the duplicate window is skipped, whatever its
+    /// timestamp, so retried stores don't create near-identical duplicates.
+    /// 
+    /// The snapshot goes through the store's writer task, batched with any
+    /// other queued snapshots; the call returns once it is stored (and
+    /// persisted, if enabled), so a read after it always sees the snapshot.
     pub async fn store_snapshot(&self, snapshot: Snapshot) {
-        let key = (snapshot.ticker.clone(), snapshot.timestamp);
-        let mut snapshots = self.snapshots.write().await;
-        snapshots.insert(key, snapshot);
+        let (stored, done) = oneshot::channel();
+        let queue = self.writer.get_or_init(|| {
+            let (queue, pending) = mpsc::unbounded_channel();
+            tokio::spawn(run_writer(self.snapshots.clone(), self.settings.clone(), pending));
+            queue
+        });
+        match queue.send(PendingStore { snapshot, stored }) {
+            Ok(()) => {
+                let _ = done.await;
+            }
+            Err(mpsc::error::SendError(pending)) => {
+                // The writer's runtime has shut down; store inline instead
+                let file = insert_snapshot(&mut *self.snapshots.write().await, &self.settings, pending.snapshot);
+                if let Some(file) = file {
+                    file.write().await;
+                }
+            }
+        }
     }
 
     /// Retrieve a snapshot by ticker and timestamp
     /// 
-    /// Returns `Some(Snapshot)` if found, `None` otherwise.
+    /// Returns `Some(Snapshot)` if found, `None` otherwise. Snapshots stored as
+    /// deltas are returned fully reconstructed.
     pub async fn get_snapshot(&self, ticker: &str, timestamp: i64) -> Option<Snapshot> {
-        let key = (ticker.to_string(), timestamp);
         let snapshots = self.snapshots.read().await;
-        snapshots.get(&key).cloned()
+        reconstruct(snapshots.get(ticker)?, ticker, timestamp)
+    }
+
+    /// Retrieve the ticker's snapshot whose timestamp is closest to `timestamp`
+    /// 
+    /// Ties are broken toward the earlier snapshot. Returns `None` if the ticker
+    /// has no snapshots.
+    pub async fn get_nearest_snapshot(&self, ticker: &str, timestamp: i64) -> Option<Snapshot> {
+        let snapshots = self.snapshots.read().await;
+        let history = snapshots.get(ticker)?;
+        let before = history.entries.range(..=timestamp).next_back().map(|(stored, _)| *stored);
+        let after = history.entries.range(timestamp..).next().map(|(stored, _)| *stored);
+        let nearest = before
+            .into_iter()
+            .chain(after)
+            .min_by_key(|stored| (stored.abs_diff(timestamp), *stored))?;
+        reconstruct(history, ticker, nearest)
+    }
+
+    /// Retrieve every stored snapshot of a ticker, oldest first
+    /// 
+    /// Snapshots stored as deltas are returned fully reconstructed; the deltas
+    /// are replayed once in order rather than each from its keyframe.
+    pub async fn get_snapshots(&self, ticker: &str) -> Vec<Snapshot> {
+        let snapshots = self.snapshots.read().await;
+        snapshots
+            .get(ticker)
+            .map(|history| replay_in_order(ticker, history.entries.iter()))
+            .unwrap_or_default()
+    }
+
+    /// Best bid and ask of every stored snapshot of a ticker, as `(timestamp, bid, ask)`, oldest first
+    /// 
+    /// Deltas record their book's top when stored, so unlike `get_snapshots`
+    /// no book is rebuilt.
+    pub async fn get_top_of_book_history(&self, ticker: &str) -> Vec<(i64, Option<f64>, Option<f64>)> {
+        let snapshots = self.snapshots.read().await;
+        snapshots
+            .get(ticker)
+            .map(|history| {
+                history.entries
+                    .iter()
+                    .map(|(&timestamp, stored)| {
+                        let (best_bid, best_ask) = stored.top_of_book();
+                        (timestamp, best_bid, best_ask)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Retrieve up to `limit` of a ticker's snapshots with `from <= timestamp <= to`, oldest first
+    /// 
+    /// Like `get_snapshots`, deltas are replayed in order, starting from the
+    /// last keyframe at or before the window. The replay stops once `limit`
+    /// snapshots are rebuilt; the returned flag is true if the window held more.
+    pub async fn get_range(&self, ticker: &str, from: i64, to: i64, limit: usize) -> (Vec<Snapshot>, bool) {
+        let mut snapshots = self.history(ticker, from, to).next_batch(limit.saturating_add(1)).await;
+        let truncated = snapshots.len() > limit;
+        snapshots.truncate(limit);
+        (snapshots, truncated)
+    }
+
+    /// Walk a ticker's snapshots with `from <= timestamp <= to` lazily, oldest first
+    /// 
+    /// See `HistoryCursor`; use this over `get_range` for windows that may be large.
+    pub fn history(&self, ticker: &str, from: i64, to: i64) -> HistoryCursor {
+        HistoryCursor {
+            snapshots: self.snapshots.clone(),
+            ticker: ticker.to_string(),
+            next: (from <= to).then_some(from),
+            to,
+            previous: None,
+        }
     }
 
     /// Get the minimum and maximum timestamps available for a specific ticker
@@ -43,61 +653,92 @@ impl SnapshotStore {
     /// Returns `Some((min, max))` if there are any snapshots for this ticker, `None` if no snapshots exist.
     pub async fn get_history_range(&self, ticker: &str) -> Option<(i64, i64)> {
         let snapshots = self.snapshots.read().await;
-        
-        // Filter keys to only include the requested ticker
-        let ticker_timestamps: Vec<i64> = snapshots
-            .keys()
-            .filter(|(t, _)| t.as_str() == ticker)
-            .map(|(_, timestamp)| *timestamp)
-            .collect();
-        
-        if ticker_timestamps.is_empty() {
-            return None;
-        }
-        
-        let min = ticker_timestamps.iter().min().copied()?;
-        let max = ticker_timestamps.iter().max().copied()?;
+        let entries = &snapshots.get(ticker)?.entries;
+        let min = *entries.keys().next()?;
+        let max = *entries.keys().next_back()?;
         Some((min, max))
     }
 
+    /// Get the distinct tickers that have stored snapshots, sorted
+    pub async fn list_tickers(&self) -> Vec<String> {
+        let snapshots = self.snapshots.read().await;
+        let mut tickers: Vec<String> = snapshots
+            .iter()
+            .filter(|(_, history)| !history.entries.is_empty())
+            .map(|(ticker, _)| ticker.clone())
+            .collect();
+        tickers.sort_unstable();
+        tickers
+    }
+
     /// Remove snapshots older than the specified cutoff timestamp
     /// 
     /// This is used for cleanup to remove snapshots older than 1 hour.
     /// If ticker is provided, only removes snapshots for that ticker.
+    /// Persisted files of removed snapshots are deleted as well.
     pub async fn remove_older_than(&self, cutoff_timestamp: i64, ticker: Option<&str>) -> usize {
         let mut snapshots = self.snapshots.write().await;
-        let initial_len = snapshots.len();
-        
-        snapshots.retain(|(t, timestamp), _| {
+        let mut removed = Vec::new();
+
+        for (t, history) in snapshots.iter_mut() {
             // If a specific ticker is provided, only delete old snapshots for THAT ticker
-            // Keep all snapshots from other tickers
-            if let Some(filter_ticker) = ticker {
-                if t.as_str() == filter_ticker {
-                    // This is the ticker we're cleaning up - keep only if recent
-                    *timestamp >= cutoff_timestamp
-                } else {
-                    // Different ticker - keep it
-                    true
+            if ticker.is_some_and(|filter| t.as_str() != filter) {
+                continue;
+            }
+            // A surviving delta whose keyframe is about to be removed becomes the new keyframe
+            if let Some((&oldest_kept, StoredSnapshot::Delta(_))) = history.entries.range(cutoff_timestamp..).next() {
+                if let Some(full) = reconstruct(history, t, oldest_kept) {
+                    history.entries.insert(oldest_kept, StoredSnapshot::Full(full));
                 }
-            } else {
-                // No ticker filter - clean up old snapshots from ALL tickers
-                *timestamp >= cutoff_timestamp
             }
-        });
-        
-        initial_len - snapshots.len()
+            let kept = history.entries.split_off(&cutoff_timestamp);
+            // Remember removed keys so their files can be deleted after the lock is released
+            removed.extend(history.entries.keys().map(|timestamp| (t.clone(), *timestamp)));
+            history.entries = kept;
+            history.recount_deltas();
+        }
+        snapshots.retain(|_, history| !history.entries.is_empty());
+        drop(snapshots);
+
+        if let Some(dir) = &self.settings.persistence_dir {
+            for (t, timestamp) in &removed {
+                let path = snapshot_path(dir, t, *timestamp);
+                if let Err(e) = tokio::fs::remove_file(&path).await {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        tracing::warn!(path = %path.display(), error = %e, "Failed to delete snapshot file");
+                    }
+                }
+            }
+        }
+        removed.len()
     }
 
     /// Get the number of snapshots currently stored
     pub async fn len(&self) -> usize {
         let snapshots = self.snapshots.read().await;
-        snapshots.len()
+        snapshots.values().map(|history| history.entries.len()).sum()
+    }
+
+    /// Get the number of price levels held across every stored entry
+    /// 
+    /// A keyframe counts all of its levels and a delta only the levels it
+    /// changes, so this tracks the store's memory use as keyframes are spaced out.
+    pub async fn stored_levels(&self) -> usize {
+        let snapshots = self.snapshots.read().await;
+        snapshots
+            .values()
+            .flat_map(|history| history.entries.values())
+            .map(|stored| match stored {
+                StoredSnapshot::Full(snapshot) => snapshot.bids.len() + snapshot.asks.len(),
+                StoredSnapshot::Delta(delta) => delta.bids.len() + delta.asks.len(),
+            })
+            .sum()
     }
 
     /// Check if the store is empty
     pub async fn is_empty(&self) -> bool {
         let snapshots = self.snapshots.read().await;
-        snapshots.is_empty()
+        snapshots.values().all(|history| history.entries.is_empty())
     }
 }
 
@@ -110,6 +751,7 @@ impl Default for SnapshotStore {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::orderbook::engine::PriceLevelEntry;
 
     #[tokio::test]
     async fn test_new_store() {
@@ -164,6 +806,71 @@ mod tests {
         assert_eq!(retrieved.unwrap().last_price, Some(43000.0));
     }
 
+    #[tokio::test]
+    async fn test_backward_clock_jump_is_clamped() {
+        let store = SnapshotStore::new();
+
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 1000, Some(1.0), vec![], vec![])).await;
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 2000, Some(2.0), vec![], vec![])).await;
+        // Clock jumps backward
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 1500, Some(3.0), vec![], vec![])).await;
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 1501, Some(4.0), vec![], vec![])).await;
+
+        assert_eq!(store.len().await, 4);
+        assert!(store.get_snapshot("BTC", 1500).await.is_none());
+        assert_eq!(store.get_snapshot("BTC", 2001).await.unwrap().last_price, Some(3.0));
+        assert_eq!(store.get_snapshot("BTC", 2002).await.unwrap().last_price, Some(4.0));
+        assert_eq!(store.get_history_range("BTC").await, Some((1000, 2002)));
+
+        // Other tickers are unaffected by BTC's newest timestamp
+        store.store_snapshot(Snapshot::new("ETH".to_string(), 1500, None, vec![], vec![])).await;
+        assert!(store.get_snapshot("ETH", 1500).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_backward_clock_jump_clamps_one_second_later() {
+        use crate::orderbook::engine::OrderbookEngine;
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        // Capture the newest snapshot the way the storage task does, at the current time
+        let store = SnapshotStore::new();
+        let newest = Snapshot::from_orderbook_state("BTC".to_string(), OrderbookEngine::default().get_current_state());
+        let newest_time = UNIX_EPOCH + Duration::from_secs(newest.timestamp as u64);
+        assert!(SystemTime::now().duration_since(newest_time).unwrap() < Duration::from_secs(1));
+        store.store_snapshot(newest.clone()).await;
+
+        // The clock jumps back a minute
+        let jumped = Snapshot::new("BTC".to_string(), newest.timestamp - 60, Some(1.0), vec![], vec![]);
+        store.store_snapshot(jumped).await;
+
+        let (_min, clamped) = store.get_history_range("BTC").await.unwrap();
+        let clamped_time = UNIX_EPOCH + Duration::from_secs(clamped as u64);
+        assert_eq!(clamped_time.duration_since(newest_time).unwrap(), Duration::from_secs(1));
+        assert_eq!(store.get_snapshot("BTC", clamped).await.unwrap().last_price, Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn test_backward_clock_jump_reject_and_accept() {
+        let store = SnapshotStore::new().with_clock_skew_policy(ClockSkewPolicy::Reject);
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 2000, None, vec![], vec![])).await;
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 1500, None, vec![], vec![])).await;
+        assert_eq!(store.len().await, 1);
+        assert_eq!(store.get_history_range("BTC").await, Some((2000, 2000)));
+
+        let store = SnapshotStore::new().with_clock_skew_policy(ClockSkewPolicy::Accept);
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 2000, None, vec![], vec![])).await;
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 1500, None, vec![], vec![])).await;
+        assert!(store.get_snapshot("BTC", 1500).await.is_some());
+    }
+
+    #[test]
+    fn test_clock_skew_policy_from_str() {
+        assert_eq!("reject".parse::<ClockSkewPolicy>().unwrap(), ClockSkewPolicy::Reject);
+        assert_eq!("Clamp".parse::<ClockSkewPolicy>().unwrap(), ClockSkewPolicy::Clamp);
+        assert_eq!("ACCEPT".parse::<ClockSkewPolicy>().unwrap(), ClockSkewPolicy::Accept);
+        assert!("sometimes".parse::<ClockSkewPolicy>().is_err());
+    }
+
     #[tokio::test]
     async fn test_get_history_range_empty() {
         let store = SnapshotStore::new();
@@ -174,7 +881,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_history_range() {
-        let store = SnapshotStore::new();
+        // Out-of-order insertion is kept as-is so the range spans stored timestamps
+        let store = SnapshotStore::new().with_clock_skew_policy(ClockSkewPolicy::Accept);
         
         store.store_snapshot(Snapshot::new("BTC".to_string(), 1000, None, vec![], vec![])).await;
         store.store_snapshot(Snapshot::new("BTC".to_string(), 2000, None, vec![], vec![])).await;
@@ -187,6 +895,17 @@ mod tests {
         assert_eq!(max, 2000);
     }
 
+    #[tokio::test]
+    async fn test_list_tickers() {
+        let store = SnapshotStore::new();
+        assert!(store.list_tickers().await.is_empty());
+
+        store.store_snapshot(Snapshot::new("ETH".to_string(), 1000, None, vec![], vec![])).await;
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 1000, None, vec![], vec![])).await;
+        store.store_snapshot(Snapshot::new("BTC".to_string(), 2000, None, vec![], vec![])).await;
+        assert_eq!(store.list_tickers().await, vec!["BTC".to_string(), "ETH".to_string()]);
+    }
+
     #[tokio::test]
     async fn test_remove_older_than() {
         let store = SnapshotStore::new();
@@ -203,5 +922,328 @@ mod tests {
         assert!(store.get_snapshot("BTC", 2000).await.is_none());
         assert!(store.get_snapshot("BTC", 3000).await.is_some());
     }
+
+    #[tokio::test]
+    async fn test_persisted_snapshots_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = |ticker: &str, timestamp| Snapshot::new(ticker.to_string(), timestamp, Some(42000.0), vec![], vec![]);
+
+        {
+            let store = SnapshotStore::new().with_persistence(dir.path().to_path_buf()).unwrap();
+            store.store_snapshot(snapshot("BTC", 1000)).await;
+            store.store_snapshot(snapshot("BTC", 2000)).await;
+            store.store_snapshot(snapshot("ETH", 1500)).await;
+            assert!(dir.path().join("BTC_1000.json").exists());
+
+            // Removing a snapshot also deletes its file
+            assert_eq!(store.remove_older_than(1200, Some("BTC")).await, 1);
+            assert!(!dir.path().join("BTC_1000.json").exists());
+        }
+
+        let store = SnapshotStore::new().with_persistence(dir.path().to_path_buf()).unwrap();
+        assert_eq!(store.len().await, 2);
+        assert_eq!(store.get_history_range("BTC").await, Some((2000, 2000)));
+        assert_eq!(store.get_snapshot("ETH", 1500).await.unwrap().last_price, Some(42000.0));
+    }
+
+    #[tokio::test]
+    async fn test_batched_writes_persist_the_newest_snapshot_of_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::new().with_persistence(dir.path().to_path_buf()).unwrap();
+        let snapshot = |ticker: &str, price| Snapshot::new(ticker.to_string(), 1000, Some(price), vec![], vec![]);
+
+        // Queued together, so they land in one batch and their files are written concurrently
+        tokio::join!(
+            store.store_snapshot(snapshot("BTC", 1.0)),
+            store.store_snapshot(snapshot("ETH", 2.0)),
+            store.store_snapshot(snapshot("BTC", 3.0)),
+        );
+
+        let reloaded = SnapshotStore::new().with_persistence(dir.path().to_path_buf()).unwrap();
+        assert_eq!(reloaded.get_snapshot("BTC", 1000).await.unwrap().last_price, Some(3.0));
+        assert_eq!(reloaded.get_snapshot("ETH", 1000).await.unwrap().last_price, Some(2.0));
+    }
+
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_rapid_concurrent_stores_are_all_retrievable() {
+        let store = Arc::new(SnapshotStore::new());
+        let tasks: Vec<_> = ["BTC", "ETH", "XMR", "ZEC"]
+            .into_iter()
+            .map(|ticker| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    for timestamp in 0..25 {
+                        let bids = vec![PriceLevelEntry { price: 100.0, volume: timestamp as f64 + 1.0 }];
+                        store.store_snapshot(Snapshot::new(ticker.to_string(), timestamp, None, bids, vec![])).await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(store.len().await, 100);
+        for ticker in ["BTC", "ETH", "XMR", "ZEC"] {
+            for timestamp in 0..25 {
+                let snapshot = store.get_snapshot(ticker, timestamp).await.unwrap();
+                assert_eq!(snapshot.bids[0].volume, timestamp as f64 + 1.0);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_nearest_snapshot() {
+        let store = SnapshotStore::new();
+        assert!(store.get_nearest_snapshot("BTC", 2000).await.is_none());
+        for timestamp in [1000, 2000, 3000] {
+            store.store_snapshot(Snapshot::new("BTC".to_string(), timestamp, None, vec![], vec![])).await;
+        }
+        store.store_snapshot(Snapshot::new("ETH".to_string(), 2400, None, vec![], vec![])).await;
+
+        let nearest = |timestamp| {
+            let store = &store;
+            async move { store.get_nearest_snapshot("BTC", timestamp).await.unwrap().timestamp }
+        };
+        assert_eq!(nearest(2400).await, 2000);
+        assert_eq!(nearest(2600).await, 3000);
+        // Equidistant: the earlier snapshot wins
+        assert_eq!(nearest(2500).await, 2000);
+        assert_eq!(nearest(0).await, 1000);
+        assert_eq!(nearest(i64::MAX).await, 3000);
+    }
+
+
+    #[tokio::test]
+    async fn test_identical_snapshot_within_window_is_stored_once() {
+        let store = SnapshotStore::new();
+        let bids = vec![PriceLevelEntry { price: 100.0, volume: 1.0 }];
+        let snapshot = |timestamp, volume| Snapshot::new(
+            "BTC".to_string(),
+            timestamp,
+            Some(100.5),
+            bids.clone(),
+            vec![PriceLevelEntry { price: 101.0, volume }],
+        );
+
+        store.store_snapshot(snapshot(1000, 2.0)).await;
+        // Retry of the same content a second later is a no-op
+        store.store_snapshot(snapshot(1001, 2.0)).await;
+        assert_eq!(store.len().await, 1);
+        assert_eq!(store.get_history_range("BTC").await, Some((1000, 1000)));
+
+        // Changed content, or the same content outside the window, is stored
+        store.store_snapshot(snapshot(1002, 3.0)).await;
+        store.store_snapshot(snapshot(1002 + DUPLICATE_WINDOW_SECS as i64 + 1, 3.0)).await;
+        assert_eq!(store.len().await, 3);
+    }
+
+    /// A BTC book that changes a little at every step
+    fn evolving_snapshot(step: i64) -> Snapshot {
+        let level = |price: f64, volume: f64| PriceLevelEntry { price, volume };
+        let mut bids = vec![level(100.0, 1.0 + step as f64), level(99.5, 2.0)];
+        if step % 3 == 0 {
+            bids.push(level(99.0, 0.5));
+        }
+        let mut asks = vec![level(101.0, 1.5)];
+        if step % 2 == 1 {
+            asks.insert(0, level(100.5, step as f64));
+        }
+        Snapshot {
+            traded_volume: step as f64 * 0.25,
+            ..Snapshot::new("BTC".to_string(), 1000 + step * 10, Some(100.0 + step as f64), bids, asks)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keyframe_deltas_reconstruct_full_snapshots() {
+        let full = SnapshotStore::new();
+        let keyframed = SnapshotStore::new().with_keyframe_interval(4);
+        for step in 0..10 {
+            full.store_snapshot(evolving_snapshot(step)).await;
+            keyframed.store_snapshot(evolving_snapshot(step)).await;
+        }
+
+        // Keyframes at steps 0, 4 and 8; deltas in between
+        let keyframes = keyframed.snapshots.read().await["BTC"].entries.values()
+            .filter(|stored| matches!(stored, StoredSnapshot::Full(_)))
+            .count();
+        assert_eq!(keyframes, 3);
+
+        for step in 0..10 {
+            let timestamp = 1000 + step * 10;
+            let expected = serde_json::to_value(full.get_snapshot("BTC", timestamp).await.unwrap()).unwrap();
+            let rebuilt = serde_json::to_value(keyframed.get_snapshot("BTC", timestamp).await.unwrap()).unwrap();
+            assert_eq!(rebuilt, expected, "step {}", step);
+        }
+        let nearest = keyframed.get_nearest_snapshot("BTC", 1052).await.unwrap();
+        assert_eq!(serde_json::to_value(nearest).unwrap(), serde_json::to_value(evolving_snapshot(5)).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_keyframes_shrink_a_deep_slowly_changing_history() {
+        // An hour of depth-1000 books at 10s, each changing a handful of levels
+        let book = |step: usize| {
+            let side = |base: f64, sign: f64| -> Vec<PriceLevelEntry> {
+                (0..1000)
+                    .map(|i| PriceLevelEntry {
+                        price: base + sign * i as f64 * 0.5,
+                        volume: if i % 200 == step % 200 { 1.0 + step as f64 } else { 1.0 },
+                    })
+                    .collect()
+            };
+            Snapshot::new("BTC".to_string(), 1000 + step as i64 * 10, Some(100.0), side(1000.0, -1.0), side(1000.5, 1.0))
+        };
+        let full = SnapshotStore::new();
+        let keyframed = SnapshotStore::new().with_keyframe_interval(10);
+        for step in 0..360 {
+            full.store_snapshot(book(step)).await;
+            keyframed.store_snapshot(book(step)).await;
+        }
+
+        assert_eq!(full.stored_levels().await, 360 * 2000);
+        // 36 keyframes in full, plus 324 deltas of a few levels each
+        assert!(keyframed.stored_levels().await < full.stored_levels().await / 8);
+
+        // An intermediate snapshot still comes back whole
+        let expected = serde_json::to_value(full.get_snapshot("BTC", 1000 + 125 * 10).await.unwrap()).unwrap();
+        let rebuilt = serde_json::to_value(keyframed.get_snapshot("BTC", 1000 + 125 * 10).await.unwrap()).unwrap();
+        assert_eq!(rebuilt, expected);
+    }
+
+    #[tokio::test]
+    async fn test_get_snapshots_replays_deltas_in_order() {
+        let store = SnapshotStore::new().with_keyframe_interval(4);
+        for step in 0..10 {
+            store.store_snapshot(evolving_snapshot(step)).await;
+        }
+        store.store_snapshot(Snapshot::new("ETH".to_string(), 1005, None, vec![], vec![])).await;
+
+        let history = store.get_snapshots("BTC").await;
+        assert_eq!(history.len(), 10);
+        for (step, snapshot) in history.into_iter().enumerate() {
+            assert_eq!(serde_json::to_value(snapshot).unwrap(), serde_json::to_value(evolving_snapshot(step as i64)).unwrap());
+        }
+        assert!(store.get_snapshots("XMR").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_top_of_book_history_matches_rebuilt_snapshots() {
+        let store = SnapshotStore::new().with_keyframe_interval(4);
+        for step in 0..10 {
+            store.store_snapshot(evolving_snapshot(step)).await;
+        }
+
+        let expected: Vec<_> = store.get_snapshots("BTC").await
+            .iter()
+            .map(|snapshot| (snapshot.timestamp, snapshot.bids.first().map(|l| l.price), snapshot.asks.first().map(|l| l.price)))
+            .collect();
+        let tops = store.get_top_of_book_history("BTC").await;
+        assert_eq!(tops, expected);
+        // Odd steps have the 100.5 ask in front of 101
+        assert_eq!(tops[1], (1010, Some(100.0), Some(100.5)));
+        assert_eq!(tops[2], (1020, Some(100.0), Some(101.0)));
+        assert!(store.get_top_of_book_history("XMR").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_range_is_inclusive_and_rebuilds_deltas() {
+        let store = SnapshotStore::new().with_keyframe_interval(4);
+        for step in 0..10 {
+            store.store_snapshot(evolving_snapshot(step)).await;
+        }
+
+        // Steps 2..=5 straddle the keyframe at step 4; both endpoints are included
+        let range = store.get_range("BTC", 1020, 1050, usize::MAX).await.0;
+        let timestamps: Vec<i64> = range.iter().map(|snapshot| snapshot.timestamp).collect();
+        assert_eq!(timestamps, vec![1020, 1030, 1040, 1050]);
+        for (snapshot, step) in range.into_iter().zip(2..) {
+            assert_eq!(serde_json::to_value(snapshot).unwrap(), serde_json::to_value(evolving_snapshot(step)).unwrap());
+        }
+
+        // Bounds between snapshots, a single point, and empty windows
+        assert_eq!(store.get_range("BTC", 1015, 1025, usize::MAX).await.0.len(), 1);
+        assert_eq!(store.get_range("BTC", 1090, 1090, usize::MAX).await.0.len(), 1);
+        assert!(store.get_range("BTC", 1091, 2000, usize::MAX).await.0.is_empty());
+        assert!(store.get_range("BTC", 1001, 1009, usize::MAX).await.0.is_empty());
+        assert!(store.get_range("XMR", 0, i64::MAX, usize::MAX).await.0.is_empty());
+
+        // The replay stops at the limit and reports whether anything was left out
+        let (capped, truncated) = store.get_range("BTC", 1000, 1090, 3).await;
+        assert_eq!(capped.iter().map(|snapshot| snapshot.timestamp).collect::<Vec<_>>(), vec![1000, 1010, 1020]);
+        assert!(truncated);
+        assert!(!store.get_range("BTC", 1000, 1090, 10).await.1);
+    }
+
+    #[tokio::test]
+    async fn test_out_of_order_snapshot_keeps_later_deltas_reconstructible() {
+        let store = SnapshotStore::new()
+            .with_keyframe_interval(4)
+            .with_clock_skew_policy(ClockSkewPolicy::Accept);
+        for step in [0, 1, 3, 4, 5] {
+            store.store_snapshot(evolving_snapshot(step)).await;
+        }
+        // Lands between the deltas at steps 1 and 3
+        store.store_snapshot(evolving_snapshot(2)).await;
+        store.store_snapshot(evolving_snapshot(6)).await;
+
+        for step in 0..7 {
+            let rebuilt = store.get_snapshot("BTC", 1000 + step * 10).await.unwrap();
+            assert_eq!(serde_json::to_value(rebuilt).unwrap(), serde_json::to_value(evolving_snapshot(step)).unwrap());
+        }
+        // Steps 2 and 3 became keyframes alongside the regular ones at 0 and 5
+        let snapshots = store.snapshots.read().await;
+        let keyframes: Vec<i64> = snapshots["BTC"].entries.iter()
+            .filter(|(_, stored)| matches!(stored, StoredSnapshot::Full(_)))
+            .map(|(timestamp, _)| *timestamp)
+            .collect();
+        assert_eq!(keyframes, vec![1000, 1020, 1030, 1050]);
+        assert_eq!(snapshots["BTC"].deltas_since_keyframe, 1);
+    }
+
+    #[tokio::test]
+    async fn test_history_cursor_rebuilds_window_in_batches() {
+        let store = SnapshotStore::new().with_keyframe_interval(4);
+        for step in 0..10 {
+            store.store_snapshot(evolving_snapshot(step)).await;
+        }
+
+        // Opens on the delta at step 2 and crosses the keyframes at steps 4 and 8
+        let mut history = store.history("BTC", 1015, 1085);
+        let mut steps = Vec::new();
+        loop {
+            let batch = history.next_batch(3).await;
+            if batch.is_empty() {
+                break;
+            }
+            assert!(batch.len() <= 3);
+            for snapshot in batch {
+                let step = (snapshot.timestamp - 1000) / 10;
+                assert_eq!(serde_json::to_value(snapshot).unwrap(), serde_json::to_value(evolving_snapshot(step)).unwrap());
+                steps.push(step);
+            }
+        }
+        assert_eq!(steps, (2..=8).collect::<Vec<_>>());
+        assert!(history.next_batch(3).await.is_empty());
+
+        assert!(store.history("BTC", 1050, 1040).next_batch(3).await.is_empty());
+        assert!(store.history("XMR", 0, i64::MAX).next_batch(3).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_older_than_keeps_deltas_reconstructible() {
+        let store = SnapshotStore::new().with_keyframe_interval(4);
+        for step in 0..6 {
+            store.store_snapshot(evolving_snapshot(step)).await;
+        }
+
+        // Removes the keyframe at step 0 and the deltas at steps 1 and 2
+        assert_eq!(store.remove_older_than(1030, Some("BTC")).await, 3);
+        for step in 3..6 {
+            let rebuilt = store.get_snapshot("BTC", 1000 + step * 10).await.unwrap();
+            assert_eq!(serde_json::to_value(rebuilt).unwrap(), serde_json::to_value(evolving_snapshot(step)).unwrap());
+        }
+    }
 }
 
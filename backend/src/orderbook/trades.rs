@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::Serialize;
+use tokio::sync::RwLock;
+
+/// Aggressor side of an inferred trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeSide {
+    /// A buyer lifted the best ask
+    Buy,
+    /// A seller hit the best bid
+    Sell,
+}
+
+impl TradeSide {
+    /// Lowercase name, as used in JSON and CSV output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeSide::Buy => "buy",
+            TradeSide::Sell => "sell",
+        }
+    }
+}
+
+/// A trade inferred from a volume decrease at the top of the book
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetectedTrade {
+    /// Unix timestamp in milliseconds
+    pub timestamp_ms: i64,
+    pub price: f64,
+    /// Volume consumed at the level
+    pub volume: f64,
+    pub side: TradeSide,
+}
+
+/// Trades grouped by (ticker, timestamp_ms), in arrival order within a key
+type TradeMap = HashMap<(String, i64), Vec<DetectedTrade>>;
+
+/// In-memory storage for inferred trades, mirroring `SnapshotStore`
+/// 
+/// Trades are keyed by (ticker, timestamp_ms); several trades may share a key.
+pub struct TradeStore {
+    trades: Arc<RwLock<TradeMap>>,
+}
+
+impl TradeStore {
+    /// Create a new empty trade store
+    pub fn new() -> Self {
+        Self {
+            trades: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Store a trade for a ticker
+    pub async fn store_trade(&self, ticker: &str, trade: DetectedTrade) {
+        let mut trades = self.trades.write().await;
+        trades
+            .entry((ticker.to_string(), trade.timestamp_ms))
+            .or_default()
+            .push(trade);
+    }
+
+    /// Get a ticker's trades with `start_ms <= timestamp_ms <= end_ms`, oldest first
+    pub async fn get_range(&self, ticker: &str, start_ms: i64, end_ms: i64) -> Vec<DetectedTrade> {
+        let trades = self.trades.read().await;
+        let mut keys: Vec<i64> = trades
+            .keys()
+            .filter(|(t, timestamp)| t.as_str() == ticker && (start_ms..=end_ms).contains(timestamp))
+            .map(|(_, timestamp)| *timestamp)
+            .collect();
+        keys.sort_unstable();
+
+        keys.into_iter()
+            .flat_map(|timestamp| trades[&(ticker.to_string(), timestamp)].iter().cloned())
+            .collect()
+    }
+
+    /// Remove trades older than the cutoff timestamp (milliseconds)
+    /// 
+    /// If ticker is provided, only removes trades for that ticker.
+    /// Returns the number of trades removed.
+    pub async fn remove_older_than(&self, cutoff_ms: i64, ticker: Option<&str>) -> usize {
+        let mut trades = self.trades.write().await;
+        let mut removed = 0;
+        trades.retain(|(t, timestamp), at_timestamp| {
+            let expired = *timestamp < cutoff_ms && ticker.is_none_or(|filter| t.as_str() == filter);
+            if expired {
+                removed += at_timestamp.len();
+            }
+            !expired
+        });
+        removed
+    }
+}
+
+impl Default for TradeStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Render trades as CSV with a `timestamp,price,volume,side` header
+pub fn trades_to_csv(trades: &[DetectedTrade]) -> String {
+    let mut csv = String::from("timestamp,price,volume,side\n");
+    for trade in trades {
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            trade.timestamp_ms,
+            trade.price,
+            trade.volume,
+            trade.side.as_str()
+        ));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(timestamp_ms: i64, price: f64, side: TradeSide) -> DetectedTrade {
+        DetectedTrade { timestamp_ms, price, volume: 0.5, side }
+    }
+
+    #[tokio::test]
+    async fn test_range_export_to_csv() {
+        let store = TradeStore::new();
+        store.store_trade("BTC", trade(3000, 101.0, TradeSide::Buy)).await;
+        store.store_trade("BTC", trade(1000, 100.0, TradeSide::Sell)).await;
+        store.store_trade("BTC", trade(2000, 100.5, TradeSide::Buy)).await;
+        store.store_trade("BTC", trade(2000, 100.25, TradeSide::Sell)).await;
+        store.store_trade("ETH", trade(2000, 10.0, TradeSide::Buy)).await;
+
+        let trades = store.get_range("BTC", 1500, 3000).await;
+        assert_eq!(
+            trades_to_csv(&trades),
+            "timestamp,price,volume,side\n\
+             2000,100.5,0.5,buy\n\
+             2000,100.25,0.5,sell\n\
+             3000,101,0.5,buy\n"
+        );
+
+        assert_eq!(trades_to_csv(&store.get_range("BTC", 5000, 6000).await), "timestamp,price,volume,side\n");
+    }
+
+    #[tokio::test]
+    async fn test_store_and_range_ordering() {
+        let store = TradeStore::new();
+        store.store_trade("BTC", trade(2000, 100.5, TradeSide::Buy)).await;
+        store.store_trade("BTC", trade(1000, 100.0, TradeSide::Sell)).await;
+        store.store_trade("BTC", trade(2000, 100.25, TradeSide::Sell)).await;
+        store.store_trade("ETH", trade(1500, 10.0, TradeSide::Buy)).await;
+
+        // Oldest first; trades sharing a millisecond keep arrival order
+        let prices: Vec<f64> = store.get_range("BTC", i64::MIN, i64::MAX).await.iter().map(|t| t.price).collect();
+        assert_eq!(prices, vec![100.0, 100.5, 100.25]);
+
+        // Bounds are inclusive and other tickers are excluded
+        assert_eq!(store.get_range("BTC", 1000, 1000).await, vec![trade(1000, 100.0, TradeSide::Sell)]);
+        assert_eq!(store.get_range("ETH", 0, 5000).await.len(), 1);
+        assert!(store.get_range("XMR", 0, 5000).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_older_than() {
+        let store = TradeStore::new();
+        store.store_trade("BTC", trade(1000, 100.0, TradeSide::Sell)).await;
+        store.store_trade("BTC", trade(1000, 100.5, TradeSide::Buy)).await;
+        store.store_trade("BTC", trade(3000, 101.0, TradeSide::Buy)).await;
+        store.store_trade("ETH", trade(1000, 10.0, TradeSide::Buy)).await;
+
+        // Every trade at an expired key counts; the ticker filter spares ETH
+        assert_eq!(store.remove_older_than(2000, Some("BTC")).await, 2);
+        assert_eq!(store.get_range("BTC", i64::MIN, i64::MAX).await.len(), 1);
+        assert_eq!(store.get_range("ETH", i64::MIN, i64::MAX).await.len(), 1);
+
+        // The cutoff itself is kept
+        assert_eq!(store.remove_older_than(3000, None).await, 1);
+        assert_eq!(store.get_range("BTC", i64::MIN, i64::MAX).await.len(), 1);
+    }
+}
@@ -0,0 +1,147 @@
+//! Coordinated shutdown of the server's background tasks
+//!
+//! On Ctrl-C, main triggers the coordinator: feed tasks watching its signal
+//! close their exchange connections and return, tasks that don't watch it (such
+//! as snapshot storage) are aborted, and the HTTP server drains its connections.
+
+use std::time::Duration;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+
+/// How long cooperative tasks get to stop on their own before being aborted
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Receiving side of the shutdown trigger, handed to each cooperative task
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal(watch::Receiver<bool>);
+
+impl ShutdownSignal {
+    /// Whether shutdown has been triggered
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Wait until shutdown is triggered (or the coordinator is dropped)
+    pub async fn triggered(&mut self) {
+        let _ = self.0.wait_for(|triggered| *triggered).await;
+    }
+}
+
+/// Tracks spawned tasks and stops them all on shutdown
+#[derive(Debug)]
+pub struct Shutdown {
+    trigger: watch::Sender<bool>,
+    /// Tasks that watch the signal and are given `SHUTDOWN_GRACE` to return
+    cooperative: Mutex<Vec<JoinHandle<()>>>,
+    /// Tasks that are aborted as soon as shutdown starts
+    abortable: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Shutdown {
+    /// Create a coordinator with no tasks registered
+    pub fn new() -> Self {
+        Self {
+            trigger: watch::Sender::new(false),
+            cooperative: Mutex::new(Vec::new()),
+            abortable: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A signal for a task to watch
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal(self.trigger.subscribe())
+    }
+
+    /// Register a task that returns on its own once the signal fires
+    pub async fn register(&self, handle: JoinHandle<()>) {
+        self.cooperative.lock().await.push(handle);
+    }
+
+    /// Register a task to abort on shutdown
+    pub async fn register_abortable(&self, handle: JoinHandle<()>) {
+        self.abortable.lock().await.push(handle);
+    }
+
+    /// Fire the signal without waiting for any task
+    pub fn trigger(&self) {
+        self.trigger.send_replace(true);
+    }
+
+    /// Fire the signal and stop every registered task
+    ///
+    /// Abortable tasks are aborted straight away; cooperative ones are awaited
+    /// for up to `grace` and aborted if still running. Returns the number of
+    /// cooperative tasks that had to be aborted.
+    pub async fn finish(&self, grace: Duration) -> usize {
+        self.trigger();
+        for handle in self.abortable.lock().await.drain(..) {
+            handle.abort();
+        }
+
+        let handles: Vec<JoinHandle<()>> = self.cooperative.lock().await.drain(..).collect();
+        let aborts: Vec<_> = handles.iter().map(JoinHandle::abort_handle).collect();
+        if tokio::time::timeout(grace, futures_util::future::join_all(handles)).await.is_ok() {
+            return 0;
+        }
+        let mut aborted = 0;
+        for abort in aborts.iter().filter(|abort| !abort.is_finished()) {
+            abort.abort();
+            aborted += 1;
+        }
+        aborted
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_finish_stops_every_registered_task() {
+        let shutdown = Shutdown::new();
+        let stopped = Arc::new(AtomicUsize::new(0));
+
+        // Two tasks that return when signalled
+        for _ in 0..2 {
+            let mut signal = shutdown.signal();
+            let stopped = stopped.clone();
+            shutdown.register(tokio::spawn(async move {
+                signal.triggered().await;
+                stopped.fetch_add(1, Ordering::SeqCst);
+            })).await;
+        }
+        // One that ignores the signal, and one only meant to be aborted
+        shutdown.register(tokio::spawn(std::future::pending())).await;
+        let abortable = tokio::spawn(std::future::pending::<()>());
+        let abortable_handle = abortable.abort_handle();
+        shutdown.register_abortable(abortable).await;
+
+        let signal = shutdown.signal();
+        assert!(!signal.is_triggered());
+        assert_eq!(shutdown.finish(Duration::from_millis(100)).await, 1);
+        assert!(signal.is_triggered());
+        assert_eq!(stopped.load(Ordering::SeqCst), 2);
+
+        tokio::task::yield_now().await;
+        assert!(abortable_handle.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_finish_returns_promptly_when_tasks_cooperate() {
+        let shutdown = Shutdown::new();
+        let mut signal = shutdown.signal();
+        shutdown.register(tokio::spawn(async move { signal.triggered().await })).await;
+
+        let started = std::time::Instant::now();
+        assert_eq!(shutdown.finish(Duration::from_secs(10)).await, 0);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -57,17 +57,22 @@ export function useWebSocket(pauseUpdates = false, ticker = 'ZEC') {
             return;
           }
           
-          // Handle orderbook data (either with type wrapper or legacy format)
-          const data = message.type === 'orderbook' ? message.data : message;
+          // Handle orderbook data: full books ('orderbook'), diffs of changed
+          // levels ('orderbookDiff'), or the legacy unwrapped format
+          const isDiff = message.type === 'orderbookDiff';
+          const data = message.type === 'orderbook' || isDiff ? message.data : message;
+          // Full books always replace the accumulated state, e.g. after a resync
+          const isFullBook = message.type === 'orderbook' || (isFirstMessageRef.current && !isDiff);
           
           if (data && (data.bids || data.asks)) {
             const accumulated = accumulatedOrderbookRef.current;
             const incomingOrderCount = (data.bids?.length || 0) + (data.asks?.length || 0);
             
             // First message after connection is ALWAYS a full snapshot from the backend
-            // All subsequent messages are deltas
+            // Subsequent messages are diffs, or full books after a resync
             console.log('Processing message:', { 
               isFirst: isFirstMessageRef.current, 
+              isFullBook, 
               bids: data.bids?.length || 0, 
               asks: data.asks?.length || 0, 
               total: incomingOrderCount,
@@ -75,7 +80,7 @@ export function useWebSocket(pauseUpdates = false, ticker = 'ZEC') {
               currentAsks: accumulated.asks.size
             });
             
-            if (isFirstMessageRef.current) {
+            if (isFullBook) {
               // Full snapshot: REPLACE the accumulated state
               console.log('Received initial full snapshot:', { bids: data.bids?.length || 0, asks: data.asks?.length || 0, total: incomingOrderCount });
               accumulated.bids.clear();